@@ -1,25 +1,42 @@
 use async_trait::async_trait;
 use dashmap::DashMap;
+use futures::{Stream, StreamExt};
 use schemars::gen::SchemaSettings;
-use serde::Serializer;
+use serde::{Deserialize, Serialize, Serializer};
 use serde_json::json;
 use std::{
     any::{Any, TypeId},
-    sync::Arc,
+    borrow::Cow,
+    collections::BTreeMap,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 use thiserror::Error;
-use tokio::sync::RwLock;
-use tracing::{error, info, instrument};
+use tokio::sync::{OwnedSemaphorePermit, RwLock, Semaphore};
+use tracing::{debug, error, info, instrument, warn};
 
 use crate::{
     embeddings::Embedder,
-    tools::{ExecutionStrategy, ToolCall, ToolResponse, ToolSet, ToolSetError},
+    tools::{CancellationToken, ExecutionStrategy, ToolCall, ToolResponse, ToolSet, ToolSetError},
     vector_store::VectorStoreError,
 };
 
+mod moderation;
+
+pub use moderation::{ModerationError, Moderator, NoopModerator, WordlistModerator};
+
 // Default top_n context documents to query from the vector store
 const DEFAULT_TOP_N: usize = 1;
 
+/// Placeholder substituted with the retrieved context when found in the preamble, see
+/// [`ClientBuilder::preamble`]
+pub const CONTEXT_PLACEHOLDER: &str = "{context}";
+
 /// Messages exchanged with the completion model
 #[derive(Debug, Clone, PartialEq)]
 pub enum Message {
@@ -31,6 +48,8 @@ pub enum Message {
         content: String,
         /// Optional tool execution results
         tool_responses: Option<Vec<ToolResponse>>,
+        /// Optional name of the sender, distinguishing the participants of a conversation
+        name: Option<String>,
     },
     /// Model-generated response
     Assistant {
@@ -38,7 +57,26 @@ pub enum Message {
         content: String,
         /// Optional requested tool calls
         tool_calls: Option<Vec<ToolCall>>,
+        /// Optional name of the responding model, eg. its role in a multi-agent conversation
+        name: Option<String>,
     },
+    /// Result of a tool call requested by the assistant
+    Tool(ToolResponse),
+}
+
+impl Message {
+    /// The tool calls requested by an assistant message, `None` for other messages and
+    /// assistant messages without tool calls
+    #[must_use]
+    pub fn tool_calls(&self) -> Option<&[ToolCall]> {
+        match self {
+            Self::Assistant {
+                tool_calls: Some(calls),
+                ..
+            } => Some(calls),
+            _ => None,
+        }
+    }
 }
 
 /// Tracks token usage statistics for model interactions
@@ -52,6 +90,222 @@ pub struct TokenUsage {
     pub total_tokens: Option<u64>,
 }
 
+/// Optional sampling parameters of a request, only the set ones are sent to the provider
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SamplingParams {
+    /// Nucleus sampling, only tokens within the top `top_p` probability mass are considered
+    pub top_p: Option<f64>,
+    /// Penalizes tokens proportionally to how often they already appeared
+    pub frequency_penalty: Option<f64>,
+    /// Penalizes tokens that already appeared at all
+    pub presence_penalty: Option<f64>,
+    /// Seed for best-effort deterministic sampling, supported by `OpenAI` and xAI
+    pub seed: Option<u64>,
+    /// How much reasoning models think before answering, supported by `OpenAI` and xAI
+    pub reasoning_effort: Option<ReasoningEffort>,
+}
+
+/// Reasoning effort of reasoning models, lower efforts answer faster with fewer reasoning tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+impl SamplingParams {
+    /// Returns whether none of the params are set
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns these params, with the unset ones taken from `defaults`
+    #[must_use]
+    pub fn or(self, defaults: Self) -> Self {
+        Self {
+            top_p: self.top_p.or(defaults.top_p),
+            frequency_penalty: self.frequency_penalty.or(defaults.frequency_penalty),
+            presence_penalty: self.presence_penalty.or(defaults.presence_penalty),
+            seed: self.seed.or(defaults.seed),
+            reasoning_effort: self.reasoning_effort.or(defaults.reasoning_effort),
+        }
+    }
+
+    /// Inserts the set params into a JSON request body, under their OpenAI names
+    pub fn insert_into(&self, request_body: &mut serde_json::Value) {
+        let Some(obj) = request_body.as_object_mut() else {
+            return;
+        };
+        for (key, value) in [
+            ("top_p", self.top_p),
+            ("frequency_penalty", self.frequency_penalty),
+            ("presence_penalty", self.presence_penalty),
+        ] {
+            if let Some(value) = value {
+                obj.insert(key.to_string(), json!(value));
+            }
+        }
+        if let Some(seed) = self.seed {
+            obj.insert("seed".to_string(), json!(seed));
+        }
+        if let Some(effort) = self.reasoning_effort {
+            obj.insert("reasoning_effort".to_string(), json!(effort));
+        }
+    }
+}
+
+/// What to do when context is requested but none is retrieved, eg. with an empty vector store
+/// or no document meeting the min relevance
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum NoContextPolicy {
+    /// Sends the prompt without context
+    #[default]
+    Proceed,
+    /// Fails the prompt with [`CompletionError::NoContextFound`] without sending it
+    Fail,
+    /// Sends the instruction in place of the context, eg. "Say you don't know"
+    Instruct(String),
+}
+
+/// Where the context retrieved from the embedders is placed in the request
+///
+/// Ignored if the preamble contains the [`CONTEXT_PLACEHOLDER`], the context then takes its place.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContextPosition {
+    /// Appended to the prompt inside `<context>` tags
+    #[default]
+    AppendToUser,
+    /// Sent as a system message right before the prompt
+    AsSystemMessage,
+    /// Sent as a separate user message right before the prompt
+    AsSeparateUserMessage,
+}
+
+/// Reason the model stopped generating a response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FinishReason {
+    /// The model reached a natural stopping point or a stop sequence
+    Stop,
+    /// The response was truncated after reaching the maximum number of tokens
+    Length,
+    /// The model requested tool calls
+    ToolCalls,
+    /// The response was omitted or cut short by a content filter
+    ContentFilter,
+    /// Provider specific reason not covered by the other variants
+    Other(String),
+}
+
+impl From<&str> for FinishReason {
+    /// Parses the OpenAI style `finish_reason` values
+    fn from(value: &str) -> Self {
+        match value {
+            "stop" => Self::Stop,
+            "length" => Self::Length,
+            "tool_calls" | "function_call" => Self::ToolCalls,
+            "content_filter" => Self::ContentFilter,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+/// Response of a completion model along with its metadata
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletionOutput {
+    /// The response message
+    pub message: Message,
+    /// Tokens used by the request
+    pub token_usage: TokenUsage,
+    /// Why the model stopped generating, `None` if the provider didn't report it
+    pub finish_reason: Option<FinishReason>,
+    /// Identifies the backend configuration that served the request, changes in it can
+    /// explain different outputs for the same seed. `None` if the provider didn't report it
+    pub system_fingerprint: Option<String>,
+}
+
+/// Time spent in each phase of a prompt, see [`PromptBuilder::send_with_metrics`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PromptMetrics {
+    /// Retrieving the context, embedding the prompt and searching the vector stores.
+    /// `None` if no context was retrieved
+    pub retrieval: Option<Duration>,
+    /// The request to the completion model, `None` if the model wasn't called
+    pub completion: Option<Duration>,
+    /// Executing the tool calls requested by the model, `None` if no tools were executed
+    pub tools: Option<Duration>,
+    /// The whole prompt, from building the request to handling the response
+    pub total: Duration,
+}
+
+/// A piece of a streamed model response
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamChunk {
+    /// Text content delta
+    Text(String),
+    /// Fragment of a requested tool call, fragments with the same `index` belong to the same call
+    ToolCallDelta {
+        /// Position of the tool call in the response
+        index: usize,
+        /// Id of the tool call, usually only sent with the first fragment
+        id: Option<String>,
+        /// Name of the tool, usually only sent with the first fragment
+        name: Option<String>,
+        /// Partial JSON of the tool call's arguments
+        arguments: String,
+    },
+    /// Token usage of the response, usually sent at the end of the stream
+    Usage(TokenUsage),
+}
+
+/// Stream of response chunks returned by [`CompletionModel::stream`]
+pub type CompletionStream =
+    Pin<Box<dyn Stream<Item = Result<StreamChunk, CompletionError>> + Send>>;
+
+/// Assembles complete [`ToolCall`]s from streamed [`StreamChunk::ToolCallDelta`]s
+#[derive(Debug, Default)]
+pub(crate) struct ToolCallAccumulator {
+    calls: BTreeMap<usize, (String, String, String)>,
+}
+
+impl ToolCallAccumulator {
+    /// Adds a tool call fragment to the call at `index`
+    pub(crate) fn push(
+        &mut self,
+        index: usize,
+        id: Option<String>,
+        name: Option<String>,
+        arguments: &str,
+    ) {
+        let call = self.calls.entry(index).or_default();
+        if let Some(id) = id {
+            call.0.push_str(&id);
+        }
+        if let Some(name) = name {
+            call.1.push_str(&name);
+        }
+        call.2.push_str(arguments);
+    }
+
+    /// Returns the assembled tool calls ordered by their index, `None` if no fragments were pushed
+    pub(crate) fn finish(self) -> Option<Vec<ToolCall>> {
+        if self.calls.is_empty() {
+            return None;
+        }
+        Some(
+            self.calls
+                .into_values()
+                .map(|(id, name, arguments)| ToolCall {
+                    id,
+                    name,
+                    arguments: serde_json::Value::String(arguments).to_string(),
+                })
+                .collect(),
+        )
+    }
+}
+
 pub(crate) type MessageHistory = Vec<Message>;
 
 #[allow(clippy::module_name_repetitions)]
@@ -76,6 +330,18 @@ pub enum CompletionError {
     /// Error with tool call states
     #[error(transparent)]
     StateError(#[from] StateError),
+    /// The model doesn't implement streaming
+    #[error("Model does not support streaming")]
+    StreamingNotSupported,
+    /// The last turn in the history isn't an assistant response preceded by a user prompt
+    #[error("No assistant response to regenerate")]
+    NothingToRegenerate,
+    /// The model doesn't expose the body of its requests
+    #[error("Model does not support building request bodies")]
+    RequestBodyNotSupported,
+    /// No context was retrieved for a prompt requiring it, see [`NoContextPolicy::Fail`]
+    #[error("No relevant context found for the prompt")]
+    NoContextFound,
 }
 
 /// Types that can be deserialized from model completion responses.
@@ -89,6 +355,14 @@ pub trait Extractor: schemars::JsonSchema + serde::de::DeserializeOwned {}
 pub enum ExtractionError {
     #[error("Model does not support extraction")]
     ExtractionNotSupported,
+    /// The model's output doesn't deserialize into the extracted type
+    #[error("Invalid extracted output: {error}")]
+    InvalidOutput {
+        /// The output of the model
+        output: String,
+        /// Why the output doesn't deserialize
+        error: String,
+    },
 }
 
 /// Errors related to state management in the [`Client`]
@@ -136,6 +410,62 @@ pub trait CompletionModel: Send {
         max_tokens: usize,
     ) -> Result<(Message, TokenUsage), CompletionError>;
 
+    /// Sends a message to the model and returns its response along with the finish reason
+    ///
+    /// Default implementation calls [`CompletionModel::send`] and reports no finish reason,
+    /// models that expose it should override this.
+    async fn send_detailed(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionOutput, CompletionError> {
+        let (message, token_usage) = self
+            .send(message, history, tools, temperature, max_tokens)
+            .await?;
+        Ok(CompletionOutput {
+            message,
+            token_usage,
+            finish_reason: None,
+            system_fingerprint: None,
+        })
+    }
+
+    #[allow(unused)]
+    /// Builds the body of the request [`CompletionModel::send`] would send for a message,
+    /// without sending it
+    ///
+    /// Default implementation returns [`CompletionError::RequestBodyNotSupported`]
+    /// unless overridden by the model implementation.
+    fn build_request_body(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<serde_json::Value, CompletionError> {
+        Err(CompletionError::RequestBodyNotSupported)
+    }
+
+    #[allow(unused)]
+    /// Streams the model's response to a message
+    ///
+    /// Default implementation returns [`CompletionError::StreamingNotSupported`]
+    /// unless overridden by the model implementation.
+    async fn stream(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionStream, CompletionError> {
+        Err(CompletionError::StreamingNotSupported)
+    }
+
     #[allow(unused)]
     /// Extracts structured data from a model response
     ///
@@ -152,6 +482,291 @@ pub trait CompletionModel: Send {
             ExtractionError::ExtractionNotSupported,
         ))
     }
+
+    /// Sets the idempotency key sent with the following requests, `None` clears it
+    ///
+    /// Returns whether the model supports idempotency keys. Default implementation ignores
+    /// the key and returns `false`, models whose provider supports them should override this.
+    fn set_idempotency_key(&mut self, key: Option<String>) -> bool {
+        let _ = key;
+        false
+    }
+
+    /// Sets the sampling params of the following requests, taking precedence over the ones of
+    /// the model's config, `SamplingParams::default()` clears them
+    ///
+    /// Returns whether the model supports sampling params. Default implementation ignores
+    /// the params and returns `false`.
+    fn set_sampling_params(&mut self, params: SamplingParams) -> bool {
+        let _ = params;
+        false
+    }
+
+    /// Routes the following requests to the model named `route`, on models routing between
+    /// several models, `None` routes them back to the default one
+    ///
+    /// Returns whether the model supports routing. Default implementation ignores the route
+    /// and returns `false`.
+    fn set_route(&mut self, route: Option<String>) -> bool {
+        let _ = route;
+        false
+    }
+
+    /// Name of the provider serving the model, reported in the `sf.provider` metrics field
+    fn provider_name(&self) -> &str {
+        "unknown"
+    }
+
+    /// Name of the model, reported in the `sf.model` metrics field
+    fn model_name(&self) -> &str {
+        "unknown"
+    }
+}
+
+/// Per prompt options of a request, the model ones are set on the model right before its
+/// request and cleared after it
+#[derive(Debug, Default, Clone)]
+struct RequestOptions<'a> {
+    idempotency_key: Option<&'a str>,
+    sampling: SamplingParams,
+    prefill: Option<&'a str>,
+    route: Option<&'a str>,
+}
+
+/// Options of the context sent with a prompt
+#[derive(Debug, Default, Clone)]
+struct ContextOptions {
+    documents: Vec<String>,
+    min_relevance: Option<f64>,
+    max_chars: Option<usize>,
+    on_no_context: NoContextPolicy,
+}
+
+impl RequestOptions<'_> {
+    /// Moves `message` to the end of `history` and returns the prefill as the message to send,
+    /// if a prefill is set
+    fn prefill_messages<'h>(
+        &self,
+        history: Cow<'h, MessageHistory>,
+        message: Message,
+    ) -> (Cow<'h, MessageHistory>, Message) {
+        match self.prefill {
+            Some(prefill) => (
+                extend_history(history, message),
+                Message::Assistant {
+                    content: prefill.to_string(),
+                    tool_calls: None,
+                    name: None,
+                },
+            ),
+            None => (history, message),
+        }
+    }
+
+    /// Prepends the prefill to the content of `response`, the model only returns what follows it
+    fn prepend_prefill(&self, response: &mut Message) {
+        if let (Some(prefill), Message::Assistant { content, .. }) = (self.prefill, response) {
+            content.insert_str(0, prefill);
+        }
+    }
+
+    /// Sets the options on `model`, warning about the ones it doesn't support.
+    /// The options are cleared from `model` once the returned guard is dropped.
+    fn apply<'m, M: CompletionModel>(&self, model: &'m mut M) -> AppliedOptions<'m, '_, M> {
+        // the route goes first, so routers forward the other options to the routed model
+        if let Some(route) = self.route {
+            if !model.set_route(Some(route.to_string())) {
+                warn!(
+                    provider = model.provider_name(),
+                    route, "Model doesn't support routing, sending the request to it"
+                );
+            }
+        }
+        if let Some(key) = self.idempotency_key {
+            if !model.set_idempotency_key(Some(key.to_string())) {
+                warn!(
+                    provider = model.provider_name(),
+                    "Model doesn't support idempotency keys, sending the request without one"
+                );
+            }
+        }
+        if !self.sampling.is_empty() && !model.set_sampling_params(self.sampling) {
+            warn!(
+                provider = model.provider_name(),
+                "Model doesn't support sampling params, sending the request without them"
+            );
+        }
+        AppliedOptions {
+            model,
+            options: self,
+        }
+    }
+
+    /// Clears the options set by [`RequestOptions::apply`] from `model`
+    fn clear<M: CompletionModel>(&self, model: &mut M) {
+        // the route goes last, so routers clear the options of the routed model
+        if self.idempotency_key.is_some() {
+            model.set_idempotency_key(None);
+        }
+        if !self.sampling.is_empty() {
+            model.set_sampling_params(SamplingParams::default());
+        }
+        if self.route.is_some() {
+            model.set_route(None);
+        }
+    }
+}
+
+/// A model with [`RequestOptions`] applied, clearing them once dropped, even if the request
+/// gets cancelled
+struct AppliedOptions<'m, 'o, M: CompletionModel> {
+    model: &'m mut M,
+    options: &'o RequestOptions<'o>,
+}
+
+impl<M: CompletionModel> Deref for AppliedOptions<'_, '_, M> {
+    type Target = M;
+
+    fn deref(&self) -> &M {
+        self.model
+    }
+}
+
+impl<M: CompletionModel> DerefMut for AppliedOptions<'_, '_, M> {
+    fn deref_mut(&mut self) -> &mut M {
+        self.model
+    }
+}
+
+impl<M: CompletionModel> Drop for AppliedOptions<'_, '_, M> {
+    fn drop(&mut self) {
+        self.options.clear(self.model);
+    }
+}
+
+/// Target of the metrics events emitted by clients with metrics enabled
+pub const METRICS_TARGET: &str = "seedframe::metrics";
+
+/// Target of the prompt phase events emitted by clients with metrics enabled
+pub const PROMPT_METRICS_TARGET: &str = "seedframe::metrics::prompt";
+
+const RAW_RESPONSE_SNIPPET_LEN: usize = 500;
+
+/// Adds the `extra` params of a provider, eg. the unknown keys of its json config, to
+/// `request_body`, skipping the ones conflicting with fields already set
+pub fn insert_extra_params(
+    extra: &serde_json::Map<String, serde_json::Value>,
+    request_body: &mut serde_json::Value,
+) {
+    let Some(obj) = request_body.as_object_mut() else {
+        return;
+    };
+    for (key, value) in extra {
+        if obj.contains_key(key) {
+            warn!(param = %key, "Extra param conflicts with a request field, ignoring it");
+            continue;
+        }
+        obj.insert(key.clone(), value.clone());
+    }
+}
+
+/// Warns about the unknown keys of a provider's json config, as they're passed through to
+/// the request body, so typos like `modle` don't go unnoticed
+pub fn warn_unknown_config_keys(extra: &serde_json::Map<String, serde_json::Value>) {
+    for key in extra.keys() {
+        warn!(key = %key, "Unknown config key, passing it through to the request body");
+    }
+}
+
+/// Parses the body of a provider response as json
+///
+/// With `capture_raw` set, a parse error includes a snippet of the raw body (truncated to
+/// 500 characters) for debugging provider quirks, otherwise only the serde error is reported.
+///
+/// # Errors
+/// Returns [`CompletionError::ParseError`] if the body isn't valid json
+pub fn parse_response_body(
+    body: &str,
+    capture_raw: bool,
+) -> Result<serde_json::Value, CompletionError> {
+    serde_json::from_str(body).map_err(|e| {
+        error!(error = ?e, "Failed to parse response JSON");
+        if capture_raw {
+            let mut snippet: String = body.chars().take(RAW_RESPONSE_SNIPPET_LEN).collect();
+            if snippet.len() < body.len() {
+                snippet.push_str("...");
+            }
+            CompletionError::ParseError(format!("{e}, raw response: {snippet}"))
+        } else {
+            CompletionError::ParseError(e.to_string())
+        }
+    })
+}
+
+/// Reads the `usage` object of an OpenAI-style completion response, some proxies and models
+/// leave it out so a missing `usage` gives an empty [`TokenUsage`] rather than an error
+#[must_use]
+pub fn parse_usage(response_json: &serde_json::Value) -> TokenUsage {
+    let usage = &response_json["usage"];
+    if !usage.is_object() {
+        debug!("Response has no usage data");
+        return TokenUsage::default();
+    }
+    TokenUsage {
+        prompt_tokens: usage["prompt_tokens"].as_u64(),
+        completion_tokens: usage["completion_tokens"].as_u64(),
+        total_tokens: usage["total_tokens"].as_u64(),
+    }
+}
+
+/// Strips the whitespace and the markdown code fence some models wrap their output in, eg.
+/// JSON in a ```` ```json ```` block, returning the content of the block
+#[must_use]
+pub fn strip_code_fences(content: &str) -> &str {
+    let trimmed = content.trim();
+    let Some(inner) = trimmed
+        .strip_prefix("```")
+        .and_then(|rest| rest.strip_suffix("```"))
+    else {
+        return trimmed;
+    };
+    let body = match inner.split_once('\n') {
+        // drops the language tag of the opening fence
+        Some((tag, body)) if tag.trim().chars().all(char::is_alphanumeric) => body,
+        _ => inner,
+    };
+    body.trim()
+}
+
+/// Deserializes the extracted output in the content of an OpenAI-style completion response,
+/// see [`strip_code_fences`]
+///
+/// # Errors
+/// Returns [`CompletionError::ParseError`] if the response has no content, or
+/// [`ExtractionError::InvalidOutput`] if the content doesn't deserialize into `T`
+pub fn parse_extracted<T: Extractor>(
+    response_json: &serde_json::Value,
+) -> Result<T, CompletionError> {
+    let extracted_str = response_json["choices"][0]["message"]["content"]
+        .as_str()
+        .ok_or_else(|| {
+            error!("Missing content in extraction response");
+            CompletionError::ParseError("Missing content".to_string())
+        })?;
+
+    let extracted: T = serde_json::from_str(strip_code_fences(extracted_str)).map_err(|e| {
+        error!(error = ?e, raw_response = %extracted_str, "Failed to deserialize extracted content");
+        ExtractionError::InvalidOutput {
+            output: extracted_str.to_string(),
+            error: e.to_string(),
+        }
+    })?;
+
+    info!(
+        extractor_type = std::any::type_name::<T>(),
+        "Successfully extracted data"
+    );
+    Ok(extracted)
 }
 
 /// Extractor for state
@@ -180,524 +795,3741 @@ pub struct Client<M: CompletionModel> {
     token_usage: TokenUsage,
     /// Type-mapped state storage for arbitrary values
     states: DashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    /// Whether the missing embedders warning has already been logged
+    warned_no_embedders: AtomicBool,
+    /// Limits the number of requests in flight to the model, if set
+    in_flight: Option<Arc<Semaphore>>,
+    /// Whether token usage and latency metrics are emitted for every request
+    emit_metrics: bool,
+    /// Caps the retrieved context of every prompt at this many characters, if set
+    max_context_chars: Option<usize>,
+    /// Response returned in place of the error when a request to the model fails, if set
+    fallback_response: Option<String>,
+    /// Screens the prompts, and the responses if `moderate_responses` is set
+    moderator: Option<Box<dyn Moderator>>,
+    moderate_responses: bool,
 
     // common prompt parameters
     temperature: f64,
     max_tokens: usize,
 }
 
-#[allow(clippy::struct_excessive_bools)]
-/// Builder for constructing and executing completion prompts
-pub struct PromptBuilder<'a, M: CompletionModel> {
-    prompt: String,
-    client: &'a mut Client<M>,
-    execute_tools: bool,
-    with_tools: bool,
-    append_tool_response: bool,
-    one_shot: (bool, Option<MessageHistory>),
-    with_context: bool,
+/// Runtime alternative to the `#[client]` macro, assembling a [`Client`] piece by piece
+///
+/// Useful when the client setup is only known at runtime, eg. read from a config file.
+/// The client gets built through [`CompletionModel::build_client`], so the model's default
+/// temperature and token limit apply unless overridden.
+/// Since [`CompletionModel`] isn't object safe, picking a provider at runtime means building
+/// the client in each branch of a `match` on the configured provider.
+///
+/// # Example
+/// ```rust,ignore
+/// let mut client = ClientBuilder::new(OpenAI::new(Some(r#"{"model": "gpt-4o"}"#)))
+///     .preamble("You're a helpful assistant")
+///     .embedder(embedder)
+///     .tool(Box::new(AgentTool::new(researcher, "researcher", "Researches a topic")))
+///     .state(AppState::default())
+///     .temperature(0.2)
+///     .build()?;
+/// ```
+pub struct ClientBuilder<M: CompletionModel> {
+    model: M,
+    preamble: String,
+    embedders: Vec<Embedder>,
+    tools: Vec<Box<dyn crate::tools::Tool>>,
+    execution_strategy: ExecutionStrategy,
+    states: Vec<(&'static str, TypeId, Box<dyn Any + Send + Sync>)>,
+    temperature: Option<f64>,
+    max_tokens: Option<usize>,
+    in_flight: Option<Arc<Semaphore>>,
+    emit_metrics: bool,
+    max_context_chars: Option<usize>,
+    fallback_response: Option<String>,
+    moderator: Option<Box<dyn Moderator>>,
+    moderate_responses: bool,
 }
 
-impl<'a, M: CompletionModel> PromptBuilder<'a, M> {
-    fn new(client: &'a mut Client<M>, prompt: impl Into<String>) -> Self {
+impl<M: CompletionModel> ClientBuilder<M> {
+    /// Creates a new builder for a client using `model`, with an empty preamble, no embedders,
+    /// no tools and the [`ExecutionStrategy::FailEarly`] strategy
+    pub fn new(model: M) -> Self {
         Self {
-            prompt: prompt.into(),
-            client,
-            execute_tools: true,
-            with_tools: true,
-            append_tool_response: false,
-            one_shot: (false, None),
-            with_context: true,
+            model,
+            preamble: String::new(),
+            embedders: vec![],
+            tools: vec![],
+            execution_strategy: ExecutionStrategy::FailEarly,
+            states: vec![],
+            temperature: None,
+            max_tokens: None,
+            in_flight: None,
+            emit_metrics: false,
+            max_context_chars: None,
+            fallback_response: None,
+            moderator: None,
+            moderate_responses: false,
         }
     }
 
-    /// Execute the tool calls if the LLM responds with a Tool call request, `true` by default
+    /// Sets the system instructions for the model
+    ///
+    /// If the preamble contains the [`CONTEXT_PLACEHOLDER`], the context retrieved for each
+    /// prompt is substituted for it in the preamble sent with the prompt instead of being
+    /// added to the prompt, or an empty string if there's no context.
     #[must_use]
-    pub fn execute_tools(mut self, execute: bool) -> Self {
-        self.execute_tools = execute;
+    pub fn preamble(mut self, preamble: impl Into<String>) -> Self {
+        self.preamble = preamble.into();
         self
     }
 
-    /// Wether to send any tool definitions with the prompt, `true` by default
+    /// Adds an embedder to retrieve context from
     #[must_use]
-    pub fn with_tools(mut self, no_tools: bool) -> Self {
-        self.with_tools = no_tools;
+    pub fn embedder(mut self, embedder: Embedder) -> Self {
+        self.embedders.push(embedder);
         self
     }
 
-    /// Create a `Message::User` with the tool reponses and append it to the client history, `false` by default
+    /// Adds a tool the model can call
     #[must_use]
-    pub fn append_tool_response(mut self, append: bool) -> Self {
-        self.append_tool_response = append;
+    pub fn tool(mut self, tool: Box<dyn crate::tools::Tool>) -> Self {
+        self.tools.push(tool);
         self
     }
 
-    /// Wether to retrieve and append the context to the prompt, true by default.
-    /// If true, the client's vector store will get looked up for the top matches to the prompt and
-    /// the context will get appended to the prompt before being sent.
+    /// Sets how the tool calls get executed, [`ExecutionStrategy::FailEarly`] by default
     #[must_use]
-    pub fn with_context(mut self, append_context: bool) -> Self {
-        self.with_context = append_context;
+    pub fn execution_strategy(mut self, strategy: ExecutionStrategy) -> Self {
+        self.execution_strategy = strategy;
         self
     }
 
-    /// Prompt the LLM with a custom history, and get a response.
-    /// Response won't be stored in the client's history
+    /// Registers state with the client, see [`Client::with_state`]
     #[must_use]
-    pub fn one_shot(mut self, one_shot: bool, history: Option<MessageHistory>) -> Self {
-        self.one_shot = (one_shot, history);
+    pub fn state<T: Send + Sync + 'static>(mut self, state: T) -> Self {
+        self.states.push((
+            std::any::type_name::<T>(),
+            TypeId::of::<T>(),
+            Box::new(Arc::new(state)),
+        ));
         self
     }
 
-    /// Extracts structured data from the model's response
-    ///
-    /// Handles context retrieval and message construction automatically.
-    ///
-    /// # Errors
-    /// Returns errors from:
-    /// - Context retrieval ([`VectorStoreError`])
-    /// - Model execution ([`CompletionError`])
-    /// - Extraction ([`ExtractionError`])
-    pub async fn extract<T: Extractor>(self) -> Result<T, crate::error::Error> {
-        let history = if self.one_shot.0 {
-            &self.one_shot.1.unwrap_or_default()
-        } else {
-            &self.client.history
-        };
+    /// Overrides the model's default sampling temperature
+    #[must_use]
+    pub fn temperature(mut self, temperature: f64) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
 
-        let retrieved_context = self.client.get_context(&self.prompt).await?;
-        let context = if self.with_context {
-            retrieved_context
-                .map_or_else(String::new, |c| format!("\n\n<context>\n{c}\n</context>\n"))
-        } else {
-            String::new()
-        };
+    /// Overrides the model's default maximum response length
+    #[must_use]
+    pub fn max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
 
-        let message = Message::User {
-            content: format!("{}{}", self.prompt, context),
-            tool_responses: None,
-        };
+    /// Limits the client to `limit` requests in flight to the model at once, further prompts
+    /// wait in a fair queue for a free slot instead of piling up on the model's lock.
+    /// Unlimited by default.
+    #[must_use]
+    pub fn max_in_flight_prompts(mut self, limit: usize) -> Self {
+        self.in_flight = Some(Arc::new(Semaphore::new(limit)));
+        self
+    }
 
-        let model = self.client.completion_model.clone();
-        let mut guard = model.write().await;
+    /// Like [`ClientBuilder::max_in_flight_prompts`], but shares the slots of `limiter`,
+    /// eg. to cap the requests of several clients using the same provider account
+    #[must_use]
+    pub fn in_flight_limiter(mut self, limiter: Arc<Semaphore>) -> Self {
+        self.in_flight = Some(limiter);
+        self
+    }
 
-        guard
-            .extract::<T>(
-                message,
-                history,
-                self.client.temperature,
-                self.client.max_tokens,
-            )
-            .await
-            .map_err(Into::into)
+    /// Enables emitting token usage and latency metrics, see [`Client::with_metrics`]
+    #[must_use]
+    pub fn metrics(mut self, enabled: bool) -> Self {
+        self.emit_metrics = enabled;
+        self
     }
 
-    /// Builds the prompt and sends it to the completion model
-    ///
-    /// # Errors
+    /// Caps the retrieved context of every prompt, see [`Client::with_max_context_chars`]
+    #[must_use]
+    pub fn max_context_chars(mut self, max: usize) -> Self {
+        self.max_context_chars = Some(max);
+        self
+    }
+
+    /// Sets a response returned when the model can't be reached, see
+    /// [`Client::with_fallback_response`]
+    #[must_use]
+    pub fn fallback_response(mut self, response: impl Into<String>) -> Self {
+        self.fallback_response = Some(response.into());
+        self
+    }
+
+    /// Screens the prompts with `moderator`, see [`Client::with_moderator`]
+    #[must_use]
+    pub fn moderator(mut self, moderator: Box<dyn Moderator>) -> Self {
+        self.moderator = Some(moderator);
+        self
+    }
+
+    /// Also screens the model's responses, see [`Client::with_response_moderation`]
+    #[must_use]
+    pub fn response_moderation(mut self, enabled: bool) -> Self {
+        self.moderate_responses = enabled;
+        self
+    }
+
+    /// Builds the client
+    ///
+    /// # Errors
+    /// Returns [`StateError::AlreadyExists`] if state of the same type was registered twice
+    pub fn build(self) -> Result<Client<impl CompletionModel>, CompletionError> {
+        let mut client = self.model.build_client(
+            self.preamble,
+            self.embedders,
+            ToolSet(self.tools, self.execution_strategy),
+        );
+        if let Some(temperature) = self.temperature {
+            client.temperature = temperature;
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            client.max_tokens = max_tokens;
+        }
+        client.in_flight = self.in_flight;
+        client.emit_metrics = self.emit_metrics;
+        client.max_context_chars = self.max_context_chars;
+        client.fallback_response = self.fallback_response;
+        client.moderator = self.moderator;
+        client.moderate_responses = self.moderate_responses;
+        for (type_name, type_id, state) in self.states {
+            if client.states.contains_key(&type_id) {
+                error!(
+                    state_type = type_name,
+                    "Failed to add state to client because state of a similar type already exists"
+                );
+                return Err(CompletionError::StateError(StateError::AlreadyExists(
+                    format!("{type_name:?}"),
+                )));
+            }
+            client.states.insert(type_id, state);
+        }
+        Ok(client)
+    }
+}
+
+type Observer<'a, T> = Box<dyn Fn(&T) + Send + Sync + 'a>;
+
+/// Callbacks observing the tool calls executed for a prompt
+#[derive(Default)]
+struct ToolObservers<'a> {
+    on_tool_call: Option<Observer<'a, ToolCall>>,
+    on_tool_response: Option<Observer<'a, ToolResponse>>,
+}
+
+impl ToolObservers<'_> {
+    fn call(&self, call: &ToolCall) {
+        if let Some(on_tool_call) = &self.on_tool_call {
+            on_tool_call(call);
+        }
+    }
+
+    fn response(&self, response: &ToolResponse) {
+        if let Some(on_tool_response) = &self.on_tool_response {
+            on_tool_response(response);
+        }
+    }
+}
+
+/// The input of a prompt, see [`IntoPrompt`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Prompt {
+    text: String,
+    history: Option<MessageHistory>,
+}
+
+impl Prompt {
+    /// A prompt sending `text`
+    #[must_use]
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            history: None,
+        }
+    }
+
+    /// Sends the prompt after `history` instead of the client's history, see
+    /// [`PromptBuilder::one_shot`]
+    #[must_use]
+    pub fn history(mut self, history: Vec<Message>) -> Self {
+        self.history = Some(history);
+        self
+    }
+}
+
+/// Types [`Client::prompt`] can be built from
+///
+/// Text, like `&str`, `String` or the output of `format_args!`, becomes the prompt as is.
+/// A `Vec<Message>` gets sent as a one-shot conversation, see [`PromptBuilder::one_shot`],
+/// with the content of its last message as the prompt if it's a user message.
+pub trait IntoPrompt {
+    fn into_prompt(self) -> Prompt;
+}
+
+impl IntoPrompt for Prompt {
+    fn into_prompt(self) -> Prompt {
+        self
+    }
+}
+
+impl IntoPrompt for String {
+    fn into_prompt(self) -> Prompt {
+        Prompt::new(self)
+    }
+}
+
+impl IntoPrompt for &str {
+    fn into_prompt(self) -> Prompt {
+        self.to_string().into_prompt()
+    }
+}
+
+impl IntoPrompt for &String {
+    fn into_prompt(self) -> Prompt {
+        self.clone().into_prompt()
+    }
+}
+
+impl IntoPrompt for std::borrow::Cow<'_, str> {
+    fn into_prompt(self) -> Prompt {
+        self.into_owned().into_prompt()
+    }
+}
+
+impl IntoPrompt for std::fmt::Arguments<'_> {
+    fn into_prompt(self) -> Prompt {
+        self.to_string().into_prompt()
+    }
+}
+
+impl IntoPrompt for Vec<Message> {
+    fn into_prompt(mut self) -> Prompt {
+        let text = match self.pop() {
+            Some(Message::User { content, .. }) => content,
+            Some(message) => {
+                self.push(message);
+                String::new()
+            }
+            None => String::new(),
+        };
+        Prompt::new(text).history(self)
+    }
+}
+
+#[allow(clippy::struct_excessive_bools)]
+/// Builder for constructing and executing completion prompts
+pub struct PromptBuilder<'a, M: CompletionModel> {
+    prompt: String,
+    client: &'a mut Client<M>,
+    execute_tools: bool,
+    with_tools: bool,
+    append_tool_response: bool,
+    one_shot: (bool, Option<MessageHistory>),
+    with_context: bool,
+    context_position: ContextPosition,
+    history_window: Option<usize>,
+    cancellation_token: CancellationToken,
+    tool_observers: ToolObservers<'a>,
+    idempotency_key: Option<String>,
+    sampling: SamplingParams,
+    prefill: Option<String>,
+    route: Option<String>,
+    context_options: ContextOptions,
+    examples: MessageHistory,
+    timeout: Option<Duration>,
+    metrics: PromptMetrics,
+    max_extract_retries: usize,
+}
+
+impl<'a, M: CompletionModel> PromptBuilder<'a, M> {
+    fn new(client: &'a mut Client<M>, prompt: impl IntoPrompt) -> Self {
+        let Prompt { text, history } = prompt.into_prompt();
+        Self {
+            prompt: text,
+            client,
+            execute_tools: true,
+            with_tools: true,
+            append_tool_response: false,
+            one_shot: (history.is_some(), history),
+            with_context: true,
+            context_position: ContextPosition::default(),
+            history_window: None,
+            cancellation_token: CancellationToken::new(),
+            tool_observers: ToolObservers::default(),
+            idempotency_key: None,
+            sampling: SamplingParams::default(),
+            prefill: None,
+            route: None,
+            context_options: ContextOptions::default(),
+            examples: vec![],
+            timeout: None,
+            metrics: PromptMetrics::default(),
+            max_extract_retries: 0,
+        }
+    }
+
+    /// Execute the tool calls if the LLM responds with a Tool call request, `true` by default
+    #[must_use]
+    pub fn execute_tools(mut self, execute: bool) -> Self {
+        self.execute_tools = execute;
+        self
+    }
+
+    /// Wether to send any tool definitions with the prompt, `true` by default
+    #[must_use]
+    pub fn with_tools(mut self, no_tools: bool) -> Self {
+        self.with_tools = no_tools;
+        self
+    }
+
+    /// Append a `Message::Tool` for every tool response to the client history, `false` by default
+    #[must_use]
+    pub fn append_tool_response(mut self, append: bool) -> Self {
+        self.append_tool_response = append;
+        self
+    }
+
+    /// Wether to retrieve and append the context to the prompt, true by default.
+    /// If true, the client's vector store will get looked up for the top matches to the prompt and
+    /// the context will get appended to the prompt before being sent.
+    #[must_use]
+    pub fn with_context(mut self, append_context: bool) -> Self {
+        self.with_context = append_context;
+        self
+    }
+
+    /// Prompt the LLM with a custom history, and get a response.
+    /// Response won't be stored in the client's history
+    #[must_use]
+    pub fn one_shot(mut self, one_shot: bool, history: Option<MessageHistory>) -> Self {
+        self.one_shot = (one_shot, history);
+        self
+    }
+
+    /// Where to place the retrieved context in the request, appended to the prompt by default.
+    /// Context placed in separate messages isn't stored in the client's history.
+    #[must_use]
+    pub fn context_position(mut self, position: ContextPosition) -> Self {
+        self.context_position = position;
+        self
+    }
+
+    /// Only send the preamble and the last `n` messages of the history with this prompt.
+    /// The client's stored history is left untouched.
+    #[must_use]
+    pub fn history_window(mut self, n: usize) -> Self {
+        self.history_window = Some(n);
+        self
+    }
+
+    /// Bounds the time the whole prompt can take, including retrieving the context, the
+    /// request to the model and executing the requested tool calls
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Re-prompts the model up to `retries` times when its output doesn't deserialize into
+    /// the type passed to [`PromptBuilder::extract`], sending back the invalid output along with
+    /// the deserialization error and the expected schema. No retries by default.
+    #[must_use]
+    pub fn max_extract_retries(mut self, retries: usize) -> Self {
+        self.max_extract_retries = retries;
+        self
+    }
+
+    /// Aborts the tool calls executed for this prompt once `token` gets cancelled,
+    /// eg. when the caller gives up on the prompt
+    #[must_use]
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = token;
+        self
+    }
+
+    /// Sends `key` as the idempotency key of the request, letting providers supporting them
+    /// (OpenAI and Anthropic) deduplicate retries of a request they already processed.
+    /// Ignored with a warning by providers without support.
+    #[must_use]
+    pub fn idempotency_key(mut self, key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(key.into());
+        self
+    }
+
+    /// Sends this prompt to the model routed as `name` of a [`Routing`] model, instead of its
+    /// primary model. Ignored with a warning by models not routing between several models.
+    ///
+    /// [`Routing`]: crate::providers::completions::Routing
+    #[must_use]
+    pub fn route_to(mut self, name: impl Into<String>) -> Self {
+        self.route = Some(name.into());
+        self
+    }
+
+    /// Sets `top_p` for this prompt, taking precedence over the one of the model's config
+    #[must_use]
+    pub fn top_p(mut self, top_p: f64) -> Self {
+        self.sampling.top_p = Some(top_p);
+        self
+    }
+
+    /// Sets `frequency_penalty` for this prompt, taking precedence over the one of the model's
+    /// config
+    #[must_use]
+    pub fn frequency_penalty(mut self, penalty: f64) -> Self {
+        self.sampling.frequency_penalty = Some(penalty);
+        self
+    }
+
+    /// Sets `presence_penalty` for this prompt, taking precedence over the one of the model's
+    /// config
+    #[must_use]
+    pub fn presence_penalty(mut self, penalty: f64) -> Self {
+        self.sampling.presence_penalty = Some(penalty);
+        self
+    }
+
+    /// Sets the sampling `seed` for this prompt, for best-effort reproducible outputs.
+    /// Check [`CompletionOutput::system_fingerprint`] to detect backend changes that break it.
+    #[must_use]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.sampling.seed = Some(seed);
+        self
+    }
+
+    /// Adds `documents` to the context of this prompt, ahead of the retrieved context, eg. to
+    /// pass reference text without storing it. Inline documents are sent even if retrieving
+    /// context is disabled, and aren't subject to the context limits.
+    #[must_use]
+    pub fn with_documents(mut self, documents: &[impl AsRef<str>]) -> Self {
+        self.context_options
+            .documents
+            .extend(documents.iter().map(|d| d.as_ref().to_string()));
+        self
+    }
+
+    /// Sets the `reasoning_effort` of reasoning models for this prompt, taking precedence over
+    /// the one of the model's config
+    #[must_use]
+    pub fn reasoning_effort(mut self, effort: ReasoningEffort) -> Self {
+        self.sampling.reasoning_effort = Some(effort);
+        self
+    }
+
+    /// Only includes retrieved documents with a similarity score of at least `threshold` in the
+    /// context, omitting the context entirely if none of them qualify
+    #[must_use]
+    pub fn min_relevance(mut self, threshold: f64) -> Self {
+        self.context_options.min_relevance = Some(threshold);
+        self
+    }
+
+    /// Caps the retrieved context at `max` characters, keeping the higher scored documents
+    /// first, overriding the cap of the client
+    #[must_use]
+    pub fn max_context_chars(mut self, max: usize) -> Self {
+        self.context_options.max_chars = Some(max);
+        self
+    }
+
+    /// Sets what happens when no context is retrieved for the prompt, the prompt is sent
+    /// without context by default. Ignored if the context is disabled.
+    #[must_use]
+    pub fn on_no_context(mut self, policy: NoContextPolicy) -> Self {
+        self.context_options.on_no_context = policy;
+        self
+    }
+
+    /// Prefills the start of the response, sending `prefill` as a trailing assistant message
+    /// for the model to continue from, eg. `"{"` to get a JSON object. The returned response
+    /// starts with the prefill.
+    ///
+    /// Providers supporting prefills, like Anthropic, continue the prefill. Most `OpenAI`
+    /// compatible providers only approximate it, taking it as a hint for the response.
+    #[must_use]
+    pub fn prefill(mut self, prefill: impl Into<String>) -> Self {
+        self.prefill = Some(prefill.into());
+        self
+    }
+
+    /// Sends `examples` as pairs of user and assistant turns before the prompt, for few-shot
+    /// prompting. The examples are only sent with this prompt and aren't stored in the history.
+    #[must_use]
+    pub fn with_examples<U, A>(mut self, examples: impl IntoIterator<Item = (U, A)>) -> Self
+    where
+        U: Into<String>,
+        A: Into<String>,
+    {
+        for (user, assistant) in examples {
+            self.examples.push(Message::User {
+                content: user.into(),
+                tool_responses: None,
+                name: None,
+            });
+            self.examples.push(Message::Assistant {
+                content: assistant.into(),
+                tool_calls: None,
+                name: None,
+            });
+        }
+        self
+    }
+
+    fn request_options(&self) -> RequestOptions<'_> {
+        RequestOptions {
+            idempotency_key: self.idempotency_key.as_deref(),
+            sampling: self.sampling,
+            prefill: self.prefill.as_deref(),
+            route: self.route.as_deref(),
+        }
+    }
+
+    /// Builds the history and the message the prompt is sent with, along with the options of
+    /// the request: the one-shot or the client's history, windowed, followed by the examples,
+    /// the retrieved context, the prompt and the prefill
+    async fn build_messages(
+        &self,
+        metrics: &mut PromptMetrics,
+    ) -> Result<(Cow<'_, MessageHistory>, Message, RequestOptions<'_>), crate::error::Error> {
+        let history = match &self.one_shot {
+            (true, Some(history)) => window_history(history, self.history_window),
+            (true, None) => Cow::Owned(vec![]),
+            (false, _) => window_history(&self.client.history, self.history_window),
+        };
+        let history = append_examples(history, &self.examples);
+        let (history, message) = self
+            .client
+            .build_user_message(
+                &self.prompt,
+                history,
+                self.with_context,
+                self.context_position,
+                &self.context_options,
+                metrics,
+            )
+            .await?;
+        let options = self.request_options();
+        let (history, message) = options.prefill_messages(history, message);
+        Ok((history, message, options))
+    }
+
+    /// Calls `callback` with every tool call requested by the model, right before it's executed
+    #[must_use]
+    pub fn on_tool_call(mut self, callback: impl Fn(&ToolCall) + Send + Sync + 'a) -> Self {
+        self.tool_observers.on_tool_call = Some(Box::new(callback));
+        self
+    }
+
+    /// Calls `callback` with the response of every successfully executed tool call
+    #[must_use]
+    pub fn on_tool_response(mut self, callback: impl Fn(&ToolResponse) + Send + Sync + 'a) -> Self {
+        self.tool_observers.on_tool_response = Some(Box::new(callback));
+        self
+    }
+
+    /// Extracts structured data from the model's response
+    ///
+    /// Handles context retrieval and message construction automatically.
+    ///
+    /// # Errors
+    /// Returns errors from:
+    /// - Context retrieval ([`VectorStoreError`])
+    /// - Model execution ([`CompletionError`])
+    /// - Extraction ([`ExtractionError`]), [`ExtractionError::InvalidOutput`] once the retries
+    ///   set by [`PromptBuilder::max_extract_retries`] run out
+    /// - The prompt timing out, retries included
+    pub async fn extract<T: Extractor>(self) -> Result<T, crate::error::Error> {
+        let timeout = self.timeout;
+        with_timeout(timeout, self.extract_untimed()).await
+    }
+
+    async fn extract_untimed<T: Extractor>(self) -> Result<T, crate::error::Error> {
+        let (mut history, mut message, options) =
+            self.build_messages(&mut PromptMetrics::default()).await?;
+
+        let _permit = self.client.acquire_in_flight().await;
+        let model = self.client.completion_model.clone();
+        let mut guard = model.write().await;
+
+        let mut retries = 0;
+        loop {
+            let result = options
+                .apply(&mut *guard)
+                .extract::<T>(
+                    message.clone(),
+                    &history,
+                    self.client.temperature,
+                    self.client.max_tokens,
+                )
+                .await;
+            match result {
+                Err(CompletionError::ExtractorError(ExtractionError::InvalidOutput {
+                    output,
+                    error,
+                })) if retries < self.max_extract_retries => {
+                    retries += 1;
+                    warn!(retries, %error, "Extracted output is invalid, retrying with feedback");
+                    let history = history.to_mut();
+                    // the invalid output takes the place of the prefill, if any
+                    if !matches!(message, Message::Assistant { .. }) {
+                        history.push(message);
+                    }
+                    history.push(Message::Assistant {
+                        content: output,
+                        tool_calls: None,
+                        name: None,
+                    });
+                    message = Message::User {
+                        content: extraction_feedback::<T>(&error),
+                        tool_responses: None,
+                        name: None,
+                    };
+                }
+                result => return result.map_err(Into::into),
+            }
+        }
+    }
+
+    /// Builds the prompt and sends it to the completion model
+    ///
+    /// # Errors
     /// This method will error if it fails to send the prompt or tool calls fail
     pub async fn send(self) -> Result<Message, crate::error::Error> {
+        self.send_detailed().await.map(|output| output.message)
+    }
+
+    /// Sends the prompt without executing the tool calls the model requests, returning them
+    /// for the caller to handle instead, eg. to ask a user for approval before running them
+    /// with [`Client::run_tools`]. Returns no calls if the model didn't request any.
+    ///
+    /// # Errors
+    /// Same as [`PromptBuilder::send`]
+    pub async fn tool_calls(mut self) -> Result<Vec<ToolCall>, crate::error::Error> {
+        self.execute_tools = false;
+        let message = self.send().await?;
+        Ok(message
+            .tool_calls()
+            .map(<[ToolCall]>::to_vec)
+            .unwrap_or_default())
+    }
+
+    /// Sends the prompt like [`PromptBuilder::send`], then deserializes the responses of the
+    /// calls to the tool `tool` into `T`, in call order. Returns no results if the model
+    /// didn't call the tool.
+    ///
+    /// # Errors
+    /// Same as [`PromptBuilder::send`], and [`ToolSetError::ToolError`] if a response doesn't
+    /// deserialize into `T`
+    pub async fn collect_tool_results<T: serde::de::DeserializeOwned>(
+        self,
+        tool: &str,
+    ) -> Result<Vec<T>, crate::error::Error> {
+        let Message::User {
+            tool_responses: Some(responses),
+            ..
+        } = self.send().await?
+        else {
+            return Ok(vec![]);
+        };
+        responses
+            .iter()
+            .filter(|response| response.name == tool)
+            .map(|response| response.parse().map_err(|e| ToolSetError::from(e).into()))
+            .collect()
+    }
+
+    /// Like [`PromptBuilder::send`], but also returns the token usage and the finish reason
+    /// reported by the model, eg. to detect responses truncated by the token limit.
+    ///
+    /// # Errors
+    /// This method will error if it fails to send the prompt, tool calls fail or the prompt
+    /// times out. The request to the model failing or timing out returns the client's fallback
+    /// response instead if it has one, see [`Client::with_fallback_response`].
+    pub async fn send_detailed(self) -> Result<CompletionOutput, crate::error::Error> {
+        self.send_with_metrics().await.map(|(output, _)| output)
+    }
+
+    /// Like [`PromptBuilder::send_detailed`], but also returns how long each phase of the
+    /// prompt took, eg. to tell whether the context retrieval or the model is slowing prompts
+    /// down. Only the total is measured when the fallback response is returned.
+    ///
+    /// # Errors
+    /// Same as [`PromptBuilder::send_detailed`]
+    pub async fn send_with_metrics(
+        self,
+    ) -> Result<(CompletionOutput, PromptMetrics), crate::error::Error> {
+        let start = Instant::now();
+        let timeout = self.timeout;
+        let fallback = self.client.fallback_response.clone();
+        match (with_timeout(timeout, self.send_untimed()).await, fallback) {
+            (
+                Err(crate::error::Error::Completion(
+                    e @ (CompletionError::ProviderError(..)
+                    | CompletionError::RequestError(_)
+                    | CompletionError::ParseError(_)),
+                )),
+                Some(fallback),
+            ) => {
+                error!(error = ?e, "Completion request failed, returning the fallback response");
+                let output = CompletionOutput {
+                    message: Message::Assistant {
+                        content: fallback,
+                        tool_calls: None,
+                        name: None,
+                    },
+                    token_usage: TokenUsage::default(),
+                    finish_reason: None,
+                    system_fingerprint: None,
+                };
+                let metrics = PromptMetrics {
+                    total: start.elapsed(),
+                    ..PromptMetrics::default()
+                };
+                Ok((output, metrics))
+            }
+            (result, _) => result,
+        }
+    }
+
+    async fn send_untimed(
+        mut self,
+    ) -> Result<(CompletionOutput, PromptMetrics), crate::error::Error> {
+        let start = Instant::now();
+        let tools = if self.with_tools && !self.client.tools.0.is_empty() {
+            Some(&*self.client.tools)
+        } else {
+            None
+        };
+        let mut metrics = PromptMetrics::default();
+        let (history, message, options) = self.build_messages(&mut metrics).await?;
+        let output = self
+            .client
+            .send_request(message, &history, tools, &options, &mut metrics)
+            .await?;
+        self.metrics = metrics;
+
+        let message = self
+            .handle_response(output.message, output.token_usage.clone())
+            .await?;
+        self.metrics.total = start.elapsed();
+        self.client.record_prompt_metrics(&self.metrics);
+        Ok((CompletionOutput { message, ..output }, self.metrics))
+    }
+
+    /// Builds the request the prompt would be sent with, without sending it, eg. to debug
+    /// context injection or tool serialization. Context is still retrieved for the prompt,
+    /// but nothing gets sent to the completion model or stored in the history.
+    ///
+    /// # Errors
+    /// This method will error if retrieving the context fails or the model doesn't support
+    /// building request bodies
+    pub async fn dry_run(self) -> Result<serde_json::Value, crate::error::Error> {
         let tools = if self.with_tools && !self.client.tools.0.is_empty() {
             Some(&*self.client.tools)
         } else {
             None
         };
-        let history = if self.one_shot.0 {
-            &self.one_shot.1.unwrap_or_default()
-        } else {
-            &self.client.history
+        let (history, message, options) =
+            self.build_messages(&mut PromptMetrics::default()).await?;
+        self.client
+            .build_request(message, &history, tools, &options)
+            .await
+    }
+
+    /// Builds the prompt and streams the response from the completion model,
+    /// calling `on_text` with every text delta as it arrives.
+    ///
+    /// Tool calls in the stream are assembled from their deltas and, like with
+    /// [`PromptBuilder::send`], executed once the stream completes if `execute_tools` is set.
+    ///
+    /// # Errors
+    /// This method will error if the model doesn't support streaming, the stream fails,
+    /// tool calls fail or the prompt times out
+    pub async fn stream<F: FnMut(&str) + Send>(
+        self,
+        on_text: F,
+    ) -> Result<Message, crate::error::Error> {
+        let timeout = self.timeout;
+        with_timeout(timeout, self.stream_untimed(on_text)).await
+    }
+
+    async fn stream_untimed<F: FnMut(&str) + Send>(
+        mut self,
+        mut on_text: F,
+    ) -> Result<Message, crate::error::Error> {
+        let tools = if self.with_tools && !self.client.tools.0.is_empty() {
+            Some(&*self.client.tools)
+        } else {
+            None
+        };
+        let mut metrics = PromptMetrics::default();
+        let (history, message, options) = self.build_messages(&mut metrics).await?;
+
+        let _permit = self.client.acquire_in_flight().await;
+        let start = Instant::now();
+        let (mut stream, provider, model_name) = {
+            let model = self.client.completion_model.clone();
+            let mut guard = model.write().await;
+            let stream = options
+                .apply(&mut *guard)
+                .stream(
+                    message,
+                    &history,
+                    tools,
+                    self.client.temperature,
+                    self.client.max_tokens,
+                )
+                .await?;
+            (
+                stream,
+                guard.provider_name().to_string(),
+                guard.model_name().to_string(),
+            )
+        };
+
+        let mut content = String::new();
+        if let Some(prefill) = options.prefill {
+            on_text(prefill);
+            content.push_str(prefill);
+        }
+        self.metrics = metrics;
+        let mut tool_calls = ToolCallAccumulator::default();
+        let mut token_usage = TokenUsage::default();
+        while let Some(chunk) = stream.next().await {
+            match chunk? {
+                StreamChunk::Text(text) => {
+                    on_text(&text);
+                    content.push_str(&text);
+                }
+                StreamChunk::ToolCallDelta {
+                    index,
+                    id,
+                    name,
+                    arguments,
+                } => tool_calls.push(index, id, name, &arguments),
+                StreamChunk::Usage(usage) => token_usage = usage,
+            }
+        }
+        self.client
+            .record_metrics_for(&provider, &model_name, &token_usage, start.elapsed());
+
+        let response = Message::Assistant {
+            content,
+            tool_calls: tool_calls.finish(),
+            name: None,
+        };
+        self.handle_response(response, token_usage).await
+    }
+
+    /// Records the model's response in the client and executes any requested tool calls
+    async fn handle_response(
+        &mut self,
+        mut response: Message,
+        token_usage: TokenUsage,
+    ) -> Result<Message, crate::error::Error> {
+        if let (Some(moderator), true, Message::Assistant { content, .. }) = (
+            &self.client.moderator,
+            self.client.moderate_responses,
+            &response,
+        ) {
+            moderator.check(content).await.inspect_err(|e| {
+                warn!(error = %e, "Model response rejected by moderation");
+            })?;
+        }
+        if !self.one_shot.0 {
+            self.client.history.push(Message::User {
+                content: self.prompt.clone(),
+                tool_responses: None,
+                name: None,
+            });
+            self.client.history.push(response.clone());
+        }
+
+        self.client.update_token_usage(&token_usage);
+        if token_usage.total_tokens.is_some() {
+            info!(
+                "Prompt used up: {:?} tokens, Total tokens used: {:?}",
+                token_usage.total_tokens, self.client.token_usage.total_tokens
+            );
+        }
+
+        if self.execute_tools {
+            if let Message::Assistant {
+                content: _,
+                tool_calls: Some(calls),
+                ..
+            } = response.clone()
+            {
+                if self.one_shot.0 {
+                    self.client.history.push(response);
+                }
+                let tools_start = Instant::now();
+                let values = self
+                    .client
+                    .run_tools_observed(
+                        Some(&calls),
+                        &self.cancellation_token,
+                        &self.tool_observers,
+                    )
+                    .await?;
+                self.metrics.tools = Some(tools_start.elapsed());
+                if self.one_shot.0 {
+                    self.client.history.pop();
+                }
+                if self.append_tool_response && !self.one_shot.0 {
+                    let tool_messages: Vec<Message> =
+                        values.iter().cloned().map(Message::Tool).collect();
+                    self.client.append_history(&tool_messages);
+                }
+                response = Message::User {
+                    content: String::new(),
+                    tool_responses: Some(values),
+                    name: None,
+                };
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+impl<M: CompletionModel + Send> Client<M> {
+    /// Creates a new client with the specified configuration
+    ///
+    /// # Arguments
+    /// * `completion_model` - The underlying model implementation
+    /// * `preamble` - System instructions for the model
+    /// * `temperature` - Sampling temperature (0.0-1.0)
+    /// * `max_tokens` - Maximum response length
+    /// * `embedders` - Embedding models for context retrieval
+    /// * `tools` - Available tools for function calling
+    pub fn new(
+        completion_model: M,
+        preamble: impl AsRef<str>,
+        temperature: f64,
+        max_tokens: usize,
+        embedders: Vec<Embedder>,
+        tools: ToolSet,
+    ) -> Self {
+        Self {
+            completion_model: Arc::new(RwLock::new(completion_model)),
+            history: vec![Message::Preamble(String::from(preamble.as_ref()))],
+            embedders,
+            tools: Box::new(tools),
+            temperature,
+            max_tokens,
+            token_usage: TokenUsage::default(),
+            states: DashMap::new(),
+            warned_no_embedders: AtomicBool::new(false),
+            in_flight: None,
+            emit_metrics: false,
+            max_context_chars: None,
+            fallback_response: None,
+            moderator: None,
+            moderate_responses: false,
+        }
+    }
+
+    /// Enables emitting the token usage and latency of every request to the model as a
+    /// `tracing` event, for scraping by eg. an OpenTelemetry layer. Disabled by default.
+    ///
+    /// Events are emitted at the `INFO` level with the [`METRICS_TARGET`] target and the
+    /// `sf.provider`, `sf.model`, `sf.prompt_tokens`, `sf.completion_tokens` and `sf.latency_ms`
+    /// fields, token counts are omitted when the provider doesn't report them.
+    /// Prompts sent with [`PromptBuilder::send`] and its variants also emit the durations of
+    /// their phases, see [`PromptMetrics`], with the [`PROMPT_METRICS_TARGET`] target and the
+    /// `sf.retrieval_ms`, `sf.completion_ms`, `sf.tools_ms` and `sf.total_ms` fields.
+    #[must_use]
+    pub fn with_metrics(mut self, enabled: bool) -> Self {
+        self.emit_metrics = enabled;
+        self
+    }
+
+    /// Caps the context retrieved for every prompt at `max` characters, keeping the higher scored
+    /// documents first and truncating the rest, so that large documents can't crowd out the
+    /// prompt. Can be overridden per prompt with [`PromptBuilder::max_context_chars`].
+    #[must_use]
+    pub fn with_max_context_chars(mut self, max: usize) -> Self {
+        self.max_context_chars = Some(max);
+        self
+    }
+
+    /// Answers prompts with `response` instead of failing when the request to the model fails,
+    /// eg. when the provider is down, for apps preferring a degraded answer over an error.
+    /// Disabled by default.
+    ///
+    /// Only provider, request (timeouts included) and response parsing errors are replaced.
+    /// The underlying error is logged, and neither the prompt nor the fallback response get
+    /// added to the history. Failures that aren't requests to the model, like retrieving
+    /// context, finding no context or tool calls failing, are still returned as errors.
+    #[must_use]
+    pub fn with_fallback_response(mut self, response: impl Into<String>) -> Self {
+        self.fallback_response = Some(response.into());
+        self
+    }
+
+    /// Screens every prompt with `moderator` before it's sent, a flagged prompt fails with
+    /// [`ModerationError::Flagged`] without reaching the model. Only the prompt is checked,
+    /// not the retrieved context or the history.
+    #[must_use]
+    pub fn with_moderator(mut self, moderator: Box<dyn Moderator>) -> Self {
+        self.moderator = Some(moderator);
+        self
+    }
+
+    /// Also screens the model's responses with the moderator set by
+    /// [`Client::with_moderator`], a flagged response fails the prompt before being added to
+    /// the history or its tool calls being executed. Disabled by default.
+    #[must_use]
+    pub fn with_response_moderation(mut self, enabled: bool) -> Self {
+        self.moderate_responses = enabled;
+        self
+    }
+
+    /// Clear conversation history while maintaining premble
+    pub fn clear_history(&mut self) {
+        self.history.retain(|m| matches!(m, Message::Preamble(_)));
+    }
+
+    /// Replaces the current message history with the provided history
+    pub fn load_history(&mut self, history: MessageHistory) {
+        self.history = history;
+    }
+
+    /// Returns a reference to the current message history
+    #[must_use]
+    pub fn export_history(&self) -> &MessageHistory {
+        &self.history
+    }
+
+    /// Number of messages in the history, the preamble included
+    #[must_use]
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Returns the last response of the model in the history, if any
+    #[must_use]
+    pub fn last_assistant_message(&self) -> Option<&Message> {
+        self.history
+            .iter()
+            .rfind(|m| matches!(m, Message::Assistant { .. }))
+    }
+
+    /// Returns the last prompt in the history, if any
+    #[must_use]
+    pub fn last_user_message(&self) -> Option<&Message> {
+        self.history
+            .iter()
+            .rfind(|m| matches!(m, Message::User { .. }))
+    }
+
+    /// Returns the tokens used by all the prompts sent by the client, streamed ones included
+    #[must_use]
+    pub fn token_usage(&self) -> &TokenUsage {
+        &self.token_usage
+    }
+
+    /// Exports the conversation history as a list of `OpenAI` chat completions messages
+    ///
+    /// Useful for sharing conversations with tools outside seedframe, the output uses the
+    /// `system`, `user`, `assistant` and `tool` roles regardless of the model of the client.
+    #[must_use]
+    pub fn export_history_as_openai(&self) -> serde_json::Value {
+        crate::providers::completions::openai::history_to_json(&self.history)
+    }
+
+    /// Appends messages to the conversation history
+    pub fn append_history(&mut self, messages: &[Message]) {
+        messages.iter().for_each(|m| self.history.push(m.clone()));
+    }
+
+    /// Regenerates the last assistant response
+    ///
+    /// Pops the last assistant turn, re-sends the preceding user turn with the current
+    /// temperature and replaces the assistant turn with the new response.
+    /// Tool calls in the new response aren't executed.
+    ///
+    /// # Errors
+    /// Returns [`CompletionError::NothingToRegenerate`] if the last turn isn't an assistant
+    /// message preceded by a user message, or any error from the completion model
+    #[instrument(skip(self))]
+    pub async fn regenerate(&mut self) -> Result<Message, crate::error::Error> {
+        let len = self.history.len();
+        let prompt = match self.history.as_slice() {
+            [.., prompt @ Message::User { .. }, Message::Assistant { .. }] => prompt.clone(),
+            _ => {
+                error!("Last message in message history isn't an assistant response");
+                return Err(CompletionError::NothingToRegenerate.into());
+            }
+        };
+        let history = self.history[..len - 2].to_vec();
+        let tools = if self.tools.0.is_empty() {
+            None
+        } else {
+            Some(&*self.tools)
+        };
+
+        let _permit = self.acquire_in_flight().await;
+        let model = self.completion_model.clone();
+        let mut guard = model.write().await;
+        let start = Instant::now();
+        let (response, token_usage) = guard
+            .send(prompt, &history, tools, self.temperature, self.max_tokens)
+            .await?;
+        self.record_metrics(&*guard, &token_usage, start.elapsed());
+        drop(guard);
+        self.update_token_usage(&token_usage);
+        self.history[len - 1] = response.clone();
+        Ok(response)
+    }
+
+    #[instrument(skip(self, state), fields())]
+    /// Registers new state with the client
+    ///
+    /// # Errors
+    /// Returns [`StateError::AlreadyExists`] if state of this type is already registered
+    pub fn with_state<T: Send + Sync + 'static>(self, state: T) -> Result<Self, CompletionError> {
+        let type_id = state.type_id();
+        if self.states.contains_key(&type_id) {
+            error!(
+                state_type = std::any::type_name::<T>(),
+                "Failed to add state to client because state of a similar type already exists"
+            );
+            return Err(CompletionError::StateError(StateError::AlreadyExists(
+                format!("{:?}", std::any::type_name::<T>()),
+            )));
+        }
+        self.states.insert(type_id, Box::new(Arc::new(state)));
+        info!(
+            state_type = std::any::type_name::<T>(),
+            "Successfully added state new state to client!"
+        );
+        Ok(self)
+    }
+
+    #[instrument(skip(self))]
+    /// Retrieves previously registered state
+    ///
+    /// # Errors
+    /// Returns [`StateError::NotFound`] if no state of type `T` exists
+    pub fn get_state<T: Send + Sync + 'static>(&self) -> Result<State<T>, StateError> {
+        let boxed = self.states.get(&TypeId::of::<T>()).ok_or({
+            error!(
+                state_type = std::any::type_name::<T>(),
+                "Failed to find requested state on client"
+            );
+            StateError::NotFound
+        })?;
+
+        let arc = boxed.downcast_ref::<Arc<T>>().ok_or(StateError::NotFound)?;
+
+        Ok(State(arc.clone()))
+    }
+
+    /// Creates a `PromptBuilder` instance, from text or a conversation, see [`IntoPrompt`]
+    pub fn prompt(&mut self, prompt: impl IntoPrompt) -> PromptBuilder<'_, M> {
+        PromptBuilder::new(self, prompt)
+    }
+
+    /// Executes requested tool calls from the model
+    ///
+    /// If no calls are provided, attempts to use calls from the last assistant message.
+    /// Supports different execution strategies (see [`ExecutionStrategy`]), whatever the
+    /// strategy the returned responses are in the same order as the calls.
+    ///
+    /// # Errors
+    /// Returns [`ToolSetError`] for:
+    /// - Empty message history when calls not provided
+    /// - Last message not containing tool calls
+    /// - Individual tool execution failures
+    pub async fn run_tools(
+        &self,
+        calls: Option<&[ToolCall]>,
+    ) -> Result<Vec<ToolResponse>, ToolSetError> {
+        self.run_tools_with_cancellation(calls, &CancellationToken::new())
+            .await
+    }
+
+    /// Like [`Client::run_tools`], but aborts the in-flight tool calls once `cancel` is triggered
+    ///
+    /// # Errors
+    /// Same as [`Client::run_tools`], cancelled calls fail with [`crate::tools::ToolError::Cancelled`]
+    pub async fn run_tools_with_cancellation(
+        &self,
+        calls: Option<&[ToolCall]>,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<ToolResponse>, ToolSetError> {
+        self.run_tools_observed(calls, cancel, &ToolObservers::default())
+            .await
+    }
+
+    #[instrument(skip_all, fields(fetch_toolcall_from_history = calls.is_some()))]
+    async fn run_tools_observed(
+        &self,
+        calls: Option<&[ToolCall]>,
+        cancel: &CancellationToken,
+        observers: &ToolObservers<'_>,
+    ) -> Result<Vec<ToolResponse>, ToolSetError> {
+        let calls = if let Some(calls) = calls {
+            calls
+        } else {
+            let last = self.history.last().ok_or({
+                error!("Attempting to extract tool to call from an empty message history!");
+                ToolSetError::EmptyMessageHistory
+            })?;
+            if let Message::Assistant {
+                content: _,
+                tool_calls: Some(tcs),
+                ..
+            } = last
+            {
+                tcs
+            } else {
+                error!("Last message in message history doesnt contain tools to call");
+                Err(ToolSetError::LastMessageNotAToolCall)?
+            }
+        };
+
+        let mut values = vec![];
+        match self.tools.1 {
+            ExecutionStrategy::FailEarly | ExecutionStrategy::Sequential => {
+                for call in calls {
+                    info!(
+                        tool_name = call.name,
+                        "Calling tool with a sequential `FailEarly` execution strategy!"
+                    );
+                    observers.call(call);
+                    let call_result = self
+                        .tools
+                        .call_with_cancellation(
+                            &call.id,
+                            &call.name,
+                            &call.arguments,
+                            &self.states,
+                            cancel,
+                        )
+                        .await;
+                    match call_result {
+                        Ok(ref v) => observers.response(v),
+                        Err(ref e) => {
+                            error!(error = ?e, tool_name = call.name, "Tool call failed");
+                        }
+                    }
+                    values.push(call_result?);
+                }
+            }
+            ExecutionStrategy::BestEffort | ExecutionStrategy::ReportFailures => {
+                let report_failures = matches!(self.tools.1, ExecutionStrategy::ReportFailures);
+                for call in calls {
+                    info!(
+                        tool_name = call.name,
+                        report_failures, "Calling tool with a `BestEffort` execution strategy!"
+                    );
+                    observers.call(call);
+                    let tr = self
+                        .tools
+                        .call_with_cancellation(
+                            &call.id,
+                            &call.name,
+                            &call.arguments,
+                            &self.states,
+                            cancel,
+                        )
+                        .await;
+                    match tr {
+                        Ok(v) => {
+                            observers.response(&v);
+                            values.push(v);
+                        }
+                        Err(e) => {
+                            error!(error = ?e, tool_name = call.name, "Tool call failed");
+                            if report_failures {
+                                let response = ToolResponse::failure(&call.id, &call.name, &e);
+                                observers.response(&response);
+                                values.push(response);
+                            }
+                        }
+                    }
+                }
+            }
+            ExecutionStrategy::Concurrent => {
+                info!(
+                    tool_call_count = calls.len(),
+                    "Calling tools with a `Concurrent` execution strategy!"
+                );
+                calls.iter().for_each(|call| observers.call(call));
+                // join_all yields the results in the order of the futures, not of completion
+                let results = futures::future::join_all(calls.iter().map(|call| {
+                    self.tools.call_with_cancellation(
+                        &call.id,
+                        &call.name,
+                        &call.arguments,
+                        &self.states,
+                        cancel,
+                    )
+                }))
+                .await;
+                for (call, result) in calls.iter().zip(results) {
+                    match result {
+                        Ok(ref v) => observers.response(v),
+                        Err(ref e) => {
+                            error!(error = ?e, tool_name = call.name, "Tool call failed");
+                        }
+                    }
+                    values.push(result?);
+                }
+            }
+        }
+
+        Ok(values)
+    }
+
+    fn update_token_usage(&mut self, usage: &TokenUsage) {
+        self.token_usage.prompt_tokens =
+            combine_options(self.token_usage.prompt_tokens, usage.prompt_tokens);
+        self.token_usage.completion_tokens =
+            combine_options(self.token_usage.completion_tokens, usage.completion_tokens);
+        self.token_usage.total_tokens =
+            combine_options(self.token_usage.total_tokens, usage.total_tokens);
+    }
+
+    /// Waits for a free in-flight slot if the client limits its requests in flight
+    async fn acquire_in_flight(&self) -> Option<OwnedSemaphorePermit> {
+        let limiter = Arc::clone(self.in_flight.as_ref()?);
+        limiter.acquire_owned().await.ok()
+    }
+
+    /// Sends `message` to the completion model, recording how long the request took in `metrics`
+    async fn send_request(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        options: &RequestOptions<'_>,
+        metrics: &mut PromptMetrics,
+    ) -> Result<CompletionOutput, crate::error::Error> {
+        let _permit = self.acquire_in_flight().await;
+        let model = self.completion_model.clone();
+        let mut guard = model.write().await;
+        let start = Instant::now();
+        let mut output = options
+            .apply(&mut *guard)
+            .send_detailed(message, history, tools, self.temperature, self.max_tokens)
+            .await?;
+        let latency = start.elapsed();
+        metrics.completion = Some(latency);
+        options.prepend_prefill(&mut output.message);
+        self.record_metrics(&*guard, &output.token_usage, latency);
+        Ok(output)
+    }
+
+    /// Builds the body of the request [`Client::send_request`] would send, without sending it
+    async fn build_request(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        options: &RequestOptions<'_>,
+    ) -> Result<serde_json::Value, crate::error::Error> {
+        let model = self.completion_model.clone();
+        let mut guard = model.write().await;
+        let body = options.apply(&mut *guard).build_request_body(
+            message,
+            history,
+            tools,
+            self.temperature,
+            self.max_tokens,
+        );
+        body.map_err(Into::into)
+    }
+
+    /// Emits the metrics of a request if the client has metrics enabled
+    fn record_metrics(&self, model: &M, usage: &TokenUsage, latency: Duration) {
+        self.record_metrics_for(model.provider_name(), model.model_name(), usage, latency);
+    }
+
+    /// Emits the phase durations of a prompt if the client has metrics enabled
+    fn record_prompt_metrics(&self, metrics: &PromptMetrics) {
+        if self.emit_metrics {
+            tracing::event!(
+                target: PROMPT_METRICS_TARGET,
+                tracing::Level::INFO,
+                sf.retrieval_ms = metrics.retrieval.map(millis),
+                sf.completion_ms = metrics.completion.map(millis),
+                sf.tools_ms = metrics.tools.map(millis),
+                sf.total_ms = millis(metrics.total),
+                "Prompt finished"
+            );
+        }
+    }
+
+    fn record_metrics_for(
+        &self,
+        provider: &str,
+        model: &str,
+        usage: &TokenUsage,
+        latency: Duration,
+    ) {
+        if self.emit_metrics {
+            let latency_ms = millis(latency);
+            tracing::event!(
+                target: METRICS_TARGET,
+                tracing::Level::INFO,
+                sf.provider = provider,
+                sf.model = model,
+                sf.prompt_tokens = usage.prompt_tokens,
+                sf.completion_tokens = usage.completion_tokens,
+                sf.latency_ms = latency_ms,
+                "Completion request finished"
+            );
+        }
+    }
+
+    /// Builds the user message for a prompt along with the retrieved context if requested,
+    /// returning `history` with the context placed in the preamble or added as a message if
+    /// it isn't appended to the prompt
+    async fn build_user_message<'h>(
+        &self,
+        prompt: &str,
+        history: Cow<'h, MessageHistory>,
+        append_context: bool,
+        position: ContextPosition,
+        limits: &ContextOptions,
+        metrics: &mut PromptMetrics,
+    ) -> Result<(Cow<'h, MessageHistory>, Message), crate::error::Error> {
+        if let Some(moderator) = &self.moderator {
+            moderator.check(prompt).await.inspect_err(|e| {
+                warn!(error = %e, "Prompt rejected by moderation");
+            })?;
+        }
+        let user_message = |content: String| Message::User {
+            content,
+            tool_responses: None,
+            name: None,
+        };
+        let context = self
+            .build_context(prompt, append_context, limits, metrics)
+            .await?;
+        if has_context_placeholder(&history) {
+            let history = fill_context_placeholder(history, context.as_deref().unwrap_or_default());
+            return Ok((history, user_message(prompt.to_string())));
+        }
+        let Some(context) = context else {
+            return Ok((history, user_message(prompt.to_string())));
+        };
+
+        Ok(match position {
+            ContextPosition::AppendToUser => {
+                (history, user_message(format!("{prompt}\n\n{context}\n")))
+            }
+            ContextPosition::AsSystemMessage => (
+                extend_history(history, Message::Preamble(context)),
+                user_message(prompt.to_string()),
+            ),
+            ContextPosition::AsSeparateUserMessage => (
+                extend_history(history, user_message(context)),
+                user_message(prompt.to_string()),
+            ),
+        })
+    }
+
+    /// Retrieves the context of a prompt if requested and joins it with the documents passed
+    /// along the prompt, `None` if the prompt has to be sent without context
+    async fn build_context(
+        &self,
+        prompt: &str,
+        append_context: bool,
+        limits: &ContextOptions,
+        metrics: &mut PromptMetrics,
+    ) -> Result<Option<String>, crate::error::Error> {
+        let limits = ContextOptions {
+            max_chars: limits.max_chars.or(self.max_context_chars),
+            ..limits.clone()
+        };
+        let inline_context = (!limits.documents.is_empty()).then(|| limits.documents.join("\n"));
+        if !append_context && inline_context.is_none() {
+            return Ok(None);
+        }
+        let retrieved_context = if append_context {
+            self.warn_if_no_embedders();
+            let start = Instant::now();
+            let context = self.get_context(prompt, &limits).await?;
+            metrics.retrieval = Some(start.elapsed());
+            context
+        } else {
+            None
+        };
+        let context = match (inline_context, retrieved_context) {
+            (Some(inline), Some(retrieved)) => Some(format!("{inline}\n{retrieved}")),
+            (inline, retrieved) => inline.or(retrieved),
+        };
+        Ok(match (context, limits.on_no_context) {
+            (Some(context), _) => Some(format!("<context>\n{context}\n</context>")),
+            (None, NoContextPolicy::Proceed) => None,
+            (None, NoContextPolicy::Fail) => Err(CompletionError::NoContextFound)?,
+            (None, NoContextPolicy::Instruct(instruction)) => {
+                info!("No context retrieved, sending the fallback instruction instead");
+                Some(instruction)
+            }
+        })
+    }
+
+    /// Logs a warning, once per client, when context is requested but no embedders are attached
+    fn warn_if_no_embedders(&self) {
+        if self.embedders.is_empty() && !self.warned_no_embedders.swap(true, Ordering::Relaxed) {
+            warn!("Context retrieval is enabled but the client has no embedders, prompts will be sent without context");
+        }
+    }
+
+    async fn get_context(
+        &self,
+        prompt: &str,
+        limits: &ContextOptions,
+    ) -> Result<Option<String>, crate::error::Error> {
+        if self.embedders.is_empty() {
+            return Ok(None);
+        }
+        if limits.min_relevance.is_none() && limits.max_chars.is_none() {
+            let mut context = String::new();
+            for embedder in &self.embedders {
+                let query_results = embedder.query_text(prompt, DEFAULT_TOP_N).await?;
+                if query_results.is_empty() {
+                    return Ok(None);
+                }
+                for (_, raw_data) in query_results {
+                    context.push_str(&raw_data);
+                }
+            }
+            return Ok(Some(context));
+        }
+
+        let mut documents = vec![];
+        for embedder in &self.embedders {
+            documents.extend(embedder.query_scored(prompt, DEFAULT_TOP_N).await?);
+        }
+        if let Some(min_relevance) = limits.min_relevance {
+            documents.retain(|(r, score)| {
+                let relevant = *score >= min_relevance;
+                if !relevant {
+                    debug!(id = %r.id, score, "Dropping context document below min relevance");
+                }
+                relevant
+            });
+        }
+        documents.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        let mut context: String = documents.iter().map(|(r, _)| r.raw_data.as_str()).collect();
+        if let Some((cut, _)) = limits
+            .max_chars
+            .and_then(|max| context.char_indices().nth(max))
+        {
+            info!(
+                length = context.chars().count(),
+                "Truncating the retrieved context to the max context chars"
+            );
+            context.truncate(cut);
+        }
+        if context.is_empty() {
+            info!("No retrieved document met the context limits, omitting the context");
+            return Ok(None);
+        }
+        Ok(Some(context))
+    }
+}
+
+/// Generates a JSON schema serializer for extractor types
+///
+/// # Errors
+/// Returns `serde_json::Error` if schema serialization fails
+pub fn default_extractor_serializer<'a, T: schemars::JsonSchema + serde::Deserialize<'a>>(
+) -> Result<serde_json::Value, serde_json::error::Error> {
+    extractor_serializer::<T>(true)
+}
+
+/// Generates a JSON schema serializer for extractor types, omitting the `strict` flag if
+/// `strict` is false
+///
+/// # Errors
+/// Returns `serde_json::Error` if schema serialization fails
+pub fn extractor_serializer<'a, T: schemars::JsonSchema + serde::Deserialize<'a>>(
+    strict: bool,
+) -> Result<serde_json::Value, serde_json::error::Error> {
+    let settings = SchemaSettings::default().with(|s| {
+        s.inline_subschemas = true;
+    });
+    let generator = settings.into_generator();
+    let schema = generator.into_root_schema_for::<T>();
+    let mut schema_value = serde_json::to_value(&schema)?;
+
+    let type_name: &str = std::any::type_name::<T>();
+    let type_name = type_name.split("::").last().unwrap_or("ExtractorType");
+    if let Some(obj) = schema_value.as_object_mut() {
+        obj.remove("$schema");
+        obj.remove("format");
+        obj.remove("title");
+    }
+    process_json_value(&mut schema_value);
+    let mut schema = json!({
+        "name": type_name,
+        "schema": schema_value
+    });
+    if strict {
+        schema["strict"] = json!(true);
+    }
+    Ok(json!({
+        "type": "json_schema",
+        "json_schema": schema
+    }))
+}
+
+fn process_json_value(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(obj) => {
+            let fields_to_remove = ["$schema", "format", "title", "minimum"];
+            for &f in &fields_to_remove {
+                if obj.get(f).is_some_and(|v| v.is_string() || v.is_number()) {
+                    obj.remove(f);
+                }
+            }
+            if let Some(v) = obj.get("oneOf").cloned() {
+                obj.remove("oneOf");
+                obj.insert("anyOf".to_string(), v);
+            };
+
+            // map-typed nodes carry the schema of their values in `additionalProperties`
+            if obj.contains_key("properties")
+                && !obj
+                    .get("additionalProperties")
+                    .is_some_and(serde_json::Value::is_object)
+            {
+                obj.insert("additionalProperties".to_string(), json!(false));
+            }
+            for (_, v) in obj.iter_mut() {
+                process_json_value(v);
+            }
+        }
+        serde_json::Value::Array(arr) => {
+            for elem in arr.iter_mut() {
+                process_json_value(elem);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Serializes user messages, ignoring tool call responses
+///
+/// # Errors
+/// If serializer fails
+pub fn serialize_user<S>(
+    content: &str,
+    _tool_calls: &Option<Vec<ToolResponse>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_newtype_struct("user", &content)
+}
+
+/// Serializes assistant messages with integrated tool call information
+///
+/// # Errors
+/// If serializer fails
+pub fn serialize_assistant<S>(
+    content: &str,
+    tool_calls: &Option<Vec<ToolCall>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let combined_content = match tool_calls {
+        Some(calls) => &format!("{content} {calls:?}"),
+        None => content,
+    };
+    serializer.serialize_newtype_struct("assistant", &combined_content)
+}
+
+/// Serializes tool calls in the `tool_calls` format expected by OpenAI compatible APIs
+///
+/// # Errors
+/// If serializer fails
+pub fn serialize_tool_calls<S>(
+    tool_calls: &Option<Vec<ToolCall>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let calls: Vec<serde_json::Value> = tool_calls
+        .iter()
+        .flatten()
+        .map(|c| {
+            let arguments =
+                serde_json::from_str::<String>(&c.arguments).unwrap_or(c.arguments.clone());
+            json!({
+                "id": c.id,
+                "type": "function",
+                "function": {
+                    "name": c.name,
+                    "arguments": arguments,
+                }
+            })
+        })
+        .collect();
+    calls.serialize(serializer)
+}
+
+/// Serializes the content of a tool response as a string
+///
+/// # Errors
+/// If serializer fails
+pub fn serialize_tool_content<S>(
+    content: &serde_json::Value,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match content {
+        serde_json::Value::String(s) => serializer.serialize_str(s),
+        v => serializer.serialize_str(&v.to_string()),
+    }
+}
+
+/// Adds up token counts, a count the provider didn't report counts as nothing
+fn combine_options(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (Some(a_val), Some(b_val)) => Some(a_val + b_val),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Asks the model to correct an extracted output that failed to deserialize with `error`
+fn extraction_feedback<T: Extractor>(error: &str) -> String {
+    let schema = default_extractor_serializer::<T>()
+        .map(|extractor| extractor["json_schema"]["schema"].to_string())
+        .unwrap_or_default();
+    format!(
+        "Your response isn't valid: {error}\n\
+        Respond again with only JSON matching this schema:\n{schema}"
+    )
+}
+
+/// Milliseconds in `duration`, as reported in the metrics events
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Appends `message` to the history sent with a request
+fn extend_history(history: Cow<'_, MessageHistory>, message: Message) -> Cow<'_, MessageHistory> {
+    let mut history = history.into_owned();
+    history.push(message);
+    Cow::Owned(history)
+}
+
+/// Whether the preamble of `history` contains the [`CONTEXT_PLACEHOLDER`]
+fn has_context_placeholder(history: &MessageHistory) -> bool {
+    matches!(
+        history.first(),
+        Some(Message::Preamble(preamble)) if preamble.contains(CONTEXT_PLACEHOLDER)
+    )
+}
+
+/// Substitutes `context` for the [`CONTEXT_PLACEHOLDER`] in a copy of the preamble of `history`
+fn fill_context_placeholder<'h>(
+    history: Cow<'h, MessageHistory>,
+    context: &str,
+) -> Cow<'h, MessageHistory> {
+    let mut history = history.into_owned();
+    if let Some(Message::Preamble(preamble)) = history.first_mut() {
+        *preamble = preamble.replace(CONTEXT_PLACEHOLDER, context);
+    }
+    Cow::Owned(history)
+}
+
+/// Runs `prompt`, failing with a [`CompletionError::RequestError`] if it doesn't complete
+/// within `timeout`
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    prompt: impl std::future::Future<Output = Result<T, crate::error::Error>>,
+) -> Result<T, crate::error::Error> {
+    let Some(timeout) = timeout else {
+        return prompt.await;
+    };
+    tokio::time::timeout(timeout, prompt).await.map_err(|_| {
+        warn!(?timeout, "Prompt timed out");
+        CompletionError::RequestError("prompt timed out".to_string())
+    })?
+}
+
+/// Appends the few-shot `examples` to `history`
+fn append_examples<'h>(
+    history: Cow<'h, MessageHistory>,
+    examples: &[Message],
+) -> Cow<'h, MessageHistory> {
+    if examples.is_empty() {
+        return history;
+    }
+    let mut history = history.into_owned();
+    history.extend_from_slice(examples);
+    Cow::Owned(history)
+}
+
+/// Keeps the preamble and the last `window` messages of `history`, if a window is set
+fn window_history(history: &MessageHistory, window: Option<usize>) -> Cow<'_, MessageHistory> {
+    let Some(window) = window else {
+        return Cow::Borrowed(history);
+    };
+    let preamble_len = usize::from(matches!(history.first(), Some(Message::Preamble(_))));
+    let (preamble, turns) = history.split_at(preamble_len);
+    let skip = turns.len().saturating_sub(window);
+    Cow::Owned(preamble.iter().chain(&turns[skip..]).cloned().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{Tool, ToolArg, ToolError};
+    use serde_json::Value;
+    use std::{
+        collections::VecDeque,
+        sync::{atomic::AtomicUsize, Mutex},
+    };
+    use tracing_subscriber::{layer::Context, prelude::*, Layer};
+
+    /// Completion model replaying canned responses and recording every request it receives
+    struct MockModel {
+        responses: VecDeque<Message>,
+        streams: VecDeque<Vec<StreamChunk>>,
+        requests: Arc<Mutex<Vec<MessageHistory>>>,
+    }
+
+    impl MockModel {
+        fn new(responses: Vec<Message>) -> (Self, Arc<Mutex<Vec<MessageHistory>>>) {
+            let requests = Arc::new(Mutex::new(vec![]));
+            (
+                Self {
+                    responses: responses.into(),
+                    streams: VecDeque::new(),
+                    requests: Arc::clone(&requests),
+                },
+                requests,
+            )
+        }
+    }
+
+    #[allow(refining_impl_trait)]
+    #[async_trait]
+    impl CompletionModel for MockModel {
+        fn build_client(
+            self,
+            preamble: impl AsRef<str>,
+            embedder_instances: Vec<Embedder>,
+            tools: ToolSet,
+        ) -> Client<Self> {
+            Client::new(self, preamble, 0.0, 100, embedder_instances, tools)
+        }
+
+        async fn send(
+            &mut self,
+            message: Message,
+            history: &MessageHistory,
+            _tools: Option<&ToolSet>,
+            _temperature: f64,
+            _max_tokens: usize,
+        ) -> Result<(Message, TokenUsage), CompletionError> {
+            let mut request = history.clone();
+            request.push(message);
+            self.requests.lock().unwrap().push(request);
+            let response = self.responses.pop_front().unwrap_or(Message::Assistant {
+                content: "done".to_string(),
+                tool_calls: None,
+                name: None,
+            });
+            Ok((response, TokenUsage::default()))
+        }
+
+        async fn stream(
+            &mut self,
+            message: Message,
+            history: &MessageHistory,
+            _tools: Option<&ToolSet>,
+            _temperature: f64,
+            _max_tokens: usize,
+        ) -> Result<CompletionStream, CompletionError> {
+            let mut request = history.clone();
+            request.push(message);
+            self.requests.lock().unwrap().push(request);
+            let chunks = self.streams.pop_front().unwrap_or_default();
+            Ok(Box::pin(futures::stream::iter(chunks.into_iter().map(Ok))))
+        }
+    }
+
+    struct EchoTool {
+        args: Vec<ToolArg>,
+    }
+
+    #[async_trait]
+    impl Tool for EchoTool {
+        fn name(&self) -> &str {
+            "echo"
+        }
+        fn description(&self) -> &str {
+            "Echoes the input back"
+        }
+        fn args(&self) -> &[ToolArg] {
+            &self.args
+        }
+        async fn call(
+            &self,
+            args: &str,
+            _states: &DashMap<TypeId, Box<dyn Any + Send + Sync>>,
+        ) -> Result<Value, ToolError> {
+            #[derive(serde::Deserialize)]
+            struct Params {
+                input: String,
+            }
+            let args = serde_json::from_str::<String>(args).unwrap_or(args.to_string());
+            let params: Params = serde_json::from_str(&args)?;
+            Ok(Value::from(params.input))
+        }
+    }
+
+    fn echo_toolset() -> ToolSet {
+        ToolSet(
+            vec![Box::new(EchoTool {
+                args: vec![ToolArg::new::<String>("input", "text to echo")],
+            })],
+            ExecutionStrategy::FailEarly,
+        )
+    }
+
+    #[test]
+    fn test_raw_response_capture() {
+        let body = r#"{"choices": [{"message": <html>Bad Gateway</html>"#;
+        let CompletionError::ParseError(plain) = parse_response_body(body, false).unwrap_err()
+        else {
+            panic!("expected a parse error");
+        };
+        assert!(!plain.contains("Bad Gateway"));
+
+        let CompletionError::ParseError(captured) = parse_response_body(body, true).unwrap_err()
+        else {
+            panic!("expected a parse error");
+        };
+        assert!(captured.starts_with(&plain));
+        assert!(captured.contains("<html>Bad Gateway</html>"));
+
+        let long_body = format!("<{}>", "x".repeat(1000));
+        let CompletionError::ParseError(truncated) =
+            parse_response_body(&long_body, true).unwrap_err()
+        else {
+            panic!("expected a parse error");
+        };
+        assert!(truncated.ends_with(&format!("<{}...", "x".repeat(499))));
+
+        assert_eq!(parse_response_body("{}", true).unwrap(), json!({}));
+    }
+
+    #[tokio::test]
+    async fn test_tool_arguments_are_validated() {
+        let tools = echo_toolset();
+        let states = DashMap::new();
+
+        let result = tools
+            .call("call_1", "echo", r#"{"input": 5}"#, &states)
+            .await;
+        let Err(ToolSetError::ToolError(ToolError::InvalidArguments { tool, details })) = result
+        else {
+            panic!("expected invalid arguments, got {result:?}");
+        };
+        assert_eq!(tool, "echo");
+        assert_eq!(details, [r#"field `input` expected "string", got number"#]);
+
+        let result = tools.call("call_2", "echo", "{}", &states).await;
+        assert!(matches!(
+            result,
+            Err(ToolSetError::ToolError(ToolError::InvalidArguments { details, .. }))
+                if details == ["missing required field `input`"]
+        ));
+
+        let response = tools
+            .call("call_3", "echo", r#"{"input": "hi"}"#, &states)
+            .await
+            .unwrap();
+        assert_eq!(response.content, Value::from("hi"));
+    }
+
+    struct ConstantEmbeddingModel;
+
+    #[async_trait]
+    impl crate::embeddings::model::EmbeddingModel for ConstantEmbeddingModel {
+        async fn embed(&self, _data: &str) -> Result<Vec<f64>, crate::embeddings::EmbedderError> {
+            Ok(vec![1.0, 0.0])
+        }
+    }
+
+    /// Builds an embedder always retrieving "retrieved facts" as context
+    async fn context_embedder() -> Embedder {
+        use crate::{
+            embeddings::embedding::Embedding,
+            vector_store::{InMemoryVectorStore, VectorStore},
+        };
+        let store = InMemoryVectorStore::new(None).await.unwrap();
+        store
+            .store(Embedding {
+                id: "facts".to_string(),
+                embedded_data: vec![1.0, 0.0],
+                raw_data: "retrieved facts".to_string(),
+            })
+            .await
+            .unwrap();
+        Embedder::init(
+            vec![],
+            Arc::new(tokio::sync::Mutex::new(Box::new(store))),
+            Arc::new(Box::new(ConstantEmbeddingModel)),
+        )
+        .await
+    }
+
+    /// Builds a client whose embedder always retrieves "retrieved facts" as context
+    async fn client_with_context() -> (Client<MockModel>, Arc<Mutex<Vec<MessageHistory>>>) {
+        let (model, requests) = MockModel::new(vec![]);
+        (
+            model.build_client("preamble", vec![context_embedder().await], echo_toolset()),
+            requests,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_builds_the_request_without_sending() {
+        let model = crate::providers::completions::OpenAICompatible::new(Some(
+            r#"{"api_url": "http://127.0.0.1:9", "model": "test-model"}"#,
+        ));
+        let mut client =
+            model.build_client("preamble", vec![context_embedder().await], echo_toolset());
+
+        let body = client.prompt("what do you know?").dry_run().await.unwrap();
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(
+            messages.last().unwrap(),
+            &json!({
+                "role": "user",
+                "content": "what do you know?\n\n<context>\nretrieved facts\n</context>\n"
+            })
+        );
+        assert_eq!(body["model"], json!("test-model"));
+        assert_eq!(body["tools"][0]["function"]["name"], json!("echo"));
+        assert_eq!(client.export_history().len(), 1);
+
+        let (model, requests) = MockModel::new(vec![]);
+        let mut client = model.build_client(
+            "preamble",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+        assert!(matches!(
+            client.prompt("hi").dry_run().await,
+            Err(crate::error::Error::Completion(
+                CompletionError::RequestBodyNotSupported
+            ))
+        ));
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_seed_is_sent_in_the_request_body() {
+        let model = crate::providers::completions::OpenAICompatible::new(Some(
+            r#"{"api_url": "http://127.0.0.1:9", "model": "test-model"}"#,
+        ));
+        let mut client = model.build_client(
+            "preamble",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+
+        let body = client.prompt("hi").seed(7).dry_run().await.unwrap();
+        assert_eq!(body["seed"], json!(7));
+        let body = client.prompt("hi").dry_run().await.unwrap();
+        assert!(body.get("seed").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reasoning_effort_is_sent_in_the_request_body() {
+        let model = crate::providers::completions::OpenAICompatible::new(Some(
+            r#"{"api_url": "http://127.0.0.1:9", "model": "o3-mini", "reasoning_effort": "low"}"#,
+        ));
+        let mut client = model.build_client(
+            "preamble",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+
+        let body = client.prompt("hi").dry_run().await.unwrap();
+        assert_eq!(body["reasoning_effort"], json!("low"));
+        let body = client
+            .prompt("hi")
+            .reasoning_effort(ReasoningEffort::High)
+            .dry_run()
+            .await
+            .unwrap();
+        assert_eq!(body["reasoning_effort"], json!("high"));
+    }
+
+    #[tokio::test]
+    async fn test_context_positions() {
+        let user = |content: &str| Message::User {
+            content: content.to_string(),
+            tool_responses: None,
+            name: None,
+        };
+        let preamble = Message::Preamble("preamble".to_string());
+        let context = "<context>\nretrieved facts\n</context>";
+        for (position, expected) in [
+            (
+                ContextPosition::AppendToUser,
+                vec![preamble.clone(), user(&format!("question\n\n{context}\n"))],
+            ),
+            (
+                ContextPosition::AsSystemMessage,
+                vec![
+                    preamble.clone(),
+                    Message::Preamble(context.to_string()),
+                    user("question"),
+                ],
+            ),
+            (
+                ContextPosition::AsSeparateUserMessage,
+                vec![preamble.clone(), user(context), user("question")],
+            ),
+        ] {
+            let (mut client, requests) = client_with_context().await;
+            client
+                .prompt("question")
+                .context_position(position)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(requests.lock().unwrap()[0], expected, "{position:?}");
+            assert_eq!(client.export_history()[1], user("question"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_context_placeholder_in_preamble_is_substituted() {
+        let user = |content: &str| Message::User {
+            content: content.to_string(),
+            tool_responses: None,
+            name: None,
+        };
+        let (model, requests) = MockModel::new(vec![]);
+        let mut client = model.build_client(
+            "Answer from the context.\n{context}",
+            vec![context_embedder().await],
+            echo_toolset(),
+        );
+
+        client.prompt("question").send().await.unwrap();
+        client
+            .prompt("question")
+            .with_context(false)
+            .send()
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(
+            requests[0],
+            vec![
+                Message::Preamble(
+                    "Answer from the context.\n<context>\nretrieved facts\n</context>".to_string()
+                ),
+                user("question"),
+            ]
+        );
+        assert_eq!(
+            requests[1][0],
+            Message::Preamble("Answer from the context.\n".to_string())
+        );
+        assert_eq!(
+            client.export_history()[0],
+            Message::Preamble("Answer from the context.\n{context}".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inline_documents_are_added_to_the_context() {
+        let user = |content: &str| Message::User {
+            content: content.to_string(),
+            tool_responses: None,
+            name: None,
+        };
+        let (mut client, requests) = client_with_context().await;
+
+        client
+            .prompt("summarize")
+            .with_documents(&["first note", "second note"])
+            .send()
+            .await
+            .unwrap();
+        client
+            .prompt("summarize")
+            .with_documents(&["first note"])
+            .with_context(false)
+            .send()
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(
+            requests[0].last(),
+            Some(&user(
+                "summarize\n\n<context>\nfirst note\nsecond note\nretrieved facts\n</context>\n"
+            ))
+        );
+        assert_eq!(
+            requests[1].last(),
+            Some(&user("summarize\n\n<context>\nfirst note\n</context>\n"))
+        );
+    }
+
+    /// Vector store returning a poor and a good match for every query
+    struct ScoredStore;
+
+    #[async_trait]
+    impl crate::vector_store::VectorStore for ScoredStore {
+        async fn get_by_id(
+            &self,
+            _id: String,
+        ) -> Result<crate::embeddings::embedding::Embedding, VectorStoreError> {
+            Err(VectorStoreError::EmbeddingNotFound)
+        }
+
+        async fn store(
+            &self,
+            _embedding: crate::embeddings::embedding::Embedding,
+        ) -> Result<(), VectorStoreError> {
+            Ok(())
+        }
+
+        async fn top_n(
+            &self,
+            _query: &[f64],
+            _n: usize,
+        ) -> Result<Vec<crate::embeddings::embedding::Embedding>, VectorStoreError> {
+            Ok(vec![])
+        }
+
+        async fn top_n_scored(
+            &self,
+            _query: &[f64],
+            _n: usize,
+        ) -> Result<Vec<(crate::embeddings::embedding::Embedding, f64)>, VectorStoreError> {
+            let embedding = |id: &str, raw_data: &str| crate::embeddings::embedding::Embedding {
+                id: id.to_string(),
+                embedded_data: vec![1.0, 0.0],
+                raw_data: raw_data.to_string(),
+            };
+            Ok(vec![
+                (embedding("poor", "unrelated noise"), 0.2),
+                (embedding("good", "relevant facts"), 0.9),
+            ])
+        }
+
+        async fn top_n_text(
+            &self,
+            _query: &[f64],
+            _n: usize,
+        ) -> Result<Vec<(String, String)>, VectorStoreError> {
+            Ok(vec![("good".to_string(), "projected facts".to_string())])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_context_is_built_from_the_text_projection() {
+        let embedder = Embedder::init(
+            vec![],
+            Arc::new(tokio::sync::Mutex::new(Box::new(ScoredStore))),
+            Arc::new(Box::new(ConstantEmbeddingModel)),
+        )
+        .await;
+        let (model, requests) = MockModel::new(vec![]);
+        let mut client = model.build_client("preamble", vec![embedder], echo_toolset());
+
+        client.prompt("question").send().await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(
+            requests[0].last(),
+            Some(&Message::User {
+                content: "question\n\n<context>\nprojected facts\n</context>\n".to_string(),
+                tool_responses: None,
+                name: None,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_min_relevance_drops_poor_matches() {
+        let embedder = Embedder::init(
+            vec![],
+            Arc::new(tokio::sync::Mutex::new(Box::new(ScoredStore))),
+            Arc::new(Box::new(ConstantEmbeddingModel)),
+        )
+        .await;
+        let (model, requests) = MockModel::new(vec![]);
+        let mut client = model.build_client("preamble", vec![embedder], echo_toolset());
+        let user = |content: &str| Message::User {
+            content: content.to_string(),
+            tool_responses: None,
+            name: None,
+        };
+
+        client
+            .prompt("question")
+            .one_shot(true, None)
+            .min_relevance(0.5)
+            .send()
+            .await
+            .unwrap();
+        client
+            .prompt("question")
+            .one_shot(true, None)
+            .min_relevance(0.95)
+            .send()
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(
+            requests[0].last(),
+            Some(&user("question\n\n<context>\nrelevant facts\n</context>\n"))
+        );
+        assert_eq!(requests[1].last(), Some(&user("question")));
+    }
+
+    #[tokio::test]
+    async fn test_clients_share_a_cloned_embedder() {
+        let embedder = Embedder::init(
+            vec![],
+            Arc::new(tokio::sync::Mutex::new(Box::new(
+                crate::vector_store::InMemoryVectorStore::new(None)
+                    .await
+                    .unwrap(),
+            ))),
+            Arc::new(Box::new(ConstantEmbeddingModel)),
+        )
+        .await;
+        let (model_a, requests_a) = MockModel::new(vec![]);
+        let (model_b, requests_b) = MockModel::new(vec![]);
+        let mut client_a = model_a.build_client("preamble", vec![embedder.clone()], echo_toolset());
+        let mut client_b = model_b.build_client("preamble", vec![embedder.clone()], echo_toolset());
+
+        assert!(embedder
+            .store_documents(vec![crate::document::Document::new(
+                "facts",
+                "shared facts"
+            )])
+            .await
+            .is_success());
+        client_a.prompt("question").send().await.unwrap();
+        client_b.prompt("question").send().await.unwrap();
+
+        let expected = Message::User {
+            content: "question\n\n<context>\nshared facts\n</context>\n".to_string(),
+            tool_responses: None,
+            name: None,
+        };
+        assert_eq!(requests_a.lock().unwrap()[0].last(), Some(&expected));
+        assert_eq!(requests_b.lock().unwrap()[0].last(), Some(&expected));
+    }
+
+    #[tokio::test]
+    async fn test_no_context_policies() {
+        let embedder = Embedder::init(
+            vec![],
+            Arc::new(tokio::sync::Mutex::new(Box::new(
+                crate::vector_store::InMemoryVectorStore::new(None)
+                    .await
+                    .unwrap(),
+            ))),
+            Arc::new(Box::new(ConstantEmbeddingModel)),
+        )
+        .await;
+        let (model, requests) = MockModel::new(vec![]);
+        let mut client = model.build_client("preamble", vec![embedder], echo_toolset());
+        let user = |content: &str| Message::User {
+            content: content.to_string(),
+            tool_responses: None,
+            name: None,
         };
-        let (mut response, token_usage) = self
-            .client
-            .send_prompt(
-                &self.prompt,
-                history,
-                tools,
-                self.client.temperature,
-                self.client.max_tokens,
-                self.with_context,
-            )
-            .await?;
 
-        if !self.one_shot.0 {
-            self.client.history.push(Message::User {
-                content: self.prompt.clone(),
+        client
+            .prompt("question")
+            .one_shot(true, None)
+            .on_no_context(NoContextPolicy::Proceed)
+            .send()
+            .await
+            .unwrap();
+        let result = client
+            .prompt("question")
+            .one_shot(true, None)
+            .on_no_context(NoContextPolicy::Fail)
+            .send()
+            .await;
+        assert!(matches!(
+            result,
+            Err(crate::error::Error::Completion(
+                CompletionError::NoContextFound
+            ))
+        ));
+        client
+            .prompt("question")
+            .one_shot(true, None)
+            .on_no_context(NoContextPolicy::Instruct("Say you don't know".to_string()))
+            .send()
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].last(), Some(&user("question")));
+        assert_eq!(
+            requests[1].last(),
+            Some(&user("question\n\nSay you don't know\n"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_context_chars_caps_context() {
+        let embedder = Embedder::init(
+            vec![],
+            Arc::new(tokio::sync::Mutex::new(Box::new(ScoredStore))),
+            Arc::new(Box::new(ConstantEmbeddingModel)),
+        )
+        .await;
+        let (model, requests) = MockModel::new(vec![]);
+        let mut client = model
+            .build_client("preamble", vec![embedder], echo_toolset())
+            .with_max_context_chars(20);
+        let user = |content: &str| Message::User {
+            content: content.to_string(),
+            tool_responses: None,
+            name: None,
+        };
+
+        client
+            .prompt("question")
+            .one_shot(true, None)
+            .send()
+            .await
+            .unwrap();
+        client
+            .prompt("question")
+            .one_shot(true, None)
+            .max_context_chars(8)
+            .send()
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(
+            requests[0].last(),
+            Some(&user(
+                "question\n\n<context>\nrelevant factsunrela\n</context>\n"
+            ))
+        );
+        assert_eq!(
+            requests[1].last(),
+            Some(&user("question\n\n<context>\nrelevant\n</context>\n"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_history_window_sends_only_recent_turns() {
+        let (model, requests) = MockModel::new(vec![]);
+        let mut client = model.build_client("preamble", vec![], echo_toolset());
+        for i in 0..5 {
+            client.prompt(format!("prompt {i}")).send().await.unwrap();
+        }
+        assert_eq!(client.export_history().len(), 11);
+
+        client
+            .prompt("last prompt")
+            .history_window(3)
+            .send()
+            .await
+            .unwrap();
+
+        let history = client.export_history();
+        assert_eq!(history.len(), 13);
+        let sent = requests.lock().unwrap().last().unwrap().clone();
+        let mut expected = vec![history[0].clone()];
+        expected.extend_from_slice(&history[8..11]);
+        expected.push(Message::User {
+            content: "last prompt".to_string(),
+            tool_responses: None,
+            name: None,
+        });
+        assert_eq!(sent, expected);
+    }
+
+    #[tokio::test]
+    async fn test_history_accessors() {
+        let reply = |content: &str| Message::Assistant {
+            content: content.to_string(),
+            tool_calls: None,
+            name: None,
+        };
+        let (model, _) = MockModel::new(vec![reply("first reply"), reply("second reply")]);
+        let mut client = model.build_client("preamble", vec![], echo_toolset());
+        assert_eq!(client.history_len(), 1);
+        assert_eq!(client.last_assistant_message(), None);
+        assert_eq!(client.last_user_message(), None);
+
+        client.prompt("first prompt").send().await.unwrap();
+        client.prompt("second prompt").send().await.unwrap();
+
+        assert_eq!(client.history_len(), 5);
+        assert_eq!(
+            client.last_assistant_message(),
+            Some(&reply("second reply"))
+        );
+        assert_eq!(
+            client.last_user_message(),
+            Some(&Message::User {
+                content: "second prompt".to_string(),
                 tool_responses: None,
-            });
-            self.client.history.push(response.clone());
+                name: None,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_examples_are_sent_before_the_prompt_only() {
+        let (model, requests) = MockModel::new(vec![]);
+        let mut client = model.build_client("preamble", vec![], echo_toolset());
+        client.prompt("first prompt").send().await.unwrap();
+
+        client
+            .prompt("hello")
+            .with_examples([("bonjour", "hello"), ("merci", "thank you")])
+            .send()
+            .await
+            .unwrap();
+
+        let user = |content: &str| Message::User {
+            content: content.to_string(),
+            tool_responses: None,
+            name: None,
+        };
+        let assistant = |content: &str| Message::Assistant {
+            content: content.to_string(),
+            tool_calls: None,
+            name: None,
+        };
+        let history = client.export_history();
+        let sent = requests.lock().unwrap().last().unwrap().clone();
+        let mut expected = history[..3].to_vec();
+        expected.extend([
+            user("bonjour"),
+            assistant("hello"),
+            user("merci"),
+            assistant("thank you"),
+            user("hello"),
+        ]);
+        assert_eq!(sent, expected);
+        assert_eq!(history.len(), 5);
+        assert_eq!(history[3], user("hello"));
+    }
+
+    #[tokio::test]
+    async fn test_regenerate_replaces_last_assistant_turn() {
+        let (model, requests) = MockModel::new(vec![
+            Message::Assistant {
+                content: "first".to_string(),
+                tool_calls: None,
+                name: None,
+            },
+            Message::Assistant {
+                content: "second".to_string(),
+                tool_calls: None,
+                name: None,
+            },
+        ]);
+        let mut client = model.build_client(
+            "preamble",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+
+        client.prompt("hello").send().await.unwrap();
+        let response = client.regenerate().await.unwrap();
+
+        let second = Message::Assistant {
+            content: "second".to_string(),
+            tool_calls: None,
+            name: None,
+        };
+        assert_eq!(response, second);
+        let history = client.export_history();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.last(), Some(&second));
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0], requests[1]);
+    }
+
+    #[tokio::test]
+    async fn test_regenerate_errors_without_assistant_turn() {
+        let (model, _) = MockModel::new(vec![]);
+        let mut client = model.build_client(
+            "preamble",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+
+        assert!(matches!(
+            client.regenerate().await,
+            Err(crate::error::Error::Completion(
+                CompletionError::NothingToRegenerate
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_strict_flag_can_be_disabled() {
+        #[derive(schemars::JsonSchema, serde::Deserialize)]
+        #[allow(unused)]
+        struct Person {
+            name: String,
         }
 
-        self.client.update_token_usage(&token_usage);
-        if token_usage.total_tokens.is_some() {
-            info!(
-                "Prompt used up: {:?} tokens, Total tokens used: {:?}",
-                token_usage.total_tokens, self.client.token_usage.total_tokens
-            );
+        let strict = default_extractor_serializer::<Person>().unwrap();
+        assert_eq!(strict["json_schema"]["strict"], json!(true));
+        let lax = extractor_serializer::<Person>(false).unwrap();
+        assert!(lax["json_schema"].get("strict").is_none());
+        assert_eq!(
+            lax["json_schema"]["schema"],
+            strict["json_schema"]["schema"]
+        );
+
+        let tool = EchoTool {
+            args: vec![ToolArg::new::<String>("input", "text to echo")],
+        };
+        assert_eq!(tool.default_serializer()["function"]["strict"], json!(true));
+        assert!(tool.serializer(false)["function"].get("strict").is_none());
+    }
+
+    #[test]
+    fn test_anthropic_serializer_shape() {
+        let tool = EchoTool {
+            args: vec![ToolArg::new::<String>("input", "text to echo")],
+        };
+        let serialized = tool.anthropic_serializer();
+        assert_eq!(
+            serialized,
+            json!({
+                "name": "echo",
+                "description": "Echoes the input back",
+                "input_schema": tool.default_serializer()["function"]["parameters"]
+            })
+        );
+        assert_eq!(serialized["input_schema"]["type"], json!("object"));
+        assert_eq!(serialized["input_schema"]["required"], json!(["input"]));
+    }
+
+    #[test]
+    fn test_extractor_schema_permits_maps() {
+        #[derive(schemars::JsonSchema, serde::Deserialize)]
+        #[allow(unused)]
+        struct Scores {
+            name: String,
+            scores: std::collections::HashMap<String, i32>,
         }
 
-        if self.execute_tools {
-            if let Message::Assistant {
-                content: _,
-                tool_calls: Some(calls),
-            } = response.clone()
-            {
-                if self.one_shot.0 {
-                    self.client.history.push(response);
-                }
-                let values = self.client.run_tools(Some(&calls)).await?;
-                if self.one_shot.0 {
-                    self.client.history.pop();
-                }
-                response = Message::User {
-                    content: String::new(),
-                    tool_responses: Some(values.clone()),
-                };
-                if self.append_tool_response && !self.one_shot.0 {
-                    self.client.append_history(&[response.clone()]);
-                }
+        let serialized = default_extractor_serializer::<Scores>().unwrap();
+        let schema = &serialized["json_schema"]["schema"];
+        assert_eq!(schema["additionalProperties"], json!(false));
+        assert_eq!(
+            schema["properties"]["scores"]["additionalProperties"],
+            json!({"type": "integer"})
+        );
+
+        let mut open_schema = json!({
+            "type": "object",
+            "properties": {"name": {"type": "string"}},
+            "additionalProperties": {"type": "integer"}
+        });
+        process_json_value(&mut open_schema);
+        assert_eq!(
+            open_schema["additionalProperties"],
+            json!({"type": "integer"})
+        );
+    }
+
+    struct SleepTool {
+        args: Vec<ToolArg>,
+    }
+
+    #[async_trait]
+    impl Tool for SleepTool {
+        fn name(&self) -> &str {
+            "sleep"
+        }
+        fn description(&self) -> &str {
+            "Sleeps for the given number of milliseconds"
+        }
+        fn args(&self) -> &[ToolArg] {
+            &self.args
+        }
+        async fn call(
+            &self,
+            args: &str,
+            _states: &DashMap<TypeId, Box<dyn Any + Send + Sync>>,
+        ) -> Result<Value, ToolError> {
+            let millis: u64 = serde_json::from_str(args)?;
+            tokio::time::sleep(std::time::Duration::from_millis(millis)).await;
+            Ok(Value::from(millis))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report_failures_answers_failed_calls() {
+        let (model, _) = MockModel::new(vec![]);
+        let tools = ToolSet(
+            vec![Box::new(SleepTool { args: vec![] })],
+            ExecutionStrategy::ReportFailures,
+        );
+        let client = model.build_client("preamble", vec![], tools);
+        let calls: Vec<ToolCall> = ["0", "not a number"]
+            .iter()
+            .enumerate()
+            .map(|(i, arguments)| ToolCall {
+                id: format!("call_{i}"),
+                name: "sleep".to_string(),
+                arguments: (*arguments).to_string(),
+            })
+            .collect();
+
+        let responses = client.run_tools(Some(&calls)).await.unwrap();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0].content, Value::from(0));
+        assert_eq!(responses[1].id, "call_1");
+        assert_eq!(responses[1].content["success"], json!(false));
+        assert!(responses[1].content["error"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_tool_responses_keep_call_order() {
+        let (model, _) = MockModel::new(vec![]);
+        let tools = ToolSet(
+            vec![Box::new(SleepTool { args: vec![] })],
+            ExecutionStrategy::Concurrent,
+        );
+        let client = model.build_client("preamble", vec![], tools);
+        let calls: Vec<ToolCall> = [200, 20, 100, 0]
+            .iter()
+            .enumerate()
+            .map(|(i, millis)| ToolCall {
+                id: format!("call_{i}"),
+                name: "sleep".to_string(),
+                arguments: millis.to_string(),
+            })
+            .collect();
+
+        let start = std::time::Instant::now();
+        let responses = client.run_tools(Some(&calls)).await.unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_millis(300));
+        assert_eq!(
+            responses.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(),
+            ["call_0", "call_1", "call_2", "call_3"]
+        );
+        assert_eq!(
+            responses
+                .iter()
+                .map(|r| r.content.clone())
+                .collect::<Vec<_>>(),
+            [200, 20, 100, 0].map(Value::from)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_aborts_running_tool() {
+        let (model, _) = MockModel::new(vec![Message::Assistant {
+            content: String::new(),
+            tool_calls: Some(vec![ToolCall {
+                id: "call_1".to_string(),
+                name: "sleep".to_string(),
+                arguments: "10000".to_string(),
+            }]),
+            name: None,
+        }]);
+        let tools = ToolSet(
+            vec![Box::new(SleepTool { args: vec![] })],
+            ExecutionStrategy::FailEarly,
+        );
+        let mut client = model.build_client("preamble", vec![], tools);
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            canceller.cancel();
+        });
+
+        let start = std::time::Instant::now();
+        let result = client
+            .prompt("sleep for a while")
+            .cancellation_token(token)
+            .send()
+            .await;
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        assert!(matches!(
+            result,
+            Err(crate::error::Error::ToolSet(ToolSetError::ToolError(
+                ToolError::Cancelled
+            )))
+        ));
+    }
+
+    /// Completion model taking `delay` to answer, tracking the sampling params set on it
+    struct SlowModel {
+        delay: std::time::Duration,
+        sampling: Arc<Mutex<SamplingParams>>,
+    }
+
+    #[allow(refining_impl_trait)]
+    #[async_trait]
+    impl CompletionModel for SlowModel {
+        fn build_client(
+            self,
+            preamble: impl AsRef<str>,
+            embedder_instances: Vec<Embedder>,
+            tools: ToolSet,
+        ) -> Client<Self> {
+            Client::new(self, preamble, 0.0, 100, embedder_instances, tools)
+        }
+
+        async fn send(
+            &mut self,
+            _message: Message,
+            _history: &MessageHistory,
+            _tools: Option<&ToolSet>,
+            _temperature: f64,
+            _max_tokens: usize,
+        ) -> Result<(Message, TokenUsage), CompletionError> {
+            tokio::time::sleep(self.delay).await;
+            Ok((
+                Message::Assistant {
+                    content: "late".to_string(),
+                    tool_calls: None,
+                    name: None,
+                },
+                TokenUsage::default(),
+            ))
+        }
+
+        fn set_sampling_params(&mut self, params: SamplingParams) -> bool {
+            *self.sampling.lock().unwrap() = params;
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timeout_bounds_the_whole_prompt() {
+        let sampling = Arc::new(Mutex::new(SamplingParams::default()));
+        let mut client = SlowModel {
+            delay: std::time::Duration::from_secs(10),
+            sampling: Arc::clone(&sampling),
+        }
+        .build_client("preamble", vec![], echo_toolset());
+
+        let start = std::time::Instant::now();
+        let result = client
+            .prompt("hi")
+            .top_p(0.5)
+            .timeout(std::time::Duration::from_millis(50))
+            .send()
+            .await;
+
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        assert!(matches!(
+            result,
+            Err(crate::error::Error::Completion(CompletionError::RequestError(e)))
+                if e == "prompt timed out"
+        ));
+        assert!(sampling.lock().unwrap().is_empty());
+        assert_eq!(client.export_history().len(), 1);
+
+        client.completion_model.write().await.delay = std::time::Duration::ZERO;
+        let response = client
+            .prompt("hi")
+            .timeout(std::time::Duration::from_secs(5))
+            .send()
+            .await
+            .unwrap();
+        assert!(matches!(response, Message::Assistant { content, .. } if content == "late"));
+    }
+
+    /// Completion model failing every request, like a provider that's down
+    struct FailingModel;
+
+    #[allow(refining_impl_trait)]
+    #[async_trait]
+    impl CompletionModel for FailingModel {
+        fn build_client(
+            self,
+            preamble: impl AsRef<str>,
+            embedder_instances: Vec<Embedder>,
+            tools: ToolSet,
+        ) -> Client<Self> {
+            Client::new(self, preamble, 0.0, 100, embedder_instances, tools)
+        }
+
+        async fn send(
+            &mut self,
+            _message: Message,
+            _history: &MessageHistory,
+            _tools: Option<&ToolSet>,
+            _temperature: f64,
+            _max_tokens: usize,
+        ) -> Result<(Message, TokenUsage), CompletionError> {
+            Err(CompletionError::RequestError(
+                "service unavailable".to_string(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_response_replaces_failed_requests() {
+        let mut client = FailingModel.build_client("preamble", vec![], echo_toolset());
+        assert!(matches!(
+            client.prompt("hi").send().await,
+            Err(crate::error::Error::Completion(
+                CompletionError::RequestError(_)
+            ))
+        ));
+
+        let mut client = client.with_fallback_response("We're having trouble, try again later");
+        let response = client.prompt("hi").send().await.unwrap();
+        assert_eq!(
+            response,
+            Message::Assistant {
+                content: "We're having trouble, try again later".to_string(),
+                tool_calls: None,
+                name: None,
             }
+        );
+        assert_eq!(client.export_history().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_response_keeps_non_request_errors() {
+        let embedder = Embedder::init(
+            vec![],
+            Arc::new(tokio::sync::Mutex::new(Box::new(
+                crate::vector_store::InMemoryVectorStore::new(None)
+                    .await
+                    .unwrap(),
+            ))),
+            Arc::new(Box::new(ConstantEmbeddingModel)),
+        )
+        .await;
+        let (model, requests) = MockModel::new(vec![]);
+        let mut client = model
+            .build_client("preamble", vec![embedder], echo_toolset())
+            .with_fallback_response("We're having trouble, try again later");
+
+        let result = client
+            .prompt("question")
+            .on_no_context(NoContextPolicy::Fail)
+            .send()
+            .await;
+        assert!(matches!(
+            result,
+            Err(crate::error::Error::Completion(
+                CompletionError::NoContextFound
+            ))
+        ));
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_moderator_blocks_flagged_prompts_and_responses() {
+        let (model, requests) = MockModel::new(vec![Message::Assistant {
+            content: "The password is hunter2".to_string(),
+            tool_calls: None,
+            name: None,
+        }]);
+        let mut client = model
+            .build_client("preamble", vec![], echo_toolset())
+            .with_moderator(Box::new(WordlistModerator::new(["password"])));
+
+        assert!(matches!(
+            client.prompt("What's the admin PASSWORD?").send().await,
+            Err(crate::error::Error::Moderation(ModerationError::Flagged(_)))
+        ));
+        assert!(requests.lock().unwrap().is_empty());
+
+        let mut client = client.with_response_moderation(true);
+        assert!(matches!(
+            client.prompt("How do I log in?").send().await,
+            Err(crate::error::Error::Moderation(ModerationError::Flagged(_)))
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        assert_eq!(client.export_history().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_prompt_metrics_time_every_phase() {
+        let (model, _) = MockModel::new(vec![echo_call()]);
+        let mut client =
+            model.build_client("preamble", vec![context_embedder().await], echo_toolset());
+
+        let (_, metrics) = client.prompt("hi").send_with_metrics().await.unwrap();
+
+        let (Some(retrieval), Some(completion), Some(tools)) =
+            (metrics.retrieval, metrics.completion, metrics.tools)
+        else {
+            panic!("missing phase durations: {metrics:?}");
+        };
+        assert!(metrics.total >= retrieval + completion + tools);
+
+        let (_, metrics) = client
+            .prompt("hi")
+            .with_context(false)
+            .send_with_metrics()
+            .await
+            .unwrap();
+        assert_eq!(metrics.retrieval, None);
+        assert_eq!(metrics.tools, None);
+        assert!(metrics.completion.is_some());
+    }
+
+    fn echo_call() -> Message {
+        Message::Assistant {
+            content: String::new(),
+            tool_calls: Some(vec![ToolCall {
+                id: "call_1".to_string(),
+                name: "echo".to_string(),
+                arguments: r#"{"input":"hello"}"#.to_string(),
+            }]),
+            name: None,
         }
+    }
 
-        Ok(response)
+    #[test]
+    fn test_prompts_from_every_input() {
+        let text = |text: &str| Prompt {
+            text: text.to_string(),
+            history: None,
+        };
+        let owned = "owned".to_string();
+        let name = "jack";
+        assert_eq!("borrowed".into_prompt(), text("borrowed"));
+        assert_eq!(owned.clone().into_prompt(), text("owned"));
+        assert_eq!((&owned).into_prompt(), text("owned"));
+        assert_eq!(std::borrow::Cow::from("cow").into_prompt(), text("cow"));
+        assert_eq!(
+            format_args!("Greet {name}").into_prompt(),
+            text("Greet jack")
+        );
+
+        let user = |content: &str| Message::User {
+            content: content.to_string(),
+            tool_responses: None,
+            name: None,
+        };
+        let answer = Message::Assistant {
+            content: "hi".to_string(),
+            tool_calls: None,
+            name: None,
+        };
+        assert_eq!(
+            vec![user("hello"), answer.clone()].into_prompt(),
+            Prompt {
+                text: String::new(),
+                history: Some(vec![user("hello"), answer.clone()]),
+            }
+        );
+
+        let (model, _) = MockModel::new(vec![]);
+        let mut client = model.build_client(
+            "preamble",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+        let builder = client.prompt(vec![user("hello"), answer.clone(), user("bye")]);
+        assert_eq!(builder.prompt, "bye");
+        assert_eq!(builder.one_shot, (true, Some(vec![user("hello"), answer])));
+        drop(builder);
+        assert!(!client.prompt("hello").one_shot.0);
     }
-}
 
-impl<M: CompletionModel + Send> Client<M> {
-    /// Creates a new client with the specified configuration
-    ///
-    /// # Arguments
-    /// * `completion_model` - The underlying model implementation
-    /// * `preamble` - System instructions for the model
-    /// * `temperature` - Sampling temperature (0.0-1.0)
-    /// * `max_tokens` - Maximum response length
-    /// * `embedders` - Embedding models for context retrieval
-    /// * `tools` - Available tools for function calling
-    pub fn new(
-        completion_model: M,
-        preamble: impl AsRef<str>,
-        temperature: f64,
-        max_tokens: usize,
-        embedders: Vec<Embedder>,
-        tools: ToolSet,
-    ) -> Self {
-        Self {
-            completion_model: Arc::new(RwLock::new(completion_model)),
-            history: vec![Message::Preamble(String::from(preamble.as_ref()))],
-            embedders,
-            tools: Box::new(tools),
-            temperature,
-            max_tokens,
-            token_usage: TokenUsage::default(),
-            states: DashMap::new(),
+    #[tokio::test]
+    async fn test_tool_calls_are_returned_without_being_executed() {
+        let call = echo_call();
+        assert_eq!(call.tool_calls().unwrap()[0].name, "echo");
+        let answer = Message::Assistant {
+            content: "hi".to_string(),
+            tool_calls: None,
+            name: None,
+        };
+        assert!(answer.tool_calls().is_none());
+        assert!(Message::Preamble("preamble".to_string())
+            .tool_calls()
+            .is_none());
+
+        let (model, _) = MockModel::new(vec![echo_call()]);
+        let mut client = model.build_client("preamble", vec![], echo_toolset());
+        let calls = client.prompt("echo hello").tool_calls().await.unwrap();
+
+        assert_eq!(calls, call.tool_calls().unwrap());
+        assert_eq!(client.export_history().last(), Some(&call));
+    }
+
+    #[tokio::test]
+    async fn test_prefill_is_sent_last_and_prepended_to_response() {
+        let (model, requests) = MockModel::new(vec![Message::Assistant {
+            content: "\"answer\": 42}".to_string(),
+            tool_calls: None,
+            name: None,
+        }]);
+        let mut client = model.build_client(
+            "preamble",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+
+        let response = client
+            .prompt("reply in json")
+            .prefill("{")
+            .send()
+            .await
+            .unwrap();
+        let prefilled = Message::Assistant {
+            content: "{\"answer\": 42}".to_string(),
+            tool_calls: None,
+            name: None,
+        };
+        assert_eq!(response, prefilled);
+
+        let request = requests.lock().unwrap()[0].clone();
+        assert_eq!(
+            request[request.len() - 2..],
+            [
+                Message::User {
+                    content: "reply in json".to_string(),
+                    tool_responses: None,
+                    name: None,
+                },
+                Message::Assistant {
+                    content: "{".to_string(),
+                    tool_calls: None,
+                    name: None,
+                },
+            ]
+        );
+        assert_eq!(client.export_history().last(), Some(&prefilled));
+    }
+
+    #[test]
+    fn test_history_exported_as_openai_messages() {
+        let mut client = MockModel::new(vec![]).0.build_client(
+            "preamble",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+        client.append_history(&[
+            Message::User {
+                content: "echo hello".to_string(),
+                tool_responses: None,
+                name: None,
+            },
+            echo_call(),
+            Message::Tool(ToolResponse {
+                id: "call_1".to_string(),
+                name: "echo".to_string(),
+                content: Value::from("hello"),
+                images: vec![],
+            }),
+        ]);
+
+        let exported = client.export_history_as_openai();
+        let messages = exported.as_array().unwrap();
+        let roles: Vec<_> = messages.iter().map(|m| m["role"].clone()).collect();
+        assert_eq!(
+            roles,
+            vec![
+                json!("system"),
+                json!("user"),
+                json!("assistant"),
+                json!("tool")
+            ]
+        );
+        assert_eq!(messages[2]["tool_calls"][0]["id"], json!("call_1"));
+        assert_eq!(messages[3]["tool_call_id"], json!("call_1"));
+    }
+
+    #[tokio::test]
+    async fn test_client_builder_assembles_client() {
+        let (model, requests) = MockModel::new(vec![echo_call()]);
+        let mut client = ClientBuilder::new(model)
+            .preamble("You're a helpful assistant")
+            .tool(Box::new(EchoTool {
+                args: vec![ToolArg::new::<String>("input", "text to echo")],
+            }))
+            .execution_strategy(ExecutionStrategy::BestEffort)
+            .state(7u32)
+            .temperature(0.3)
+            .max_tokens(50)
+            .build()
+            .unwrap();
+
+        assert_eq!(client.temperature, 0.3);
+        assert_eq!(client.max_tokens, 50);
+        assert_eq!(*client.get_state::<u32>().unwrap().0, 7);
+        assert_eq!(
+            client.export_history()[0],
+            Message::Preamble("You're a helpful assistant".to_string())
+        );
+
+        let response = client.prompt("echo hello").send().await.unwrap();
+        assert_eq!(
+            response,
+            Message::User {
+                content: String::new(),
+                tool_responses: Some(vec![ToolResponse {
+                    id: "call_1".to_string(),
+                    name: "echo".to_string(),
+                    content: Value::from("hello"),
+                    images: vec![],
+                }]),
+                name: None,
+            }
+        );
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        let duplicate_state = ClientBuilder::new(MockModel::new(vec![]).0)
+            .state(1u32)
+            .state(2u32)
+            .build();
+        assert!(matches!(
+            duplicate_state,
+            Err(CompletionError::StateError(StateError::AlreadyExists(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tool_observers_fire_for_tool_calls() {
+        let (model, _) = MockModel::new(vec![echo_call()]);
+        let mut client = model.build_client("preamble", vec![], echo_toolset());
+        let calls = Mutex::new(vec![]);
+        let responses = Mutex::new(vec![]);
+
+        client
+            .prompt("echo hello")
+            .on_tool_call(|call| calls.lock().unwrap().push(call.clone()))
+            .on_tool_response(|response| responses.lock().unwrap().push(response.clone()))
+            .send()
+            .await
+            .unwrap();
+
+        let Message::Assistant {
+            tool_calls: Some(expected_calls),
+            ..
+        } = echo_call()
+        else {
+            unreachable!()
+        };
+        assert_eq!(*calls.lock().unwrap(), expected_calls);
+        assert_eq!(
+            *responses.lock().unwrap(),
+            [ToolResponse {
+                id: "call_1".to_string(),
+                name: "echo".to_string(),
+                content: Value::from("hello"),
+                images: vec![],
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_append_tool_response_appends_tool_messages() {
+        let (model, requests) = MockModel::new(vec![echo_call()]);
+        let mut client = model.build_client("preamble", vec![], echo_toolset());
+
+        let response = client
+            .prompt("echo hello")
+            .append_tool_response(true)
+            .send()
+            .await
+            .unwrap();
+        assert!(matches!(
+            response,
+            Message::User {
+                tool_responses: Some(_),
+                ..
+            }
+        ));
+
+        let tool_message = Message::Tool(ToolResponse {
+            id: "call_1".to_string(),
+            name: "echo".to_string(),
+            content: Value::from("hello"),
+            images: vec![],
+        });
+        assert_eq!(client.export_history().len(), 4);
+        assert_eq!(client.export_history()[2], echo_call());
+        assert_eq!(client.export_history()[3], tool_message);
+
+        client.prompt("what did it say?").send().await.unwrap();
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[1][2], echo_call());
+        assert_eq!(requests[1][3], tool_message);
+    }
+
+    #[tokio::test]
+    async fn test_tool_responses_not_appended_by_default() {
+        let (model, _) = MockModel::new(vec![echo_call()]);
+        let mut client = model.build_client("preamble", vec![], echo_toolset());
+
+        client.prompt("echo hello").send().await.unwrap();
+        assert_eq!(client.export_history().len(), 3);
+        assert!(!client
+            .export_history()
+            .iter()
+            .any(|m| matches!(m, Message::Tool(_))));
+    }
+
+    struct WarnCounter(Arc<AtomicUsize>);
+
+    impl<S: tracing::Subscriber> Layer<S> for WarnCounter {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            if *event.metadata().level() == tracing::Level::WARN {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
         }
     }
 
-    /// Clear conversation history while maintaining premble
-    pub fn clear_history(&mut self) {
-        self.history.retain(|m| matches!(m, Message::Preamble(_)));
-    }
+    #[tokio::test]
+    async fn test_missing_embedders_warning_logged_once() {
+        let warnings = Arc::new(AtomicUsize::new(0));
+        let subscriber = tracing_subscriber::registry().with(WarnCounter(Arc::clone(&warnings)));
+        let _guard = tracing::subscriber::set_default(subscriber);
 
-    /// Replaces the current message history with the provided history
-    pub fn load_history(&mut self, history: MessageHistory) {
-        self.history = history;
-    }
+        let (model, _) = MockModel::new(vec![]);
+        let mut client = model.build_client(
+            "preamble",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+        client.prompt("first").send().await.unwrap();
+        client.prompt("second").send().await.unwrap();
 
-    /// Returns a reference to the current message history
-    #[must_use]
-    pub fn export_history(&self) -> &MessageHistory {
-        &self.history
+        assert_eq!(warnings.load(Ordering::SeqCst), 1);
     }
 
-    /// Appends messages to the conversation history
-    pub fn append_history(&mut self, messages: &[Message]) {
-        messages.iter().for_each(|m| self.history.push(m.clone()));
-    }
+    #[derive(Default)]
+    struct FieldRecorder(BTreeMap<String, String>);
 
-    #[instrument(skip(self, state), fields())]
-    /// Registers new state with the client
-    ///
-    /// # Errors
-    /// Returns [`StateError::AlreadyExists`] if state of this type is already registered
-    pub fn with_state<T: Send + Sync + 'static>(self, state: T) -> Result<Self, CompletionError> {
-        let type_id = state.type_id();
-        if self.states.contains_key(&type_id) {
-            error!(
-                state_type = std::any::type_name::<T>(),
-                "Failed to add state to client because state of a similar type already exists"
+    impl tracing::field::Visit for FieldRecorder {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(
+                field.name().to_string(),
+                format!("{value:?}").replace('"', ""),
             );
-            return Err(CompletionError::StateError(StateError::AlreadyExists(
-                format!("{:?}", std::any::type_name::<T>()),
-            )));
         }
-        self.states.insert(type_id, Box::new(Arc::new(state)));
-        info!(
-            state_type = std::any::type_name::<T>(),
-            "Successfully added state new state to client!"
-        );
-        Ok(self)
     }
 
-    #[instrument(skip(self))]
-    /// Retrieves previously registered state
-    ///
-    /// # Errors
-    /// Returns [`StateError::NotFound`] if no state of type `T` exists
-    pub fn get_state<T: Send + Sync + 'static>(&self) -> Result<State<T>, StateError> {
-        let boxed = self.states.get(&TypeId::of::<T>()).ok_or({
-            error!(
-                state_type = std::any::type_name::<T>(),
-                "Failed to find requested state on client"
-            );
-            StateError::NotFound
-        })?;
-
-        let arc = boxed.downcast_ref::<Arc<T>>().ok_or(StateError::NotFound)?;
+    struct MetricsCapture(Arc<Mutex<Vec<BTreeMap<String, String>>>>);
 
-        Ok(State(arc.clone()))
+    impl<S: tracing::Subscriber> Layer<S> for MetricsCapture {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            if event.metadata().target() == METRICS_TARGET {
+                let mut recorder = FieldRecorder::default();
+                event.record(&mut recorder);
+                self.0.lock().unwrap().push(recorder.0);
+            }
+        }
     }
 
-    /// Creates a `PromptBuilder` instance .
-    pub fn prompt(&mut self, prompt: impl Into<String>) -> PromptBuilder<M> {
-        PromptBuilder::new(self, prompt)
-    }
+    struct MeteredModel;
 
-    #[instrument(skip(self, calls), fields(fetch_toolcall_from_history = calls.is_some()))]
-    /// Executes requested tool calls from the model
-    ///
-    /// If no calls are provided, attempts to use calls from the last assistant message.
-    /// Supports different execution strategies (fail-early vs best-effort).
-    ///
-    /// # Errors
-    /// Returns [`ToolSetError`] for:
-    /// - Empty message history when calls not provided
-    /// - Last message not containing tool calls
-    /// - Individual tool execution failures
-    pub async fn run_tools(
-        &self,
-        calls: Option<&[ToolCall]>,
-    ) -> Result<Vec<ToolResponse>, ToolSetError> {
-        let calls = calls.unwrap_or({
-            let last = self.history.last().ok_or({
-                error!("Attempting to extract tool to call from an empty message history!");
-                ToolSetError::EmptyMessageHistory
-            })?;
-            if let Message::Assistant {
-                content: _,
-                tool_calls: Some(tcs),
-            } = last
-            {
-                tcs
-            } else {
-                error!("Last message in message history doesnt contain tools to call");
-                Err(ToolSetError::LastMessageNotAToolCall)?
-            }
-        });
+    #[allow(refining_impl_trait)]
+    #[async_trait]
+    impl CompletionModel for MeteredModel {
+        fn build_client(
+            self,
+            preamble: impl AsRef<str>,
+            embedder_instances: Vec<Embedder>,
+            tools: ToolSet,
+        ) -> Client<Self> {
+            Client::new(self, preamble, 0.0, 100, embedder_instances, tools)
+        }
 
-        let mut values = vec![];
-        match self.tools.1 {
-            ExecutionStrategy::FailEarly => {
-                for call in calls {
-                    info!(
-                        tool_name = call.name,
-                        "Calling tool with a `FailEarly` execution strategy!"
-                    );
-                    let call_result = self
-                        .tools
-                        .call(&call.id, &call.name, &call.arguments, &self.states)
-                        .await;
-                    if let Err(ref e) = call_result {
-                        error!(error = ?e, tool_name = call.name, "Tool call failed");
-                    }
-                    values.push(call_result?);
-                }
-            }
-            ExecutionStrategy::BestEffort => {
-                for call in calls {
-                    info!(
-                        tool_name = call.name,
-                        "Calling tool with a `BestEffort` execution strategy!"
-                    );
-                    let tr = self
-                        .tools
-                        .call(&call.id, &call.name, &call.arguments, &self.states)
-                        .await;
-                    match tr {
-                        Ok(v) => {
-                            values.push(v);
-                        }
-                        Err(e) => {
-                            error!(error = ?e, tool_name = call.name, "Tool call failed");
-                        }
-                    }
-                }
-            }
+        async fn send(
+            &mut self,
+            _message: Message,
+            _history: &MessageHistory,
+            _tools: Option<&ToolSet>,
+            _temperature: f64,
+            _max_tokens: usize,
+        ) -> Result<(Message, TokenUsage), CompletionError> {
+            Ok((
+                Message::Assistant {
+                    content: "done".to_string(),
+                    tool_calls: None,
+                    name: None,
+                },
+                TokenUsage {
+                    prompt_tokens: Some(10),
+                    completion_tokens: Some(5),
+                    total_tokens: Some(15),
+                },
+            ))
         }
 
-        Ok(values)
-    }
+        fn provider_name(&self) -> &str {
+            "metered"
+        }
 
-    fn update_token_usage(&mut self, usage: &TokenUsage) {
-        self.token_usage.prompt_tokens =
-            combine_options(self.token_usage.prompt_tokens, usage.prompt_tokens);
-        self.token_usage.completion_tokens =
-            combine_options(self.token_usage.completion_tokens, usage.completion_tokens);
-        self.token_usage.total_tokens =
-            combine_options(self.token_usage.total_tokens, usage.total_tokens);
+        fn model_name(&self) -> &str {
+            "metered-1"
+        }
     }
 
-    async fn send_prompt(
-        &self,
-        prompt: &str,
-        history: &MessageHistory,
-        tools: Option<&ToolSet>,
-        temperature: f64,
-        max_tokens: usize,
-        append_context: bool,
-    ) -> Result<(Message, TokenUsage), crate::error::Error> {
-        let retrieved_context = self.get_context(prompt).await?;
-        let context = if append_context {
-            retrieved_context
-                .map_or_else(String::new, |c| format!("\n\n<context>\n{c}\n</context>\n"))
-        } else {
-            String::new()
-        };
+    #[tokio::test]
+    async fn test_metrics_are_emitted_after_prompt() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let subscriber = tracing_subscriber::registry().with(MetricsCapture(Arc::clone(&events)));
+        let _guard = tracing::subscriber::set_default(subscriber);
 
-        let message_with_context = Message::User {
-            content: format!("{prompt}{context}"),
-            tool_responses: None,
-        };
+        let mut client = MeteredModel.build_client(
+            "preamble",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+        client.prompt("not measured").send().await.unwrap();
+        assert!(events.lock().unwrap().is_empty());
 
-        let model = self.completion_model.clone();
-        let mut guard = model.write().await;
-        guard
-            .send(
-                message_with_context,
-                history,
-                tools,
-                temperature,
-                max_tokens,
-            )
-            .await
-            .map_err(crate::error::Error::from)
+        let mut client = client.with_metrics(true);
+        client.prompt("measured").send().await.unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        let fields = &events[0];
+        assert_eq!(fields["sf.provider"], "metered");
+        assert_eq!(fields["sf.model"], "metered-1");
+        assert_eq!(fields["sf.prompt_tokens"], "10");
+        assert_eq!(fields["sf.completion_tokens"], "5");
+        assert!(fields["sf.latency_ms"].parse::<u64>().is_ok());
     }
 
-    async fn get_context(&self, prompt: &str) -> Result<Option<String>, crate::error::Error> {
-        if self.embedders.is_empty() {
-            return Ok(None);
-        }
-        let mut context = String::new();
-        for embedder in &self.embedders {
-            let query_results = embedder.query(prompt, DEFAULT_TOP_N).await?;
-            if query_results.is_empty() {
-                return Ok(None);
-            }
-            for r in query_results {
-                context.push_str(&r.raw_data);
-            }
+    fn tool_call_delta(
+        index: usize,
+        id: Option<&str>,
+        name: Option<&str>,
+        arguments: &str,
+    ) -> StreamChunk {
+        StreamChunk::ToolCallDelta {
+            index,
+            id: id.map(ToString::to_string),
+            name: name.map(ToString::to_string),
+            arguments: arguments.to_string(),
         }
-        Ok(Some(context))
     }
-}
 
-/// Generates a JSON schema serializer for extractor types
-///
-/// # Errors
-/// Returns `serde_json::Error` if schema serialization fails
-pub fn default_extractor_serializer<'a, T: schemars::JsonSchema + serde::Deserialize<'a>>(
-) -> Result<serde_json::Value, serde_json::error::Error> {
-    let settings = SchemaSettings::default().with(|s| {
-        s.inline_subschemas = true;
-    });
-    let generator = settings.into_generator();
-    let schema = generator.into_root_schema_for::<T>();
-    let mut schema_value = serde_json::to_value(&schema)?;
+    #[test]
+    fn test_tool_call_accumulator_assembles_fragments() {
+        let mut acc = ToolCallAccumulator::default();
+        acc.push(1, Some("call_2".to_string()), Some("echo".to_string()), "");
+        acc.push(
+            0,
+            Some("call_1".to_string()),
+            Some("ec".to_string()),
+            r#"{"inp"#,
+        );
+        acc.push(0, None, Some("ho".to_string()), r#"ut":"hel"#);
+        acc.push(1, None, None, r#"{"input":"bye"}"#);
+        acc.push(0, None, None, r#"lo"}"#);
 
-    let type_name: &str = std::any::type_name::<T>();
-    let type_name = type_name.split("::").last().unwrap_or("ExtractorType");
-    if let Some(obj) = schema_value.as_object_mut() {
-        obj.remove("$schema");
-        obj.remove("format");
-        obj.remove("title");
+        let calls = acc.finish().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].id, "call_1");
+        assert_eq!(calls[0].name, "echo");
+        assert_eq!(
+            serde_json::from_str::<String>(&calls[0].arguments).unwrap(),
+            r#"{"input":"hello"}"#
+        );
+        assert_eq!(calls[1].id, "call_2");
+        assert!(ToolCallAccumulator::default().finish().is_none());
     }
-    process_json_value(&mut schema_value);
-    let schema = json!({
-        "name": type_name,
-        "strict": true,
-        "schema": schema_value
-    });
-    Ok(json!({
-        "type": "json_schema",
-        "json_schema": schema
-    }))
-}
 
-fn process_json_value(value: &mut serde_json::Value) {
-    match value {
-        serde_json::Value::Object(obj) => {
-            let fields_to_remove = ["$schema", "format", "title", "minimum"];
-            for &f in &fields_to_remove {
-                if obj.get(f).map_or(false, |v| v.is_string() || v.is_number()) {
-                    obj.remove(f);
-                }
-            }
-            if let Some(v) = obj.get("oneOf").cloned() {
-                obj.remove("oneOf");
-                obj.insert("anyOf".to_string(), v);
-            };
+    #[tokio::test]
+    async fn test_stream_reconstructs_and_executes_tool_calls() {
+        let (mut model, _) = MockModel::new(vec![]);
+        model.streams.push_back(vec![
+            StreamChunk::Text("Let me ".to_string()),
+            StreamChunk::Text("check".to_string()),
+            tool_call_delta(0, Some("call_1"), Some("echo"), ""),
+            tool_call_delta(0, None, None, r#"{"in"#),
+            tool_call_delta(0, None, None, r#"put": "hel"#),
+            tool_call_delta(0, None, None, r#"lo"}"#),
+        ]);
+        let mut client = model.build_client("preamble", vec![], echo_toolset());
 
-            if obj.contains_key("properties") {
-                obj.insert("additionalProperties".to_string(), json!(false));
+        let mut streamed = String::new();
+        let response = client
+            .prompt("echo hello")
+            .append_tool_response(true)
+            .stream(|text| streamed.push_str(text))
+            .await
+            .unwrap();
+        assert_eq!(streamed, "Let me check");
+
+        let Message::Assistant {
+            content,
+            tool_calls: Some(calls),
+            ..
+        } = &client.export_history()[2]
+        else {
+            panic!("expected an assistant message with tool calls");
+        };
+        assert_eq!(content, "Let me check");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, "call_1");
+        assert_eq!(calls[0].name, "echo");
+        assert_eq!(
+            serde_json::from_str::<String>(&calls[0].arguments).unwrap(),
+            r#"{"input": "hello"}"#
+        );
+
+        let tool_response = ToolResponse {
+            id: "call_1".to_string(),
+            name: "echo".to_string(),
+            content: Value::from("hello"),
+            images: vec![],
+        };
+        assert_eq!(
+            response,
+            Message::User {
+                content: String::new(),
+                tool_responses: Some(vec![tool_response.clone()]),
+                name: None,
             }
-            for (_, v) in obj.iter_mut() {
-                process_json_value(v);
+        );
+        assert_eq!(client.export_history()[3], Message::Tool(tool_response));
+    }
+
+    #[tokio::test]
+    async fn test_stream_usage_reported_last_updates_the_total() {
+        let usage = |prompt, completion| {
+            StreamChunk::Usage(TokenUsage {
+                prompt_tokens: Some(prompt),
+                completion_tokens: Some(completion),
+                total_tokens: Some(prompt + completion),
+            })
+        };
+        let (mut model, _) = MockModel::new(vec![]);
+        model.streams.push_back(vec![
+            StreamChunk::Text("Hel".to_string()),
+            StreamChunk::Text("lo".to_string()),
+            usage(10, 2),
+        ]);
+        model
+            .streams
+            .push_back(vec![StreamChunk::Text("again".to_string()), usage(15, 1)]);
+        let mut client = model.build_client("preamble", vec![], echo_toolset());
+
+        client.prompt("hi").stream(|_| {}).await.unwrap();
+        assert_eq!(client.token_usage().total_tokens, Some(12));
+        client.prompt("hi again").stream(|_| {}).await.unwrap();
+        assert_eq!(
+            client.token_usage(),
+            &TokenUsage {
+                prompt_tokens: Some(25),
+                completion_tokens: Some(3),
+                total_tokens: Some(28),
             }
+        );
+    }
+
+    struct LatencyModel {
+        in_flight: Arc<AtomicUsize>,
+        max_in_flight: Arc<AtomicUsize>,
+    }
+
+    #[allow(refining_impl_trait)]
+    #[async_trait]
+    impl CompletionModel for LatencyModel {
+        fn build_client(
+            self,
+            preamble: impl AsRef<str>,
+            embedder_instances: Vec<Embedder>,
+            tools: ToolSet,
+        ) -> Client<Self> {
+            Client::new(self, preamble, 0.0, 100, embedder_instances, tools)
         }
-        serde_json::Value::Array(arr) => {
-            for elem in arr.iter_mut() {
-                process_json_value(elem);
-            }
+
+        async fn send(
+            &mut self,
+            _message: Message,
+            _history: &MessageHistory,
+            _tools: Option<&ToolSet>,
+            _temperature: f64,
+            _max_tokens: usize,
+        ) -> Result<(Message, TokenUsage), CompletionError> {
+            let current = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(current, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok((
+                Message::Assistant {
+                    content: "done".to_string(),
+                    tool_calls: None,
+                    name: None,
+                },
+                TokenUsage::default(),
+            ))
         }
-        _ => {}
     }
-}
 
-/// Serializes user messages, ignoring tool call responses
-///
-/// # Errors
-/// If serializer fails
-pub fn serialize_user<S>(
-    content: &str,
-    _tool_calls: &Option<Vec<ToolResponse>>,
-    serializer: S,
-) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    serializer.serialize_newtype_struct("user", &content)
-}
+    #[tokio::test]
+    async fn test_in_flight_prompts_are_limited() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let limiter = Arc::new(Semaphore::new(2));
 
-/// Serializes assistant messages with integrated tool call information
-///
-/// # Errors
-/// If serializer fails
-pub fn serialize_assistant<S>(
-    content: &str,
-    tool_calls: &Option<Vec<ToolCall>>,
-    serializer: S,
-) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    let combined_content = match tool_calls {
-        Some(calls) => &format!("{content} {calls:?}"),
-        None => content,
-    };
-    serializer.serialize_newtype_struct("assistant", &combined_content)
-}
+        let tasks: Vec<_> = (0..6)
+            .map(|_| {
+                let mut client = ClientBuilder::new(LatencyModel {
+                    in_flight: Arc::clone(&in_flight),
+                    max_in_flight: Arc::clone(&max_in_flight),
+                })
+                .in_flight_limiter(Arc::clone(&limiter))
+                .build()
+                .unwrap();
+                tokio::spawn(async move { client.prompt("hi").send().await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
 
-fn combine_options(a: Option<u64>, b: Option<u64>) -> Option<u64> {
-    match (a, b) {
-        (Some(a_val), Some(b_val)) => Some(a_val + b_val),
-        _ => None,
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+        assert_eq!(limiter.available_permits(), 2);
     }
 }
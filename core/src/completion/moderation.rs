@@ -0,0 +1,89 @@
+use async_trait::async_trait;
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// Errors returned by a [`Moderator`]
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum ModerationError {
+    /// The text was flagged and must not be sent or returned
+    #[error("Content flagged by moderation: {0}")]
+    Flagged(String),
+    /// The moderator couldn't check the text, eg. a moderation API being unreachable
+    #[error("Moderation failed: {0}")]
+    Failed(String),
+}
+
+/// Screens the text exchanged with the model, see [`Client::with_moderator`]
+///
+/// [`Client::with_moderator`]: super::Client::with_moderator
+#[async_trait]
+pub trait Moderator: Send + Sync {
+    /// Checks `text`, returning [`ModerationError::Flagged`] if it must be blocked
+    ///
+    /// # Errors
+    /// Returns an error if the text is flagged or couldn't be checked
+    async fn check(&self, text: &str) -> Result<(), ModerationError>;
+}
+
+/// Moderator letting everything through
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopModerator;
+
+#[async_trait]
+impl Moderator for NoopModerator {
+    async fn check(&self, _text: &str) -> Result<(), ModerationError> {
+        Ok(())
+    }
+}
+
+/// Moderator flagging text containing any word of a list, ignoring case
+///
+/// Text is matched word by word, so a blocked word inside a longer word isn't flagged.
+#[derive(Debug, Default, Clone)]
+pub struct WordlistModerator {
+    words: HashSet<String>,
+}
+
+impl WordlistModerator {
+    /// Creates a moderator flagging the words in `words`
+    pub fn new<S: AsRef<str>>(words: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            words: words
+                .into_iter()
+                .map(|word| word.as_ref().to_lowercase())
+                .collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl Moderator for WordlistModerator {
+    async fn check(&self, text: &str) -> Result<(), ModerationError> {
+        let flagged = text
+            .split(|c: char| !c.is_alphanumeric())
+            .find(|word| !word.is_empty() && self.words.contains(&word.to_lowercase()));
+        match flagged {
+            Some(word) => Err(ModerationError::Flagged(format!(
+                "contains the blocked word `{word}`"
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_wordlist_matches_whole_words_ignoring_case() {
+        let moderator = WordlistModerator::new(["secret"]);
+
+        assert!(matches!(
+            moderator.check("Tell me the SECRET, please").await,
+            Err(ModerationError::Flagged(_))
+        ));
+        assert!(moderator.check("What do secretaries do?").await.is_ok());
+        assert!(NoopModerator.check("Tell me the secret").await.is_ok());
+    }
+}
@@ -1,16 +1,190 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+/// Metadata key overriding the [`Document::dedup_key`] of a document
+pub const DEDUP_KEY_METADATA: &str = "dedup_key";
+
 /// Represents contents of a document for use in embedding,
 /// and similarity search.
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Document {
     /// identifies a document in the store
     pub id: String,
     /// raw data of the document
     pub data: String,
+    /// additional information about the document, eg. its source
+    pub metadata: HashMap<String, String>,
+    /// pre-computed embedding of the data, stored as is instead of embedding the data
+    pub embedding: Option<Vec<f64>>,
 }
 
 impl Document {
+    /// Creates a new document without metadata
+    #[must_use]
+    pub fn new(id: impl Into<String>, data: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            data: data.into(),
+            metadata: HashMap::new(),
+            embedding: None,
+        }
+    }
+
+    /// Returns a [`DocumentBuilder`] for constructing a document
+    #[must_use]
+    pub fn builder() -> DocumentBuilder {
+        DocumentBuilder::default()
+    }
+
+    /// Key identifying documents with the same content regardless of their id, the
+    /// [`DEDUP_KEY_METADATA`] metadata entry if set, a hash of the data otherwise.
+    /// Content hashes are only meant to be compared within a process.
+    #[must_use]
+    pub fn dedup_key(&self) -> String {
+        if let Some(key) = self.metadata.get(DEDUP_KEY_METADATA) {
+            return key.clone();
+        }
+        let mut hasher = DefaultHasher::new();
+        self.data.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// Generates the ids of the documents loaded by a loader, see eg.
+/// [`FileOnceLoaderBuilder::id_generator`](crate::loader::builtins::file_loaders::FileOnceLoaderBuilder::id_generator)
+pub trait IdGenerator: Send + Sync {
+    /// Returns the id of a document loaded from `source`, eg. a file path
+    fn generate(&self, source: &str) -> String;
+}
+
+/// Uses the source of a document as its id, the default of the built-in loaders
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SourceIds;
+
+impl IdGenerator for SourceIds {
+    fn generate(&self, source: &str) -> String {
+        source.to_string()
+    }
+}
+
+/// Gives every document a random UUID v4 id
+#[derive(Debug, Default, Clone, Copy)]
+pub struct UuidIds;
+
+impl IdGenerator for UuidIds {
+    fn generate(&self, _source: &str) -> String {
+        uuid::Uuid::new_v4().to_string()
+    }
+}
+
+/// Builder for [`Document`]s
+///
+/// If no id is set a random one gets generated when building the document.
+#[derive(Debug, Default)]
+pub struct DocumentBuilder {
+    id: Option<String>,
+    data: String,
+    metadata: HashMap<String, String>,
+    embedding: Option<Vec<f64>>,
+}
+
+impl DocumentBuilder {
+    /// Sets the id of the document
     #[must_use]
-    pub fn new(id: String, data: String) -> Self {
-        Self { id, data }
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Sets the raw data of the document
+    #[must_use]
+    pub fn data(mut self, data: impl Into<String>) -> Self {
+        self.data = data.into();
+        self
+    }
+
+    /// Adds a metadata entry, replacing any previous value for `key`
+    #[must_use]
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets the key documents are deduplicated by, instead of the hash of their data
+    #[must_use]
+    pub fn dedup_key(self, key: impl Into<String>) -> Self {
+        self.metadata(DEDUP_KEY_METADATA, key)
+    }
+
+    /// Sets a pre-computed embedding of the data, skipping the embedding model when the
+    /// document gets stored
+    #[must_use]
+    pub fn embedding(mut self, embedding: Vec<f64>) -> Self {
+        self.embedding = Some(embedding);
+        self
+    }
+
+    /// Builds the document
+    #[must_use]
+    pub fn build(self) -> Document {
+        Document {
+            id: self.id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            data: self.data,
+            metadata: self.metadata,
+            embedding: self.embedding,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_with_metadata() {
+        let document = Document::builder()
+            .id("doc")
+            .data("hello world")
+            .metadata("source", "test")
+            .metadata("lang", "en")
+            .build();
+
+        assert_eq!(document.id, "doc");
+        assert_eq!(document.data, "hello world");
+        assert_eq!(document.metadata.len(), 2);
+        assert_eq!(document.metadata["source"], "test");
+        assert_eq!(document.metadata["lang"], "en");
+        assert_eq!(
+            Document::new("doc", "hello world"),
+            Document {
+                metadata: HashMap::new(),
+                ..document
+            }
+        );
+    }
+
+    #[test]
+    fn test_dedup_key() {
+        let a = Document::new("a", "same content");
+        let b = Document::new("b", "same content");
+        assert_eq!(a.dedup_key(), b.dedup_key());
+        assert_ne!(
+            a.dedup_key(),
+            Document::new("a", "other content").dedup_key()
+        );
+        let keyed = Document::builder()
+            .data("other content")
+            .dedup_key("https://example.com/page")
+            .build();
+        assert_eq!(keyed.dedup_key(), "https://example.com/page");
+    }
+
+    #[test]
+    fn test_builder_generates_id() {
+        let (a, b) = (Document::builder().build(), Document::builder().build());
+        assert!(!a.id.is_empty());
+        assert_ne!(a.id, b.id);
     }
 }
@@ -0,0 +1,66 @@
+/// Per item results of a batch operation, in the order of the items of the batch
+///
+/// Lets callers keep the successes of a batch and handle the items that failed, instead of
+/// losing the whole batch to a single failure.
+#[derive(Debug)]
+pub struct BatchResult<T, E>(Vec<Result<T, E>>);
+
+impl<T, E> BatchResult<T, E> {
+    /// Number of items in the batch
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether the batch had no items
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The result of the item at `index`
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&Result<T, E>> {
+        self.0.get(index)
+    }
+
+    /// Returns whether every item of the batch succeeded
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.0.iter().all(Result::is_ok)
+    }
+
+    /// The index and value of every item that succeeded
+    pub fn successes(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.0
+            .iter()
+            .enumerate()
+            .filter_map(|(i, result)| result.as_ref().ok().map(|value| (i, value)))
+    }
+
+    /// The index and error of every item that failed
+    pub fn failures(&self) -> impl Iterator<Item = (usize, &E)> {
+        self.0
+            .iter()
+            .enumerate()
+            .filter_map(|(i, result)| result.as_ref().err().map(|error| (i, error)))
+    }
+
+    /// The results of the items, in batch order
+    #[must_use]
+    pub fn into_results(self) -> Vec<Result<T, E>> {
+        self.0
+    }
+}
+
+impl<T, E> From<Vec<Result<T, E>>> for BatchResult<T, E> {
+    fn from(results: Vec<Result<T, E>>) -> Self {
+        Self(results)
+    }
+}
+
+impl<T, E> FromIterator<Result<T, E>> for BatchResult<T, E> {
+    fn from_iter<I: IntoIterator<Item = Result<T, E>>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
@@ -1,5 +1,10 @@
-#[derive(Clone, Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 /// Embedding of a document
+///
+/// Serializes as `{"id": .., "embedded_data": [..], "raw_data": ..}`, with the `bincode`
+/// feature it can also be encoded in a compact binary format better suited to large vectors.
 pub struct Embedding {
     /// A unique identifier for the embedding.
     pub id: String,
@@ -8,3 +13,62 @@ pub struct Embedding {
     /// The raw text data from which the embedding was generated.
     pub raw_data: String,
 }
+
+#[cfg(feature = "bincode")]
+impl Embedding {
+    /// Encodes the embedding in the binary format
+    ///
+    /// # Errors
+    /// Returns [`EmbedderError::EncodingError`](super::EmbedderError::EncodingError) if encoding
+    /// fails
+    pub fn to_bytes(&self) -> Result<Vec<u8>, super::EmbedderError> {
+        bincode::serialize(self).map_err(|e| super::EmbedderError::EncodingError(e.to_string()))
+    }
+
+    /// Decodes an embedding encoded with [`Embedding::to_bytes`]
+    ///
+    /// # Errors
+    /// Returns [`EmbedderError::EncodingError`](super::EmbedderError::EncodingError) if `bytes`
+    /// isn't a valid encoding of an embedding
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, super::EmbedderError> {
+        bincode::deserialize(bytes).map_err(|e| super::EmbedderError::EncodingError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embedding() -> Embedding {
+        Embedding {
+            id: "doc".to_string(),
+            embedded_data: vec![0.1, -2.5, 1e-9],
+            raw_data: "some text".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let json = serde_json::to_value(embedding()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "id": "doc",
+                "embedded_data": [0.1, -2.5, 1e-9],
+                "raw_data": "some text"
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<Embedding>(json).unwrap(),
+            embedding()
+        );
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn test_binary_round_trip() {
+        let bytes = embedding().to_bytes().unwrap();
+        assert_eq!(Embedding::from_bytes(&bytes).unwrap(), embedding());
+        assert!(Embedding::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -1,11 +1,14 @@
+pub mod batch;
 pub mod embedding;
 pub mod model;
-use crate::{loader::LoaderInstance, vector_store::VectorStore};
+use crate::{document::Document, loader::LoaderInstance, vector_store::VectorStore};
+pub use batch::BatchResult;
 use embedding::Embedding;
+use futures::StreamExt;
 use model::EmbeddingModel;
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 use thiserror::Error;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Semaphore};
 use tracing::{error, info};
 
 #[derive(Debug, Error)]
@@ -16,17 +19,64 @@ pub enum EmbedderError {
     ParseError(String),
     #[error("Provider error: {0}")]
     ProviderError(String),
+    #[error("No embedding model configured to embed with")]
+    MissingEmbeddingModel,
+    #[error("Input of about {tokens} tokens exceeds the model's limit of {limit} tokens")]
+    TokenLimitExceeded { tokens: usize, limit: usize },
+    #[error("Encoding error: {0}")]
+    EncodingError(String),
+}
+
+/// Options of how an [`Embedder`] ingests the documents of its loaders
+#[derive(Debug, Clone, Copy)]
+pub struct IngestOptions {
+    workers: usize,
+    deduplicate: bool,
+}
+
+impl Default for IngestOptions {
+    fn default() -> Self {
+        Self {
+            workers: 1,
+            deduplicate: false,
+        }
+    }
+}
+
+impl IngestOptions {
+    /// Embeds and stores up to `workers` documents of each loader concurrently, 1 by default.
+    /// Documents may end up stored in a different order than they were loaded.
+    #[must_use]
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Skips loaded documents with the same [`Document::dedup_key`] as a document already
+    /// stored under another id, disabled by default
+    #[must_use]
+    pub fn deduplicate(mut self, deduplicate: bool) -> Self {
+        self.deduplicate = deduplicate;
+        self
+    }
 }
 
 /// The `Embedder` listens to loaders, generates embeddings for incoming documents,
 /// and stores them in a vector store. It also provides functionality to query the vector store
+///
+/// Documents arriving with a pre-computed [`Document::embedding`] are stored as is,
+/// an embedder without an embedding model only accepts such documents.
+///
+/// Clones share the vector store, model and loader listeners of the original, so one
+/// embedder can back several clients without loading and embedding everything again.
+#[derive(Clone)]
 pub struct Embedder {
     /// A list of loaders to listen to for new documents.
     loaders: Vec<LoaderInstance>,
     /// Vector store for storing and querying embeddings.
     vector_store: Arc<Mutex<Box<dyn VectorStore>>>,
     /// An embedding model used to generate embeddings from raw data.
-    embedding_model: Arc<Box<dyn EmbeddingModel>>,
+    embedding_model: Option<Arc<Box<dyn EmbeddingModel>>>,
 }
 
 impl Embedder {
@@ -35,63 +85,216 @@ impl Embedder {
         loaders: Vec<LoaderInstance>,
         vector_store: Arc<Mutex<Box<dyn VectorStore>>>,
         embedding_model: Arc<Box<dyn EmbeddingModel>>,
+    ) -> Self {
+        Self::init_with_workers(loaders, vector_store, embedding_model, 1).await
+    }
+
+    /// Like [`Embedder::init`], but embeds and stores up to `workers` documents of each loader
+    /// concurrently. Documents may end up stored in a different order than they were loaded.
+    pub async fn init_with_workers(
+        loaders: Vec<LoaderInstance>,
+        vector_store: Arc<Mutex<Box<dyn VectorStore>>>,
+        embedding_model: Arc<Box<dyn EmbeddingModel>>,
+        workers: usize,
+    ) -> Self {
+        let options = IngestOptions::default().workers(workers);
+        Self::init_with_options(loaders, vector_store, embedding_model, options).await
+    }
+
+    /// Like [`Embedder::init`], but ingests the loaded documents as set by `options`
+    pub async fn init_with_options(
+        loaders: Vec<LoaderInstance>,
+        vector_store: Arc<Mutex<Box<dyn VectorStore>>>,
+        embedding_model: Arc<Box<dyn EmbeddingModel>>,
+        options: IngestOptions,
     ) -> Self {
         let embedder = Self {
             loaders,
             vector_store,
-            embedding_model,
+            embedding_model: Some(embedding_model),
         };
-        embedder.init_loaders_listeners().await;
+        embedder.init_loaders_listeners(options).await;
+        embedder
+    }
+
+    /// Initializes an `Embedder` without an embedding model, for documents with pre-computed
+    /// embeddings.
+    ///
+    /// Documents without an embedding are skipped, and text queries fail with
+    /// [`EmbedderError::MissingEmbeddingModel`], use [`Embedder::query_by_embedding`] instead.
+    pub async fn init_without_model(
+        loaders: Vec<LoaderInstance>,
+        vector_store: Arc<Mutex<Box<dyn VectorStore>>>,
+    ) -> Self {
+        let embedder = Self {
+            loaders,
+            vector_store,
+            embedding_model: None,
+        };
+        embedder
+            .init_loaders_listeners(IngestOptions::default())
+            .await;
         embedder
     }
 
     /// Initializes listeners for the loaders.
     ///
-    /// This method spawns asynchronous tasks to listen for new documents from the loaders,
-    /// generate embeddings, and store them in the vector store.
-    async fn init_loaders_listeners(&self) {
+    /// This method spawns an asynchronous task per loader to listen for new documents,
+    /// each document is then embedded and stored by one of up to `workers` concurrent tasks.
+    async fn init_loaders_listeners(&self, options: IngestOptions) {
+        let deduplicator = options
+            .deduplicate
+            .then(|| Arc::new(Deduplicator::default()));
         for loader in &self.loaders {
             info!("Initializing loader");
-            let embedding_model = Arc::clone(&self.embedding_model);
+            let embedding_model = self.embedding_model.clone();
             let vector_store = Arc::clone(&self.vector_store);
             let loader = Arc::clone(loader);
+            let workers = Arc::new(Semaphore::new(options.workers.max(1)));
+            let deduplicator = deduplicator.clone();
 
-            let mut listener = loader.subscribe().await;
+            let mut listener = loader.subscribe_stream().await;
             tokio::spawn(async move {
                 info!("Spawned a thread for loader");
-                while let Ok(doc) = listener.recv().await {
+                while let Some(doc) = listener.next().await {
                     info!("Recieved document :{}", &doc.id);
-                    let embedded_data = if doc.data.is_empty() {
-                        vec![]
-                    } else {
-                        embedding_model.embed(&doc.data).await.unwrap()
+                    let doc = match &deduplicator {
+                        Some(deduplicator) => match deduplicator.check(doc) {
+                            Ok(doc) => doc,
+                            Err(duplicate_of) => {
+                                info!("Skipping a duplicate of document {duplicate_of}");
+                                continue;
+                            }
+                        },
+                        None => doc,
                     };
-                    match vector_store
-                        .lock()
-                        .await
-                        .store(Embedding {
-                            id: doc.id.clone(),
-                            embedded_data,
-                            raw_data: doc.data,
-                        })
-                        .await
-                    {
-                        Ok(()) => {
-                            info!(
-                                "Added embedding for document {} to the vector store",
-                                &doc.id
-                            );
-                        }
-                        Err(e) => {
-                            error!(error = ?e, "Failed to store embedding for document {}", &doc.id);
-                            panic!("{e}");
-                        }
+                    let Ok(permit) = Arc::clone(&workers).acquire_owned().await else {
+                        break;
                     };
+                    let embedding_model = embedding_model.clone();
+                    let vector_store = Arc::clone(&vector_store);
+                    let deduplicator = deduplicator.clone();
+                    tokio::spawn(async move {
+                        let id = doc.id.clone();
+                        let stored = ingest_document(
+                            embedding_model.as_ref().map(|m| &***m),
+                            &vector_store,
+                            doc,
+                        )
+                        .await;
+                        if let (false, Some(deduplicator)) = (stored, deduplicator) {
+                            deduplicator.forget(&id);
+                        }
+                        drop(permit);
+                    });
                 }
             });
         }
     }
 
+    /// Re-embeds every document in the vector store with the current embedding model,
+    /// eg. after switching embedding models. Meant as a one-shot maintenance operation.
+    ///
+    /// # Returns
+    /// * - The number of re-embedded documents.
+    ///
+    /// # Errors
+    ///  returns `Err(seedframe::error::Error)` - If listing, fetching, embedding or storing fails,
+    ///  documents re-embedded before the failure keep their new embeddings.
+    pub async fn reindex(&self) -> Result<usize, crate::error::Error> {
+        let embedding_model = self.embedding_model()?;
+        let vector_store = self.vector_store.lock().await;
+        let ids = vector_store.list_ids().await?;
+        info!("Reindexing {} documents", ids.len());
+        for id in &ids {
+            let embedding = vector_store.get_by_id(id.clone()).await?;
+            let embedded_data = embed_checked(embedding_model, &embedding.raw_data).await?;
+            vector_store
+                .store(Embedding {
+                    embedded_data,
+                    ..embedding
+                })
+                .await?;
+        }
+        Ok(ids.len())
+    }
+
+    /// Embeds and stores `documents` as a batch, storing the documents that succeed even if
+    /// others fail. Like for loaded documents, pre-computed embeddings skip the model and
+    /// documents without data are removed from the store.
+    ///
+    /// # Returns
+    /// * - The result of every document, in the order of `documents`. Failures are logged too.
+    pub async fn store_documents(
+        &self,
+        documents: Vec<Document>,
+    ) -> BatchResult<(), crate::error::Error> {
+        let mut embedded: Vec<Option<Result<Vec<f64>, crate::error::Error>>> = documents
+            .iter()
+            .map(|doc| {
+                if doc.data.is_empty() {
+                    Some(Ok(vec![]))
+                } else {
+                    doc.embedding.clone().map(Ok)
+                }
+            })
+            .collect();
+        let pending: Vec<usize> = (0..documents.len())
+            .filter(|&i| embedded[i].is_none())
+            .collect();
+        if !pending.is_empty() {
+            match self.embedding_model() {
+                Ok(model) => {
+                    let data: Vec<&str> = pending
+                        .iter()
+                        .map(|&i| documents[i].data.as_str())
+                        .collect();
+                    let results = model.batch_embed(&data).await.into_results();
+                    for ((&i, result), data) in pending.iter().zip(results).zip(data) {
+                        embedded[i] = Some(
+                            result
+                                .and_then(|embedding| check_embedding(embedding, data))
+                                .map_err(Into::into),
+                        );
+                    }
+                }
+                Err(_) => {
+                    for &i in &pending {
+                        embedded[i] = Some(Err(EmbedderError::MissingEmbeddingModel.into()));
+                    }
+                }
+            }
+        }
+
+        let mut results: Vec<Result<(), crate::error::Error>> = Vec::with_capacity(documents.len());
+        let mut to_store = vec![];
+        let mut stored_indices = vec![];
+        for (i, (doc, embedding)) in documents.into_iter().zip(embedded).enumerate() {
+            match embedding.expect("every document is embedded or failed") {
+                Ok(embedded_data) => {
+                    stored_indices.push(i);
+                    to_store.push(Embedding {
+                        id: doc.id,
+                        embedded_data,
+                        raw_data: doc.data,
+                    });
+                    results.push(Ok(()));
+                }
+                Err(e) => results.push(Err(e)),
+            }
+        }
+        let store_results = self.vector_store.lock().await.batch_store(to_store).await;
+        for (i, result) in stored_indices.into_iter().zip(store_results.into_results()) {
+            results[i] = result.map_err(Into::into);
+        }
+
+        let results = BatchResult::from(results);
+        for (i, e) in results.failures() {
+            error!(error = ?e, "Failed to embed or store document {i} of the batch");
+        }
+        results
+    }
+
     /// Queries the vector store for documents similar to the provided query.
     ///
     /// # Arguments
@@ -108,7 +311,7 @@ impl Embedder {
         query: &str,
         top_n: usize,
     ) -> Result<Vec<Embedding>, crate::error::Error> {
-        let query = self.embedding_model.embed(query).await?;
+        let query = embed_checked(self.embedding_model()?, query).await?;
         self.vector_store
             .lock()
             .await
@@ -116,4 +319,522 @@ impl Embedder {
             .await
             .map_err(Into::into)
     }
+
+    /// Like [`Embedder::query`], but returns the similarity score of every embedding along
+    /// with it
+    ///
+    /// # Errors
+    /// Returns an error if embedding the query or querying the vector store fails
+    pub async fn query_scored(
+        &self,
+        query: &str,
+        top_n: usize,
+    ) -> Result<Vec<(Embedding, f64)>, crate::error::Error> {
+        let query = embed_checked(self.embedding_model()?, query).await?;
+        self.vector_store
+            .lock()
+            .await
+            .top_n_scored(&query, top_n)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Like [`Embedder::query`], but only returns the id and raw data of the matching
+    /// embeddings, sparing remote stores from sending back the vectors
+    ///
+    /// # Errors
+    /// Returns an error if embedding the query or querying the vector store fails
+    pub async fn query_text(
+        &self,
+        query: &str,
+        top_n: usize,
+    ) -> Result<Vec<(String, String)>, crate::error::Error> {
+        let query = embed_checked(self.embedding_model()?, query).await?;
+        self.vector_store
+            .lock()
+            .await
+            .top_n_text(&query, top_n)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Like [`Embedder::query`], but searches with an already computed `embedding`,
+    /// without going through the embedding model
+    ///
+    /// # Errors
+    /// Returns an error if querying the vector store fails
+    pub async fn query_by_embedding(
+        &self,
+        embedding: &[f64],
+        top_n: usize,
+    ) -> Result<Vec<Embedding>, crate::error::Error> {
+        self.vector_store
+            .lock()
+            .await
+            .top_n(embedding, top_n)
+            .await
+            .map_err(Into::into)
+    }
+
+    fn embedding_model(&self) -> Result<&dyn EmbeddingModel, EmbedderError> {
+        self.embedding_model
+            .as_deref()
+            .map(|m| &**m)
+            .ok_or(EmbedderError::MissingEmbeddingModel)
+    }
+}
+
+/// Tracks the dedup keys of the loaded documents, to skip documents duplicating another one
+#[derive(Default)]
+struct Deduplicator {
+    /// Ids of the documents by dedup key, and the dedup keys by id
+    keys: std::sync::Mutex<(HashMap<String, String>, HashMap<String, String>)>,
+}
+
+impl Deduplicator {
+    /// Records `doc`, or returns the id of the document it duplicates.
+    ///
+    /// A document duplicating another one after an update of its data becomes a deletion of
+    /// its previous data.
+    fn check(&self, mut doc: Document) -> Result<Document, String> {
+        let mut keys = self
+            .keys
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let (ids_by_key, keys_by_id) = &mut *keys;
+        let previous_key = keys_by_id.remove(&doc.id);
+        if let Some(previous_key) = &previous_key {
+            ids_by_key.remove(previous_key);
+        }
+        if doc.data.is_empty() {
+            return Ok(doc);
+        }
+        let key = doc.dedup_key();
+        if let Some(id) = ids_by_key.get(&key) {
+            if previous_key.is_none() {
+                return Err(id.clone());
+            }
+            info!(
+                "Document {} now duplicates document {id}, removing it",
+                doc.id
+            );
+            doc.data.clear();
+            doc.embedding = None;
+            return Ok(doc);
+        }
+        ids_by_key.insert(key.clone(), doc.id.clone());
+        keys_by_id.insert(doc.id.clone(), key);
+        Ok(doc)
+    }
+
+    /// Stops tracking the document `id`, eg. after failing to store it
+    fn forget(&self, id: &str) {
+        let mut keys = self
+            .keys
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let (ids_by_key, keys_by_id) = &mut *keys;
+        if let Some(key) = keys_by_id.remove(id) {
+            ids_by_key.remove(&key);
+        }
+    }
+}
+
+/// Embeds `doc` and stores it in `vector_store`, logging and skipping it if embedding fails.
+/// Returns whether the document got stored
+async fn ingest_document(
+    embedding_model: Option<&dyn EmbeddingModel>,
+    vector_store: &Mutex<Box<dyn VectorStore>>,
+    mut doc: Document,
+) -> bool {
+    let embedded_data = match embed_document(embedding_model, &mut doc).await {
+        Ok(embedded_data) => embedded_data,
+        Err(e) => {
+            error!(error = ?e, "Failed to embed document {}, skipping it", &doc.id);
+            return false;
+        }
+    };
+    match vector_store
+        .lock()
+        .await
+        .store(Embedding {
+            id: doc.id.clone(),
+            embedded_data,
+            raw_data: doc.data,
+        })
+        .await
+    {
+        Ok(()) => {
+            info!(
+                "Added embedding for document {} to the vector store",
+                &doc.id
+            );
+            true
+        }
+        Err(e) => {
+            error!(error = ?e, "Failed to store embedding for document {}", &doc.id);
+            panic!("{e}");
+        }
+    }
+}
+
+/// Embeds the data of `doc`, taking its pre-computed embedding if it has one.
+/// Documents without data are deletions and get an empty embedding
+async fn embed_document(
+    embedding_model: Option<&dyn EmbeddingModel>,
+    doc: &mut Document,
+) -> Result<Vec<f64>, EmbedderError> {
+    if doc.data.is_empty() {
+        return Ok(vec![]);
+    }
+    if let Some(embedding) = doc.embedding.take() {
+        return Ok(embedding);
+    }
+    embed_checked(
+        embedding_model.ok_or(EmbedderError::MissingEmbeddingModel)?,
+        &doc.data,
+    )
+    .await
+}
+
+/// Embeds `data`, erroring if a non-empty input yields an empty embedding
+async fn embed_checked(
+    embedding_model: &dyn EmbeddingModel,
+    data: &str,
+) -> Result<Vec<f64>, EmbedderError> {
+    check_embedding(embedding_model.embed(data).await?, data)
+}
+
+/// Errors if a non-empty input yielded an empty embedding
+fn check_embedding(embedding: Vec<f64>, data: &str) -> Result<Vec<f64>, EmbedderError> {
+    if embedding.is_empty() && !data.is_empty() {
+        return Err(EmbedderError::ParseError("empty embedding".to_string()));
+    }
+    Ok(embedding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        loader::{forward_stream, Loader},
+        testing::ChannelLoader,
+        vector_store::{InMemoryVectorStore, VectorStoreError},
+    };
+    use async_trait::async_trait;
+    use futures::stream::BoxStream;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::sync::broadcast;
+
+    struct EmptyEmbeddingModel;
+
+    #[async_trait]
+    impl EmbeddingModel for EmptyEmbeddingModel {
+        async fn embed(&self, _data: &str) -> Result<Vec<f64>, EmbedderError> {
+            Ok(vec![])
+        }
+    }
+
+    struct LengthEmbeddingModel;
+
+    #[async_trait]
+    impl EmbeddingModel for LengthEmbeddingModel {
+        #[allow(clippy::cast_precision_loss)]
+        async fn embed(&self, data: &str) -> Result<Vec<f64>, EmbedderError> {
+            Ok(vec![data.len() as f64, 1.0])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reindex_reembeds_stored_documents() {
+        let store = InMemoryVectorStore::new(None).await.unwrap();
+        for (id, raw_data) in [("a", "hello"), ("b", "hello world")] {
+            store
+                .store(Embedding {
+                    id: id.to_string(),
+                    embedded_data: vec![0.0, 0.0, 0.0],
+                    raw_data: raw_data.to_string(),
+                })
+                .await
+                .unwrap();
+        }
+        let vector_store: Arc<Mutex<Box<dyn VectorStore>>> = Arc::new(Mutex::new(Box::new(store)));
+        let embedder = Embedder::init(
+            vec![],
+            Arc::clone(&vector_store),
+            Arc::new(Box::new(LengthEmbeddingModel)),
+        )
+        .await;
+
+        assert_eq!(embedder.reindex().await.unwrap(), 2);
+        let store = vector_store.lock().await;
+        let a = store.get_by_id("a".to_string()).await.unwrap();
+        assert_eq!(a.embedded_data, vec![5.0, 1.0]);
+        assert_eq!(a.raw_data, "hello");
+        let b = store.get_by_id("b".to_string()).await.unwrap();
+        assert_eq!(b.embedded_data, vec![11.0, 1.0]);
+    }
+
+    #[tokio::test]
+    async fn test_precomputed_embeddings_skip_the_model() {
+        let vector_store: Arc<Mutex<Box<dyn VectorStore>>> = Arc::new(Mutex::new(Box::new(
+            InMemoryVectorStore::new(None).await.unwrap(),
+        )));
+        let loader = Arc::new(ChannelLoader::new(3));
+        let embedder =
+            Embedder::init_without_model(vec![loader.clone()], Arc::clone(&vector_store)).await;
+        for (id, data, embedding) in [
+            ("a", "hello", vec![1.0, 0.0]),
+            ("b", "hello world", vec![0.0, 1.0]),
+        ] {
+            let doc = Document::builder()
+                .id(id)
+                .data(data)
+                .embedding(embedding)
+                .build();
+            loader.send(doc);
+        }
+        loader.send(Document::new("c", "not embedded"));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let results = embedder.query_by_embedding(&[0.1, 0.9], 1).await.unwrap();
+        assert_eq!(
+            results,
+            vec![Embedding {
+                id: "b".to_string(),
+                embedded_data: vec![0.0, 1.0],
+                raw_data: "hello world".to_string(),
+            }]
+        );
+        assert_eq!(
+            vector_store.lock().await.get_by_id("c".to_string()).await,
+            Err(VectorStoreError::EmbeddingNotFound)
+        );
+        assert!(matches!(
+            embedder.query("hello", 1).await,
+            Err(crate::error::Error::Embedder(
+                EmbedderError::MissingEmbeddingModel
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_empty_embeddings_are_rejected() {
+        let model: Arc<Box<dyn EmbeddingModel>> = Arc::new(Box::new(EmptyEmbeddingModel));
+        assert!(matches!(
+            embed_checked(&**model, "hello world").await,
+            Err(EmbedderError::ParseError(e)) if e == "empty embedding"
+        ));
+        assert_eq!(
+            embed_checked(&**model, "").await.unwrap(),
+            Vec::<f64>::new()
+        );
+
+        let vector_store: Arc<Mutex<Box<dyn VectorStore>>> = Arc::new(Mutex::new(Box::new(
+            InMemoryVectorStore::new(None).await.unwrap(),
+        )));
+        let loader = Arc::new(ChannelLoader::new(1));
+        let embedder = Embedder::init(vec![loader.clone()], Arc::clone(&vector_store), model).await;
+        loader.send(Document::new("doc", "hello world"));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(
+            vector_store.lock().await.get_by_id("doc".to_string()).await,
+            Err(VectorStoreError::EmbeddingNotFound)
+        );
+        assert!(embedder.query("hello", 1).await.is_err());
+    }
+
+    #[derive(Clone, Default)]
+    struct SlowEmbeddingModel {
+        in_flight: Arc<AtomicUsize>,
+        max_in_flight: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl EmbeddingModel for SlowEmbeddingModel {
+        async fn embed(&self, _data: &str) -> Result<Vec<f64>, EmbedderError> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(vec![1.0, 0.0])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_workers_embed_documents_concurrently() {
+        let model = SlowEmbeddingModel::default();
+        let vector_store: Arc<Mutex<Box<dyn VectorStore>>> = Arc::new(Mutex::new(Box::new(
+            InMemoryVectorStore::new(None).await.unwrap(),
+        )));
+        let loader = Arc::new(ChannelLoader::new(8));
+        let _embedder = Embedder::init_with_workers(
+            vec![loader.clone()],
+            Arc::clone(&vector_store),
+            Arc::new(Box::new(model.clone())),
+            4,
+        )
+        .await;
+        for i in 0..8 {
+            loader.send(Document::new(format!("doc-{i}"), "hello world"));
+        }
+
+        // sequentially this would take 400ms
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(vector_store.lock().await.list_ids().await.unwrap().len(), 8);
+        let max_in_flight = model.max_in_flight.load(Ordering::SeqCst);
+        assert!(max_in_flight > 1 && max_in_flight <= 4, "{max_in_flight}");
+    }
+
+    #[tokio::test]
+    async fn test_dedup_skips_duplicate_content_under_other_ids() {
+        let vector_store: Arc<Mutex<Box<dyn VectorStore>>> = Arc::new(Mutex::new(Box::new(
+            InMemoryVectorStore::new(None).await.unwrap(),
+        )));
+        let loader = Arc::new(ChannelLoader::new(8));
+        let _embedder = Embedder::init_with_options(
+            vec![loader.clone()],
+            Arc::clone(&vector_store),
+            Arc::new(Box::new(LengthEmbeddingModel)),
+            IngestOptions::default().deduplicate(true),
+        )
+        .await;
+        loader.send(Document::new("https://example.com/a", "same page"));
+        loader.send(Document::new("https://example.com/a?ref=home", "same page"));
+        loader.send(Document::new("https://example.com/b", "other page"));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut ids = vector_store.lock().await.list_ids().await.unwrap();
+        ids.sort();
+        assert_eq!(ids, vec!["https://example.com/a", "https://example.com/b"]);
+
+        // once the original changes, its old content isn't a duplicate anymore
+        loader.send(Document::new("https://example.com/a", "new page"));
+        loader.send(Document::new("https://example.com/c", "same page"));
+        // and a document updated to duplicate another one gets removed
+        loader.send(Document::new("https://example.com/b", "new page"));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut ids = vector_store.lock().await.list_ids().await.unwrap();
+        ids.sort();
+        assert_eq!(ids, vec!["https://example.com/a", "https://example.com/c"]);
+    }
+
+    /// Loader streaming its documents without a broadcast channel
+    struct StreamLoader(Vec<Document>);
+
+    #[async_trait]
+    impl Loader for StreamLoader {
+        async fn subscribe_stream(&self) -> BoxStream<'static, Document> {
+            futures::stream::iter(self.0.clone()).boxed()
+        }
+
+        async fn subscribe(&self) -> broadcast::Receiver<Document> {
+            forward_stream(self.subscribe_stream().await)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_loaders_feed_the_embedder() {
+        let vector_store: Arc<Mutex<Box<dyn VectorStore>>> = Arc::new(Mutex::new(Box::new(
+            InMemoryVectorStore::new(None).await.unwrap(),
+        )));
+        let loader = Arc::new(StreamLoader(vec![
+            Document::new("a", "hello"),
+            Document::new("b", "hello world"),
+        ]));
+        let _embedder = Embedder::init(
+            vec![loader.clone()],
+            Arc::clone(&vector_store),
+            Arc::new(Box::new(LengthEmbeddingModel)),
+        )
+        .await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut ids = vector_store.lock().await.list_ids().await.unwrap();
+        ids.sort();
+        assert_eq!(ids, vec!["a", "b"]);
+
+        let mut receiver = loader.subscribe().await;
+        assert_eq!(receiver.recv().await.unwrap().id, "a");
+        assert_eq!(receiver.recv().await.unwrap().id, "b");
+    }
+
+    #[tokio::test]
+    async fn test_listener_keeps_consuming_after_lagging() {
+        let vector_store: Arc<Mutex<Box<dyn VectorStore>>> = Arc::new(Mutex::new(Box::new(
+            InMemoryVectorStore::new(None).await.unwrap(),
+        )));
+        let loader = Arc::new(ChannelLoader::new(2));
+        let _embedder = Embedder::init(
+            vec![loader.clone()],
+            Arc::clone(&vector_store),
+            Arc::new(Box::new(LengthEmbeddingModel)),
+        )
+        .await;
+        for i in 0..5 {
+            loader.send(Document::new(format!("doc-{i}"), "hello world"));
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        loader.send(Document::new("doc-5", "hello world"));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut ids = vector_store.lock().await.list_ids().await.unwrap();
+        ids.sort();
+        assert_eq!(ids, vec!["doc-3", "doc-4", "doc-5"]);
+    }
+
+    struct RejectingEmbeddingModel;
+
+    #[async_trait]
+    impl EmbeddingModel for RejectingEmbeddingModel {
+        async fn embed(&self, data: &str) -> Result<Vec<f64>, EmbedderError> {
+            if data == "bad" {
+                return Err(EmbedderError::ProviderError("rejected input".to_string()));
+            }
+            Ok(vec![1.0, 0.0])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_failures_dont_lose_the_other_documents() {
+        let vector_store: Arc<Mutex<Box<dyn VectorStore>>> = Arc::new(Mutex::new(Box::new(
+            InMemoryVectorStore::new(None).await.unwrap(),
+        )));
+        let embedder = Embedder::init(
+            vec![],
+            Arc::clone(&vector_store),
+            Arc::new(Box::new(RejectingEmbeddingModel)),
+        )
+        .await;
+
+        let results = embedder
+            .store_documents(vec![
+                Document::new("a", "good"),
+                Document::new("b", "bad"),
+                Document::new("c", "also good"),
+            ])
+            .await;
+        assert_eq!(results.len(), 3);
+        assert!(!results.is_success());
+        assert_eq!(
+            results.successes().map(|(i, ())| i).collect::<Vec<_>>(),
+            [0, 2]
+        );
+        let failures: Vec<_> = results.failures().collect();
+        assert_eq!(failures.len(), 1);
+        assert!(matches!(
+            failures[0],
+            (
+                1,
+                crate::error::Error::Embedder(EmbedderError::ProviderError(_))
+            )
+        ));
+
+        let mut ids = vector_store.lock().await.list_ids().await.unwrap();
+        ids.sort();
+        assert_eq!(ids, ["a", "c"]);
+    }
 }
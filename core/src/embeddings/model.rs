@@ -1,8 +1,132 @@
-use crate::embeddings::EmbedderError;
+use crate::embeddings::{BatchResult, EmbedderError};
 use async_trait::async_trait;
 
 #[allow(clippy::module_name_repetitions)]
 #[async_trait]
 pub trait EmbeddingModel: Send + Sync {
     async fn embed(&self, data: &str) -> Result<Vec<f64>, EmbedderError>;
+
+    /// Embeds every item of `data`, reporting the failures per item
+    ///
+    /// Default implementation embeds the items one by one, models with a batch endpoint
+    /// should override it.
+    async fn batch_embed(&self, data: &[&str]) -> BatchResult<Vec<f64>, EmbedderError> {
+        let mut results = Vec::with_capacity(data.len());
+        for item in data {
+            results.push(self.embed(item).await);
+        }
+        results.into()
+    }
+}
+
+/// Wraps an embedding model, L2-normalizing every embedding it produces.
+///
+/// Needed for dot-product search and stores configured for unit vectors, enabled for an
+/// `#[embedder]` with `"normalize": true` in its config.
+#[allow(clippy::module_name_repetitions)]
+pub struct NormalizedEmbeddingModel(Box<dyn EmbeddingModel>);
+
+impl NormalizedEmbeddingModel {
+    #[must_use]
+    pub fn new(model: Box<dyn EmbeddingModel>) -> Self {
+        Self(model)
+    }
+}
+
+#[async_trait]
+impl EmbeddingModel for NormalizedEmbeddingModel {
+    async fn embed(&self, data: &str) -> Result<Vec<f64>, EmbedderError> {
+        let mut embedding = self.0.embed(data).await?;
+        normalize(&mut embedding);
+        Ok(embedding)
+    }
+}
+
+/// Scales `vector` to unit length, zero vectors are left as is
+pub fn normalize(vector: &mut [f64]) {
+    let magnitude = vector.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if magnitude > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= magnitude);
+    }
+}
+
+/// Rough number of characters per token, for estimating token counts without a tokenizer
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Estimates the number of tokens in `data` at about 4 characters per token
+#[must_use]
+pub fn estimate_tokens(data: &str) -> usize {
+    data.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Checks `data` against a model's max input tokens before sending it, truncating it to
+/// the limit if `truncate` is set
+///
+/// # Errors
+/// Returns [`EmbedderError::TokenLimitExceeded`] if `data` is over the limit and `truncate`
+/// isn't set, so the caller can chunk it instead
+pub fn enforce_token_limit(
+    data: &str,
+    max_tokens: usize,
+    truncate: bool,
+) -> Result<&str, EmbedderError> {
+    let tokens = estimate_tokens(data);
+    if tokens <= max_tokens {
+        return Ok(data);
+    }
+    if !truncate {
+        return Err(EmbedderError::TokenLimitExceeded {
+            tokens,
+            limit: max_tokens,
+        });
+    }
+    let end = data
+        .char_indices()
+        .nth(max_tokens * CHARS_PER_TOKEN)
+        .map_or(data.len(), |(i, _)| i);
+    Ok(&data[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedEmbeddingModel(Vec<f64>);
+
+    #[async_trait]
+    impl EmbeddingModel for FixedEmbeddingModel {
+        async fn embed(&self, _data: &str) -> Result<Vec<f64>, EmbedderError> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_normalized_embeddings_have_unit_magnitude() {
+        let model =
+            NormalizedEmbeddingModel::new(Box::new(FixedEmbeddingModel(vec![3.0, 4.0, 12.0])));
+        let embedding = model.embed("hello").await.unwrap();
+
+        let magnitude = embedding.iter().map(|x| x * x).sum::<f64>().sqrt();
+        assert!((magnitude - 1.0).abs() < 1e-9);
+        assert!((embedding[0] - 3.0 / 13.0).abs() < 1e-9);
+
+        let model = NormalizedEmbeddingModel::new(Box::new(FixedEmbeddingModel(vec![0.0, 0.0])));
+        assert_eq!(model.embed("hello").await.unwrap(), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_token_limit_errors_or_truncates() {
+        let data = "abcdefghij";
+        assert_eq!(estimate_tokens(data), 3);
+        assert_eq!(enforce_token_limit(data, 3, false).unwrap(), data);
+        assert!(matches!(
+            enforce_token_limit(data, 2, false),
+            Err(EmbedderError::TokenLimitExceeded {
+                tokens: 3,
+                limit: 2
+            })
+        ));
+        assert_eq!(enforce_token_limit(data, 2, true).unwrap(), "abcdefgh");
+        assert_eq!(enforce_token_limit("ééééé", 1, true).unwrap(), "éééé");
+    }
 }
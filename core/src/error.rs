@@ -1,5 +1,7 @@
 use crate::{
-    completion::CompletionError, embeddings::EmbedderError, tools::ToolSetError,
+    completion::{CompletionError, ModerationError},
+    embeddings::EmbedderError,
+    tools::ToolSetError,
     vector_store::VectorStoreError,
 };
 use thiserror::Error;
@@ -14,4 +16,6 @@ pub enum Error {
     VectorStore(#[from] VectorStoreError),
     #[error(transparent)]
     Embedder(#[from] EmbedderError),
+    #[error(transparent)]
+    Moderation(#[from] ModerationError),
 }
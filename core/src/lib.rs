@@ -171,6 +171,21 @@
 //! }
 //! ```
 //!
+//! ### Building clients at runtime
+//!
+//! When the setup is only known at runtime, eg. read from a config file, a [`completion::ClientBuilder`]
+//! assembles the same client the `#[client]` macro would.
+//!
+//! ```rust,ignore
+//! use seedframe::{providers::completions::OpenAI, prelude::*};
+//!
+//! let mut client = ClientBuilder::new(OpenAI::new(Some(&model_config)))
+//!     .preamble("You're a helpful assistant")
+//!     .tool(Box::new(AgentTool::new(researcher, "researcher", "Researches a topic")))
+//!     .state(AppState { count: std::sync::Mutex::new(0u32) })
+//!     .build()?;
+//! ```
+//!
 //! ## Feature flags
 //!
 //! seedframe uses a set of [feature flags] to reduce the amount of compiled and
@@ -181,6 +196,9 @@
 //! Name | Description | Default?
 //! ---|---|---
 //! `pdf` | enables file loaders to parse PDFs | No
+//! `local-embeddings` | enables [`LocalEmbedding`](providers::embeddings::LocalEmbedding), embedding offline with a local model | No
+//! `bincode` | enables the compact binary encoding of [`Embedding`](embeddings::embedding::Embedding)s | No
+//! `test-util` | enables the [`testing`] helpers for testing custom components, including an offline embedding model | No
 
 /// Language model completion and conversation management
 ///
@@ -214,6 +232,10 @@ pub mod prelude;
 /// Builtin completion and embedding model providers
 pub mod providers;
 
+/// Helpers for testing custom loaders and other components
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
+
 /// Function calling and tool execution support
 pub mod tools;
 
@@ -4,8 +4,15 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::broadcast;
 use tracing::{debug, error, info, instrument};
 
-use super::{utils::load_initial, FileLoaderError};
-use crate::{document::Document, loader::Loader};
+use super::{
+    parser::{DocumentParser, ParserRegistry},
+    utils::{load_initial, FileIds},
+    FileLoaderError,
+};
+use crate::{
+    document::{Document, IdGenerator},
+    loader::Loader,
+};
 
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug)]
@@ -16,6 +23,8 @@ use crate::{document::Document, loader::Loader};
 pub struct FileOnceLoaderBuilder {
     glob_patterns: Vec<String>,
     evaluated: Vec<glob::Pattern>,
+    parsers: ParserRegistry,
+    ids: FileIds,
 }
 
 impl FileOnceLoaderBuilder {
@@ -38,9 +47,27 @@ impl FileOnceLoaderBuilder {
         Ok(Self {
             glob_patterns,
             evaluated,
+            parsers: ParserRegistry::default(),
+            ids: FileIds::default(),
         })
     }
 
+    /// Generates the ids of the loaded documents with `generator` instead of using their path,
+    /// each file keeps the first id generated for it
+    #[must_use]
+    pub fn id_generator(mut self, generator: impl IdGenerator + 'static) -> Self {
+        self.ids = FileIds::new(generator);
+        self
+    }
+
+    /// Registers `parser` for files with the extension `extension`, eg. `"docx"`, see
+    /// [`ParserRegistry`] for the built-in parsers
+    #[must_use]
+    pub fn parser(mut self, extension: &str, parser: impl DocumentParser + 'static) -> Self {
+        self.parsers.register(extension, parser);
+        self
+    }
+
     #[instrument(fields(self = format!("FileOnceLoaderBuilder {{glob_patterns: {:?}}}", self.glob_patterns)))]
     /// Constructs a `FileOnceLoader` instance.
     ///
@@ -51,7 +78,7 @@ impl FileOnceLoaderBuilder {
     /// * `Ok(FileOnceLoader)` - A new `FileOnceLoader` instance.
     /// * `Err(FileLoaderError)` - An error if build fails.
     pub fn build(self) -> Result<FileOnceLoader, FileLoaderError> {
-        let documents = load_initial(&self.evaluated);
+        let documents = load_initial(&self.evaluated, &self.parsers, &self.ids);
         if documents.is_empty() {
             error!("No documents matched the provided glob patterns");
             Err(FileLoaderError::NoMatchingDocuments)?;
@@ -74,7 +101,7 @@ impl FileOnceLoaderBuilder {
 /// A loader that reads documents from files and sends them to subscribers
 /// via a broadcast channel.
 ///
-/// Currently can parse PDF files, and treats all other formats as plain text.
+/// Files are parsed by the parser registered for their extension, see [`ParserRegistry`].
 pub struct FileOnceLoader {
     tx: broadcast::Sender<Document>,
     documents: Vec<Document>,
@@ -122,11 +149,29 @@ impl Loader for FileOnceLoader {
         }
         receiver
     }
+
+    #[instrument(fields(self = format!("FileOnceLoader {{sent: {}}}", self.sent.load(Ordering::Acquire))))]
+    /// Subscribes to a dedicated channel that receives all of the loaded documents,
+    /// regardless of whether they've already been sent to other subscribers.
+    ///
+    /// # Returns
+    /// A `tokio::sync::broadcast::Receiver<Document>`.
+    async fn subscribe_replay(&self) -> broadcast::Receiver<Document> {
+        let (tx, receiver) = broadcast::channel(self.documents.len());
+        for doc in &self.documents {
+            if let Err(e) = tx.send(doc.clone()) {
+                error!("Loader failed to replay document: {} to subscriber", e.0.id);
+            }
+        }
+        info!("Loader replayed {} documents", self.documents.len());
+        receiver
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::collect_documents;
     use tempfile::tempdir;
     use tokio::time::{timeout, Duration};
 
@@ -176,12 +221,7 @@ mod tests {
             .collect();
 
         let loader = FileOnceLoaderBuilder::new(paths).unwrap().build().unwrap();
-        let mut receiver = loader.subscribe().await;
-
-        let mut received = Vec::new();
-        while let Ok(doc) = timeout(Duration::from_millis(100), receiver.recv()).await {
-            received.push(doc.unwrap());
-        }
+        let received = collect_documents(&loader, Duration::from_millis(100)).await;
 
         assert_eq!(received.len(), 2);
     }
@@ -197,15 +237,89 @@ mod tests {
             .build()
             .unwrap();
 
+        let received = collect_documents(&loader, Duration::from_millis(100)).await;
+
+        assert_eq!(received.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_replay_sends_to_late_subscribers() {
+        let dir = tempdir().unwrap();
+        create_test_files(dir.path(), &["t1.txt", "t2.txt"]).await;
+
+        let glob_path = dir.path().join("*.txt").to_str().unwrap().to_string();
+        let loader = FileOnceLoaderBuilder::new(vec![glob_path])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        for _ in 0..2 {
+            let mut receiver = loader.subscribe_replay().await;
+            let mut received = Vec::new();
+            while let Ok(Ok(doc)) = timeout(Duration::from_millis(100), receiver.recv()).await {
+                received.push(doc);
+            }
+            assert_eq!(received.len(), 2);
+        }
+
         let mut receiver = loader.subscribe().await;
         let mut received = Vec::new();
         while let Ok(doc) = timeout(Duration::from_millis(100), receiver.recv()).await {
             received.push(doc.unwrap());
         }
-
         assert_eq!(received.len(), 2);
     }
 
+    #[tokio::test]
+    async fn test_custom_parser_is_used_for_its_extension() {
+        let dir = tempdir().unwrap();
+        create_test_files(dir.path(), &["doc.fake"]).await;
+
+        let glob_path = dir.path().join("*.fake").to_str().unwrap().to_string();
+        let loader = FileOnceLoaderBuilder::new(vec![glob_path])
+            .unwrap()
+            .parser("fake", |path: &std::path::Path| {
+                Ok(std::fs::read_to_string(path)?.to_uppercase())
+            })
+            .build()
+            .unwrap();
+
+        let mut receiver = loader.subscribe().await;
+        let doc = timeout(Duration::from_millis(100), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(doc.data, "TEST CONTENT");
+    }
+
+    #[tokio::test]
+    async fn test_id_generator_sets_the_document_ids() {
+        struct SequentialIds(std::sync::atomic::AtomicUsize);
+        impl IdGenerator for SequentialIds {
+            fn generate(&self, _source: &str) -> String {
+                format!("doc-{}", self.0.fetch_add(1, Ordering::SeqCst))
+            }
+        }
+
+        let dir = tempdir().unwrap();
+        create_test_files(dir.path(), &["t1.txt", "t2.txt"]).await;
+
+        let glob_path = dir.path().join("*.txt").to_str().unwrap().to_string();
+        let loader = FileOnceLoaderBuilder::new(vec![glob_path])
+            .unwrap()
+            .id_generator(SequentialIds(0.into()))
+            .build()
+            .unwrap();
+
+        let mut ids: Vec<String> = collect_documents(&loader, Duration::from_millis(100))
+            .await
+            .into_iter()
+            .map(|doc| doc.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["doc-0", "doc-1"]);
+    }
+
     #[tokio::test]
     async fn test_no_matching_files() {
         let dir = tempdir().unwrap();
@@ -0,0 +1,367 @@
+use async_trait::async_trait;
+use glob::Pattern;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+use tracing::{debug, error, info, instrument};
+
+use crate::{
+    document::{Document, IdGenerator},
+    loader::{
+        builtins::file_loaders::utils::{parse_file, resolve_input_to_files, FileIds},
+        BoxStream, DeliveryMode, DocumentSender, Loader,
+    },
+};
+
+use super::{
+    parser::{DocumentParser, ParserRegistry},
+    FileLoaderError,
+};
+
+const DEFAULT_CHANNEL_CAPACITY: usize = 20;
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 5;
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug)]
+/// A builder for constructing a `FilePollingLoader`.
+///
+/// it takes a list of glob patterns and the interval at which they get re-evaluated.
+pub struct FilePollingLoaderBuilder {
+    glob_patterns: Vec<String>,
+    interval: Duration,
+    parsers: ParserRegistry,
+    ids: FileIds,
+    delivery: DeliveryMode,
+}
+
+impl FilePollingLoaderBuilder {
+    #[instrument]
+    /// Creates a new `FilePollingLoaderBuilder` instance.
+    ///
+    /// # Arguments
+    /// * `glob_patterns` - A vector of glob pattern strings to be loaded.
+    ///
+    /// # Returns
+    /// * `Ok(Self)` - A new `FilePollingLoaderBuilder` instance.
+    /// * `Err(FileLoaderError)` - An error if initialization fails.
+    pub fn new(glob_patterns: Vec<String>) -> Result<Self, FileLoaderError> {
+        let evaluated: Vec<Pattern> = glob_patterns
+            .iter()
+            .map(|p| Pattern::new(p))
+            .collect::<Result<_, _>>()?;
+        info!("Successfully evaluated {} glob patterns", evaluated.len());
+
+        Ok(Self {
+            glob_patterns,
+            interval: Duration::from_secs(DEFAULT_POLL_INTERVAL_SECS),
+            parsers: ParserRegistry::default(),
+            ids: FileIds::default(),
+            delivery: DeliveryMode::default(),
+        })
+    }
+
+    /// Sets the interval between polls, 5 seconds by default
+    #[must_use]
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Generates the ids of the loaded documents with `generator` instead of using their path,
+    /// each file keeps the first id generated for it
+    #[must_use]
+    pub fn id_generator(mut self, generator: impl IdGenerator + 'static) -> Self {
+        self.ids = FileIds::new(generator);
+        self
+    }
+
+    /// Sets how documents are delivered to subscribers falling behind, see [`DeliveryMode`]
+    #[must_use]
+    pub fn delivery_mode(mut self, mode: DeliveryMode) -> Self {
+        self.delivery = mode;
+        self
+    }
+
+    /// Registers `parser` for files with the extension `extension`, eg. `"docx"`, see
+    /// [`ParserRegistry`] for the built-in parsers
+    #[must_use]
+    pub fn parser(mut self, extension: &str, parser: impl DocumentParser + 'static) -> Self {
+        self.parsers.register(extension, parser);
+        self
+    }
+
+    #[instrument]
+    /// Constructs a `FilePollingLoader` instance.
+    ///
+    /// # Returns
+    /// * `Ok(FilePollingLoader)` - A new `FilePollingLoader` instance.
+    /// * `Err(FileLoaderError)` - An error if build fails.
+    pub fn build(self) -> Result<FilePollingLoader, FileLoaderError> {
+        let tx = DocumentSender::new(DEFAULT_CHANNEL_CAPACITY, self.delivery);
+        debug!(
+            "broadcast channel with capacity: {} created",
+            DEFAULT_CHANNEL_CAPACITY
+        );
+
+        Ok(FilePollingLoader {
+            tx,
+            sent: AtomicBool::new(false),
+            glob_patterns: self.glob_patterns,
+            interval: self.interval,
+            parsers: self.parsers,
+            ids: self.ids,
+        })
+    }
+}
+
+#[derive(Debug)]
+/// Polls files matching glob patterns and emits document updates
+///
+/// An alternative to [`super::file_updating_loader::FileUpdatingLoader`] for filesystems
+/// where change notifications aren't reliable, eg network filesystems.
+/// When subscribed:
+/// 1. Immediately sends all matching documents
+/// 2. Re-evaluates the glob patterns every `interval`
+/// 3. Sends documents for new and changed files
+///
+/// Files are considered changed when their modification time or size changes and their
+/// content differs from the last sent version. Deleted files are sent with empty content.
+pub struct FilePollingLoader {
+    tx: DocumentSender,
+    sent: AtomicBool,
+    glob_patterns: Vec<String>,
+    interval: Duration,
+    parsers: ParserRegistry,
+    ids: FileIds,
+}
+
+impl FilePollingLoader {
+    /// Here for making use by the `proc_macro` lib more convinient, just calls
+    /// `FilePollingLoaderBuilder::new()`
+    #[allow(clippy::new_ret_no_self)]
+    #[allow(clippy::missing_errors_doc)]
+    pub fn new(glob_patterns: Vec<String>) -> Result<FilePollingLoaderBuilder, FileLoaderError> {
+        FilePollingLoaderBuilder::new(glob_patterns)
+    }
+
+    /// Starts polling the files on the first subscription
+    fn start(&self) {
+        if !self.sent.load(Ordering::Acquire)
+            && self
+                .sent
+                .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+        {
+            let txc = self.tx.clone();
+            let patterns = self.glob_patterns.clone();
+            let parsers = self.parsers.clone();
+            let ids = self.ids.clone();
+            let mut interval = tokio::time::interval(self.interval);
+            tokio::spawn(async move {
+                let mut seen: HashMap<PathBuf, FileState> = HashMap::new();
+                loop {
+                    interval.tick().await;
+                    for doc in poll(&patterns, &parsers, &ids, &mut seen) {
+                        if let Err(e) = txc.send(doc).await {
+                            error!("Loader failed to send document: {} to subscribers", e.0.id);
+                        }
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[async_trait]
+impl Loader for FilePollingLoader {
+    #[instrument(fields(self = format!("FilePollingLoader {{sent: {}}}", self.sent.load(Ordering::Acquire))))]
+    /// Subscribes to the loader's broadcast channel to receive documents.
+    ///
+    /// # Returns
+    /// A `tokio::sync::broadcast::Receiver<Document>`.
+    async fn subscribe(&self) -> broadcast::Receiver<Document> {
+        let receiver = self.tx.subscribe();
+        self.start();
+        receiver
+    }
+
+    /// Subscribes to the loader as a stream of documents, waiting for the stream to make
+    /// room for every document in [`DeliveryMode::Backpressure`]
+    async fn subscribe_stream(&self) -> BoxStream<'static, Document> {
+        let stream = self.tx.subscribe_stream();
+        self.start();
+        stream
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct FileState {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+/// Re-evaluates the glob patterns, returning documents for new, changed and deleted files
+#[instrument(skip(seen))]
+fn poll(
+    patterns: &[String],
+    parsers: &ParserRegistry,
+    ids: &FileIds,
+    seen: &mut HashMap<PathBuf, FileState>,
+) -> Vec<Document> {
+    let files = match resolve_input_to_files(patterns.iter().map(String::as_str).collect()) {
+        Ok(files) => files,
+        Err(e) => {
+            error!("Failed to resolve glob patterns: {e}");
+            return vec![];
+        }
+    };
+
+    let mut documents = vec![];
+    for file in &files {
+        let Ok(metadata) = std::fs::metadata(file) else {
+            continue;
+        };
+        let (modified, len) = (metadata.modified().ok(), metadata.len());
+        if seen
+            .get(file)
+            .is_some_and(|s| s.modified == modified && s.len == len)
+        {
+            continue;
+        }
+        let data = match parse_file(file, parsers) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("Failed to parse file {:?}: {e}", file);
+                continue;
+            }
+        };
+        let hash = content_hash(&data);
+        let unchanged = seen.get(file).is_some_and(|s| s.hash == hash);
+        seen.insert(
+            file.clone(),
+            FileState {
+                modified,
+                len,
+                hash,
+            },
+        );
+        if !unchanged {
+            debug!("Detected new or changed file {:?}", file);
+            documents.push(Document::new(ids.id(&file.to_string_lossy()), data));
+        }
+    }
+
+    let deleted: Vec<PathBuf> = seen
+        .keys()
+        .filter(|p| !files.contains(p))
+        .cloned()
+        .collect();
+    for file in deleted {
+        debug!("Detected deleted file {:?}", file);
+        seen.remove(&file);
+        documents.push(Document::new(
+            ids.id(&file.to_string_lossy()),
+            String::new(),
+        ));
+    }
+    documents
+}
+
+fn content_hash(data: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use tokio::time::timeout;
+
+    fn polling_loader(dir: &std::path::Path) -> FilePollingLoader {
+        FilePollingLoaderBuilder::new(vec![dir.join("*.txt").to_str().unwrap().to_string()])
+            .unwrap()
+            .interval(Duration::from_millis(50))
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_backpressure_delivers_every_document_to_slow_subscribers() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_count = DEFAULT_CHANNEL_CAPACITY * 2;
+        for i in 0..file_count {
+            std::fs::write(temp_dir.path().join(format!("{i}.txt")), "content").unwrap();
+        }
+        let pattern = temp_dir.path().join("*.txt").to_str().unwrap().to_string();
+        let loader = FilePollingLoaderBuilder::new(vec![pattern])
+            .unwrap()
+            .delivery_mode(DeliveryMode::Backpressure)
+            .build()
+            .unwrap();
+
+        let mut stream = loader.subscribe_stream().await;
+        let mut ids = std::collections::HashSet::new();
+        while ids.len() < file_count {
+            let doc = timeout(Duration::from_secs(1), stream.next())
+                .await
+                .expect("Documents were dropped")
+                .expect("Stream ended early");
+            ids.insert(doc.id);
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_poll_emits_modified_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        std::fs::write(&file_path, "initial").unwrap();
+
+        let loader = polling_loader(temp_dir.path());
+        let mut receiver = loader.subscribe().await;
+
+        let doc = receiver.recv().await.unwrap();
+        assert_eq!(doc.id, file_path.to_str().unwrap());
+        assert_eq!(doc.data, "initial");
+
+        std::fs::write(&file_path, "modified content").unwrap();
+        let doc = timeout(Duration::from_secs(1), receiver.recv())
+            .await
+            .expect("Didn't receive modified document")
+            .unwrap();
+        assert_eq!(doc.id, file_path.to_str().unwrap());
+        assert_eq!(doc.data, "modified content");
+    }
+
+    #[test]
+    fn test_poll_detects_new_unchanged_and_deleted_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let patterns = vec![temp_dir.path().join("*.txt").to_str().unwrap().to_string()];
+        let mut seen = HashMap::new();
+        let ids = FileIds::default();
+        std::fs::write(temp_dir.path().join("a.txt"), "a").unwrap();
+
+        assert_eq!(
+            poll(&patterns, &ParserRegistry::default(), &ids, &mut seen).len(),
+            1
+        );
+        assert!(poll(&patterns, &ParserRegistry::default(), &ids, &mut seen).is_empty());
+
+        std::fs::write(temp_dir.path().join("b.txt"), "b").unwrap();
+        let docs = poll(&patterns, &ParserRegistry::default(), &ids, &mut seen);
+        assert_eq!(docs.len(), 1);
+        assert_eq!(docs[0].data, "b");
+
+        std::fs::remove_file(temp_dir.path().join("a.txt")).unwrap();
+        let docs = poll(&patterns, &ParserRegistry::default(), &ids, &mut seen);
+        assert_eq!(docs.len(), 1);
+        assert!(docs[0].data.is_empty());
+    }
+}
@@ -11,16 +11,20 @@ use tokio::sync::broadcast;
 use tracing::{debug, error, info, instrument};
 
 use crate::{
-    document::Document,
+    document::{Document, IdGenerator},
     loader::{
         builtins::file_loaders::utils::{
             extract_parent_dir, get_dirs_to_watch, parse_file, resolve_input_to_files,
         },
-        Loader,
+        BoxStream, DeliveryMode, DocumentSender, Loader,
     },
 };
 
-use super::{utils::load_initial, FileLoaderError};
+use super::{
+    parser::{DocumentParser, ParserRegistry},
+    utils::{load_initial, FileIds},
+    FileLoaderError,
+};
 
 const DEFAULT_CHANNEL_CAPACITY: usize = 20;
 const DEBOUNCE_DURATION_MILLIS: u64 = 500;
@@ -34,6 +38,9 @@ const DEBOUNCE_DURATION_MILLIS: u64 = 500;
 pub struct FileUpdatingLoaderBuilder {
     glob_patterns: Vec<String>,
     evaluated_patterns: Vec<Pattern>,
+    parsers: ParserRegistry,
+    ids: FileIds,
+    delivery: DeliveryMode,
 }
 
 impl FileUpdatingLoaderBuilder {
@@ -59,9 +66,35 @@ impl FileUpdatingLoaderBuilder {
         Ok(Self {
             glob_patterns,
             evaluated_patterns,
+            parsers: ParserRegistry::default(),
+            ids: FileIds::default(),
+            delivery: DeliveryMode::default(),
         })
     }
 
+    /// Generates the ids of the loaded documents with `generator` instead of using their path,
+    /// each file keeps the first id generated for it
+    #[must_use]
+    pub fn id_generator(mut self, generator: impl IdGenerator + 'static) -> Self {
+        self.ids = FileIds::new(generator);
+        self
+    }
+
+    /// Sets how documents are delivered to subscribers falling behind, see [`DeliveryMode`]
+    #[must_use]
+    pub fn delivery_mode(mut self, mode: DeliveryMode) -> Self {
+        self.delivery = mode;
+        self
+    }
+
+    /// Registers `parser` for files with the extension `extension`, eg. `"docx"`, see
+    /// [`ParserRegistry`] for the built-in parsers
+    #[must_use]
+    pub fn parser(mut self, extension: &str, parser: impl DocumentParser + 'static) -> Self {
+        self.parsers.register(extension, parser);
+        self
+    }
+
     #[instrument]
     /// Constructs a `FileUpdatingLoader` instance.
     ///
@@ -83,13 +116,15 @@ impl FileUpdatingLoaderBuilder {
         } else {
             files.len()
         };
-        let (tx, _rx) = broadcast::channel(capacity);
+        let tx = DocumentSender::new(capacity, self.delivery);
         debug!("broadcast channel with capacity: {} created", capacity);
 
         FileUpdatingLoader {
             patterns: self.evaluated_patterns,
             tx,
             sent: AtomicBool::new(false),
+            parsers: self.parsers,
+            ids: self.ids,
         }
     }
 }
@@ -105,31 +140,27 @@ impl FileUpdatingLoaderBuilder {
 /// Deleted files are sent with empty content. Multiple subscribers are supported
 /// via broadcast channel.
 pub struct FileUpdatingLoader {
-    tx: broadcast::Sender<Document>,
+    tx: DocumentSender,
     sent: AtomicBool,
     patterns: Vec<Pattern>,
+    parsers: ParserRegistry,
+    ids: FileIds,
 }
 
-#[async_trait]
-impl Loader for FileUpdatingLoader {
-    #[instrument(fields(self = format!("FileUpdatingLoader {{sent: {}}}", self.sent.load(Ordering::Acquire))))]
-    /// Subscribes to the loader's broadcast channel to receive documents.
-    ///
-    /// # Returns
-    /// A `tokio::sync::broadcast::Receiver<Document>`.
-    async fn subscribe(&self) -> broadcast::Receiver<Document> {
-        let receiver = self.tx.subscribe();
+impl FileUpdatingLoader {
+    /// Sends the matching documents and starts watching the files on the first subscription
+    fn start(&self) {
         if !self.sent.load(Ordering::Acquire)
             && self
                 .sent
                 .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
                 .is_ok()
         {
-            let initial_docs = load_initial(&self.patterns);
+            let initial_docs = load_initial(&self.patterns, &self.parsers, &self.ids);
             let mut sent_docs_count = 0;
             let total_docs_count = initial_docs.len();
             for doc in initial_docs {
-                if let Err(e) = self.tx.send(doc) {
+                if let Err(e) = self.tx.send_now(doc) {
                     error!("Loader failed to send document: {} to subscribers", e.0.id);
                 } else {
                     sent_docs_count += 1;
@@ -151,9 +182,18 @@ impl Loader for FileUpdatingLoader {
 
             let txc = self.tx.clone();
             let pc = self.patterns.clone();
+            let parsers = self.parsers.clone();
+            let ids = self.ids.clone();
             tokio::spawn(async move {
-                let (evt_tx, evt_rx) = std::sync::mpsc::channel::<notify::Result<notify::Event>>();
-                let mut watcher = RecommendedWatcher::new(evt_tx, Config::default()).unwrap();
+                let (evt_tx, mut evt_rx) =
+                    tokio::sync::mpsc::unbounded_channel::<notify::Result<notify::Event>>();
+                let mut watcher = RecommendedWatcher::new(
+                    move |event| {
+                        let _ = evt_tx.send(event);
+                    },
+                    Config::default(),
+                )
+                .unwrap();
 
                 for path in &to_be_watched.clone() {
                     if let Err(e) = watcher.watch(path, RecursiveMode::Recursive) {
@@ -175,22 +215,46 @@ impl Loader for FileUpdatingLoader {
                 loop {
                     let now = Instant::now();
                     if now.duration_since(last_event_time) >= debounce_duration {
-                        for event in &evt_rx {
+                        while let Some(event) = evt_rx.recv().await {
                             let event = event.unwrap();
                             let out = process_event(&event, &pc);
                             if out.is_none() {
                                 continue;
                             }
                             let out = out.unwrap();
-                            txc.send(document_for_event(out.0.as_str(), out.1)).unwrap();
+                            let doc = document_for_event(out.0.as_str(), out.1, &parsers, &ids);
+                            if let Err(e) = txc.send(doc).await {
+                                error!("Loader failed to send document: {} to subscribers", e.0.id);
+                            }
                             last_event_time = now;
                         }
                     }
                 }
             });
         }
+    }
+}
+
+#[async_trait]
+impl Loader for FileUpdatingLoader {
+    #[instrument(fields(self = format!("FileUpdatingLoader {{sent: {}}}", self.sent.load(Ordering::Acquire))))]
+    /// Subscribes to the loader's broadcast channel to receive documents.
+    ///
+    /// # Returns
+    /// A `tokio::sync::broadcast::Receiver<Document>`.
+    async fn subscribe(&self) -> broadcast::Receiver<Document> {
+        let receiver = self.tx.subscribe();
+        self.start();
         receiver
     }
+
+    /// Subscribes to the loader as a stream of documents, waiting for the stream to make
+    /// room for every document in [`DeliveryMode::Backpressure`]
+    async fn subscribe_stream(&self) -> BoxStream<'static, Document> {
+        let stream = self.tx.subscribe_stream();
+        self.start();
+        stream
+    }
 }
 
 #[derive(Debug)]
@@ -200,18 +264,20 @@ enum EventType {
     Delete,
 }
 
-#[instrument]
-fn document_for_event(path: &str, et: EventType) -> Document {
+#[instrument(skip(parsers, ids))]
+fn document_for_event(
+    path: &str,
+    et: EventType,
+    parsers: &ParserRegistry,
+    ids: &FileIds,
+) -> Document {
     let file = std::path::Path::new(&path);
     let data = match et {
-        EventType::Modify | EventType::Create => parse_file(file).unwrap(),
+        EventType::Modify | EventType::Create => parse_file(file, parsers).unwrap(),
         EventType::Delete => String::new(),
     };
     debug!("Created document for {} with event type {:?}", path, et);
-    Document {
-        id: path.to_string(),
-        data,
-    }
+    Document::new(ids.id(path), data)
 }
 
 #[instrument]
@@ -275,14 +341,24 @@ mod tests {
         let file_path = temp_dir.path().join("test.txt");
         std::fs::write(&file_path, "test content").unwrap();
 
-        let doc = document_for_event(file_path.to_str().unwrap(), EventType::Create);
+        let doc = document_for_event(
+            file_path.to_str().unwrap(),
+            EventType::Create,
+            &ParserRegistry::default(),
+            &FileIds::default(),
+        );
         assert_eq!(doc.id, file_path.to_str().unwrap());
         assert_eq!(doc.data, "test content");
     }
 
     #[test]
     fn test_document_for_event_delete() {
-        let doc = document_for_event("test.txt", EventType::Delete);
+        let doc = document_for_event(
+            "test.txt",
+            EventType::Delete,
+            &ParserRegistry::default(),
+            &FileIds::default(),
+        );
         assert_eq!(doc.data, "");
     }
 
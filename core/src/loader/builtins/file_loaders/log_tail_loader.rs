@@ -0,0 +1,375 @@
+use async_trait::async_trait;
+use std::fs::{File, Metadata};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{debug, error, info, instrument};
+
+use crate::{
+    document::{Document, IdGenerator},
+    loader::{BoxStream, DeliveryMode, DocumentSender, Loader},
+};
+
+use super::FileLoaderError;
+
+const DEFAULT_CHANNEL_CAPACITY: usize = 20;
+const DEFAULT_POLL_INTERVAL_MILLIS: u64 = 1000;
+
+#[allow(clippy::module_name_repetitions)]
+/// A builder for constructing a `LogTailLoader`.
+///
+/// it takes the paths of the files to tail, the files don't have to exist yet.
+pub struct LogTailLoaderBuilder {
+    paths: Vec<PathBuf>,
+    interval: Duration,
+    batch_lines: usize,
+    ids: Option<Arc<dyn IdGenerator>>,
+    delivery: DeliveryMode,
+}
+
+impl std::fmt::Debug for LogTailLoaderBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogTailLoaderBuilder")
+            .field("paths", &self.paths)
+            .field("interval", &self.interval)
+            .field("batch_lines", &self.batch_lines)
+            .field("delivery", &self.delivery)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LogTailLoaderBuilder {
+    #[instrument]
+    /// Creates a new `LogTailLoaderBuilder` instance.
+    ///
+    /// # Arguments
+    /// * `paths` - The paths of the files to tail.
+    ///
+    /// # Returns
+    /// * `Ok(Self)` - A new `LogTailLoaderBuilder` instance.
+    /// * `Err(FileLoaderError)` - An error if no path was given.
+    pub fn new(paths: Vec<String>) -> Result<Self, FileLoaderError> {
+        if paths.is_empty() {
+            return Err(FileLoaderError::NoMatchingDocuments);
+        }
+        info!("Tailing {} files", paths.len());
+
+        Ok(Self {
+            paths: paths.into_iter().map(PathBuf::from).collect(),
+            interval: Duration::from_millis(DEFAULT_POLL_INTERVAL_MILLIS),
+            batch_lines: 1,
+            ids: None,
+            delivery: DeliveryMode::default(),
+        })
+    }
+
+    /// Sets the interval between checks for appended lines, 1 second by default
+    #[must_use]
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Groups up to `lines` appended lines in every document instead of sending one document
+    /// per line, the lines appended since the last check are never held back for a full batch
+    #[must_use]
+    pub fn batch_lines(mut self, lines: usize) -> Self {
+        self.batch_lines = lines.max(1);
+        self
+    }
+
+    /// Generates the ids of the documents with `generator`, called with the path of the file
+    /// for every document, instead of using the path and the number of the first line
+    #[must_use]
+    pub fn id_generator(mut self, generator: impl IdGenerator + 'static) -> Self {
+        self.ids = Some(Arc::new(generator));
+        self
+    }
+
+    /// Sets how documents are delivered to subscribers falling behind, see [`DeliveryMode`]
+    #[must_use]
+    pub fn delivery_mode(mut self, mode: DeliveryMode) -> Self {
+        self.delivery = mode;
+        self
+    }
+
+    #[instrument]
+    /// Constructs a `LogTailLoader` instance.
+    ///
+    /// # Returns
+    /// * `Ok(LogTailLoader)` - A new `LogTailLoader` instance.
+    /// * `Err(FileLoaderError)` - An error if build fails.
+    pub fn build(self) -> Result<LogTailLoader, FileLoaderError> {
+        let tx = DocumentSender::new(DEFAULT_CHANNEL_CAPACITY, self.delivery);
+        debug!(
+            "broadcast channel with capacity: {} created",
+            DEFAULT_CHANNEL_CAPACITY
+        );
+
+        Ok(LogTailLoader {
+            tx,
+            sent: AtomicBool::new(false),
+            paths: self.paths,
+            interval: self.interval,
+            batch_lines: self.batch_lines,
+            ids: self.ids,
+        })
+    }
+}
+
+/// Tails files, emitting documents for the lines appended to them, like `tail -f`
+///
+/// When subscribed:
+/// 1. Skips the current content of the files
+/// 2. Checks the files for appended lines every `interval`
+/// 3. Sends a document per appended line, or per batch of lines, see
+///    [`LogTailLoaderBuilder::batch_lines`]
+///
+/// A line is only sent once its terminating newline is written, and empty lines are skipped.
+/// Rotated logs are followed, a file that got truncated or recreated is read from its start.
+/// Document ids are the path of the file and the number of their first line, counted from
+/// when the tailing started, eg. `logs/app.log:42`.
+pub struct LogTailLoader {
+    tx: DocumentSender,
+    sent: AtomicBool,
+    paths: Vec<PathBuf>,
+    interval: Duration,
+    batch_lines: usize,
+    ids: Option<Arc<dyn IdGenerator>>,
+}
+
+impl std::fmt::Debug for LogTailLoader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogTailLoader")
+            .field("paths", &self.paths)
+            .field("interval", &self.interval)
+            .field("batch_lines", &self.batch_lines)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LogTailLoader {
+    /// Here for making use by the `proc_macro` lib more convinient, just calls
+    /// `LogTailLoaderBuilder::new()`
+    #[allow(clippy::new_ret_no_self)]
+    #[allow(clippy::missing_errors_doc)]
+    pub fn new(paths: Vec<String>) -> Result<LogTailLoaderBuilder, FileLoaderError> {
+        LogTailLoaderBuilder::new(paths)
+    }
+
+    /// Starts tailing the files on the first subscription
+    fn start(&self) {
+        if !self.sent.load(Ordering::Acquire)
+            && self
+                .sent
+                .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+        {
+            // the positions are taken before returning, so lines appended right after
+            // subscribing aren't mistaken for existing content
+            let mut tails: Vec<Tail> = self.paths.iter().cloned().map(Tail::at_end).collect();
+            let txc = self.tx.clone();
+            let batch_lines = self.batch_lines;
+            let ids = self.ids.clone();
+            let mut interval = tokio::time::interval(self.interval);
+            tokio::spawn(async move {
+                loop {
+                    interval.tick().await;
+                    for tail in &mut tails {
+                        for doc in tail.poll(batch_lines, ids.as_deref()) {
+                            if let Err(e) = txc.send(doc).await {
+                                error!("Loader failed to send document: {} to subscribers", e.0.id);
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[async_trait]
+impl Loader for LogTailLoader {
+    #[instrument(fields(self = format!("LogTailLoader {{sent: {}}}", self.sent.load(Ordering::Acquire))))]
+    /// Subscribes to the loader's broadcast channel to receive documents.
+    ///
+    /// # Returns
+    /// A `tokio::sync::broadcast::Receiver<Document>`.
+    async fn subscribe(&self) -> broadcast::Receiver<Document> {
+        let receiver = self.tx.subscribe();
+        self.start();
+        receiver
+    }
+
+    /// Subscribes to the loader as a stream of documents, waiting for the stream to make
+    /// room for every document in [`DeliveryMode::Backpressure`]
+    async fn subscribe_stream(&self) -> BoxStream<'static, Document> {
+        let stream = self.tx.subscribe_stream();
+        self.start();
+        stream
+    }
+}
+
+/// Read position in a tailed file
+#[derive(Debug)]
+struct Tail {
+    path: PathBuf,
+    file_id: Option<u64>,
+    offset: u64,
+    /// Start of a line whose newline wasn't written yet
+    partial: Vec<u8>,
+    lines_read: usize,
+}
+
+impl Tail {
+    fn at_end(path: PathBuf) -> Self {
+        let metadata = std::fs::metadata(&path).ok();
+        Self {
+            file_id: metadata.as_ref().and_then(file_id),
+            offset: metadata.map_or(0, |m| m.len()),
+            path,
+            partial: vec![],
+            lines_read: 0,
+        }
+    }
+
+    /// Reads the lines appended since the last poll, returning them as documents
+    fn poll(&mut self, batch_lines: usize, ids: Option<&dyn IdGenerator>) -> Vec<Document> {
+        let lines = match self.read_lines() {
+            Ok(lines) => lines,
+            Err(e) => {
+                debug!("Failed to read {:?}: {e}", self.path);
+                return vec![];
+            }
+        };
+        let source = self.path.to_string_lossy();
+        let mut documents = vec![];
+        for batch in lines.chunks(batch_lines) {
+            let id = match ids {
+                Some(ids) => ids.generate(&source),
+                None => format!("{source}:{}", self.lines_read + 1),
+            };
+            self.lines_read += batch.len();
+            documents.push(Document::new(id, batch.join("\n")));
+        }
+        documents
+    }
+
+    fn read_lines(&mut self) -> std::io::Result<Vec<String>> {
+        let mut file = File::open(&self.path)?;
+        let metadata = file.metadata()?;
+        let id = file_id(&metadata);
+        if id != self.file_id || metadata.len() < self.offset {
+            info!(
+                "Detected rotation of {:?}, reading it from the start",
+                self.path
+            );
+            self.file_id = id;
+            self.offset = 0;
+            self.partial.clear();
+        }
+        if metadata.len() == self.offset {
+            return Ok(vec![]);
+        }
+
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut appended = std::mem::take(&mut self.partial);
+        let read = file.read_to_end(&mut appended)?;
+        self.offset += read as u64;
+
+        let complete = appended
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map_or(0, |i| i + 1);
+        self.partial = appended.split_off(complete);
+        Ok(String::from_utf8_lossy(&appended)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+/// Identifies the file behind a path, to tell a recreated file from an appended one
+#[cfg(unix)]
+#[allow(clippy::unnecessary_wraps)]
+fn file_id(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+/// Identifies the file behind a path, recreated files are only detected by their size
+/// shrinking where it isn't available
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tokio::time::timeout;
+
+    fn append(path: &std::path::Path, text: &str) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    async fn next_doc(receiver: &mut broadcast::Receiver<Document>) -> Document {
+        timeout(Duration::from_secs(1), receiver.recv())
+            .await
+            .expect("Didn't receive the appended lines")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_appended_lines_are_emitted() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("app.log");
+        append(&path, "existing line\n");
+        let source = path.to_str().unwrap().to_string();
+        let loader = LogTailLoaderBuilder::new(vec![source.clone()])
+            .unwrap()
+            .interval(Duration::from_millis(20))
+            .build()
+            .unwrap();
+        let mut receiver = loader.subscribe().await;
+
+        append(&path, "first\nsecond\nthi");
+        let doc = next_doc(&mut receiver).await;
+        assert_eq!((doc.id, doc.data), (format!("{source}:1"), "first".into()));
+        let doc = next_doc(&mut receiver).await;
+        assert_eq!((doc.id, doc.data), (format!("{source}:2"), "second".into()));
+
+        append(&path, "rd\n");
+        let doc = next_doc(&mut receiver).await;
+        assert_eq!((doc.id, doc.data), (format!("{source}:3"), "third".into()));
+    }
+
+    #[tokio::test]
+    async fn test_rotated_files_are_read_from_the_start() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("app.log");
+        append(&path, "a long line written before the rotation\n");
+        let loader = LogTailLoaderBuilder::new(vec![path.to_str().unwrap().to_string()])
+            .unwrap()
+            .interval(Duration::from_millis(20))
+            .batch_lines(2)
+            .build()
+            .unwrap();
+        let mut receiver = loader.subscribe().await;
+
+        std::fs::rename(&path, temp_dir.path().join("app.log.1")).unwrap();
+        append(&path, "after\nrotation\n");
+
+        assert_eq!(next_doc(&mut receiver).await.data, "after\nrotation");
+    }
+}
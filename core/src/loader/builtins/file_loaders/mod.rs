@@ -1,16 +1,25 @@
 //! Module for loading context from files.
 //!
-//! Includes loaders for one-time loading (`file_once_loader`) and updating loaders (`file_updating_loader`).
+//! Includes loaders for one-time loading (`file_once_loader`), updating loaders (`file_updating_loader`),
+//! polling loaders (`file_polling_loader`) and loaders tailing log files (`log_tail_loader`).
 
 mod utils;
 
+pub mod parser;
+
 #[allow(dead_code)]
 pub mod file_once_loader;
 
+pub mod file_polling_loader;
+
 pub mod file_updating_loader;
 
+pub mod log_tail_loader;
+
 #[allow(unused)]
 pub use file_once_loader::{FileOnceLoader, FileOnceLoaderBuilder};
+pub use log_tail_loader::{LogTailLoader, LogTailLoaderBuilder};
+pub use parser::{DocumentParser, ParserRegistry};
 use thiserror::Error;
 use tokio::sync::broadcast::error::SendError;
 
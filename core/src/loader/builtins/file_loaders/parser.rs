@@ -0,0 +1,136 @@
+use std::{collections::HashMap, fmt, io, path::Path, sync::Arc};
+#[cfg(feature = "pdf")]
+use tracing::error;
+
+/// Extracts the text content of a file, used by the file loaders to turn files into
+/// [`crate::document::Document`]s.
+///
+/// Implemented for closures taking the path of the file, for one-off parsers.
+///
+/// # Example
+/// ```rust,ignore
+/// let loader = FileOnceLoader::new(vec!["docs/**/*.html".to_string()])?
+///     .parser("html", |path: &Path| Ok(strip_tags(&std::fs::read_to_string(path)?)))
+///     .build()?;
+/// ```
+#[allow(clippy::module_name_repetitions)]
+pub trait DocumentParser: Send + Sync {
+    /// Parses the file at `path` into text
+    ///
+    /// # Errors
+    /// Returns an `io::Error` if the file can't be read or parsed
+    fn parse(&self, path: &Path) -> io::Result<String>;
+}
+
+impl<F> DocumentParser for F
+where
+    F: Fn(&Path) -> io::Result<String> + Send + Sync,
+{
+    fn parse(&self, path: &Path) -> io::Result<String> {
+        self(path)
+    }
+}
+
+/// Reads files as UTF-8 plain text
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextParser;
+
+impl DocumentParser for TextParser {
+    fn parse(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+}
+
+/// Extracts the text of PDF files using the `pdf_extract` crate
+#[cfg(feature = "pdf")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PdfParser;
+
+#[cfg(feature = "pdf")]
+impl DocumentParser for PdfParser {
+    fn parse(&self, path: &Path) -> io::Result<String> {
+        pdf_extract::extract_text(path).map_err(|e| {
+            error!("Failed to parse PDF: {e}");
+            io::Error::other(format!("Failed to parse PDF: {e}"))
+        })
+    }
+}
+
+/// Parsers of the file loaders, keyed by file extension
+///
+/// Ships with a [`TextParser`] for `txt` and, with the `pdf` feature, a [`PdfParser`] for `pdf`.
+/// Files with extensions without a registered parser are read as plain text.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone)]
+pub struct ParserRegistry {
+    parsers: HashMap<String, Arc<dyn DocumentParser>>,
+    fallback: Arc<dyn DocumentParser>,
+}
+
+impl Default for ParserRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            parsers: HashMap::new(),
+            fallback: Arc::new(TextParser),
+        };
+        registry.register("txt", TextParser);
+        #[cfg(feature = "pdf")]
+        registry.register("pdf", PdfParser);
+        registry
+    }
+}
+
+impl fmt::Debug for ParserRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut extensions: Vec<_> = self.parsers.keys().collect();
+        extensions.sort();
+        f.debug_struct("ParserRegistry")
+            .field("extensions", &extensions)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ParserRegistry {
+    /// Registers `parser` for files with the extension `extension`, eg. `"docx"`, replacing
+    /// the parser previously registered for it. Extensions are matched case-insensitively.
+    pub fn register(&mut self, extension: &str, parser: impl DocumentParser + 'static) {
+        self.parsers.insert(
+            extension.trim_start_matches('.').to_lowercase(),
+            Arc::new(parser),
+        );
+    }
+
+    /// Parses the file at `path` with the parser registered for its extension
+    ///
+    /// # Errors
+    /// Returns an `io::Error` if the file can't be read or parsed
+    pub fn parse(&self, path: &Path) -> io::Result<String> {
+        let parser = path
+            .extension()
+            .and_then(|ext| self.parsers.get(&ext.to_string_lossy().to_lowercase()))
+            .unwrap_or(&self.fallback);
+        parser.parse(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsers_are_picked_by_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let (fake, text) = (dir.path().join("doc.FAKE"), dir.path().join("notes.md"));
+        std::fs::write(&fake, "raw").unwrap();
+        std::fs::write(&text, "plain").unwrap();
+
+        let mut registry = ParserRegistry::default();
+        registry.register(".fake", |path: &Path| {
+            Ok(format!("parsed {}", std::fs::read_to_string(path)?))
+        });
+
+        assert_eq!(registry.parse(&fake).unwrap(), "parsed raw");
+        assert_eq!(registry.parse(&text).unwrap(), "plain");
+        assert!(format!("{registry:?}").contains("\"fake\""));
+    }
+}
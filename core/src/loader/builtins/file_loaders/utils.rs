@@ -1,16 +1,15 @@
 use glob::{glob, Pattern};
-#[cfg(feature = "pdf")]
-use pdf_extract::extract_text;
 use std::{
-    io,
+    collections::HashMap,
+    fmt, io,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
-#[cfg(feature = "pdf")]
-use tracing::error;
 use tracing::{info, instrument};
 use walkdir::WalkDir;
 
-use crate::document::Document;
+use super::parser::ParserRegistry;
+use crate::document::{Document, IdGenerator, SourceIds};
 
 /// Resolves a list of glob patterns into a list of file paths.
 ///
@@ -47,63 +46,70 @@ pub(super) fn resolve_input_to_files(inputs: Vec<&str>) -> io::Result<Vec<PathBu
 }
 
 #[instrument]
-/// Parses the content of a file based on its extension.
-///
-/// This function reads the content of a file. If the file is a PDF, it uses the `pdf_extract` crate
-/// to extract text from the PDF. For all other file types, it reads the file as plain text.
+/// Parses the content of a file with the parser registered for its extension.
 ///
 /// # Arguments
 /// * `file_path` - The path to the file to parse.
+/// * `parsers` - The parsers to pick from.
 ///
 /// # Returns
 /// * `Ok(String)` - The content of the file as a string.
 /// * `Err(io::Error)` - An error if the file cannot be read or parsed.
-pub(super) fn parse_file(file_path: &Path) -> io::Result<String> {
-    let content = std::fs::read_to_string(file_path)?;
+pub(super) fn parse_file(file_path: &Path, parsers: &ParserRegistry) -> io::Result<String> {
+    let content = parsers.parse(file_path)?;
     info!("Successfully parsed file: {:?}", file_path);
     Ok(content)
 }
 
-#[instrument]
-/// Parses the content of a file based on its extension.
-///
-/// This function reads the content of a file. If the file is a PDF, it uses the `pdf_extract` crate
-/// to extract text from the PDF. For all other file types, it reads the file as plain text.
-///
-/// # Arguments
-/// * `file_path` - The path to the file to parse.
-///
-/// # Returns
-/// * `Ok(String)` - The content of the file as a string.
-/// * `Err(io::Error)` - An error if the file cannot be read or parsed.
-#[cfg(feature = "pdf")]
-pub(super) fn parse_file(file_path: &Path) -> io::Result<String> {
-    let content = if let Some(ext) = file_path.extension() {
-        if ext == "pdf" {
-            extract_text(file_path).map_err(|e| {
-                error!("Failed to parse PDF: {e}");
-                io::Error::new(io::ErrorKind::Other, format!("Failed to parse PDF: {e}"))
-            })?
-        } else {
-            std::fs::read_to_string(file_path)?
+/// Ids of the documents of a loader, generated once per file so later updates and deletions
+/// of a file keep the id it was first loaded with
+#[derive(Clone)]
+pub(super) struct FileIds {
+    generator: Arc<dyn IdGenerator>,
+    ids: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl FileIds {
+    pub(super) fn new(generator: impl IdGenerator + 'static) -> Self {
+        Self {
+            generator: Arc::new(generator),
+            ids: Arc::default(),
         }
-    } else {
-        std::fs::read_to_string(file_path)?
-    };
-    info!("Successfully parsed file: {:?}", file_path);
-    Ok(content)
+    }
+
+    pub(super) fn id(&self, path: &str) -> String {
+        self.ids
+            .lock()
+            .unwrap()
+            .entry(path.to_string())
+            .or_insert_with(|| self.generator.generate(path))
+            .clone()
+    }
+}
+
+impl Default for FileIds {
+    fn default() -> Self {
+        Self::new(SourceIds)
+    }
+}
+
+impl fmt::Debug for FileIds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileIds").finish_non_exhaustive()
+    }
 }
 
-pub(super) fn load_initial(patterns: &[Pattern]) -> Vec<Document> {
+pub(super) fn load_initial(
+    patterns: &[Pattern],
+    parsers: &ParserRegistry,
+    ids: &FileIds,
+) -> Vec<Document> {
     let files =
         resolve_input_to_files(patterns.iter().map(glob::Pattern::as_str).collect()).unwrap();
     let mut documents: Vec<Document> = vec![];
     for file in files {
-        let data = parse_file(&file).unwrap();
-        let document = Document {
-            id: file.to_string_lossy().to_string(),
-            data,
-        };
+        let data = parse_file(&file, parsers).unwrap();
+        let document = Document::new(ids.id(&file.to_string_lossy()), data);
         info!("Successfully loaded document: {:?}", document.id.clone());
         documents.push(document);
     }
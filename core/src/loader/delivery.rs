@@ -0,0 +1,140 @@
+use futures::StreamExt;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::SendError};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use super::{document_stream, BoxStream};
+use crate::document::Document;
+
+/// How a loader delivers documents to subscribers that fall behind
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryMode {
+    /// Documents are sent right away, once the channel is full the oldest undelivered
+    /// documents are dropped for the subscribers lagging behind
+    #[default]
+    Broadcast,
+    /// Every stream subscriber, like an embedder, gets its own bounded channel and the loader
+    /// waits for room in it before sending, so no document is dropped but a slow subscriber
+    /// slows down the loader. Receivers from [`Loader::subscribe`] are still broadcast to.
+    ///
+    /// [`Loader::subscribe`]: super::Loader::subscribe
+    Backpressure,
+}
+
+/// The sending half of a loader's channels, sending according to its [`DeliveryMode`]
+#[derive(Debug, Clone)]
+pub(crate) struct DocumentSender {
+    tx: broadcast::Sender<Document>,
+    streams: Arc<Mutex<Vec<mpsc::Sender<Document>>>>,
+    capacity: usize,
+    mode: DeliveryMode,
+}
+
+impl DocumentSender {
+    pub(crate) fn new(capacity: usize, mode: DeliveryMode) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        Self {
+            tx,
+            streams: Arc::default(),
+            capacity,
+            mode,
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<Document> {
+        self.tx.subscribe()
+    }
+
+    /// Subscribes as a stream, through a bounded channel of its own in
+    /// [`DeliveryMode::Backpressure`]
+    pub(crate) fn subscribe_stream(&self) -> BoxStream<'static, Document> {
+        match self.mode {
+            DeliveryMode::Broadcast => document_stream(self.tx.subscribe()).boxed(),
+            DeliveryMode::Backpressure => {
+                let (tx, rx) = mpsc::channel(self.capacity);
+                self.streams
+                    .lock()
+                    .expect("stream subscribers mutex poisoned")
+                    .push(tx);
+                ReceiverStream::new(rx).boxed()
+            }
+        }
+    }
+
+    /// Sends `document` to the subscribers, waiting for room in the channel of every stream
+    /// subscriber in [`DeliveryMode::Backpressure`]
+    ///
+    /// # Errors
+    /// Returns the document back if there are no subscribers
+    pub(crate) async fn send(&self, document: Document) -> Result<usize, SendError<Document>> {
+        let mut delivered = 0;
+        for stream in self.stream_senders() {
+            if stream.send(document.clone()).await.is_ok() {
+                delivered += 1;
+            }
+        }
+        self.broadcast(document, delivered)
+    }
+
+    /// Sends `document` without waiting for room in the channels, whatever the mode, eg. before
+    /// the subscriber got its receiver. Stream subscribers without room don't get it.
+    ///
+    /// # Errors
+    /// Returns the document back if there are no subscribers
+    pub(crate) fn send_now(&self, document: Document) -> Result<usize, SendError<Document>> {
+        let delivered = self
+            .stream_senders()
+            .iter()
+            .filter(|stream| stream.try_send(document.clone()).is_ok())
+            .count();
+        self.broadcast(document, delivered)
+    }
+
+    /// Broadcasts `document`, counting the `delivered` stream subscribers as receivers
+    fn broadcast(
+        &self,
+        document: Document,
+        delivered: usize,
+    ) -> Result<usize, SendError<Document>> {
+        match self.tx.send(document) {
+            Ok(receivers) => Ok(receivers + delivered),
+            Err(e) if delivered == 0 => Err(e),
+            Err(_) => Ok(delivered),
+        }
+    }
+
+    /// The stream subscribers still listening
+    fn stream_senders(&self) -> Vec<mpsc::Sender<Document>> {
+        let mut streams = self
+            .streams
+            .lock()
+            .expect("stream subscribers mutex poisoned");
+        streams.retain(|stream| !stream.is_closed());
+        streams.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_backpressure_waits_for_room_in_the_stream() {
+        let sender = DocumentSender::new(1, DeliveryMode::Backpressure);
+        let mut stream = sender.subscribe_stream();
+        let document = |id: &str| Document::new(id.to_string(), "data".to_string());
+
+        assert_eq!(sender.send(document("1")).await.unwrap(), 1);
+        let blocked = tokio::time::timeout(Duration::from_millis(50), sender.send(document("2")));
+        assert!(blocked.await.is_err());
+
+        assert_eq!(stream.next().await.unwrap().id, "1");
+        assert_eq!(sender.send(document("3")).await.unwrap(), 1);
+        assert_eq!(stream.next().await.unwrap().id, "3");
+
+        drop(stream);
+        assert!(sender.send(document("4")).await.is_err());
+    }
+}
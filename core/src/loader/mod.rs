@@ -1,23 +1,106 @@
 use crate::document::Document;
 use async_trait::async_trait;
+use futures::stream::{Stream, StreamExt};
 use std::sync::Arc;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tracing::warn;
 
-use tokio::sync::broadcast::Receiver;
+use tokio::sync::broadcast::{self, Receiver};
 
 /// Module for built-in loader implementations.
 ///
 /// Provides pre-defined loader implementations that can be used
 /// for common resource loading.
 pub mod builtins;
+mod delivery;
+
+pub use delivery::DeliveryMode;
+pub(crate) use delivery::DocumentSender;
+pub use futures::stream::BoxStream;
 
 pub(crate) type LoaderInstance = Arc<dyn Loader>;
 
+/// Capacity of the channel [`forward_stream`] forwards a loader's stream to
+const BRIDGE_CHANNEL_CAPACITY: usize = 32;
+
 /// A trait for resource loaders.
 ///
 /// Defines the interface for loaders.
 /// Implementations of this trait are responsible for loading resources and publishing
-/// them to a broadcast channel.
+/// them to their subscribers, which embedders consume as a [`Stream`] of documents.
+///
+/// Loaders publishing to a tokio broadcast channel only implement [`Loader::subscribe`].
+/// Loaders producing a stream override [`Loader::subscribe_stream`] and implement
+/// [`Loader::subscribe`] with [`forward_stream`].
+///
+/// # Example
+/// ```rust,ignore
+/// #[async_trait]
+/// impl Loader for QueueLoader {
+///     async fn subscribe_stream(&self) -> BoxStream<'static, Document> {
+///         let queue = self.queue.clone();
+///         futures::stream::unfold(queue, |queue| async move {
+///             let document = queue.pop().await?;
+///             Some((document, queue))
+///         })
+///         .boxed()
+///     }
+///
+///     async fn subscribe(&self) -> Receiver<Document> {
+///         forward_stream(self.subscribe_stream().await)
+///     }
+/// }
+/// ```
 #[async_trait]
-pub trait Loader: Sync {
+pub trait Loader: Send + Sync {
     async fn subscribe(&self) -> Receiver<Document>;
+
+    /// Subscribes to the loader, replaying documents it already published to the new receiver.
+    ///
+    /// Unlike [`Loader::subscribe`], late subscribers still get the documents sent before they
+    /// subscribed. Loaders that don't keep their published documents around fall back to
+    /// [`Loader::subscribe`].
+    async fn subscribe_replay(&self) -> Receiver<Document> {
+        self.subscribe().await
+    }
+
+    /// Subscribes to the loader as a [`Stream`] of documents, ending once the loader is done.
+    ///
+    /// Defaults to wrapping [`Loader::subscribe`] with [`document_stream`].
+    async fn subscribe_stream(&self) -> BoxStream<'static, Document> {
+        document_stream(self.subscribe().await).boxed()
+    }
+}
+
+/// Forwards the documents of `stream` to a new broadcast channel, for loaders producing a
+/// stream to implement [`Loader::subscribe`].
+///
+/// The channel drops the oldest documents for receivers falling behind.
+pub fn forward_stream(mut stream: BoxStream<'static, Document>) -> Receiver<Document> {
+    let (tx, rx) = broadcast::channel(BRIDGE_CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        while let Some(document) = stream.next().await {
+            if tx.send(document).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Wraps a loader's `receiver` in a [`Stream`] of its documents, ending once the loader
+/// closes its channel.
+///
+/// Documents missed by lagging behind the loader are skipped with a warning instead of
+/// ending the stream.
+pub fn document_stream(receiver: Receiver<Document>) -> impl Stream<Item = Document> {
+    BroadcastStream::new(receiver).filter_map(|result| async move {
+        match result {
+            Ok(document) => Some(document),
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                warn!("Document stream fell behind, skipped {skipped} documents");
+                None
+            }
+        }
+    })
 }
@@ -1,2 +1,2 @@
-pub use crate::completion::State;
+pub use crate::completion::{ClientBuilder, State};
 pub use seedframe_macros::*;
@@ -0,0 +1,374 @@
+use crate::completion::{
+    Client, CompletionError, CompletionModel, CompletionOutput, CompletionStream, Extractor,
+    Message, MessageHistory, SamplingParams, TokenUsage,
+};
+use crate::embeddings::Embedder;
+use crate::tools::ToolSet;
+use async_trait::async_trait;
+use std::{
+    collections::{hash_map::DefaultHasher, VecDeque},
+    hash::{Hash, Hasher},
+};
+use tracing::{debug, instrument};
+
+use super::openai::{DEFAULT_TEMP, DEFAULT_TOKENS};
+
+/// In-memory LRU cache of completion responses, keyed by a hash of the request
+#[derive(Debug)]
+pub struct ResponseCache {
+    entries: VecDeque<(u64, CompletionOutput)>,
+    capacity: usize,
+}
+
+impl ResponseCache {
+    /// Creates a cache holding up to `capacity` responses, evicting the least recently used
+    /// response once it's full
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Number of cached responses
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the cache is empty
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes all cached responses
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Returns the response cached for `key`, marking it as the most recently used
+    fn get(&mut self, key: u64) -> Option<CompletionOutput> {
+        let index = self.entries.iter().position(|(k, _)| *k == key)?;
+        let entry = self.entries.remove(index)?;
+        let output = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(output)
+    }
+
+    fn insert(&mut self, key: u64, output: CompletionOutput) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.entries.retain(|(k, _)| *k != key);
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((key, output));
+    }
+}
+
+/// A completion model caching the responses of the model it wraps
+///
+/// Identical requests, with the same model, messages, tools and parameters, are answered from
+/// the cache instead of reaching the wrapped model, with the token usage of the original
+/// response. Only requests with a temperature of `0` are cached unless
+/// [`CachingCompletionModel::cache_all_temperatures`] is set, as responses sampled with a higher
+/// temperature are expected to vary. Streams and extractions aren't cached.
+///
+/// # Example
+/// ```rust,ignore
+/// let model = CachingCompletionModel::new(OpenAI::new(None), 100);
+/// let mut client = ClientBuilder::new(model)
+///     .preamble("You're a helpful assistant")
+///     .temperature(0.0)
+///     .build()?;
+/// ```
+pub struct CachingCompletionModel<M> {
+    inner: M,
+    cache: ResponseCache,
+    cache_all_temperatures: bool,
+    sampling: SamplingParams,
+}
+
+impl<M: CompletionModel> CachingCompletionModel<M> {
+    /// Wraps `inner`, caching up to `capacity` responses
+    #[must_use]
+    pub fn new(inner: M, capacity: usize) -> Self {
+        Self {
+            inner,
+            cache: ResponseCache::new(capacity),
+            cache_all_temperatures: false,
+            sampling: SamplingParams::default(),
+        }
+    }
+
+    /// Caches responses regardless of the temperature of the request, `false` by default
+    #[must_use]
+    pub fn cache_all_temperatures(mut self, cache: bool) -> Self {
+        self.cache_all_temperatures = cache;
+        self
+    }
+
+    /// Returns the cache of the model
+    #[must_use]
+    pub fn cache(&self) -> &ResponseCache {
+        &self.cache
+    }
+
+    /// Returns the cache of the model mutably, eg. to clear it
+    pub fn cache_mut(&mut self) -> &mut ResponseCache {
+        &mut self.cache
+    }
+
+    /// Hashes everything affecting the response to a request
+    fn cache_key(
+        &self,
+        message: &Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.inner.provider_name().hash(&mut hasher);
+        self.inner.model_name().hash(&mut hasher);
+        format!("{history:?}{message:?}{:?}", self.sampling).hash(&mut hasher);
+        if let Some(tools) = tools {
+            for tool in &tools.0 {
+                tool.default_serializer().to_string().hash(&mut hasher);
+            }
+        }
+        temperature.to_bits().hash(&mut hasher);
+        max_tokens.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[allow(refining_impl_trait)]
+#[async_trait]
+impl<M: CompletionModel> CompletionModel for CachingCompletionModel<M> {
+    fn build_client(
+        self,
+        preamble: impl AsRef<str>,
+        embedder_instances: Vec<Embedder>,
+        tools: ToolSet,
+    ) -> Client<Self> {
+        Client::new(
+            self,
+            preamble,
+            DEFAULT_TEMP,
+            DEFAULT_TOKENS,
+            embedder_instances,
+            tools,
+        )
+    }
+
+    async fn send(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<(Message, TokenUsage), CompletionError> {
+        self.send_detailed(message, history, tools, temperature, max_tokens)
+            .await
+            .map(|output| (output.message, output.token_usage))
+    }
+
+    fn build_request_body(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<serde_json::Value, CompletionError> {
+        self.inner
+            .build_request_body(message, history, tools, temperature, max_tokens)
+    }
+
+    #[instrument(skip_all, fields(cached = self.cache.len()))]
+    async fn send_detailed(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionOutput, CompletionError> {
+        if temperature != 0.0 && !self.cache_all_temperatures {
+            return self
+                .inner
+                .send_detailed(message, history, tools, temperature, max_tokens)
+                .await;
+        }
+        let key = self.cache_key(&message, history, tools, temperature, max_tokens);
+        if let Some(output) = self.cache.get(key) {
+            debug!("Answering request from the cache");
+            return Ok(output);
+        }
+        let output = self
+            .inner
+            .send_detailed(message, history, tools, temperature, max_tokens)
+            .await?;
+        self.cache.insert(key, output.clone());
+        Ok(output)
+    }
+
+    async fn stream(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionStream, CompletionError> {
+        self.inner
+            .stream(message, history, tools, temperature, max_tokens)
+            .await
+    }
+
+    async fn extract<T: Extractor>(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<T, CompletionError> {
+        self.inner
+            .extract(message, history, temperature, max_tokens)
+            .await
+    }
+
+    fn set_idempotency_key(&mut self, key: Option<String>) -> bool {
+        self.inner.set_idempotency_key(key)
+    }
+
+    fn set_sampling_params(&mut self, params: SamplingParams) -> bool {
+        self.sampling = params;
+        self.inner.set_sampling_params(params)
+    }
+
+    fn set_route(&mut self, route: Option<String>) -> bool {
+        self.inner.set_route(route)
+    }
+
+    fn provider_name(&self) -> &str {
+        self.inner.provider_name()
+    }
+
+    fn model_name(&self) -> &str {
+        self.inner.model_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    struct CountingModel(Arc<AtomicUsize>);
+
+    #[allow(refining_impl_trait)]
+    #[async_trait]
+    impl CompletionModel for CountingModel {
+        fn build_client(
+            self,
+            preamble: impl AsRef<str>,
+            embedder_instances: Vec<Embedder>,
+            tools: ToolSet,
+        ) -> Client<Self> {
+            Client::new(self, preamble, 0.0, 100, embedder_instances, tools)
+        }
+
+        async fn send(
+            &mut self,
+            _message: Message,
+            _history: &MessageHistory,
+            _tools: Option<&ToolSet>,
+            _temperature: f64,
+            _max_tokens: usize,
+        ) -> Result<(Message, TokenUsage), CompletionError> {
+            let count = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok((
+                Message::Assistant {
+                    content: format!("response {count}"),
+                    tool_calls: None,
+                    name: None,
+                },
+                TokenUsage::default(),
+            ))
+        }
+    }
+
+    fn user(content: &str) -> Message {
+        Message::User {
+            content: content.to_string(),
+            tool_responses: None,
+            name: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_identical_prompts_are_answered_from_cache() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let model = CachingCompletionModel::new(CountingModel(Arc::clone(&calls)), 10);
+        let mut client = crate::completion::ClientBuilder::new(model)
+            .temperature(0.0)
+            .build()
+            .unwrap();
+
+        let first = client
+            .prompt("hi")
+            .one_shot(true, None)
+            .send()
+            .await
+            .unwrap();
+        let second = client
+            .prompt("hi")
+            .one_shot(true, None)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        client
+            .prompt("bye")
+            .one_shot(true, None)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_nonzero_temperatures_and_evicted_requests_reach_the_model() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut model = CachingCompletionModel::new(CountingModel(Arc::clone(&calls)), 1);
+
+        for _ in 0..2 {
+            model
+                .send(user("hi"), &vec![], None, 0.7, 10)
+                .await
+                .unwrap();
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(model.cache().is_empty());
+
+        for prompt in ["a", "b", "a"] {
+            model
+                .send(user(prompt), &vec![], None, 0.0, 10)
+                .await
+                .unwrap();
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+        assert_eq!(model.cache().len(), 1);
+    }
+}
@@ -0,0 +1,398 @@
+use crate::completion::{
+    Client, CompletionError, CompletionModel, CompletionOutput, CompletionStream, Message,
+    MessageHistory, SamplingParams, TokenUsage,
+};
+use crate::embeddings::Embedder;
+use crate::tools::ToolSet;
+use async_trait::async_trait;
+use tracing::{error, instrument, warn};
+
+use super::openai::{DEFAULT_TEMP, DEFAULT_TOKENS};
+
+/// Object safe subset of [`CompletionModel`], implemented for every completion model
+///
+/// [`CompletionModel`] can't be used as a trait object, this trait allows boxing models of
+/// different types together, eg. in a [`FallbackCompletionModel`].
+#[async_trait]
+pub trait DynCompletionModel: Send {
+    /// Calls [`CompletionModel::send_detailed`]
+    async fn dyn_send(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionOutput, CompletionError>;
+
+    /// Calls [`CompletionModel::stream`]
+    async fn dyn_stream(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionStream, CompletionError>;
+
+    /// Calls [`CompletionModel::build_request_body`]
+    fn dyn_build_request_body(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<serde_json::Value, CompletionError>;
+
+    /// Calls [`CompletionModel::set_sampling_params`]
+    fn dyn_set_sampling_params(&mut self, params: SamplingParams) -> bool;
+
+    /// Calls [`CompletionModel::set_idempotency_key`]
+    fn dyn_set_idempotency_key(&mut self, key: Option<String>) -> bool;
+}
+
+#[async_trait]
+impl<M: CompletionModel> DynCompletionModel for M {
+    async fn dyn_send(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionOutput, CompletionError> {
+        CompletionModel::send_detailed(self, message, history, tools, temperature, max_tokens).await
+    }
+
+    async fn dyn_stream(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionStream, CompletionError> {
+        CompletionModel::stream(self, message, history, tools, temperature, max_tokens).await
+    }
+
+    fn dyn_build_request_body(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<serde_json::Value, CompletionError> {
+        CompletionModel::build_request_body(self, message, history, tools, temperature, max_tokens)
+    }
+
+    fn dyn_set_sampling_params(&mut self, params: SamplingParams) -> bool {
+        CompletionModel::set_sampling_params(self, params)
+    }
+
+    fn dyn_set_idempotency_key(&mut self, key: Option<String>) -> bool {
+        CompletionModel::set_idempotency_key(self, key)
+    }
+}
+
+/// A completion model trying an ordered list of models until one of them succeeds
+///
+/// Every request goes to the first model, on failure it's retried with the next one and so on,
+/// the error of the last model is returned if all of them fail. Streams fall back only if
+/// opening the stream fails, not on errors midway through it.
+/// Request options like sampling params are set on every model, dry runs build the request
+/// body of the first one. Structured extraction isn't supported, as it can't be dispatched
+/// to boxed models.
+///
+/// # Example
+/// ```rust,ignore
+/// let model = FallbackCompletionModel::new(vec![
+///     Box::new(OpenAI::new(None)),
+///     Box::new(DeepSeek::new(None)),
+/// ]);
+/// let mut client = model.build_client("You're a helpful assistant", vec![], tools);
+/// ```
+pub struct FallbackCompletionModel {
+    models: Vec<Box<dyn DynCompletionModel>>,
+}
+
+impl FallbackCompletionModel {
+    /// Creates a new `FallbackCompletionModel` trying `models` in order
+    #[must_use]
+    pub fn new(models: Vec<Box<dyn DynCompletionModel>>) -> Self {
+        Self { models }
+    }
+}
+
+fn no_models_error() -> CompletionError {
+    error!("FallbackCompletionModel has no models to send the request to");
+    CompletionError::RequestError("No models to fall back to".to_string())
+}
+
+#[allow(refining_impl_trait)]
+#[async_trait]
+impl CompletionModel for FallbackCompletionModel {
+    fn build_client(
+        self,
+        preamble: impl AsRef<str>,
+        embedder_instances: Vec<Embedder>,
+        tools: ToolSet,
+    ) -> Client<Self> {
+        Client::new(
+            self,
+            preamble,
+            DEFAULT_TEMP,
+            DEFAULT_TOKENS,
+            embedder_instances,
+            tools,
+        )
+    }
+
+    async fn send(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<(Message, TokenUsage), CompletionError> {
+        self.send_detailed(message, history, tools, temperature, max_tokens)
+            .await
+            .map(|output| (output.message, output.token_usage))
+    }
+
+    #[instrument(skip_all, fields(model_count = self.models.len()))]
+    async fn send_detailed(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionOutput, CompletionError> {
+        let mut last_error = None;
+        for (index, model) in self.models.iter_mut().enumerate() {
+            match model
+                .dyn_send(message.clone(), history, tools, temperature, max_tokens)
+                .await
+            {
+                Ok(output) => return Ok(output),
+                Err(e) => {
+                    warn!(error = ?e, model_index = index, "Model failed, falling back");
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(no_models_error))
+    }
+
+    #[instrument(skip_all, fields(model_count = self.models.len()))]
+    async fn stream(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionStream, CompletionError> {
+        let mut last_error = None;
+        for (index, model) in self.models.iter_mut().enumerate() {
+            match model
+                .dyn_stream(message.clone(), history, tools, temperature, max_tokens)
+                .await
+            {
+                Ok(stream) => return Ok(stream),
+                Err(e) => {
+                    warn!(error = ?e, model_index = index, "Model failed, falling back");
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(no_models_error))
+    }
+
+    fn build_request_body(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<serde_json::Value, CompletionError> {
+        self.models
+            .first()
+            .ok_or_else(no_models_error)?
+            .dyn_build_request_body(message, history, tools, temperature, max_tokens)
+    }
+
+    /// Sets the params on every model, returns whether any of them supports them
+    fn set_sampling_params(&mut self, params: SamplingParams) -> bool {
+        let mut supported = false;
+        for model in &mut self.models {
+            supported |= model.dyn_set_sampling_params(params);
+        }
+        supported
+    }
+
+    /// Sets the key on every model, returns whether any of them supports it
+    fn set_idempotency_key(&mut self, key: Option<String>) -> bool {
+        let mut supported = false;
+        for model in &mut self.models {
+            supported |= model.dyn_set_idempotency_key(key.clone());
+        }
+        supported
+    }
+
+    fn provider_name(&self) -> &str {
+        "fallback"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ExecutionStrategy;
+
+    struct FailingModel;
+
+    #[allow(refining_impl_trait)]
+    #[async_trait]
+    impl CompletionModel for FailingModel {
+        fn build_client(
+            self,
+            preamble: impl AsRef<str>,
+            embedder_instances: Vec<Embedder>,
+            tools: ToolSet,
+        ) -> Client<Self> {
+            Client::new(self, preamble, 0.0, 100, embedder_instances, tools)
+        }
+
+        async fn send(
+            &mut self,
+            _message: Message,
+            _history: &MessageHistory,
+            _tools: Option<&ToolSet>,
+            _temperature: f64,
+            _max_tokens: usize,
+        ) -> Result<(Message, TokenUsage), CompletionError> {
+            Err(CompletionError::ProviderError(
+                503,
+                "overloaded".to_string(),
+            ))
+        }
+    }
+
+    struct ReplyModel(&'static str, SamplingParams);
+
+    #[allow(refining_impl_trait)]
+    #[async_trait]
+    impl CompletionModel for ReplyModel {
+        fn build_client(
+            self,
+            preamble: impl AsRef<str>,
+            embedder_instances: Vec<Embedder>,
+            tools: ToolSet,
+        ) -> Client<Self> {
+            Client::new(self, preamble, 0.0, 100, embedder_instances, tools)
+        }
+
+        async fn send(
+            &mut self,
+            _message: Message,
+            _history: &MessageHistory,
+            _tools: Option<&ToolSet>,
+            _temperature: f64,
+            _max_tokens: usize,
+        ) -> Result<(Message, TokenUsage), CompletionError> {
+            Ok((
+                Message::Assistant {
+                    content: self.0.to_string(),
+                    tool_calls: None,
+                    name: None,
+                },
+                TokenUsage::default(),
+            ))
+        }
+
+        fn build_request_body(
+            &self,
+            _message: Message,
+            _history: &MessageHistory,
+            _tools: Option<&ToolSet>,
+            _temperature: f64,
+            _max_tokens: usize,
+        ) -> Result<serde_json::Value, CompletionError> {
+            Ok(serde_json::json!({ "model": self.0, "seed": self.1.seed }))
+        }
+
+        fn set_sampling_params(&mut self, params: SamplingParams) -> bool {
+            self.1 = params;
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_to_next_model() {
+        let model = FallbackCompletionModel::new(vec![
+            Box::new(FailingModel),
+            Box::new(ReplyModel("from the fallback", SamplingParams::default())),
+            Box::new(ReplyModel("never reached", SamplingParams::default())),
+        ]);
+        let mut client = model.build_client(
+            "preamble",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+
+        let response = client.prompt("hi").send().await.unwrap();
+        assert_eq!(
+            response,
+            Message::Assistant {
+                content: "from the fallback".to_string(),
+                tool_calls: None,
+                name: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_returns_last_error_if_all_models_fail() {
+        let mut model = FallbackCompletionModel::new(vec![Box::new(FailingModel)]);
+        let message = Message::User {
+            content: "hi".to_string(),
+            tool_responses: None,
+            name: None,
+        };
+
+        let result = model.send(message.clone(), &vec![], None, 0.0, 10).await;
+        assert!(matches!(
+            result,
+            Err(CompletionError::ProviderError(503, ref body)) if body == "overloaded"
+        ));
+        assert!(matches!(
+            FallbackCompletionModel::new(vec![])
+                .send(message, &vec![], None, 0.0, 10)
+                .await,
+            Err(CompletionError::RequestError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_request_options_reach_every_model() {
+        let model = FallbackCompletionModel::new(vec![
+            Box::new(ReplyModel("first", SamplingParams::default())),
+            Box::new(ReplyModel("second", SamplingParams::default())),
+        ]);
+        let mut client = model.build_client(
+            "preamble",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+
+        let body = client.prompt("hi").seed(7).dry_run().await.unwrap();
+        assert_eq!(body, serde_json::json!({ "model": "first", "seed": 7 }));
+        assert!(!FallbackCompletionModel::new(vec![Box::new(FailingModel)])
+            .set_sampling_params(SamplingParams::default()));
+    }
+}
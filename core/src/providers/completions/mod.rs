@@ -1,7 +1,15 @@
+pub(crate) mod caching;
 pub(crate) mod deepseek;
+pub(crate) mod fallback;
 pub(crate) mod openai;
+pub(crate) mod openai_compatible;
+pub(crate) mod routing;
 pub(crate) mod xai;
 
+pub use caching::{CachingCompletionModel as Caching, ResponseCache};
 pub use deepseek::DeepseekCompletionModel as DeepSeek;
+pub use fallback::{DynCompletionModel, FallbackCompletionModel as Fallback};
 pub use openai::OpenAICompletionModel as OpenAI;
+pub use openai_compatible::OpenAICompatibleCompletionModel as OpenAICompatible;
+pub use routing::RoutingCompletionModel as Routing;
 pub use xai::XaiCompletionModel as Xai;
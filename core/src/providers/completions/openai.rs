@@ -1,40 +1,70 @@
 use crate::completion::{
-    default_extractor_serializer, serialize_assistant, serialize_user, Client, CompletionError,
-    CompletionModel, Extractor, Message, MessageHistory, TokenUsage,
+    extractor_serializer, insert_extra_params, parse_extracted, parse_response_body, parse_usage,
+    serialize_tool_calls, serialize_tool_content, warn_unknown_config_keys, Client,
+    CompletionError, CompletionModel, CompletionOutput, CompletionStream, Extractor, FinishReason,
+    Message, MessageHistory, ReasoningEffort, SamplingParams, StreamChunk, TokenUsage,
 };
 use crate::embeddings::Embedder;
 use crate::tools::{ToolCall, ToolResponse, ToolSet};
 use async_trait::async_trait;
+use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use tracing::{debug, error, info, instrument};
+use std::collections::{HashMap, VecDeque};
+use tracing::{debug, error, info, instrument, warn};
 
 const API_KEY_ENV_VAR: &str = "SEEDFRAME_OPENAI_API_KEY";
 const URL: &str = "https://api.openai.com/v1/chat/completions";
-const DEFAULT_TEMP: f64 = 1.0;
+pub(super) const DEFAULT_TEMP: f64 = 1.0;
 const DEFAULT_MODEL: &str = "gpt-4o-mini";
-const DEFAULT_TOKENS: usize = 2400;
+pub(super) const DEFAULT_TOKENS: usize = 2400;
 
 #[derive(Serialize, Deserialize, Debug)]
-#[serde(deny_unknown_fields)]
 struct ModelConfig {
     api_key: Option<String>,
     api_url: Option<String>,
     model: Option<String>,
+    strict: Option<bool>,
+    capture_raw_response: Option<bool>,
+    top_p: Option<f64>,
+    frequency_penalty: Option<f64>,
+    presence_penalty: Option<f64>,
+    reasoning_effort: Option<ReasoningEffort>,
+    developer_role: Option<bool>,
+    /// Unknown keys, passed through to the request body, eg. `logit_bias`
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
 }
 
 pub struct OpenAICompletionModel {
-    api_key: String,
+    api_key: Option<String>,
     api_url: String,
     client: reqwest::Client,
     model: String,
+    strict: bool,
+    headers: HashMap<String, String>,
+    capture_raw_response: bool,
+    sampling: SamplingParams,
+    sampling_overrides: SamplingParams,
+    idempotency_key: Option<String>,
+    extra_params: serde_json::Map<String, serde_json::Value>,
+    developer_role: bool,
 }
 
 impl OpenAICompletionModel {
     #[instrument]
     #[must_use]
     pub fn new(json_config: Option<&str>) -> Self {
-        let (api_key_var, api_url, model) = if let Some(json) = json_config {
+        let (
+            api_key_var,
+            api_url,
+            model,
+            strict,
+            capture_raw_response,
+            sampling,
+            developer_role,
+            extra_params,
+        ) = if let Some(json) = json_config {
             let config = match serde_json::from_str::<ModelConfig>(json) {
                 Ok(config) => config,
                 Err(e) => {
@@ -43,16 +73,33 @@ impl OpenAICompletionModel {
                     panic!("{e}");
                 }
             };
+            warn_unknown_config_keys(&config.extra);
             (
                 config.api_key.unwrap_or(API_KEY_ENV_VAR.to_string()),
                 config.api_url.unwrap_or(URL.to_string()),
                 config.model.unwrap_or(DEFAULT_MODEL.to_string()),
+                config.strict.unwrap_or(true),
+                config.capture_raw_response.unwrap_or(false),
+                SamplingParams {
+                    top_p: config.top_p,
+                    frequency_penalty: config.frequency_penalty,
+                    presence_penalty: config.presence_penalty,
+                    reasoning_effort: config.reasoning_effort,
+                    ..SamplingParams::default()
+                },
+                config.developer_role,
+                config.extra,
             )
         } else {
             (
                 API_KEY_ENV_VAR.to_string(),
                 URL.to_string(),
                 DEFAULT_MODEL.to_string(),
+                true,
+                false,
+                SamplingParams::default(),
+                None,
+                serde_json::Map::new(),
             )
         };
         let api_key = match std::env::var(&api_key_var) {
@@ -63,51 +110,250 @@ impl OpenAICompletionModel {
                 panic!("{e}");
             }
         };
+        let model = Self::with_endpoint(Some(api_key), api_url, model, strict, HashMap::new())
+            .with_raw_response_capture(capture_raw_response)
+            .with_sampling_params(sampling)
+            .with_extra_params(extra_params);
+        match developer_role {
+            Some(developer_role) => model.with_developer_role(developer_role),
+            None => model,
+        }
+    }
+
+    /// Creates a model talking to an arbitrary OpenAI compatible endpoint,
+    /// sending `headers` along with every request
+    pub(super) fn with_endpoint(
+        api_key: Option<String>,
+        api_url: String,
+        model: String,
+        strict: bool,
+        headers: HashMap<String, String>,
+    ) -> Self {
         Self {
             api_key,
             api_url,
             client: reqwest::Client::new(),
+            strict,
+            headers,
+            capture_raw_response: false,
+            sampling: SamplingParams::default(),
+            sampling_overrides: SamplingParams::default(),
+            idempotency_key: None,
+            extra_params: serde_json::Map::new(),
+            developer_role: is_reasoning_model(&model),
             model,
         }
     }
+
+    /// Replaces the HTTP client used for requests to OpenAI, eg. to share a connection pool
+    /// with other providers and embedders talking to the same host.
+    /// A new client is created for every instance by default.
+    #[must_use]
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Includes a truncated snippet of the raw response body in the error when a response
+    /// fails to parse, for debugging. Disabled by default, also settable with the
+    /// `capture_raw_response` config field.
+    #[must_use]
+    pub fn with_raw_response_capture(mut self, capture: bool) -> Self {
+        self.capture_raw_response = capture;
+        self
+    }
+
+    /// Sets the default sampling params of every request, also settable with the `top_p`,
+    /// `frequency_penalty` and `presence_penalty` config fields
+    #[must_use]
+    pub fn with_sampling_params(mut self, params: SamplingParams) -> Self {
+        self.sampling = params;
+        self
+    }
+
+    /// Adds `params` to the body of every request, for parameters without a dedicated setting,
+    /// eg. `logit_bias` or `seed`. Params never replace the fields set by seedframe,
+    /// like `model` or `messages`. Unknown keys of the json config are added the same way.
+    #[must_use]
+    pub fn with_extra_params(mut self, params: serde_json::Map<String, serde_json::Value>) -> Self {
+        self.extra_params.extend(params);
+        self
+    }
+
+    /// Sends the preamble with the `developer` role instead of `system`, as required by the
+    /// o-series reasoning models. Enabled by default for models named like them (`o1`, `o3-mini`,
+    /// ...), also settable with the `developer_role` config field.
+    #[must_use]
+    pub fn with_developer_role(mut self, developer_role: bool) -> Self {
+        self.developer_role = developer_role;
+        self
+    }
+}
+
+/// Whether `model` is an o-series reasoning model, taking `developer` messages over `system` ones
+/// and `max_completion_tokens` over `max_tokens`
+fn is_reasoning_model(model: &str) -> bool {
+    let mut chars = model.chars();
+    chars.next() == Some('o') && chars.next().is_some_and(|c| c.is_ascii_digit())
 }
 
 #[derive(Serialize, Deserialize, Eq, PartialEq)]
-#[serde(tag = "role", content = "content")]
+#[serde(tag = "role")]
 #[allow(non_camel_case_types)]
 enum OpenAIMessage {
-    system(String),
-    #[serde(serialize_with = "serialize_user")]
+    system {
+        content: String,
+    },
+    developer {
+        content: String,
+    },
     user {
         content: String,
+        #[serde(skip)]
         tool_responses: Option<Vec<ToolResponse>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
     },
-    #[serde(serialize_with = "serialize_assistant")]
     assistant {
         content: String,
+        #[serde(
+            skip_serializing_if = "Option::is_none",
+            serialize_with = "serialize_tool_calls"
+        )]
         tool_calls: Option<Vec<ToolCall>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+    tool {
+        tool_call_id: String,
+        #[serde(serialize_with = "serialize_tool_content")]
+        content: serde_json::Value,
     },
 }
 
 impl From<Message> for OpenAIMessage {
     fn from(value: Message) -> OpenAIMessage {
         match value {
-            Message::Preamble(s) => OpenAIMessage::system(s),
+            Message::Preamble(content) => OpenAIMessage::system { content },
             Message::User {
                 content,
                 tool_responses,
+                name,
             } => Self::user {
                 content,
                 tool_responses,
+                name,
             },
             Message::Assistant {
                 content,
                 tool_calls,
+                name,
             } => Self::assistant {
                 content,
                 tool_calls,
+                name,
             },
+            // tool messages only take text, images are described in it
+            Message::Tool(response) => Self::tool {
+                content: serde_json::Value::String(response.text_content()),
+                tool_call_id: response.id,
+            },
+        }
+    }
+}
+
+/// Converts a message history to the JSON message list of the `OpenAI` chat completions API
+pub(crate) fn history_to_json(history: &MessageHistory) -> serde_json::Value {
+    let messages: Vec<OpenAIMessage> = history.iter().cloned().map(Into::into).collect();
+    json!(messages)
+}
+
+impl OpenAICompletionModel {
+    /// Converts `history` followed by `message` to the messages of a request
+    fn messages(&self, message: Message, history: &MessageHistory) -> Vec<OpenAIMessage> {
+        history
+            .iter()
+            .cloned()
+            .chain(std::iter::once(message))
+            .map(|message| match message.into() {
+                OpenAIMessage::system { content } if self.developer_role => {
+                    OpenAIMessage::developer { content }
+                }
+                message => message,
+            })
+            .collect()
+    }
+
+    /// Starts a POST request to the completions endpoint with the auth and custom headers set
+    fn post(&self) -> reqwest::RequestBuilder {
+        let mut request = self
+            .client
+            .post(&self.api_url)
+            .header("Content-Type", "application/json");
+        if let Some(api_key) = &self.api_key {
+            request = request.header("Authorization", format!("Bearer {api_key}"));
+        }
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(key) = &self.idempotency_key {
+            request = request.header("Idempotency-Key", key);
+        }
+        request
+    }
+
+    /// Sets the temperature and the token limit, reasoning models only support their default
+    /// temperature and take the limit as `max_completion_tokens`
+    fn insert_generation_limits(
+        &self,
+        request_body: &mut serde_json::Value,
+        temperature: f64,
+        max_tokens: usize,
+    ) {
+        if is_reasoning_model(&self.model) {
+            request_body["max_completion_tokens"] = json!(max_tokens);
+        } else {
+            request_body["temperature"] = json!(temperature);
+            request_body["max_tokens"] = json!(max_tokens);
+        }
+    }
+
+    fn request_body(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> serde_json::Value {
+        let messages = self.messages(message, history);
+
+        let mut request_body = json!({
+            "store": true,
+            "model": self.model,
+            "messages": messages,
+        });
+        self.insert_generation_limits(&mut request_body, temperature, max_tokens);
+
+        if let Some(tools) = tools {
+            let tools_serialized: Vec<serde_json::Value> =
+                tools.0.iter().map(|t| t.serializer(self.strict)).collect();
+            if let Some(obj) = request_body.as_object_mut() {
+                info!(
+                    tool_count = tools_serialized.len(),
+                    "Including tools in request"
+                );
+                obj.insert(
+                    "tools".to_string(),
+                    serde_json::Value::Array(tools_serialized),
+                );
+            }
         }
+        self.sampling_overrides
+            .or(self.sampling)
+            .insert_into(&mut request_body);
+        insert_extra_params(&self.extra_params, &mut request_body);
+        request_body
     }
 }
 
@@ -129,74 +375,62 @@ impl CompletionModel for OpenAICompletionModel {
             tools,
         )
     }
+    async fn send(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<(Message, TokenUsage), CompletionError> {
+        self.send_detailed(message, history, tools, temperature, max_tokens)
+            .await
+            .map(|output| (output.message, output.token_usage))
+    }
+
+    fn build_request_body(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<serde_json::Value, CompletionError> {
+        Ok(self.request_body(message, history, tools, temperature, max_tokens))
+    }
+
     #[instrument(
         skip(self, history, tools, temperature),
         fields(
             history_len = history.len(),
             tools = tools.is_some())
     )]
-    async fn send(
+    async fn send_detailed(
         &mut self,
         message: Message,
         history: &MessageHistory,
         tools: Option<&ToolSet>,
         temperature: f64,
         max_tokens: usize,
-    ) -> Result<(Message, TokenUsage), CompletionError> {
-        let mut messages = history.clone();
-        messages.push(message);
-        let messages: Vec<_> = messages
-            .into_iter()
-            .map(Into::<OpenAIMessage>::into)
-            .collect();
-
-        let mut request_body = json!({
-            "store": true,
-            "model": self.model,
-            "messages": messages,
-            "temperature": temperature,
-            "max_tokens": max_tokens,
-        });
-
-        if let Some(tools) = tools {
-            let tools_serialized: Vec<serde_json::Value> =
-                tools.0.iter().map(|t| t.default_serializer()).collect();
-            if let Some(obj) = request_body.as_object_mut() {
-                info!(
-                    tool_count = tools_serialized.len(),
-                    "Including tools in request"
-                );
-                obj.insert(
-                    "tools".to_string(),
-                    serde_json::Value::Array(tools_serialized),
-                );
-            }
-        }
+    ) -> Result<CompletionOutput, CompletionError> {
+        let request_body = self.request_body(message, history, tools, temperature, max_tokens);
 
         debug!(request_body = ?request_body, "Sending request to OpenAI");
 
-        let response = self
-            .client
-            .post(&self.api_url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| {
-                error!(error = ?e, "Request failed");
-                CompletionError::RequestError(e.to_string())
-            })?;
+        let response = self.post().json(&request_body).send().await.map_err(|e| {
+            error!(error = ?e, "Request failed");
+            CompletionError::RequestError(e.to_string())
+        })?;
 
         let status = response.status();
         debug!(%status, "Received API response");
 
         if status.is_success() {
-            let response_json: serde_json::Value = response.json().await.map_err(|e| {
-                error!(error = ?e, "Failed to parse response JSON");
-                // error!("{}",format!("Failed to parse response as json: {:?}",e));
-                CompletionError::ParseError(e.to_string())
-            })?;
+            let body = response
+                .text()
+                .await
+                .map_err(|e| CompletionError::RequestError(e.to_string()))?;
+            let response_json = parse_response_body(&body, self.capture_raw_response)?;
 
             let resp_msg_json = &response_json["choices"][0]["message"]["content"];
             let mut response_message = String::new();
@@ -232,26 +466,7 @@ impl CompletionModel for OpenAICompletionModel {
                     result
                 });
 
-            let usage_response = &response_json["usage"];
-            let usage_parse_error =
-                CompletionError::ParseError("Failed to parse usage data from response".to_string());
-            let token_usage = TokenUsage {
-                prompt_tokens: Some(
-                    usage_response["prompt_tokens"]
-                        .as_u64()
-                        .ok_or(usage_parse_error.clone())?,
-                ),
-                completion_tokens: Some(
-                    usage_response["completion_tokens"]
-                        .as_u64()
-                        .ok_or(usage_parse_error.clone())?,
-                ),
-                total_tokens: Some(
-                    usage_response["total_tokens"]
-                        .as_u64()
-                        .ok_or(usage_parse_error)?,
-                ),
-            };
+            let token_usage = parse_usage(&response_json);
 
             info!(
                 prompt_tokens = token_usage.prompt_tokens,
@@ -260,13 +475,18 @@ impl CompletionModel for OpenAICompletionModel {
                 "Token usage recorded"
             );
 
-            Ok((
-                Message::Assistant {
+            Ok(CompletionOutput {
+                message: Message::Assistant {
                     content: response_message,
                     tool_calls,
+                    name: None,
                 },
                 token_usage,
-            ))
+                finish_reason: parse_finish_reason(&response_json),
+                system_fingerprint: response_json["system_fingerprint"]
+                    .as_str()
+                    .map(ToString::to_string),
+            })
         } else {
             let status = response.status();
             let error_msg = response
@@ -284,6 +504,53 @@ impl CompletionModel for OpenAICompletionModel {
         }
     }
 
+    #[instrument(
+        skip(self, history, tools, temperature),
+        fields(
+            history_len = history.len(),
+            tools = tools.is_some())
+    )]
+    async fn stream(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionStream, CompletionError> {
+        let mut request_body = self.request_body(message, history, tools, temperature, max_tokens);
+        if let Some(obj) = request_body.as_object_mut() {
+            obj.insert("stream".to_string(), json!(true));
+            obj.insert("stream_options".to_string(), json!({"include_usage": true}));
+        }
+
+        debug!(request_body = ?request_body, "Sending streaming request to OpenAI");
+
+        let response = self.post().json(&request_body).send().await.map_err(|e| {
+            error!(error = ?e, "Request failed");
+            CompletionError::RequestError(e.to_string())
+        })?;
+
+        let status = response.status();
+        debug!(%status, "Received API response");
+
+        if !status.is_success() {
+            let error_msg = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error (failed to read response body)".to_string());
+
+            error!(
+                status = %status,
+                error = %error_msg,
+                "API returned error response"
+            );
+            return Err(CompletionError::ProviderError(status.into(), error_msg));
+        }
+
+        Ok(sse_stream(response.bytes_stream()))
+    }
+
     #[instrument(
         skip(self, history, temperature),
         fields(history_len = history.len())
@@ -295,37 +562,32 @@ impl CompletionModel for OpenAICompletionModel {
         temperature: f64,
         max_tokens: usize,
     ) -> Result<T, CompletionError> {
-        let mut messages = history.clone();
-        messages.push(message);
-        let messages: Vec<_> = messages
-            .into_iter()
-            .map(Into::<OpenAIMessage>::into)
-            .collect();
+        let messages = self.messages(message, history);
         info!(
             message_count = messages.len(),
             "Preparing extraction request"
         );
 
-        let extractor = default_extractor_serializer::<T>().map_err(|e| {
+        let extractor = extractor_serializer::<T>(self.strict).map_err(|e| {
             error!(error = ?e, "Failed to serialize extractor");
             CompletionError::ParseError(format!("Failed to serialize extrator: {e}"))
         })?;
 
-        let request_body = json!({
+        let mut request_body = json!({
             "store": true,
             "model": self.model,
             "messages": messages,
-            "temperature": temperature,
-            "max_tokens": max_tokens,
             "response_format": extractor,
         });
+        self.insert_generation_limits(&mut request_body, temperature, max_tokens);
+        self.sampling_overrides
+            .or(self.sampling)
+            .insert_into(&mut request_body);
+        insert_extra_params(&self.extra_params, &mut request_body);
         debug!(request_body = ?request_body, "Sending extraction request");
 
         let response = self
-            .client
-            .post(&self.api_url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
+            .post()
             .json(&request_body)
             .send()
             .await
@@ -349,29 +611,119 @@ impl CompletionModel for OpenAICompletionModel {
             return Err(CompletionError::ProviderError(status.into(), error_msg));
         }
 
-        let response_json: serde_json::Value = response.json().await.map_err(|e| {
-            error!(error = ?e, "Failed to parse extraction response JSON");
-            CompletionError::ParseError(e.to_string())
-        })?;
+        let body = response
+            .text()
+            .await
+            .map_err(|e| CompletionError::RequestError(e.to_string()))?;
+        let response_json = parse_response_body(&body, self.capture_raw_response)?;
+        parse_extracted(&response_json)
+    }
 
-        let extracted_str = response_json["choices"][0]["message"]["content"]
-            .as_str()
-            .ok_or_else(|| {
-                error!("Missing content in extraction response");
-                CompletionError::ParseError("Missing content".to_string())
-            })?;
+    fn set_idempotency_key(&mut self, key: Option<String>) -> bool {
+        self.idempotency_key = key;
+        true
+    }
 
-        let extracted: T = serde_json::from_str(extracted_str)
-            .map_err(|e| {
-                error!(error = ?e, raw_response = %extracted_str, "Failed to deserialize extracted content");
-                CompletionError::ParseError(e.to_string())})?;
+    fn set_sampling_params(&mut self, params: SamplingParams) -> bool {
+        self.sampling_overrides = params;
+        true
+    }
 
-        info!(
-            extractor_type = std::any::type_name::<T>(),
-            "Successfully extracted data"
-        );
-        Ok(extracted)
+    fn provider_name(&self) -> &str {
+        "openai"
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+}
+
+/// Turns a server-sent events byte stream into a stream of [`StreamChunk`]s
+fn sse_stream<S, B>(bytes: S) -> CompletionStream
+where
+    S: Stream<Item = reqwest::Result<B>> + Send + Unpin + 'static,
+    B: AsRef<[u8]>,
+{
+    let state = (bytes, Vec::<u8>::new(), VecDeque::new());
+    Box::pin(futures::stream::unfold(
+        state,
+        |(mut bytes, mut buffer, mut pending)| async move {
+            loop {
+                if let Some(chunk) = pending.pop_front() {
+                    return Some((chunk, (bytes, buffer, pending)));
+                }
+                match bytes.next().await {
+                    Some(Ok(b)) => {
+                        buffer.extend_from_slice(b.as_ref());
+                        while let Some(pos) = buffer.iter().position(|&c| c == b'\n') {
+                            let line: Vec<u8> = buffer.drain(..=pos).collect();
+                            let line = String::from_utf8_lossy(&line);
+                            if let Some(data) = line.trim().strip_prefix("data:") {
+                                pending.extend(parse_stream_event(data.trim()));
+                            }
+                        }
+                    }
+                    Some(Err(e)) => {
+                        error!(error = ?e, "Failed to read response stream");
+                        return Some((
+                            Err(CompletionError::RequestError(e.to_string())),
+                            (bytes, buffer, pending),
+                        ));
+                    }
+                    None => return None,
+                }
+            }
+        },
+    ))
+}
+
+/// Parses the data of a single streamed event into [`StreamChunk`]s
+fn parse_stream_event(data: &str) -> Vec<Result<StreamChunk, CompletionError>> {
+    if data == "[DONE]" {
+        return vec![];
+    }
+    let event: serde_json::Value = match serde_json::from_str(data) {
+        Ok(v) => v,
+        Err(e) => {
+            error!(error = ?e, "Failed to parse stream event");
+            return vec![Err(CompletionError::ParseError(e.to_string()))];
+        }
+    };
+
+    let mut chunks = vec![];
+    let delta = &event["choices"][0]["delta"];
+    if let Some(text) = delta["content"].as_str() {
+        if !text.is_empty() {
+            chunks.push(Ok(StreamChunk::Text(text.to_string())));
+        }
+    }
+    if let Some(calls) = delta["tool_calls"].as_array() {
+        for (i, call) in calls.iter().enumerate() {
+            chunks.push(Ok(StreamChunk::ToolCallDelta {
+                index: call["index"]
+                    .as_u64()
+                    .and_then(|i| usize::try_from(i).ok())
+                    .unwrap_or(i),
+                id: call["id"].as_str().map(ToString::to_string),
+                name: call["function"]["name"].as_str().map(ToString::to_string),
+                arguments: call["function"]["arguments"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            }));
+        }
+    }
+    if event["usage"].is_object() {
+        chunks.push(Ok(StreamChunk::Usage(parse_usage(&event))));
     }
+    chunks
+}
+
+/// Parses the `finish_reason` of the first choice in a completion response
+fn parse_finish_reason(response_json: &serde_json::Value) -> Option<FinishReason> {
+    response_json["choices"][0]["finish_reason"]
+        .as_str()
+        .map(FinishReason::from)
 }
 
 #[cfg(test)]
@@ -384,6 +736,116 @@ mod tests {
     use super::*;
     use crate::tools::{ExecutionStrategy, Tool, ToolArg, ToolError};
 
+    #[test]
+    fn message_names_serialize_when_present() {
+        let named = Message::User {
+            content: "hi".to_string(),
+            tool_responses: None,
+            name: Some("alice".to_string()),
+        };
+        let unnamed = Message::Assistant {
+            content: "hello".to_string(),
+            tool_calls: None,
+            name: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(OpenAIMessage::from(named)).unwrap(),
+            json!({"role": "user", "content": "hi", "name": "alice"})
+        );
+        assert_eq!(
+            serde_json::to_value(OpenAIMessage::from(unnamed)).unwrap(),
+            json!({"role": "assistant", "content": "hello"})
+        );
+    }
+
+    #[test]
+    fn tool_messages_serialize_to_openai_format() {
+        let call = Message::Assistant {
+            content: String::new(),
+            tool_calls: Some(vec![ToolCall {
+                id: "call_1".to_string(),
+                name: "tell_joke".to_string(),
+                arguments: r#""{\"lang\":\"en\"}""#.to_string(),
+            }]),
+            name: None,
+        };
+        let response = Message::Tool(ToolResponse {
+            id: "call_1".to_string(),
+            name: "tell_joke".to_string(),
+            content: Value::from("a joke"),
+            images: vec![],
+        });
+
+        assert_eq!(
+            serde_json::to_value(OpenAIMessage::from(call)).unwrap(),
+            json!({
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": {"name": "tell_joke", "arguments": r#"{"lang":"en"}"#}
+                }]
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(OpenAIMessage::from(response)).unwrap(),
+            json!({"role": "tool", "tool_call_id": "call_1", "content": "a joke"})
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_assembles_fragmented_tool_calls() {
+        let events = concat!(
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":null,",
+            "\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"type\":\"function\",",
+            "\"function\":{\"name\":\"tell_joke\",\"arguments\":\"\"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,",
+            "\"function\":{\"arguments\":\"{\\\"la\"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,",
+            "\"function\":{\"arguments\":\"ng\\\":\\\"en\\\"}\"}}]}}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":5,",
+            "\"completion_tokens\":3,\"total_tokens\":8}}\n\n",
+            "data: [DONE]\n\n",
+        );
+        // split the events at arbitrary points to simulate network chunking
+        let bytes: Vec<reqwest::Result<Vec<u8>>> = events
+            .as_bytes()
+            .chunks(7)
+            .map(|c| Ok(c.to_vec()))
+            .collect();
+        let chunks: Vec<StreamChunk> = sse_stream(futures::stream::iter(bytes))
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        let mut acc = crate::completion::ToolCallAccumulator::default();
+        let mut usage = None;
+        for chunk in chunks {
+            match chunk {
+                StreamChunk::ToolCallDelta {
+                    index,
+                    id,
+                    name,
+                    arguments,
+                } => acc.push(index, id, name, &arguments),
+                StreamChunk::Usage(u) => usage = Some(u),
+                StreamChunk::Text(_) => panic!("unexpected text chunk"),
+            }
+        }
+        let calls = acc.finish().unwrap();
+        assert_eq!(
+            calls,
+            vec![ToolCall {
+                id: "call_1".to_string(),
+                name: "tell_joke".to_string(),
+                arguments: Value::from(r#"{"lang":"en"}"#).to_string(),
+            }]
+        );
+        assert_eq!(usage.unwrap().total_tokens, Some(8));
+    }
+
     #[tokio::test]
     #[ignore]
     async fn simple_openai_completion_request() {
@@ -398,6 +860,7 @@ For this test to be considered successful, reply with "okay" without the quotes,
 "#
                     .to_string(),
                     tool_responses: None,
+                    name: None,
                 },
                 &vec![],
                 None,
@@ -411,7 +874,8 @@ For this test to be considered successful, reply with "okay" without the quotes,
         assert!(response.clone().is_ok_and(|v| v.0
             == Message::Assistant {
                 content: "okay".to_string(),
-                tool_calls: None
+                tool_calls: None,
+                name: None,
             }));
         assert!(response.is_ok_and(|v| matches!(
             v.1,
@@ -432,6 +896,7 @@ For this test to be considered successful, reply with "okay" without the quotes,
                 Message::User {
                     content: "Tell me a joke in Farsi".to_string(),
                     tool_responses: None,
+                    name: None,
                 },
                 &vec![],
                 Some(&get_tools()),
@@ -445,7 +910,8 @@ For this test to be considered successful, reply with "okay" without the quotes,
             response.unwrap().0,
             Message::Assistant {
                 content: _,
-                tool_calls: Some(_)
+                tool_calls: Some(_),
+                ..
             }
         ));
     }
@@ -537,4 +1003,223 @@ For this test to be considered successful, reply with "okay" without the quotes,
             ExecutionStrategy::FailEarly,
         )
     }
+
+    #[test]
+    fn test_parse_finish_reason() {
+        for (reason, expected) in [
+            ("stop", FinishReason::Stop),
+            ("length", FinishReason::Length),
+            ("tool_calls", FinishReason::ToolCalls),
+            ("content_filter", FinishReason::ContentFilter),
+        ] {
+            let response = json!({"choices": [{"finish_reason": reason}]});
+            assert_eq!(parse_finish_reason(&response), Some(expected));
+        }
+        assert_eq!(parse_finish_reason(&json!({"choices": []})), None);
+    }
+
+    async fn completion_server() -> wiremock::MockServer {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .respond_with(wiremock::ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{"message": {"content": "ok"}, "finish_reason": "stop"}],
+                "usage": {"prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2}
+            })))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn injected_http_client_is_used() {
+        let server = completion_server().await;
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-seedframe-client", "injected".parse().unwrap());
+        let http_client = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()
+            .unwrap();
+        let mut model = OpenAICompletionModel::with_endpoint(
+            Some("key".to_string()),
+            server.uri(),
+            DEFAULT_MODEL.to_string(),
+            true,
+            HashMap::new(),
+        )
+        .with_http_client(http_client);
+
+        let output = model
+            .send_detailed(
+                Message::User {
+                    content: "hi".to_string(),
+                    tool_responses: None,
+                    name: None,
+                },
+                &vec![],
+                None,
+                0.0,
+                10,
+            )
+            .await
+            .unwrap();
+        assert_eq!(output.finish_reason, Some(FinishReason::Stop));
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(
+            requests[0].headers.get("x-seedframe-client").unwrap(),
+            "injected"
+        );
+    }
+
+    #[test]
+    fn sampling_params_are_sent_only_when_set() {
+        let message = Message::User {
+            content: "hi".to_string(),
+            tool_responses: None,
+            name: None,
+        };
+        let mut model = OpenAICompletionModel::with_endpoint(
+            None,
+            URL.to_string(),
+            DEFAULT_MODEL.to_string(),
+            true,
+            HashMap::new(),
+        );
+        let params = [
+            "top_p",
+            "frequency_penalty",
+            "presence_penalty",
+            "seed",
+            "reasoning_effort",
+        ];
+
+        let body = model.request_body(message.clone(), &vec![], None, 1.0, 10);
+        assert!(params.iter().all(|param| body.get(param).is_none()));
+
+        model = model.with_sampling_params(SamplingParams {
+            top_p: Some(0.9),
+            frequency_penalty: Some(0.5),
+            presence_penalty: Some(-0.5),
+            seed: Some(42),
+            reasoning_effort: None,
+        });
+        let body = model.request_body(message.clone(), &vec![], None, 1.0, 10);
+        assert_eq!(body["top_p"], json!(0.9));
+        assert_eq!(body["seed"], json!(42));
+        assert_eq!(body["frequency_penalty"], json!(0.5));
+        assert_eq!(body["presence_penalty"], json!(-0.5));
+
+        model.set_sampling_params(SamplingParams {
+            top_p: Some(0.1),
+            ..SamplingParams::default()
+        });
+        let body = model.request_body(message.clone(), &vec![], None, 1.0, 10);
+        assert_eq!(body["top_p"], json!(0.1));
+        assert_eq!(body["frequency_penalty"], json!(0.5));
+        assert!(body.get("reasoning_effort").is_none());
+
+        model.set_sampling_params(SamplingParams {
+            reasoning_effort: Some(ReasoningEffort::High),
+            ..SamplingParams::default()
+        });
+        let body = model.request_body(message, &vec![], None, 1.0, 10);
+        assert_eq!(body["reasoning_effort"], json!("high"));
+    }
+
+    #[test]
+    fn preamble_uses_the_developer_role_for_reasoning_models() {
+        let message = Message::User {
+            content: "hi".to_string(),
+            tool_responses: None,
+            name: None,
+        };
+        let history = vec![Message::Preamble("be brief".to_string())];
+        let model = |name: &str| {
+            OpenAICompletionModel::with_endpoint(
+                None,
+                URL.to_string(),
+                name.to_string(),
+                true,
+                HashMap::new(),
+            )
+        };
+        let preamble_role = |model: &OpenAICompletionModel| {
+            model.request_body(message.clone(), &history, None, 1.0, 10)["messages"][0]["role"]
+                .clone()
+        };
+
+        assert_eq!(preamble_role(&model(DEFAULT_MODEL)), json!("system"));
+        assert_eq!(preamble_role(&model("o3-mini")), json!("developer"));
+        assert_eq!(
+            preamble_role(&model(DEFAULT_MODEL).with_developer_role(true)),
+            json!("developer")
+        );
+        assert_eq!(
+            preamble_role(&model("o1").with_developer_role(false)),
+            json!("system")
+        );
+    }
+
+    #[test]
+    fn reasoning_models_take_max_completion_tokens_without_temperature() {
+        let message = Message::User {
+            content: "hi".to_string(),
+            tool_responses: None,
+            name: None,
+        };
+        let body = |name: &str| {
+            OpenAICompletionModel::with_endpoint(
+                None,
+                URL.to_string(),
+                name.to_string(),
+                true,
+                HashMap::new(),
+            )
+            .request_body(message.clone(), &vec![], None, 0.5, 10)
+        };
+
+        let body_4o = body(DEFAULT_MODEL);
+        assert_eq!(body_4o["temperature"], json!(0.5));
+        assert_eq!(body_4o["max_tokens"], json!(10));
+        assert!(body_4o.get("max_completion_tokens").is_none());
+
+        let body_o3 = body("o3-mini");
+        assert_eq!(body_o3["max_completion_tokens"], json!(10));
+        assert!(body_o3.get("max_tokens").is_none());
+        assert!(body_o3.get("temperature").is_none());
+    }
+
+    #[tokio::test]
+    async fn idempotency_key_is_sent_as_header() {
+        use crate::tools::ExecutionStrategy;
+
+        let server = completion_server().await;
+
+        let model = OpenAICompletionModel::with_endpoint(
+            Some("key".to_string()),
+            server.uri(),
+            DEFAULT_MODEL.to_string(),
+            true,
+            HashMap::new(),
+        );
+        let mut client = model.build_client(
+            "preamble",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+        client
+            .prompt("hi")
+            .idempotency_key("request-1")
+            .send()
+            .await
+            .unwrap();
+        client.prompt("hi again").send().await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(
+            requests[0].headers.get("idempotency-key").unwrap(),
+            "request-1"
+        );
+        assert!(requests[1].headers.get("idempotency-key").is_none());
+    }
 }
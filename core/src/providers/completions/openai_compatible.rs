@@ -0,0 +1,406 @@
+use crate::completion::{
+    warn_unknown_config_keys, Client, CompletionError, CompletionModel, CompletionOutput,
+    CompletionStream, Extractor, Message, MessageHistory, ReasoningEffort, SamplingParams,
+    TokenUsage,
+};
+use crate::embeddings::Embedder;
+use crate::tools::ToolSet;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::{error, instrument};
+
+use super::openai::{OpenAICompletionModel, DEFAULT_TEMP, DEFAULT_TOKENS};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ModelConfig {
+    api_url: String,
+    model: String,
+    api_key_var: Option<String>,
+    headers: Option<HashMap<String, String>>,
+    strict: Option<bool>,
+    capture_raw_response: Option<bool>,
+    top_p: Option<f64>,
+    frequency_penalty: Option<f64>,
+    presence_penalty: Option<f64>,
+    reasoning_effort: Option<ReasoningEffort>,
+    developer_role: Option<bool>,
+    /// Unknown keys, passed through to the request body, eg. `logit_bias`
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A completion model for any endpoint implementing the OpenAI chat completions API,
+/// eg. Groq, Together, Fireworks, OpenRouter or a local vLLM server.
+///
+/// Unlike the other providers there are no defaults, the `api_url` and `model` have to be set
+/// in the config. The API key is read from the env var named by `api_key_var`, no
+/// `Authorization` header is sent if it's omitted. Entries in `headers` are sent with every
+/// request. Set `strict` to `false` for endpoints rejecting strict tool schemas and
+/// `capture_raw_response` to `true` to get the raw body in errors for unparsable responses.
+/// `top_p`, `frequency_penalty` and `presence_penalty` set the default sampling params.
+/// `developer_role` sends the preamble with the `developer` role instead of `system`, it
+/// defaults to `true` for o-series model names. Any other keys, eg. `logit_bias`, are added
+/// to the body of every request.
+///
+/// # Examples
+///
+/// Groq:
+/// ```ignore
+/// #[client(
+///     provider = "OpenAICompatible",
+///     config = r#"{
+///       "api_url": "https://api.groq.com/openai/v1/chat/completions",
+///       "api_key_var": "GROQ_API_KEY",
+///       "model": "llama-3.3-70b-versatile"
+///     }"#
+/// )]
+/// struct GroqClient;
+/// ```
+///
+/// Together:
+/// ```ignore
+/// #[client(
+///     provider = "OpenAICompatible",
+///     config = r#"{
+///       "api_url": "https://api.together.xyz/v1/chat/completions",
+///       "api_key_var": "TOGETHER_API_KEY",
+///       "model": "meta-llama/Llama-3.3-70B-Instruct-Turbo",
+///       "strict": false
+///     }"#
+/// )]
+/// struct TogetherClient;
+/// ```
+pub struct OpenAICompatibleCompletionModel(OpenAICompletionModel);
+
+impl OpenAICompatibleCompletionModel {
+    /// Creates a new model from its json config
+    ///
+    /// # Panics
+    /// If no config is passed, the config is malformed or the env var named by `api_key_var`
+    /// isn't set
+    #[instrument]
+    #[must_use]
+    pub fn new(json_config: Option<&str>) -> Self {
+        let Some(json) = json_config else {
+            let e = "Expected a json config with atleast the `api_url` and `model` fields!";
+            error!(e);
+            panic!("{e}");
+        };
+        let config = match serde_json::from_str::<ModelConfig>(json) {
+            Ok(config) => config,
+            Err(e) => {
+                let e = format!("Failed to deserialize json config: {e}");
+                error!(e);
+                panic!("{e}");
+            }
+        };
+        warn_unknown_config_keys(&config.extra);
+        let api_key = config
+            .api_key_var
+            .map(|api_key_var| match std::env::var(&api_key_var) {
+                Ok(key) => key,
+                Err(e) => {
+                    let e = format!("Failed to fetch env var `{api_key_var}`!, {e}");
+                    error!(e);
+                    panic!("{e}");
+                }
+            });
+        let model = Self(
+            OpenAICompletionModel::with_endpoint(
+                api_key,
+                config.api_url,
+                config.model,
+                config.strict.unwrap_or(true),
+                config.headers.unwrap_or_default(),
+            )
+            .with_raw_response_capture(config.capture_raw_response.unwrap_or(false))
+            .with_sampling_params(SamplingParams {
+                top_p: config.top_p,
+                frequency_penalty: config.frequency_penalty,
+                presence_penalty: config.presence_penalty,
+                reasoning_effort: config.reasoning_effort,
+                ..SamplingParams::default()
+            })
+            .with_extra_params(config.extra),
+        );
+        match config.developer_role {
+            Some(developer_role) => model.with_developer_role(developer_role),
+            None => model,
+        }
+    }
+
+    /// Replaces the HTTP client used for requests to the endpoint, eg. to share a connection
+    /// pool with other providers and embedders talking to the same host.
+    /// A new client is created for every instance by default.
+    #[must_use]
+    pub fn with_http_client(self, client: reqwest::Client) -> Self {
+        Self(self.0.with_http_client(client))
+    }
+
+    /// Includes a truncated snippet of the raw response body in the error when a response
+    /// fails to parse, for debugging. Disabled by default, also settable with the
+    /// `capture_raw_response` config field.
+    #[must_use]
+    pub fn with_raw_response_capture(self, capture: bool) -> Self {
+        Self(self.0.with_raw_response_capture(capture))
+    }
+
+    /// Sets the default sampling params of every request, also settable with the `top_p`,
+    /// `frequency_penalty` and `presence_penalty` config fields
+    #[must_use]
+    pub fn with_sampling_params(self, params: SamplingParams) -> Self {
+        Self(self.0.with_sampling_params(params))
+    }
+
+    /// Adds `params` to the body of every request, for parameters without a dedicated setting,
+    /// eg. `logit_bias`. Unknown keys of the json config are added the same way.
+    #[must_use]
+    pub fn with_extra_params(self, params: serde_json::Map<String, serde_json::Value>) -> Self {
+        Self(self.0.with_extra_params(params))
+    }
+
+    /// Sends the preamble with the `developer` role instead of `system`. Enabled by default for
+    /// o-series model names (`o1`, `o3-mini`, ...), also settable with the `developer_role`
+    /// config field.
+    #[must_use]
+    pub fn with_developer_role(self, developer_role: bool) -> Self {
+        Self(self.0.with_developer_role(developer_role))
+    }
+}
+
+#[allow(refining_impl_trait)]
+#[async_trait]
+impl CompletionModel for OpenAICompatibleCompletionModel {
+    fn build_client(
+        self,
+        preamble: impl AsRef<str>,
+        embedder_instances: Vec<Embedder>,
+        tools: ToolSet,
+    ) -> Client<Self> {
+        Client::new(
+            self,
+            preamble,
+            DEFAULT_TEMP,
+            DEFAULT_TOKENS,
+            embedder_instances,
+            tools,
+        )
+    }
+
+    async fn send(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<(Message, TokenUsage), CompletionError> {
+        self.0
+            .send(message, history, tools, temperature, max_tokens)
+            .await
+    }
+
+    fn build_request_body(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<serde_json::Value, CompletionError> {
+        self.0
+            .build_request_body(message, history, tools, temperature, max_tokens)
+    }
+
+    async fn send_detailed(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionOutput, CompletionError> {
+        self.0
+            .send_detailed(message, history, tools, temperature, max_tokens)
+            .await
+    }
+
+    async fn stream(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionStream, CompletionError> {
+        self.0
+            .stream(message, history, tools, temperature, max_tokens)
+            .await
+    }
+
+    async fn extract<T: Extractor>(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<T, CompletionError> {
+        self.0
+            .extract(message, history, temperature, max_tokens)
+            .await
+    }
+
+    fn set_idempotency_key(&mut self, key: Option<String>) -> bool {
+        self.0.set_idempotency_key(key)
+    }
+
+    fn set_sampling_params(&mut self, params: SamplingParams) -> bool {
+        self.0.set_sampling_params(params)
+    }
+
+    fn provider_name(&self) -> &str {
+        "openai_compatible"
+    }
+
+    fn model_name(&self) -> &str {
+        self.0.model_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn completion_server(endpoint: &str) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(endpoint))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{"message": {"content": "ok"}, "finish_reason": "stop"}],
+                "usage": {"prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2}
+            })))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn requests_go_to_the_configured_endpoint() {
+        let server = completion_server("/openai/v1/chat/completions").await;
+
+        std::env::set_var("SEEDFRAME_TEST_COMPATIBLE_API_KEY", "compat-key");
+        let config = json!({
+            "api_url": format!("{}/openai/v1/chat/completions", server.uri()),
+            "api_key_var": "SEEDFRAME_TEST_COMPATIBLE_API_KEY",
+            "model": "llama-3.3-70b-versatile",
+            "headers": {"X-Title": "seedframe"}
+        })
+        .to_string();
+        let mut model = OpenAICompatibleCompletionModel::new(Some(&config));
+
+        let output = model
+            .send_detailed(
+                Message::User {
+                    content: "hi".to_string(),
+                    tool_responses: None,
+                    name: None,
+                },
+                &vec![],
+                None,
+                0.0,
+                10,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            output.message,
+            Message::Assistant {
+                content: "ok".to_string(),
+                tool_calls: None,
+                name: None,
+            }
+        );
+
+        let request = &server.received_requests().await.unwrap()[0];
+        assert_eq!(
+            request.headers.get("authorization").unwrap(),
+            "Bearer compat-key"
+        );
+        assert_eq!(request.headers.get("x-title").unwrap(), "seedframe");
+    }
+
+    #[tokio::test]
+    async fn unknown_config_keys_are_sent_in_the_body() {
+        let server = completion_server("/v1/chat/completions").await;
+
+        let config = json!({
+            "api_url": format!("{}/v1/chat/completions", server.uri()),
+            "model": "llama-3.3-70b-versatile",
+            "top_p": 0.5,
+            "seed": 7
+        })
+        .to_string();
+        let mut model = OpenAICompatibleCompletionModel::new(Some(&config));
+        model
+            .send(
+                Message::User {
+                    content: "hi".to_string(),
+                    tool_responses: None,
+                    name: None,
+                },
+                &vec![],
+                None,
+                0.0,
+                10,
+            )
+            .await
+            .unwrap();
+
+        let body: serde_json::Value = server.received_requests().await.unwrap()[0]
+            .body_json()
+            .unwrap();
+        assert_eq!(body["top_p"], json!(0.5));
+        assert_eq!(body["seed"], json!(7));
+        assert_eq!(body["model"], json!("llama-3.3-70b-versatile"));
+    }
+
+    #[test]
+    fn developer_role_config_sets_the_preamble_role() {
+        let preamble_role = |developer_role: bool| {
+            let config = json!({
+                "api_url": "http://localhost/v1/chat/completions",
+                "model": "llama-3.3-70b-versatile",
+                "developer_role": developer_role
+            })
+            .to_string();
+            let body = OpenAICompatibleCompletionModel::new(Some(&config))
+                .build_request_body(
+                    Message::User {
+                        content: "hi".to_string(),
+                        tool_responses: None,
+                        name: None,
+                    },
+                    &vec![Message::Preamble("be brief".to_string())],
+                    None,
+                    0.0,
+                    10,
+                )
+                .unwrap();
+            assert!(body.get("developer_role").is_none());
+            body["messages"][0]["role"].clone()
+        };
+
+        assert_eq!(preamble_role(true), json!("developer"));
+        assert_eq!(preamble_role(false), json!("system"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to deserialize json config")]
+    fn config_requires_api_url_and_model() {
+        let _ = OpenAICompatibleCompletionModel::new(Some(r#"{"model": "llama"}"#));
+    }
+}
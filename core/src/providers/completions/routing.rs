@@ -0,0 +1,326 @@
+use crate::completion::{
+    Client, CompletionError, CompletionModel, CompletionOutput, CompletionStream, Message,
+    MessageHistory, SamplingParams, TokenUsage,
+};
+use crate::embeddings::Embedder;
+use crate::tools::ToolSet;
+use async_trait::async_trait;
+use tracing::{error, instrument};
+
+use super::fallback::DynCompletionModel;
+use super::openai::{DEFAULT_TEMP, DEFAULT_TOKENS};
+
+/// A completion model routing each request to one of several named models
+///
+/// Requests go to the primary model unless a prompt picks another one with
+/// [`PromptBuilder::route_to`], eg. to send simple prompts to a cheaper model.
+/// Request options like sampling params are forwarded to the routed model. Structured
+/// extraction isn't supported, as it can't be dispatched to boxed models.
+///
+/// [`PromptBuilder::route_to`]: crate::completion::PromptBuilder::route_to
+///
+/// # Example
+/// ```rust,ignore
+/// let model = RoutingCompletionModel::new("cheap", Box::new(DeepSeek::new(None)))
+///     .route("smart", Box::new(OpenAI::new(None)));
+/// let mut client = model.build_client("You're a helpful assistant", vec![], tools);
+/// client.prompt("Prove the theorem").route_to("smart").send().await?;
+/// ```
+pub struct RoutingCompletionModel {
+    models: Vec<(String, Box<dyn DynCompletionModel>)>,
+    route: Option<String>,
+}
+
+impl RoutingCompletionModel {
+    /// Creates a router sending requests to `primary`, named `name`, by default
+    #[must_use]
+    pub fn new(name: impl Into<String>, primary: Box<dyn DynCompletionModel>) -> Self {
+        Self {
+            models: vec![(name.into(), primary)],
+            route: None,
+        }
+    }
+
+    /// Adds `model` under `name`, replacing the model already routed as `name` if any
+    #[must_use]
+    pub fn route(mut self, name: impl Into<String>, model: Box<dyn DynCompletionModel>) -> Self {
+        let name = name.into();
+        match self.models.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) => *existing = model,
+            None => self.models.push((name, model)),
+        }
+        self
+    }
+
+    /// Names of the routes, the primary one first
+    pub fn routes(&self) -> impl Iterator<Item = &str> {
+        self.models.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Index of the model the current route points to
+    fn routed_index(&self) -> Result<usize, CompletionError> {
+        let Some(route) = &self.route else {
+            return Ok(0);
+        };
+        self.models
+            .iter()
+            .position(|(name, _)| name == route)
+            .ok_or_else(|| {
+                error!(route, "No model routed under the requested name");
+                CompletionError::RequestError(format!("No model routed as `{route}`"))
+            })
+    }
+
+    /// The model the current route points to
+    fn routed_model(&mut self) -> Result<&mut Box<dyn DynCompletionModel>, CompletionError> {
+        let index = self.routed_index()?;
+        Ok(&mut self.models[index].1)
+    }
+}
+
+#[allow(refining_impl_trait)]
+#[async_trait]
+impl CompletionModel for RoutingCompletionModel {
+    fn build_client(
+        self,
+        preamble: impl AsRef<str>,
+        embedder_instances: Vec<Embedder>,
+        tools: ToolSet,
+    ) -> Client<Self> {
+        Client::new(
+            self,
+            preamble,
+            DEFAULT_TEMP,
+            DEFAULT_TOKENS,
+            embedder_instances,
+            tools,
+        )
+    }
+
+    async fn send(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<(Message, TokenUsage), CompletionError> {
+        self.send_detailed(message, history, tools, temperature, max_tokens)
+            .await
+            .map(|output| (output.message, output.token_usage))
+    }
+
+    #[instrument(skip_all, fields(route = self.model_name()))]
+    async fn send_detailed(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionOutput, CompletionError> {
+        self.routed_model()?
+            .dyn_send(message, history, tools, temperature, max_tokens)
+            .await
+    }
+
+    #[instrument(skip_all, fields(route = self.model_name()))]
+    async fn stream(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionStream, CompletionError> {
+        self.routed_model()?
+            .dyn_stream(message, history, tools, temperature, max_tokens)
+            .await
+    }
+
+    fn build_request_body(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<serde_json::Value, CompletionError> {
+        self.models[self.routed_index()?].1.dyn_build_request_body(
+            message,
+            history,
+            tools,
+            temperature,
+            max_tokens,
+        )
+    }
+
+    fn set_sampling_params(&mut self, params: SamplingParams) -> bool {
+        self.routed_model()
+            .is_ok_and(|model| model.dyn_set_sampling_params(params))
+    }
+
+    fn set_idempotency_key(&mut self, key: Option<String>) -> bool {
+        self.routed_model()
+            .is_ok_and(|model| model.dyn_set_idempotency_key(key))
+    }
+
+    fn set_route(&mut self, route: Option<String>) -> bool {
+        self.route = route;
+        true
+    }
+
+    fn provider_name(&self) -> &str {
+        "routing"
+    }
+
+    /// Name of the current route, so metrics and caches tell the routed models apart
+    fn model_name(&self) -> &str {
+        self.route.as_deref().unwrap_or(&self.models[0].0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ExecutionStrategy;
+    use std::sync::{Arc, Mutex};
+
+    struct RecordingModel {
+        reply: &'static str,
+        requests: Arc<Mutex<Vec<Message>>>,
+        sampling: SamplingParams,
+    }
+
+    #[allow(refining_impl_trait)]
+    #[async_trait]
+    impl CompletionModel for RecordingModel {
+        fn build_client(
+            self,
+            preamble: impl AsRef<str>,
+            embedder_instances: Vec<Embedder>,
+            tools: ToolSet,
+        ) -> Client<Self> {
+            Client::new(self, preamble, 0.0, 100, embedder_instances, tools)
+        }
+
+        async fn send(
+            &mut self,
+            message: Message,
+            _history: &MessageHistory,
+            _tools: Option<&ToolSet>,
+            _temperature: f64,
+            _max_tokens: usize,
+        ) -> Result<(Message, TokenUsage), CompletionError> {
+            self.requests.lock().unwrap().push(message);
+            Ok((
+                Message::Assistant {
+                    content: self.reply.to_string(),
+                    tool_calls: None,
+                    name: None,
+                },
+                TokenUsage::default(),
+            ))
+        }
+
+        fn build_request_body(
+            &self,
+            _message: Message,
+            _history: &MessageHistory,
+            _tools: Option<&ToolSet>,
+            _temperature: f64,
+            _max_tokens: usize,
+        ) -> Result<serde_json::Value, CompletionError> {
+            Ok(serde_json::json!({ "model": self.reply, "top_p": self.sampling.top_p }))
+        }
+
+        fn set_sampling_params(&mut self, params: SamplingParams) -> bool {
+            self.sampling = params;
+            true
+        }
+    }
+
+    fn recording(reply: &'static str) -> (Box<RecordingModel>, Arc<Mutex<Vec<Message>>>) {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let model = RecordingModel {
+            reply,
+            requests: Arc::clone(&requests),
+            sampling: SamplingParams::default(),
+        };
+        (Box::new(model), requests)
+    }
+
+    #[tokio::test]
+    async fn test_prompts_are_routed_to_the_chosen_model() {
+        let (cheap, cheap_requests) = recording("cheap answer");
+        let (smart, smart_requests) = recording("smart answer");
+        let model = RoutingCompletionModel::new("cheap", cheap).route("smart", smart);
+        let mut client = model.build_client(
+            "preamble",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+
+        let reply = |content: &str| Message::Assistant {
+            content: content.to_string(),
+            tool_calls: None,
+            name: None,
+        };
+        assert_eq!(
+            client.prompt("simple").send().await.unwrap(),
+            reply("cheap answer")
+        );
+        assert_eq!(
+            client
+                .prompt("complex")
+                .route_to("smart")
+                .send()
+                .await
+                .unwrap(),
+            reply("smart answer")
+        );
+        assert_eq!(
+            client.prompt("simple again").send().await.unwrap(),
+            reply("cheap answer")
+        );
+        assert_eq!(cheap_requests.lock().unwrap().len(), 2);
+        assert_eq!(smart_requests.lock().unwrap().len(), 1);
+
+        let result = client.prompt("lost").route_to("missing").send().await;
+        assert!(matches!(
+            result,
+            Err(crate::error::Error::Completion(
+                CompletionError::RequestError(_)
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_request_options_reach_the_routed_model() {
+        let (cheap, _) = recording("cheap");
+        let (smart, _) = recording("smart");
+        let model = RoutingCompletionModel::new("cheap", cheap).route("smart", smart);
+        let mut client = model.build_client(
+            "preamble",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+
+        let body = client
+            .prompt("complex")
+            .route_to("smart")
+            .top_p(0.3)
+            .dry_run()
+            .await
+            .unwrap();
+        assert_eq!(body, serde_json::json!({ "model": "smart", "top_p": 0.3 }));
+
+        let body = client.prompt("simple").dry_run().await.unwrap();
+        assert_eq!(body, serde_json::json!({ "model": "cheap", "top_p": null }));
+        let body = client.prompt("complex").route_to("smart").dry_run().await;
+        assert_eq!(
+            body.unwrap(),
+            serde_json::json!({ "model": "smart", "top_p": null })
+        );
+    }
+}
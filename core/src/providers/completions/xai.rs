@@ -1,6 +1,8 @@
 use crate::completion::{
-    serialize_assistant, serialize_user, Client, CompletionError, CompletionModel, Message,
-    MessageHistory, TokenUsage,
+    extractor_serializer, insert_extra_params, parse_extracted, parse_response_body, parse_usage,
+    serialize_tool_calls, serialize_tool_content, warn_unknown_config_keys, Client,
+    CompletionError, CompletionModel, CompletionOutput, Extractor, FinishReason, Message,
+    MessageHistory, ReasoningEffort, SamplingParams, TokenUsage,
 };
 use crate::embeddings::Embedder;
 use crate::tools::{ToolCall, ToolResponse, ToolSet};
@@ -17,11 +19,19 @@ const DEFAULT_TOKENS: usize = 2400;
 const DEFAULT_MODEL: &str = "grok-2-latest";
 
 #[derive(Serialize, Deserialize, Debug)]
-#[serde(deny_unknown_fields)]
 struct ModelConfig {
     api_key: Option<String>,
     api_url: Option<String>,
     model: Option<String>,
+    strict: Option<bool>,
+    capture_raw_response: Option<bool>,
+    top_p: Option<f64>,
+    frequency_penalty: Option<f64>,
+    presence_penalty: Option<f64>,
+    reasoning_effort: Option<ReasoningEffort>,
+    /// Unknown keys, passed through to the request body, eg. `logprobs`
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[allow(clippy::module_name_repetitions)]
@@ -30,33 +40,54 @@ pub struct XaiCompletionModel {
     api_url: String,
     client: reqwest::Client,
     model: String,
+    strict: bool,
+    capture_raw_response: bool,
+    sampling: SamplingParams,
+    sampling_overrides: SamplingParams,
+    extra_params: serde_json::Map<String, serde_json::Value>,
 }
 
 impl XaiCompletionModel {
     #[instrument]
     #[must_use]
     pub fn new(json_config: Option<&str>) -> Self {
-        let (api_key_var, api_url, model) = if let Some(json) = json_config {
-            let config = match serde_json::from_str::<ModelConfig>(json) {
-                Ok(config) => config,
-                Err(e) => {
-                    let e = format!("Failed to deserialize json config: {e}");
-                    error!(e);
-                    panic!("{e}");
-                }
+        let (api_key_var, api_url, model, strict, capture_raw_response, sampling, extra_params) =
+            if let Some(json) = json_config {
+                let config = match serde_json::from_str::<ModelConfig>(json) {
+                    Ok(config) => config,
+                    Err(e) => {
+                        let e = format!("Failed to deserialize json config: {e}");
+                        error!(e);
+                        panic!("{e}");
+                    }
+                };
+                warn_unknown_config_keys(&config.extra);
+                (
+                    config.api_key.unwrap_or(API_KEY_ENV_VAR.to_string()),
+                    config.api_url.unwrap_or(URL.to_string()),
+                    config.model.unwrap_or(DEFAULT_MODEL.to_string()),
+                    config.strict.unwrap_or(true),
+                    config.capture_raw_response.unwrap_or(false),
+                    SamplingParams {
+                        top_p: config.top_p,
+                        frequency_penalty: config.frequency_penalty,
+                        presence_penalty: config.presence_penalty,
+                        reasoning_effort: config.reasoning_effort,
+                        ..SamplingParams::default()
+                    },
+                    config.extra,
+                )
+            } else {
+                (
+                    API_KEY_ENV_VAR.to_string(),
+                    URL.to_string(),
+                    DEFAULT_MODEL.to_string(),
+                    true,
+                    false,
+                    SamplingParams::default(),
+                    serde_json::Map::new(),
+                )
             };
-            (
-                config.api_key.unwrap_or(API_KEY_ENV_VAR.to_string()),
-                config.api_url.unwrap_or(URL.to_string()),
-                config.model.unwrap_or(DEFAULT_MODEL.to_string()),
-            )
-        } else {
-            (
-                API_KEY_ENV_VAR.to_string(),
-                URL.to_string(),
-                DEFAULT_MODEL.to_string(),
-            )
-        };
         let api_key = match std::env::var(&api_key_var) {
             Ok(key) => key,
             Err(e) => {
@@ -70,82 +101,121 @@ impl XaiCompletionModel {
             api_url,
             client: reqwest::Client::new(),
             model,
+            strict,
+            capture_raw_response,
+            sampling,
+            sampling_overrides: SamplingParams::default(),
+            extra_params,
         }
     }
+
+    /// Replaces the HTTP client used for requests to xAI, eg. to share a connection pool
+    /// with other providers and embedders talking to the same host.
+    /// A new client is created for every instance by default.
+    #[must_use]
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Includes a truncated snippet of the raw response body in the error when a response
+    /// fails to parse, for debugging. Disabled by default, also settable with the
+    /// `capture_raw_response` config field.
+    #[must_use]
+    pub fn with_raw_response_capture(mut self, capture: bool) -> Self {
+        self.capture_raw_response = capture;
+        self
+    }
+
+    /// Sets the default sampling params of every request, also settable with the `top_p`,
+    /// `frequency_penalty` and `presence_penalty` config fields
+    #[must_use]
+    pub fn with_sampling_params(mut self, params: SamplingParams) -> Self {
+        self.sampling = params;
+        self
+    }
+
+    /// Adds `params` to the body of every request, for parameters without a dedicated setting.
+    /// Params never replace the fields set by seedframe, like `model` or `messages`. Unknown
+    /// keys of the json config are added the same way.
+    #[must_use]
+    pub fn with_extra_params(mut self, params: serde_json::Map<String, serde_json::Value>) -> Self {
+        self.extra_params.extend(params);
+        self
+    }
 }
 
 #[derive(Serialize, Deserialize, Eq, PartialEq)]
-#[serde(tag = "role", content = "content")]
+#[serde(tag = "role")]
 #[allow(non_camel_case_types)]
 enum XaiMessage {
-    system(String),
-    #[serde(serialize_with = "serialize_user")]
+    system {
+        content: String,
+    },
     user {
         content: String,
+        #[serde(skip)]
         tool_responses: Option<Vec<ToolResponse>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
     },
-    #[serde(serialize_with = "serialize_assistant")]
     assistant {
         content: String,
+        #[serde(
+            skip_serializing_if = "Option::is_none",
+            serialize_with = "serialize_tool_calls"
+        )]
         tool_calls: Option<Vec<ToolCall>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+    tool {
+        tool_call_id: String,
+        #[serde(serialize_with = "serialize_tool_content")]
+        content: serde_json::Value,
     },
 }
 
 impl From<Message> for XaiMessage {
     fn from(value: Message) -> XaiMessage {
         match value {
-            Message::Preamble(s) => XaiMessage::system(s),
+            Message::Preamble(content) => XaiMessage::system { content },
             Message::User {
                 content,
                 tool_responses,
+                name,
             } => Self::user {
                 content,
                 tool_responses,
+                name,
             },
             Message::Assistant {
                 content,
                 tool_calls,
+                name,
             } => Self::assistant {
                 content,
                 tool_calls,
+                name,
+            },
+            // tool messages only take text, images are described in it
+            Message::Tool(response) => Self::tool {
+                content: serde_json::Value::String(response.text_content()),
+                tool_call_id: response.id,
             },
         }
     }
 }
 
-#[allow(refining_impl_trait)]
-#[async_trait]
-impl CompletionModel for XaiCompletionModel {
-    fn build_client(
-        self,
-        preamble: impl AsRef<str>,
-        embedder_instances: Vec<Embedder>,
-        tools: ToolSet,
-    ) -> Client<Self> {
-        Client::new(
-            self,
-            preamble,
-            DEFAULT_TEMP,
-            DEFAULT_TOKENS,
-            embedder_instances,
-            tools,
-        )
-    }
-
-    #[instrument(
-        skip(self, history, tools, temperature),
-        fields(
-            history_len = history.len(),
-            tools = tools.is_some())
-    )]
-    async fn send(
-        &mut self,
+impl XaiCompletionModel {
+    fn request_body(
+        &self,
         message: Message,
         history: &MessageHistory,
         tools: Option<&ToolSet>,
         temperature: f64,
         max_tokens: usize,
-    ) -> Result<(Message, TokenUsage), CompletionError> {
+    ) -> serde_json::Value {
         let mut messages = history.clone();
         messages.push(message);
         let messages: Vec<_> = messages.into_iter().map(Into::<XaiMessage>::into).collect();
@@ -156,10 +226,13 @@ impl CompletionModel for XaiCompletionModel {
             "temperature": temperature,
             "max_tokens": max_tokens,
         });
+        self.sampling_overrides
+            .or(self.sampling)
+            .insert_into(&mut request_body);
 
         if let Some(tools) = tools {
             let tools_serialized: Vec<serde_json::Value> =
-                tools.0.iter().map(|t| t.default_serializer()).collect();
+                tools.0.iter().map(|t| t.serializer(self.strict)).collect();
             if let Some(obj) = request_body.as_object_mut() {
                 info!(
                     tool_count = tools_serialized.len(),
@@ -171,14 +244,38 @@ impl CompletionModel for XaiCompletionModel {
                 );
             }
         }
-        debug!(request_body = ?request_body, "Sending request to Xai...");
+        insert_extra_params(&self.extra_params, &mut request_body);
+        request_body
+    }
+
+    /// Builds the body of an extraction request, constraining the output to the schema of `T`
+    fn extraction_request_body<T: Extractor>(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<serde_json::Value, CompletionError> {
+        let extractor = extractor_serializer::<T>(self.strict).map_err(|e| {
+            error!(error = ?e, "Failed to serialize extractor");
+            CompletionError::ParseError(format!("Failed to serialize extractor: {e}"))
+        })?;
+        let mut request_body = self.request_body(message, history, None, temperature, max_tokens);
+        request_body["response_format"] = extractor;
+        Ok(request_body)
+    }
 
+    /// Sends `request_body` to the API and parses the response
+    async fn post_request(
+        &self,
+        request_body: &serde_json::Value,
+    ) -> Result<serde_json::Value, CompletionError> {
         let response = self
             .client
             .post(&self.api_url)
             .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
-            .json(&request_body)
+            .json(request_body)
             .send()
             .await
             .map_err(|e| {
@@ -189,78 +286,7 @@ impl CompletionModel for XaiCompletionModel {
         let status = response.status();
         debug!(%status, "Received API response");
 
-        if status.is_success() {
-            let response_json: serde_json::Value = response.json().await.map_err(|e| {
-                error!(error = ?e, "Failed to parse response JSON");
-                CompletionError::ParseError(e.to_string())
-            })?;
-
-            let response_message = response_json["choices"][0]["message"]["content"]
-                .as_str()
-                .ok_or(CompletionError::ParseError(
-                    "Invalid response body".to_string(),
-                ))?
-                .to_string();
-
-            let tool_calls: Option<Vec<ToolCall>> = response_json["choices"][0]["message"]
-                ["tool_calls"]
-                .as_array()
-                .filter(|calls| !calls.is_empty())
-                .map(|calls| {
-                    let count = calls.len();
-                    let result = calls
-                        .iter()
-                        .map(|tc| {
-                            let id = tc["id"].as_str().unwrap().to_string();
-                            let name = tc["function"]["name"].as_str().unwrap().to_string();
-                            let arguments = tc["function"]["arguments"].clone().to_string();
-                            ToolCall {
-                                id,
-                                name,
-                                arguments,
-                            }
-                        })
-                        .collect();
-                    info!(tool_call_count = count, "Parsed tool calls");
-                    result
-                });
-
-            let usage_response = &response_json["usage"];
-            let usage_parse_error =
-                CompletionError::ParseError("Failed to parse usage data from response".to_string());
-            let token_usage = TokenUsage {
-                prompt_tokens: Some(
-                    usage_response["prompt_tokens"]
-                        .as_u64()
-                        .ok_or(usage_parse_error.clone())?,
-                ),
-                completion_tokens: Some(
-                    usage_response["completion_tokens"]
-                        .as_u64()
-                        .ok_or(usage_parse_error.clone())?,
-                ),
-                total_tokens: Some(
-                    usage_response["total_tokens"]
-                        .as_u64()
-                        .ok_or(usage_parse_error)?,
-                ),
-            };
-
-            info!(
-                prompt_tokens = token_usage.prompt_tokens,
-                completion_tokens = token_usage.completion_tokens,
-                total_tokens = token_usage.total_tokens,
-                "Token usage recorded"
-            );
-            Ok((
-                Message::Assistant {
-                    content: response_message,
-                    tool_calls,
-                },
-                token_usage,
-            ))
-        } else {
-            let status = response.status();
+        if !status.is_success() {
             let error_msg = response
                 .text()
                 .await
@@ -271,15 +297,189 @@ impl CompletionModel for XaiCompletionModel {
                 error = %error_msg,
                 "API returned error response"
             );
-            Err(CompletionError::ProviderError(status.into(), error_msg))?
+            return Err(CompletionError::ProviderError(status.into(), error_msg));
         }
+        let body = response
+            .text()
+            .await
+            .map_err(|e| CompletionError::RequestError(e.to_string()))?;
+        parse_response_body(&body, self.capture_raw_response)
+    }
+}
+
+#[allow(refining_impl_trait)]
+#[async_trait]
+impl CompletionModel for XaiCompletionModel {
+    fn build_client(
+        self,
+        preamble: impl AsRef<str>,
+        embedder_instances: Vec<Embedder>,
+        tools: ToolSet,
+    ) -> Client<Self> {
+        Client::new(
+            self,
+            preamble,
+            DEFAULT_TEMP,
+            DEFAULT_TOKENS,
+            embedder_instances,
+            tools,
+        )
+    }
+
+    async fn send(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<(Message, TokenUsage), CompletionError> {
+        self.send_detailed(message, history, tools, temperature, max_tokens)
+            .await
+            .map(|output| (output.message, output.token_usage))
+    }
+
+    fn build_request_body(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<serde_json::Value, CompletionError> {
+        Ok(self.request_body(message, history, tools, temperature, max_tokens))
+    }
+
+    #[instrument(
+        skip(self, history, tools, temperature),
+        fields(
+            history_len = history.len(),
+            tools = tools.is_some())
+    )]
+    async fn send_detailed(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionOutput, CompletionError> {
+        let request_body = self.request_body(message, history, tools, temperature, max_tokens);
+        debug!(request_body = ?request_body, "Sending request to Xai...");
+        let response_json = self.post_request(&request_body).await?;
+
+        let response_message = response_json["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or(CompletionError::ParseError(
+                "Invalid response body".to_string(),
+            ))?
+            .to_string();
+
+        let tool_calls: Option<Vec<ToolCall>> = response_json["choices"][0]["message"]
+            ["tool_calls"]
+            .as_array()
+            .filter(|calls| !calls.is_empty())
+            .map(|calls| {
+                let count = calls.len();
+                let result = calls
+                    .iter()
+                    .map(|tc| {
+                        let id = tc["id"].as_str().unwrap().to_string();
+                        let name = tc["function"]["name"].as_str().unwrap().to_string();
+                        let arguments = tc["function"]["arguments"].clone().to_string();
+                        ToolCall {
+                            id,
+                            name,
+                            arguments,
+                        }
+                    })
+                    .collect();
+                info!(tool_call_count = count, "Parsed tool calls");
+                result
+            });
+
+        let token_usage = parse_usage(&response_json);
+
+        info!(
+            prompt_tokens = token_usage.prompt_tokens,
+            completion_tokens = token_usage.completion_tokens,
+            total_tokens = token_usage.total_tokens,
+            "Token usage recorded"
+        );
+        Ok(CompletionOutput {
+            message: Message::Assistant {
+                content: response_message,
+                tool_calls,
+                name: None,
+            },
+            token_usage,
+            finish_reason: parse_finish_reason(&response_json),
+            system_fingerprint: response_json["system_fingerprint"]
+                .as_str()
+                .map(ToString::to_string),
+        })
+    }
+
+    #[instrument(
+        skip(self, history, temperature),
+        fields(history_len = history.len())
+    )]
+    async fn extract<T: Extractor>(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<T, CompletionError> {
+        let request_body =
+            self.extraction_request_body::<T>(message, history, temperature, max_tokens)?;
+        debug!(request_body = ?request_body, "Sending extraction request");
+        let response_json = self.post_request(&request_body).await?;
+        parse_extracted(&response_json)
+    }
+
+    fn set_sampling_params(&mut self, params: SamplingParams) -> bool {
+        self.sampling_overrides = params;
+        true
+    }
+
+    fn provider_name(&self) -> &str {
+        "xai"
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
     }
 }
 
+/// Parses the `finish_reason` of the first choice in a completion response
+fn parse_finish_reason(response_json: &serde_json::Value) -> Option<FinishReason> {
+    response_json["choices"][0]["finish_reason"]
+        .as_str()
+        .map(FinishReason::from)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[derive(schemars::JsonSchema, serde::Deserialize)]
+    #[allow(unused)]
+    struct Forecast {
+        city: String,
+        celsius: i32,
+    }
+
+    impl Extractor for Forecast {}
+
+    fn question() -> Message {
+        Message::User {
+            content: "What's the forecast?".to_string(),
+            tool_responses: None,
+            name: None,
+        }
+    }
+
     #[tokio::test]
     #[ignore]
     async fn simple_xai_completion_request() {
@@ -296,6 +496,7 @@ For this test to be considered successful, reply with "okay" without the quotes,
 "#
                     .to_string(),
                     tool_responses: None,
+                    name: None,
                 },
                 &vec![],
                 None,
@@ -309,7 +510,8 @@ For this test to be considered successful, reply with "okay" without the quotes,
         assert!(response.clone().is_ok_and(|v| v.0
             == Message::Assistant {
                 content: "okay".to_string(),
-                tool_calls: None
+                tool_calls: None,
+                name: None,
             }));
         assert!(response.is_ok_and(|v| matches!(
             v.1,
@@ -320,4 +522,49 @@ For this test to be considered successful, reply with "okay" without the quotes,
             }
         )));
     }
+
+    #[test]
+    fn test_parse_finish_reason() {
+        for (reason, expected) in [
+            ("stop", FinishReason::Stop),
+            ("length", FinishReason::Length),
+            ("tool_calls", FinishReason::ToolCalls),
+            ("content_filter", FinishReason::ContentFilter),
+        ] {
+            let response = json!({"choices": [{"finish_reason": reason}]});
+            assert_eq!(parse_finish_reason(&response), Some(expected));
+        }
+        assert_eq!(parse_finish_reason(&json!({"choices": []})), None);
+    }
+
+    #[test]
+    fn test_extraction_requests_use_the_json_schema_format() {
+        std::env::set_var("SEEDFRAME_TEST_XAI_API_KEY", "key");
+        let model = XaiCompletionModel::new(Some(r#"{"api_key": "SEEDFRAME_TEST_XAI_API_KEY"}"#));
+
+        let body = model
+            .extraction_request_body::<Forecast>(question(), &vec![], 0.0, 100)
+            .unwrap();
+        assert_eq!(body["response_format"]["type"], json!("json_schema"));
+        assert_eq!(
+            body["response_format"]["json_schema"]["name"],
+            json!("Forecast")
+        );
+        assert_eq!(
+            body["messages"][0]["content"],
+            json!("What's the forecast?")
+        );
+    }
+
+    #[test]
+    fn test_unknown_config_keys_are_sent_in_the_body() {
+        std::env::set_var("SEEDFRAME_TEST_XAI_API_KEY", "key");
+        let model = XaiCompletionModel::new(Some(
+            r#"{"api_key": "SEEDFRAME_TEST_XAI_API_KEY", "logprobs": true}"#,
+        ));
+
+        let body = model.request_body(question(), &vec![], None, 0.0, 100);
+        assert_eq!(body["logprobs"], json!(true));
+        assert_eq!(body["model"], json!(DEFAULT_MODEL));
+    }
 }
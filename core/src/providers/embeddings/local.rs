@@ -0,0 +1,179 @@
+use crate::embeddings::{model::EmbeddingModel, EmbedderError};
+use async_trait::async_trait;
+use candle_core::{Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config, DTYPE};
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, sync::Arc};
+use tokenizers::{Tokenizer, TruncationParams};
+use tracing::{error, info, instrument};
+
+const DEFAULT_MODEL: &str = "sentence-transformers/all-MiniLM-L6-v2";
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ModelConfig {
+    model: Option<String>,
+    device: Option<String>,
+}
+
+/// Embeds locally with a BERT based sentence-transformers model, without any network access
+/// once the model is available.
+///
+/// Configured with:
+/// - `model`: A directory holding `config.json`, `tokenizer.json` and `model.safetensors`,
+///   or the id of a Hugging Face Hub model to download them from, defaults to
+///   `sentence-transformers/all-MiniLM-L6-v2`
+/// - `device`: `"cpu"` (default), `"cuda"` or `"metal"`
+///
+/// Embeddings are the mean of the token embeddings of the last layer, inputs longer than
+/// the model's max positions are truncated.
+pub struct LocalEmbedding {
+    inner: Arc<LoadedModel>,
+    dimensions: usize,
+}
+
+struct LoadedModel {
+    model: BertModel,
+    tokenizer: Tokenizer,
+    device: Device,
+}
+
+impl LocalEmbedding {
+    /// Loads the model, downloading it first if `model` isn't a local directory
+    ///
+    /// # Panics
+    /// If the config is invalid, the device isn't available or loading the model fails
+    #[instrument]
+    #[must_use]
+    pub fn new(json_config: Option<&str>) -> Self {
+        let config = match json_config.map(serde_json::from_str::<ModelConfig>) {
+            None => ModelConfig {
+                model: None,
+                device: None,
+            },
+            Some(Ok(config)) => config,
+            Some(Err(e)) => {
+                let e = format!("Failed to deserialize json config: {e}");
+                error!(e);
+                panic!("{e}");
+            }
+        };
+        let model = config.model.unwrap_or(DEFAULT_MODEL.to_string());
+        match Self::load(&model, config.device.as_deref().unwrap_or("cpu")) {
+            Ok(embedding) => embedding,
+            Err(e) => {
+                let e = format!("Failed to load local embedding model `{model}`: {e}");
+                error!(e);
+                panic!("{e}");
+            }
+        }
+    }
+
+    /// Size of the embeddings produced by the model
+    #[must_use]
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn load(model: &str, device: &str) -> Result<Self, EmbedderError> {
+        let device = match device {
+            "cpu" => Device::Cpu,
+            "cuda" => Device::new_cuda(0).map_err(provider_error)?,
+            "metal" => Device::new_metal(0).map_err(provider_error)?,
+            other => {
+                return Err(EmbedderError::ProviderError(format!(
+                    "Unknown device `{other}`, expected `cpu`, `cuda` or `metal`"
+                )))
+            }
+        };
+        let [config_file, tokenizer_file, weights_file] = model_files(model)?;
+        info!(model, "Loading local embedding model");
+
+        let config: Config =
+            serde_json::from_str(&std::fs::read_to_string(config_file).map_err(provider_error)?)
+                .map_err(provider_error)?;
+        let mut tokenizer = Tokenizer::from_file(tokenizer_file).map_err(provider_error)?;
+        tokenizer
+            .with_padding(None)
+            .with_truncation(Some(TruncationParams {
+                max_length: config.max_position_embeddings,
+                ..TruncationParams::default()
+            }))
+            .map_err(provider_error)?;
+        // SAFETY: the weights file is only read, and isn't expected to change while mapped
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[weights_file], DTYPE, &device) }
+            .map_err(provider_error)?;
+        let model = BertModel::load(vb, &config).map_err(provider_error)?;
+
+        Ok(Self {
+            inner: Arc::new(LoadedModel {
+                model,
+                tokenizer,
+                device,
+            }),
+            dimensions: config.hidden_size,
+        })
+    }
+}
+
+/// Paths of the config, tokenizer and weights of `model`, downloading them if `model`
+/// isn't a local directory
+fn model_files(model: &str) -> Result<[PathBuf; 3], EmbedderError> {
+    let files = ["config.json", "tokenizer.json", "model.safetensors"];
+    let dir = PathBuf::from(model);
+    if dir.is_dir() {
+        return Ok(files.map(|file| dir.join(file)));
+    }
+    let repo = hf_hub::api::sync::Api::new()
+        .map_err(provider_error)?
+        .model(model.to_string());
+    let [config, tokenizer, weights] = files.map(|file| repo.get(file).map_err(provider_error));
+    Ok([config?, tokenizer?, weights?])
+}
+
+fn provider_error(e: impl std::fmt::Display) -> EmbedderError {
+    EmbedderError::ProviderError(e.to_string())
+}
+
+impl LoadedModel {
+    fn embed(&self, data: &str) -> Result<Vec<f64>, EmbedderError> {
+        let encoding = self.tokenizer.encode(data, true).map_err(provider_error)?;
+        let input_ids = Tensor::new(encoding.get_ids(), &self.device)
+            .and_then(|t| t.unsqueeze(0))
+            .map_err(provider_error)?;
+        let token_type_ids = input_ids.zeros_like().map_err(provider_error)?;
+        let embedding = self
+            .model
+            .forward(&input_ids, &token_type_ids, None)
+            .and_then(|output| output.mean(1)?.squeeze(0)?.to_vec1::<f32>())
+            .map_err(provider_error)?;
+        Ok(embedding.into_iter().map(f64::from).collect())
+    }
+}
+
+#[async_trait]
+impl EmbeddingModel for LocalEmbedding {
+    #[instrument(skip(self, data), fields(input_length = data.len()))]
+    async fn embed(&self, data: &str) -> Result<Vec<f64>, EmbedderError> {
+        let inner = Arc::clone(&self.inner);
+        let data = data.to_string();
+        tokio::task::spawn_blocking(move || inner.embed(&data))
+            .await
+            .map_err(provider_error)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_embeds_with_the_model_dimensions() {
+        let model = LocalEmbedding::new(None);
+        let embedding = model.embed("hello world").await.unwrap();
+        assert_eq!(model.dimensions(), 384);
+        assert_eq!(embedding.len(), 384);
+    }
+}
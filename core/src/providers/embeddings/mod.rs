@@ -1,3 +1,7 @@
+#[cfg(feature = "local-embeddings")]
+pub mod local;
 pub mod openai;
 
+#[cfg(feature = "local-embeddings")]
+pub use local::LocalEmbedding;
 pub use openai::OpenAIEmbedding;
@@ -1,9 +1,12 @@
-use crate::embeddings::{model::EmbeddingModel, EmbedderError};
+use crate::embeddings::{
+    model::{enforce_token_limit, EmbeddingModel},
+    EmbedderError,
+};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use tracing::{debug, error, info, instrument};
+use tracing::{debug, error, info, instrument, warn};
 
 const DEFAULT_API_KEY_VAR_NAME: &str = "OPENAI_EMBEDDING_API_KEY";
 const DEFAULT_URL: &str = "https://api.openai.com/v1/embeddings";
@@ -15,6 +18,18 @@ struct ModelConfig {
     api_key: Option<String>,
     api_url: Option<String>,
     model: Option<String>,
+    max_input_tokens: Option<usize>,
+    truncate: Option<bool>,
+}
+
+/// Max input tokens of the known `OpenAI` embedding models
+fn known_max_input_tokens(model: &str) -> Option<usize> {
+    match model {
+        "text-embedding-3-small" | "text-embedding-3-large" | "text-embedding-ada-002" => {
+            Some(8191)
+        }
+        _ => None,
+    }
 }
 
 pub struct OpenAIEmbedding {
@@ -22,35 +37,43 @@ pub struct OpenAIEmbedding {
     api_url: String,
     model: String,
     client: Client,
+    max_input_tokens: Option<usize>,
+    truncate: bool,
 }
 
 impl OpenAIEmbedding {
     #[instrument]
     #[must_use]
     pub fn new(json_config: Option<&str>) -> Self {
-        let (api_key_var, api_url, model) = if let Some(json) = json_config {
-            let config = match serde_json::from_str::<ModelConfig>(json) {
-                Ok(config) => config,
-                Err(e) => {
-                    let e = format!("Failed to deserialize json config: {e}");
-                    error!(e);
-                    panic!("{e}");
-                }
+        let (api_key_var, api_url, model, max_input_tokens, truncate) =
+            if let Some(json) = json_config {
+                let config = match serde_json::from_str::<ModelConfig>(json) {
+                    Ok(config) => config,
+                    Err(e) => {
+                        let e = format!("Failed to deserialize json config: {e}");
+                        error!(e);
+                        panic!("{e}");
+                    }
+                };
+                (
+                    config
+                        .api_key
+                        .unwrap_or(DEFAULT_API_KEY_VAR_NAME.to_string()),
+                    config.api_url.unwrap_or(DEFAULT_URL.to_string()),
+                    config.model.unwrap_or(DEFAULT_MODEL.to_string()),
+                    config.max_input_tokens,
+                    config.truncate.unwrap_or(false),
+                )
+            } else {
+                (
+                    DEFAULT_API_KEY_VAR_NAME.to_string(),
+                    DEFAULT_URL.to_string(),
+                    DEFAULT_MODEL.to_string(),
+                    None,
+                    false,
+                )
             };
-            (
-                config
-                    .api_key
-                    .unwrap_or(DEFAULT_API_KEY_VAR_NAME.to_string()),
-                config.api_url.unwrap_or(DEFAULT_URL.to_string()),
-                config.model.unwrap_or(DEFAULT_MODEL.to_string()),
-            )
-        } else {
-            (
-                DEFAULT_API_KEY_VAR_NAME.to_string(),
-                DEFAULT_URL.to_string(),
-                DEFAULT_MODEL.to_string(),
-            )
-        };
+        let max_input_tokens = max_input_tokens.or_else(|| known_max_input_tokens(&model));
         let api_key = match std::env::var(&api_key_var) {
             Ok(key) => key,
             Err(e) => {
@@ -64,8 +87,19 @@ impl OpenAIEmbedding {
             api_url,
             client: reqwest::Client::new(),
             model,
+            max_input_tokens,
+            truncate,
         }
     }
+
+    /// Replaces the HTTP client used for requests to OpenAI, eg. to share a connection pool
+    /// with other providers and embedders talking to the same host.
+    /// A new client is created for every instance by default.
+    #[must_use]
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
 }
 
 #[derive(Deserialize)]
@@ -89,6 +123,16 @@ impl EmbeddingModel for OpenAIEmbedding {
         )
     )]
     async fn embed(&self, data: &str) -> Result<Vec<f64>, EmbedderError> {
+        let data = match self.max_input_tokens {
+            Some(max_tokens) => {
+                let checked = enforce_token_limit(data, max_tokens, self.truncate)?;
+                if checked.len() < data.len() {
+                    warn!(max_tokens, "Truncated embedding input to the token limit");
+                }
+                checked
+            }
+            None => data,
+        };
         info!("Preparing embedding request");
         let request_body = json!({
                 "input": data,
@@ -128,6 +172,10 @@ impl EmbeddingModel for OpenAIEmbedding {
                 .into_iter()
                 .flat_map(|d| d.embedding)
                 .collect();
+            if embeddings.is_empty() && !data.is_empty() {
+                error!("Embedding response contained no embedding");
+                return Err(EmbedderError::ParseError("empty embedding".to_string()));
+            }
             info!(
                 embedding_length = embeddings.len(),
                 "Successfully generated embeddings"
@@ -153,6 +201,27 @@ impl EmbeddingModel for OpenAIEmbedding {
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_over_limit_input_is_rejected_before_sending() {
+        let model = OpenAIEmbedding {
+            api_key: String::new(),
+            api_url: "http://127.0.0.1:9".to_string(),
+            model: DEFAULT_MODEL.to_string(),
+            client: Client::new(),
+            max_input_tokens: known_max_input_tokens(DEFAULT_MODEL),
+            truncate: false,
+        };
+
+        let result = model.embed(&"a".repeat(8192 * 4)).await;
+        assert!(matches!(
+            result,
+            Err(EmbedderError::TokenLimitExceeded {
+                tokens: 8192,
+                limit: 8191
+            })
+        ));
+    }
+
     #[tokio::test]
     #[ignore]
     async fn simple_openai_embed_request() {
@@ -0,0 +1,208 @@
+use crate::{
+    document::Document,
+    embeddings::{model::EmbeddingModel, EmbedderError},
+    loader::Loader,
+};
+use async_trait::async_trait;
+use std::{sync::Mutex, time::Duration};
+use tokio::sync::broadcast::{self, error::RecvError, Receiver, Sender};
+
+/// Subscribes to `loader` and collects the documents it publishes, until none arrives for
+/// `timeout` or the loader closes its channel.
+///
+/// Subscribes with [`Loader::subscribe_replay`], so documents published before the call are
+/// collected too for loaders supporting replays.
+///
+/// # Example
+/// ```rust,ignore
+/// let loader = FileOnceLoader::new(vec!["/tmp/data/*.txt".to_string()])?.build()?;
+/// let documents = collect_documents(&loader, Duration::from_millis(100)).await;
+/// assert_eq!(documents.len(), 2);
+/// ```
+pub async fn collect_documents<L: Loader + ?Sized>(loader: &L, timeout: Duration) -> Vec<Document> {
+    let mut receiver = loader.subscribe_replay().await;
+    let mut documents = vec![];
+    while let Ok(result) = tokio::time::timeout(timeout, receiver.recv()).await {
+        match result {
+            Ok(document) => documents.push(document),
+            Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => break,
+        }
+    }
+    documents
+}
+
+/// A loader publishing the documents sent to it, for testing code consuming loaders
+///
+/// Sent documents are kept, and replayed to subscribers using [`Loader::subscribe_replay`].
+#[derive(Debug)]
+pub struct ChannelLoader {
+    tx: Sender<Document>,
+    sent: Mutex<Vec<Document>>,
+    capacity: usize,
+}
+
+impl ChannelLoader {
+    /// Creates a loader whose subscribers can lag behind by up to `capacity` documents
+    ///
+    /// # Panics
+    /// If `capacity` is 0
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        Self {
+            tx,
+            sent: Mutex::new(vec![]),
+            capacity,
+        }
+    }
+
+    /// Publishes `document` to the current subscribers
+    ///
+    /// # Panics
+    /// If a thread panicked while sending a document
+    pub fn send(&self, document: Document) {
+        let mut sent = self.sent.lock().unwrap();
+        sent.push(document.clone());
+        let _ = self.tx.send(document);
+    }
+}
+
+#[async_trait]
+impl Loader for ChannelLoader {
+    async fn subscribe(&self) -> Receiver<Document> {
+        self.tx.subscribe()
+    }
+
+    async fn subscribe_replay(&self) -> Receiver<Document> {
+        let (mut upstream, tx, rx) = {
+            let sent = self.sent.lock().unwrap();
+            let (tx, rx) = broadcast::channel(sent.len() + self.capacity);
+            for document in sent.iter() {
+                let _ = tx.send(document.clone());
+            }
+            (self.tx.subscribe(), tx, rx)
+        };
+        tokio::spawn(async move {
+            loop {
+                match upstream.recv().await {
+                    Ok(document) => {
+                        if tx.send(document).is_err() {
+                            break;
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        rx
+    }
+}
+
+/// An embedding model hashing the words of the text into a vector, for testing retrieval
+/// without an embedding API
+///
+/// Every lowercased word adds 1 or -1 to a dimension picked by its hash, and the result is
+/// L2-normalized. Embeddings are deterministic and texts sharing words get closer vectors,
+/// but they carry no meaning beyond that.
+#[derive(Debug, Clone, Copy)]
+pub struct HashingEmbedding {
+    dimensions: usize,
+}
+
+impl HashingEmbedding {
+    /// Creates a model producing vectors of `dimensions` dimensions
+    ///
+    /// # Panics
+    /// If `dimensions` is 0
+    #[must_use]
+    pub fn new(dimensions: usize) -> Self {
+        assert!(dimensions > 0, "embeddings need at least one dimension");
+        Self { dimensions }
+    }
+}
+
+#[async_trait]
+impl EmbeddingModel for HashingEmbedding {
+    async fn embed(&self, data: &str) -> Result<Vec<f64>, EmbedderError> {
+        let mut vector = vec![0.0; self.dimensions];
+        for word in data.split(|c: char| !c.is_alphanumeric()) {
+            if word.is_empty() {
+                continue;
+            }
+            let hash = fnv1a(word.to_lowercase().as_bytes());
+            #[allow(clippy::cast_possible_truncation)]
+            let index = (hash % self.dimensions as u64) as usize;
+            vector[index] += if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        }
+        let norm = vector.iter().map(|v| v * v).sum::<f64>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|v| *v /= norm);
+        }
+        Ok(vector)
+    }
+}
+
+/// FNV-1a, hashing the same on every platform and Rust version unlike the std hashers
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_collects_sent_and_replayed_documents() {
+        let loader = Arc::new(ChannelLoader::new(8));
+        loader.send(Document::new("early", "sent before subscribing"));
+
+        let sender = Arc::clone(&loader);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            sender.send(Document::new("late", "sent after subscribing"));
+        });
+
+        let documents = collect_documents(&*loader, Duration::from_millis(200)).await;
+        let ids: Vec<_> = documents.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, ["early", "late"]);
+    }
+
+    #[tokio::test]
+    async fn test_hashing_embeddings_are_deterministic() {
+        let model = HashingEmbedding::new(64);
+        let first = model.embed("the quick brown fox").await.unwrap();
+
+        assert_eq!(first.len(), 64);
+        assert_eq!(first, model.embed("the quick brown fox").await.unwrap());
+        assert_eq!(
+            first,
+            HashingEmbedding::new(64)
+                .embed("The quick, brown fox")
+                .await
+                .unwrap()
+        );
+        assert_ne!(first, model.embed("a lazy dog").await.unwrap());
+        let norm: f64 = first.iter().map(|v| v * v).sum::<f64>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_document_stream_skips_lagged_documents() {
+        use futures::StreamExt;
+
+        let loader = ChannelLoader::new(2);
+        let stream = loader.subscribe_stream().await;
+        for id in ["a", "b", "c", "d"] {
+            loader.send(Document::new(id, "content"));
+        }
+        drop(loader);
+
+        let ids: Vec<_> = stream.map(|d| d.id).collect().await;
+        assert_eq!(ids, ["c", "d"]);
+    }
+}
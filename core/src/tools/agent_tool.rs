@@ -0,0 +1,216 @@
+use std::{
+    any::{Any, TypeId},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tracing::{debug, instrument};
+
+use super::{Tool, ToolArg, ToolError};
+use crate::completion::{Client, CompletionModel, Message};
+
+/// Exposes a [`Client`] as a tool, letting a parent agent delegate prompts to a sub-agent
+///
+/// The tool takes a single `input: String` argument, which gets sent as a prompt to the wrapped
+/// client, the sub-agent's response text is returned as the result of the tool call.
+/// The sub-agent keeps its own message history across calls.
+///
+/// # Example
+/// ```rust,ignore
+/// let researcher = ResearchClient::build("You're a researcher".to_string()).await;
+/// let tools = ToolSet(
+///     vec![Box::new(AgentTool::new(
+///         researcher,
+///         "researcher",
+///         "Researches a topic and summarizes the findings",
+///     ))],
+///     ExecutionStrategy::FailEarly,
+/// );
+/// let mut client = OpenAI::new(None).build_client("You're a helpful assistant", vec![], tools);
+/// ```
+pub struct AgentTool<M: CompletionModel> {
+    client: Arc<Mutex<Client<M>>>,
+    name: String,
+    description: String,
+    args: Vec<ToolArg>,
+}
+
+impl<M: CompletionModel> AgentTool<M> {
+    /// Creates a new `AgentTool` wrapping `client`
+    ///
+    /// # Arguments
+    /// * `client` - The sub-agent prompts get forwarded to
+    /// * `name` - Name of the tool as seen by the parent agent
+    /// * `description` - Description of the tool as seen by the parent agent
+    pub fn new(client: Client<M>, name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self::from_shared(Arc::new(Mutex::new(client)), name, description)
+    }
+
+    /// Creates a new `AgentTool` from a client that's shared with other parts of the application
+    pub fn from_shared(
+        client: Arc<Mutex<Client<M>>>,
+        name: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        Self {
+            client,
+            name: name.into(),
+            description: description.into(),
+            args: vec![ToolArg::new::<String>(
+                "input",
+                "The prompt to send to the agent",
+            )],
+        }
+    }
+
+    /// Returns the wrapped client
+    #[must_use]
+    pub fn client(&self) -> Arc<Mutex<Client<M>>> {
+        Arc::clone(&self.client)
+    }
+}
+
+#[derive(Deserialize)]
+struct AgentToolParams {
+    input: String,
+}
+
+#[async_trait]
+impl<M: CompletionModel + Sync + 'static> Tool for AgentTool<M> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn args(&self) -> &[ToolArg] {
+        &self.args
+    }
+
+    #[instrument(skip(self, _states), fields(name = self.name))]
+    async fn call(
+        &self,
+        args: &str,
+        _states: &DashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    ) -> Result<Value, ToolError> {
+        // arguments may arrive encoded as a JSON string literal
+        let args = serde_json::from_str::<String>(args).unwrap_or_else(|_| args.to_string());
+        let params: AgentToolParams = serde_json::from_str(&args)?;
+
+        let mut client = self.client.lock().await;
+        debug!("Forwarding prompt to sub-agent");
+        let response = client
+            .prompt(params.input)
+            .send()
+            .await
+            .map_err(|e| ToolError::ToolCallError(Box::new(e)))?;
+
+        let content = match response {
+            Message::Assistant { content, .. } => content,
+            _ => client
+                .export_history()
+                .iter()
+                .rev()
+                .find_map(|m| match m {
+                    Message::Assistant { content, .. } => Some(content.clone()),
+                    _ => None,
+                })
+                .unwrap_or_default(),
+        };
+        Ok(Value::String(content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        completion::{CompletionError, TokenUsage},
+        embeddings::Embedder,
+        tools::{ExecutionStrategy, ToolCall, ToolSet},
+    };
+    use std::collections::VecDeque;
+
+    struct MockModel(VecDeque<Message>);
+
+    #[allow(refining_impl_trait)]
+    #[async_trait]
+    impl CompletionModel for MockModel {
+        fn build_client(
+            self,
+            preamble: impl AsRef<str>,
+            embedder_instances: Vec<Embedder>,
+            tools: ToolSet,
+        ) -> Client<Self> {
+            Client::new(self, preamble, 0.0, 100, embedder_instances, tools)
+        }
+
+        async fn send(
+            &mut self,
+            _message: Message,
+            _history: &Vec<Message>,
+            _tools: Option<&ToolSet>,
+            _temperature: f64,
+            _max_tokens: usize,
+        ) -> Result<(Message, TokenUsage), CompletionError> {
+            Ok((self.0.pop_front().unwrap(), TokenUsage::default()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parent_tool_call_returns_sub_agent_response() {
+        let sub_agent = MockModel(VecDeque::from([Message::Assistant {
+            content: "sub-agent says hi".to_string(),
+            tool_calls: None,
+            name: None,
+        }]))
+        .build_client(
+            "You're a sub-agent",
+            vec![],
+            ToolSet(vec![], ExecutionStrategy::FailEarly),
+        );
+        let agent_tool = AgentTool::new(sub_agent, "sub_agent", "Delegates to the sub-agent");
+        let sub_agent = agent_tool.client();
+
+        let mut parent = MockModel(VecDeque::from([Message::Assistant {
+            content: String::new(),
+            tool_calls: Some(vec![ToolCall {
+                id: "call_1".to_string(),
+                name: "sub_agent".to_string(),
+                arguments: serde_json::Value::String(r#"{"input":"say hi"}"#.to_string())
+                    .to_string(),
+            }]),
+            name: None,
+        }]))
+        .build_client(
+            "You're a parent agent",
+            vec![],
+            ToolSet(vec![Box::new(agent_tool)], ExecutionStrategy::FailEarly),
+        );
+
+        let response = parent.prompt("delegate").send().await.unwrap();
+        let Message::User {
+            tool_responses: Some(responses),
+            ..
+        } = response
+        else {
+            panic!("expected tool responses, got {response:?}");
+        };
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].content, Value::from("sub-agent says hi"));
+        assert_eq!(
+            sub_agent.lock().await.export_history()[1],
+            Message::User {
+                content: "say hi".to_string(),
+                tool_responses: None,
+                name: None,
+            }
+        );
+    }
+}
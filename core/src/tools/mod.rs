@@ -3,12 +3,16 @@ use std::any::{Any, TypeId};
 use async_trait::async_trait;
 use dashmap::DashMap;
 use schemars::{gen::SchemaSettings, JsonSchema};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{json, Value};
 use thiserror::Error;
+pub use tokio_util::sync::CancellationToken;
 
 use crate::completion::StateError;
 
+mod agent_tool;
+pub use agent_tool::AgentTool;
+
 #[async_trait]
 pub trait Tool: Send + Sync {
     fn name(&self) -> &str;
@@ -20,20 +24,55 @@ pub trait Tool: Send + Sync {
         states: &DashMap<TypeId, Box<dyn Any + Send + Sync>>,
     ) -> Result<Value, ToolError>;
 
+    /// Calls the tool, aborting the call with [`ToolError::Cancelled`] once `cancel` is triggered
+    ///
+    /// The default implementation drops the in-flight [`Tool::call`] future on cancellation,
+    /// override it for tools that need to clean up before bailing out.
+    async fn call_with_cancellation(
+        &self,
+        args: &str,
+        states: &DashMap<TypeId, Box<dyn Any + Send + Sync>>,
+        cancel: &CancellationToken,
+    ) -> Result<Value, ToolError> {
+        tokio::select! {
+            () = cancel.cancelled() => Err(ToolError::Cancelled),
+            result = self.call(args, states) => result,
+        }
+    }
+
     fn output_schema(&self) -> Option<Value> {
         None
     }
 
     fn default_serializer(&self) -> Value {
+        self.serializer(true)
+    }
+
+    /// Serializes the tool definition, omitting the `strict` flag if `strict` is false
+    /// for providers that reject it
+    fn serializer(&self, strict: bool) -> Value {
         let parameters = build_parameters_schema(self.args());
+        let mut function = json!({
+            "name": self.name(),
+            "description": self.description(),
+            "parameters": parameters
+        });
+        if strict {
+            function["strict"] = json!(true);
+        }
         json!({
             "type": "function",
-            "function": {
-                "name": self.name(),
-                "strict": true,
-                "description": self.description(),
-                "parameters": parameters
-            }
+            "function": function
+        })
+    }
+
+    /// Serializes the tool definition in Anthropic's format, with the parameters schema as
+    /// `input_schema` and no `function` wrapper
+    fn anthropic_serializer(&self) -> Value {
+        json!({
+            "name": self.name(),
+            "description": self.description(),
+            "input_schema": build_parameters_schema(self.args())
         })
     }
 }
@@ -46,11 +85,30 @@ pub enum ToolError {
     JsonError(#[from] serde_json::Error),
     #[error(transparent)]
     StateError(#[from] StateError),
+    #[error("Tool call was cancelled")]
+    Cancelled,
+    #[error("Invalid arguments for tool `{tool}`: {}", .details.join("; "))]
+    InvalidArguments { tool: String, details: Vec<String> },
 }
 
+/// How the tool calls requested by the model get executed
+///
+/// Regardless of the strategy, the returned tool responses are in the same order as the
+/// tool calls they answer, with responses of failed calls left out where failures are skipped.
 pub enum ExecutionStrategy {
+    /// Runs the calls one after the other, stopping at the first failed call
     FailEarly,
+    /// Runs the calls one after the other, skipping the failed calls
     BestEffort,
+    /// Runs the calls one after the other, answering failed calls with a
+    /// `{"success": false, "error": ...}` response so the model learns they failed
+    ReportFailures,
+    /// Runs the calls one after the other in the requested order, stopping at the first failed call.
+    /// Use this when tools depend on the side effects of previous calls.
+    Sequential,
+    /// Runs all calls concurrently, failing with the error of the first failed call in
+    /// request order if any call fails
+    Concurrent,
 }
 
 pub struct ToolSet(pub Vec<Box<dyn Tool>>, pub ExecutionStrategy);
@@ -65,6 +123,8 @@ pub enum ToolSetError {
     LastMessageNotAToolCall,
     #[error("Tool error: ")]
     ToolError(#[from] ToolError),
+    #[error("Toolset already contains a tool named `{0}`")]
+    DuplicateTool(String),
 }
 
 #[allow(unused)]
@@ -84,6 +144,23 @@ impl ToolSet {
             .ok_or(ToolSetError::ToolNotFound)
     }
 
+    /// Combines the tools of `self` and `other` into one toolset, keeping the execution
+    /// strategy of `self`
+    ///
+    /// # Errors
+    /// returns a `ToolSetError::DuplicateTool` if both toolsets have a tool with the same name
+    pub fn merge(mut self, other: ToolSet) -> Result<ToolSet, ToolSetError> {
+        if let Some(duplicate) = other
+            .0
+            .iter()
+            .find(|tool| self.find_tool(tool.name()).is_ok())
+        {
+            return Err(ToolSetError::DuplicateTool(duplicate.name().to_string()));
+        }
+        self.0.extend(other.0);
+        Ok(self)
+    }
+
     /// Adds a tool to the toolset
     pub fn add_tool(&mut self, tool: Box<dyn Tool>) {
         self.0.push(tool);
@@ -125,18 +202,100 @@ impl ToolSet {
         name: &str,
         args: &str,
         states: &DashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    ) -> Result<ToolResponse, ToolSetError> {
+        self.call_with_cancellation(id, name, args, states, &CancellationToken::new())
+            .await
+    }
+
+    /// Like [`ToolSet::call`], but aborts the call once `cancel` is triggered
+    ///
+    /// # Errors
+    /// - returns `ToolSetError`: If execution fails or gets cancelled
+    pub async fn call_with_cancellation(
+        &self,
+        id: &str,
+        name: &str,
+        args: &str,
+        states: &DashMap<TypeId, Box<dyn Any + Send + Sync>>,
+        cancel: &CancellationToken,
     ) -> Result<ToolResponse, ToolSetError> {
         let tool = self.find_tool(name)?;
-        let v = tool.call(args, states).await.map_err(ToolSetError::from)?;
-        Ok(ToolResponse {
-            id: id.to_owned(),
-            name: name.to_owned(),
-            content: v,
+        validate_arguments(tool, args)?;
+        let v = tool
+            .call_with_cancellation(args, states, cancel)
+            .await
+            .map_err(ToolSetError::from)?;
+        Ok(ToolResponse::new(id, name, v))
+    }
+}
+
+/// Checks the call arguments against the tool's declared args before the tool gets called,
+/// reporting missing fields and fields of the wrong type.
+/// Tools without declared args parse their arguments themselves and aren't validated.
+fn validate_arguments(tool: &dyn Tool, args: &str) -> Result<(), ToolError> {
+    if tool.args().is_empty() {
+        return Ok(());
+    }
+    let invalid = |details| ToolError::InvalidArguments {
+        tool: tool.name().to_string(),
+        details,
+    };
+
+    // arguments may arrive encoded as a JSON string literal
+    let args = serde_json::from_str::<String>(args).unwrap_or_else(|_| args.to_string());
+    let Ok(Value::Object(args)) = serde_json::from_str::<Value>(&args) else {
+        return Err(invalid(vec!["arguments must be a JSON object".to_string()]));
+    };
+
+    let details: Vec<String> = tool
+        .args()
+        .iter()
+        .filter_map(|arg| match args.get(&arg.name) {
+            None if !schema_accepts(&arg.schema, &Value::Null) => {
+                Some(format!("missing required field `{}`", arg.name))
+            }
+            Some(value) if !schema_accepts(&arg.schema, value) => Some(format!(
+                "field `{}` expected {}, got {}",
+                arg.name,
+                arg.schema["type"],
+                json_type_name(value)
+            )),
+            _ => None,
         })
+        .collect();
+
+    if details.is_empty() {
+        Ok(())
+    } else {
+        Err(invalid(details))
+    }
+}
+
+/// Whether `value` matches the `type` of `schema`, schemas without a `type` accept anything
+fn schema_accepts(schema: &Value, value: &Value) -> bool {
+    let matches = |ty: &Value| match ty.as_str() {
+        Some("integer") => value.is_i64() || value.is_u64(),
+        Some(ty) => json_type_name(value) == ty || (ty == "number" && value.is_number()),
+        None => true,
+    };
+    match &schema["type"] {
+        Value::Null => true,
+        Value::Array(types) => types.iter().any(matches),
+        ty => matches(ty),
+    }
+}
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
     }
 }
 
-#[allow(unused)]
 pub struct ToolArg {
     name: String,
     description: String,
@@ -179,6 +338,40 @@ impl ToolArg {
             schema: schema_value,
         }
     }
+
+    /// Creates a `ToolArg` with a hand-written JSON `schema`, eg. for constraints `JsonSchema`
+    /// can't express. The schema is used as is, only getting the `description` added if it
+    /// doesn't have one.
+    #[must_use]
+    pub fn with_schema(name: &str, description: &str, mut schema: Value) -> Self {
+        if let Some(obj) = schema.as_object_mut() {
+            obj.entry("description")
+                .or_insert_with(|| json!(description));
+        }
+        ToolArg {
+            name: name.to_string(),
+            description: description.to_string(),
+            schema,
+        }
+    }
+
+    /// Name of the argument
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Human-readable description of the argument
+    #[must_use]
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// JSON schema of the argument's value
+    #[must_use]
+    pub fn schema(&self) -> &Value {
+        &self.schema
+    }
 }
 
 fn process_json_value(value: &mut serde_json::Value) {
@@ -186,7 +379,7 @@ fn process_json_value(value: &mut serde_json::Value) {
         serde_json::Value::Object(obj) => {
             let fields_to_remove = ["$schema", "format", "title", "minimum"];
             for &f in &fields_to_remove {
-                if obj.get(f).map_or(false, |v| v.is_string() || v.is_number()) {
+                if obj.get(f).is_some_and(|v| v.is_string() || v.is_number()) {
                     obj.remove(f);
                 }
             }
@@ -195,7 +388,12 @@ fn process_json_value(value: &mut serde_json::Value) {
                 obj.insert("anyOf".to_string(), v);
             };
 
-            if obj.contains_key("properties") {
+            // map-typed nodes carry the schema of their values in `additionalProperties`
+            if obj.contains_key("properties")
+                && !obj
+                    .get("additionalProperties")
+                    .is_some_and(serde_json::Value::is_object)
+            {
                 obj.insert("additionalProperties".to_string(), json!(false));
             }
             for (_, v) in obj.iter_mut() {
@@ -225,6 +423,103 @@ pub struct ToolResponse {
     pub id: String,
     pub name: String,
     pub content: serde_json::Value,
+    /// Images produced by the tool, sent as image content by providers supporting them
+    /// and described in text by the others
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ToolImage>,
+}
+
+impl ToolResponse {
+    /// Creates the response to a tool call from the tool's result, splitting the images off
+    /// results returned as a [`ToolOutput`]
+    #[must_use]
+    pub fn new(id: impl Into<String>, name: impl Into<String>, result: Value) -> Self {
+        let (content, images) = match serde_json::from_value::<ToolOutput>(result.clone()) {
+            Ok(output) => (output.content, output.images),
+            Err(_) => (result, vec![]),
+        };
+        Self {
+            id: id.into(),
+            name: name.into(),
+            content,
+            images,
+        }
+    }
+
+    /// Creates the response reporting a failed tool call, with a
+    /// `{"success": false, "error": ...}` content
+    #[must_use]
+    pub fn failure(
+        id: impl Into<String>,
+        name: impl Into<String>,
+        error: &impl std::fmt::Display,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            content: json!({"success": false, "error": error.to_string()}),
+            images: vec![],
+        }
+    }
+
+    /// Deserializes the content of the response into `T`, eg. the type the tool returned
+    ///
+    /// # Errors
+    /// Returns [`ToolError::JsonError`] if the content doesn't deserialize into `T`
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T, ToolError> {
+        Ok(T::deserialize(&self.content)?)
+    }
+
+    /// Returns the content as text followed by a description of every image, for providers
+    /// that can't take images in tool results
+    #[must_use]
+    pub fn text_content(&self) -> String {
+        let mut text = match &self.content {
+            Value::String(s) => s.clone(),
+            v => v.to_string(),
+        };
+        for image in &self.images {
+            text.push('\n');
+            text.push_str(&image.describe());
+        }
+        text
+    }
+}
+
+/// An image produced by a tool, eg. a chart or a screenshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolImage {
+    /// Base64 encoded image data
+    Base64 {
+        /// Media type of the image, eg. `image/png`
+        media_type: String,
+        data: String,
+    },
+    /// URL the image can be fetched from
+    Url { url: String },
+}
+
+impl ToolImage {
+    /// Describes the image in text, for providers without image support
+    #[must_use]
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Base64 { media_type, .. } => format!("[{media_type} image, not shown]"),
+            Self::Url { url } => format!("[image: {url}]"),
+        }
+    }
+}
+
+/// Result of a tool carrying images along with its content
+///
+/// Tools return it as their result, eg. from a `#[tool]` function, and the images end up in
+/// the [`ToolResponse::images`] of the call.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "seedframe_tool_output")]
+pub struct ToolOutput {
+    pub content: Value,
+    pub images: Vec<ToolImage>,
 }
 
 #[must_use]
@@ -248,3 +543,39 @@ pub fn build_parameters_schema(args: &[ToolArg]) -> Value {
         "additionalProperties": false
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_args_can_be_read_back() {
+        let arg = ToolArg::new::<u32>("count", "number of items");
+        assert_eq!(arg.name(), "count");
+        assert_eq!(arg.description(), "number of items");
+        assert_eq!(arg.schema()["type"], json!("integer"));
+        assert_eq!(arg.schema()["description"], json!("number of items"));
+    }
+
+    #[test]
+    fn test_hand_written_schemas_are_used_as_is() {
+        let schema = json!({"type": "string", "enum": ["celsius", "fahrenheit"]});
+        let args = [
+            ToolArg::new::<String>("city", "city to look up"),
+            ToolArg::with_schema("unit", "temperature unit", schema),
+        ];
+        assert_eq!(args[1].name(), "unit");
+        assert_eq!(args[1].description(), "temperature unit");
+
+        let parameters = build_parameters_schema(&args);
+        assert_eq!(
+            parameters["properties"]["unit"],
+            json!({
+                "type": "string",
+                "enum": ["celsius", "fahrenheit"],
+                "description": "temperature unit"
+            })
+        );
+        assert_eq!(parameters["required"], json!(["city", "unit"]));
+    }
+}
@@ -0,0 +1,368 @@
+use async_trait::async_trait;
+use std::{
+    sync::{Arc, Weak},
+    time::Duration,
+};
+use tokio::sync::Mutex;
+use tracing::error;
+
+use super::{VectorStore, VectorStoreError};
+use crate::embeddings::{embedding::Embedding, BatchResult};
+
+/// A write buffer in front of a vector store, batching `store` calls into `batch_store` calls
+///
+/// Stored embeddings are held back until `max_batch` of them are buffered, or until the flush
+/// interval elapses if one is set, then written to the store in a single batch. This cuts the
+/// round trips to remote stores when ingesting many documents.
+///
+/// Reads and tenant-scoped calls flush the buffer first, so they always see earlier stores.
+/// Embeddings failing to be written stay buffered and are retried by the next flush.
+/// Embeddings still buffered when the handle is dropped are lost, call
+/// [`BufferedVectorStore::flush`] once done writing.
+///
+/// # Example
+/// ```rust,ignore
+/// let store: Arc<dyn VectorStore> = Arc::new(PineconeVectorStore::new(config).await?);
+/// let buffered = BufferedVectorStore::new(store, 100).with_flush_interval(Duration::from_secs(1));
+/// for embedding in embeddings {
+///     buffered.store(embedding).await?;
+/// }
+/// buffered.flush().await?;
+/// ```
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone)]
+pub struct BufferedVectorStore {
+    inner: Arc<Buffer>,
+}
+
+struct Buffer {
+    store: Arc<dyn VectorStore>,
+    pending: Mutex<Vec<Embedding>>,
+    max_batch: usize,
+}
+
+impl BufferedVectorStore {
+    /// Creates a buffer writing to `store` in batches of `max_batch` embeddings
+    pub fn new(store: Arc<dyn VectorStore>, max_batch: usize) -> Self {
+        Self {
+            inner: Arc::new(Buffer {
+                store,
+                pending: Mutex::new(Vec::with_capacity(max_batch)),
+                max_batch: max_batch.max(1),
+            }),
+        }
+    }
+
+    /// Also flushes the buffer every `interval`, so embeddings don't wait for a full batch
+    /// for longer than that
+    ///
+    /// The flushes run on a task spawned on the current tokio runtime, ending once every
+    /// handle to the buffer is dropped.
+    #[must_use]
+    pub fn with_flush_interval(self, interval: Duration) -> Self {
+        let buffer = Arc::downgrade(&self.inner);
+        tokio::spawn(flush_periodically(buffer, interval));
+        self
+    }
+
+    /// Number of embeddings waiting to be written
+    pub async fn pending(&self) -> usize {
+        self.inner.pending.lock().await.len()
+    }
+
+    /// Writes every buffered embedding to the store
+    ///
+    /// # Errors
+    /// Returns [`VectorStoreError::FailedUpsert`] if any of the embeddings failed to be stored,
+    /// the failed embeddings stay buffered for the next flush to retry
+    pub async fn flush(&self) -> Result<(), VectorStoreError> {
+        self.inner.flush().await
+    }
+}
+
+impl Buffer {
+    async fn flush(&self) -> Result<(), VectorStoreError> {
+        let mut pending = self.pending.lock().await;
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let batch = std::mem::take(&mut *pending);
+        // the lock is held until the batch is written, keeping the writes in store order
+        let results = self.store.batch_store(batch.clone()).await;
+        *pending = batch
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| !matches!(results.get(*i), Some(Ok(()))))
+            .map(|(_, embedding)| embedding)
+            .collect();
+        drop(pending);
+        check_batch(&results)
+    }
+}
+
+async fn flush_periodically(buffer: Weak<Buffer>, interval: Duration) {
+    let mut ticks = tokio::time::interval(interval);
+    ticks.tick().await;
+    loop {
+        ticks.tick().await;
+        let Some(buffer) = buffer.upgrade() else {
+            break;
+        };
+        if let Err(e) = buffer.flush().await {
+            error!(error = ?e, "Failed to flush buffered embeddings");
+        }
+    }
+}
+
+fn check_batch(results: &BatchResult<(), VectorStoreError>) -> Result<(), VectorStoreError> {
+    let mut failures = results.failures().peekable();
+    let Some((_, first)) = failures.peek() else {
+        return Ok(());
+    };
+    let first = first.to_string();
+    Err(VectorStoreError::FailedUpsert(format!(
+        "{} of {} buffered embeddings failed, first error: {first}",
+        failures.count(),
+        results.len()
+    )))
+}
+
+#[async_trait]
+impl VectorStore for BufferedVectorStore {
+    async fn get_by_id(&self, id: String) -> Result<Embedding, VectorStoreError> {
+        self.flush().await?;
+        self.inner.store.get_by_id(id).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Embedding>, VectorStoreError> {
+        self.flush().await?;
+        self.inner.store.get_by_ids(ids).await
+    }
+
+    async fn store(&self, embedding: Embedding) -> Result<(), VectorStoreError> {
+        let full = {
+            let mut pending = self.inner.pending.lock().await;
+            pending.push(embedding);
+            pending.len() >= self.inner.max_batch
+        };
+        if full {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn batch_store(&self, embeddings: Vec<Embedding>) -> BatchResult<(), VectorStoreError> {
+        if let Err(e) = self.flush().await {
+            return embeddings.iter().map(|_| Err(e.clone())).collect();
+        }
+        self.inner.store.batch_store(embeddings).await
+    }
+
+    async fn top_n(&self, query: &[f64], n: usize) -> Result<Vec<Embedding>, VectorStoreError> {
+        self.flush().await?;
+        self.inner.store.top_n(query, n).await
+    }
+
+    async fn top_n_text(
+        &self,
+        query: &[f64],
+        n: usize,
+    ) -> Result<Vec<(String, String)>, VectorStoreError> {
+        self.flush().await?;
+        self.inner.store.top_n_text(query, n).await
+    }
+
+    async fn top_n_scored(
+        &self,
+        query: &[f64],
+        n: usize,
+    ) -> Result<Vec<(Embedding, f64)>, VectorStoreError> {
+        self.flush().await?;
+        self.inner.store.top_n_scored(query, n).await
+    }
+
+    async fn list_ids(&self) -> Result<Vec<String>, VectorStoreError> {
+        self.flush().await?;
+        self.inner.store.list_ids().await
+    }
+
+    async fn get_by_id_in(&self, tenant: &str, id: String) -> Result<Embedding, VectorStoreError> {
+        self.flush().await?;
+        self.inner.store.get_by_id_in(tenant, id).await
+    }
+
+    async fn store_in(&self, tenant: &str, embedding: Embedding) -> Result<(), VectorStoreError> {
+        self.flush().await?;
+        self.inner.store.store_in(tenant, embedding).await
+    }
+
+    async fn top_n_in(
+        &self,
+        tenant: &str,
+        query: &[f64],
+        n: usize,
+    ) -> Result<Vec<Embedding>, VectorStoreError> {
+        self.flush().await?;
+        self.inner.store.top_n_in(tenant, query, n).await
+    }
+
+    async fn list_ids_in(&self, tenant: &str) -> Result<Vec<String>, VectorStoreError> {
+        self.flush().await?;
+        self.inner.store.list_ids_in(tenant).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector_store::InMemoryVectorStore;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingStore {
+        store: InMemoryVectorStore,
+        batches: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl VectorStore for CountingStore {
+        async fn get_by_id(&self, id: String) -> Result<Embedding, VectorStoreError> {
+            self.store.get_by_id(id).await
+        }
+
+        async fn store(&self, embedding: Embedding) -> Result<(), VectorStoreError> {
+            self.store.store(embedding).await
+        }
+
+        async fn batch_store(
+            &self,
+            embeddings: Vec<Embedding>,
+        ) -> BatchResult<(), VectorStoreError> {
+            self.batches.fetch_add(1, Ordering::SeqCst);
+            self.store.batch_store(embeddings).await
+        }
+
+        async fn top_n(&self, query: &[f64], n: usize) -> Result<Vec<Embedding>, VectorStoreError> {
+            self.store.top_n(query, n).await
+        }
+
+        async fn list_ids(&self) -> Result<Vec<String>, VectorStoreError> {
+            self.store.list_ids().await
+        }
+    }
+
+    /// Fails every embedding of its first batch
+    struct FlakyStore {
+        store: InMemoryVectorStore,
+        failed: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait]
+    impl VectorStore for FlakyStore {
+        async fn get_by_id(&self, id: String) -> Result<Embedding, VectorStoreError> {
+            self.store.get_by_id(id).await
+        }
+
+        async fn store(&self, embedding: Embedding) -> Result<(), VectorStoreError> {
+            self.store.store(embedding).await
+        }
+
+        async fn batch_store(
+            &self,
+            embeddings: Vec<Embedding>,
+        ) -> BatchResult<(), VectorStoreError> {
+            if self.failed.swap(true, Ordering::SeqCst) {
+                return self.store.batch_store(embeddings).await;
+            }
+            embeddings
+                .iter()
+                .map(|_| Err(VectorStoreError::Provider("unavailable".to_string())))
+                .collect()
+        }
+
+        async fn top_n(&self, query: &[f64], n: usize) -> Result<Vec<Embedding>, VectorStoreError> {
+            self.store.top_n(query, n).await
+        }
+
+        async fn list_ids(&self) -> Result<Vec<String>, VectorStoreError> {
+            self.store.list_ids().await
+        }
+    }
+
+    fn embedding(id: usize) -> Embedding {
+        Embedding {
+            id: id.to_string(),
+            embedded_data: vec![1.0, 0.0],
+            raw_data: format!("document {id}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stores_are_written_in_batches() {
+        let inner = Arc::new(CountingStore {
+            store: InMemoryVectorStore::new(None).await.unwrap(),
+            batches: AtomicUsize::new(0),
+        });
+        let buffered = BufferedVectorStore::new(inner.clone(), 4);
+
+        for id in 0..10 {
+            buffered.store(embedding(id)).await.unwrap();
+        }
+        assert_eq!(inner.batches.load(Ordering::SeqCst), 2);
+        assert_eq!(buffered.pending().await, 2);
+        assert!(inner.store.get_by_id("9".to_string()).await.is_err());
+
+        buffered.flush().await.unwrap();
+        assert_eq!(inner.batches.load(Ordering::SeqCst), 3);
+        assert_eq!(buffered.pending().await, 0);
+        assert_eq!(inner.store.list_ids().await.unwrap().len(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_reads_see_buffered_stores() {
+        let inner = Arc::new(InMemoryVectorStore::new(None).await.unwrap());
+        let buffered = BufferedVectorStore::new(inner, 100);
+
+        buffered.store(embedding(1)).await.unwrap();
+
+        assert_eq!(
+            buffered.get_by_id("1".to_string()).await.unwrap(),
+            embedding(1)
+        );
+        assert_eq!(buffered.pending().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_flush_interval_drains_the_buffer() {
+        let inner = Arc::new(InMemoryVectorStore::new(None).await.unwrap());
+        let buffered = BufferedVectorStore::new(inner.clone(), 100)
+            .with_flush_interval(Duration::from_millis(20));
+
+        buffered.store(embedding(1)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(buffered.pending().await, 0);
+        assert_eq!(inner.list_ids().await.unwrap(), vec!["1".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_embeddings_are_retried_by_the_next_flush() {
+        let inner = Arc::new(FlakyStore {
+            store: InMemoryVectorStore::new(None).await.unwrap(),
+            failed: std::sync::atomic::AtomicBool::new(false),
+        });
+        let buffered = BufferedVectorStore::new(inner.clone(), 100);
+        for id in 0..3 {
+            buffered.store(embedding(id)).await.unwrap();
+        }
+
+        assert!(matches!(
+            buffered.flush().await,
+            Err(VectorStoreError::FailedUpsert(_))
+        ));
+        assert_eq!(buffered.pending().await, 3);
+
+        buffered.flush().await.unwrap();
+        assert_eq!(buffered.pending().await, 0);
+        assert_eq!(inner.store.list_ids().await.unwrap().len(), 3);
+    }
+}
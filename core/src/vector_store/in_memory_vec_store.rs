@@ -4,12 +4,22 @@ use std::collections::HashMap;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, instrument};
 
-use super::{cosine_similarity, VectorStore, VectorStoreError};
+use super::{
+    metrics::{cosine_similarity, MetricError},
+    VectorStore, VectorStoreError,
+};
 use crate::embeddings::embedding::Embedding;
 
+type Embeddings = HashMap<String, Embedding>;
+
+/// A vector store keeping the embeddings in memory
+///
+/// Embeddings stored for a tenant, through the `*_in` methods or a
+/// [`super::ScopedVectorStore`], are kept apart from the unscoped ones and from other tenants.
 #[derive(Debug)]
 pub struct InMemoryVectorStore {
-    embeddings: RwLock<HashMap<String, Embedding>>,
+    embeddings: RwLock<Embeddings>,
+    tenants: RwLock<HashMap<String, Embeddings>>,
 }
 
 impl InMemoryVectorStore {
@@ -27,6 +37,7 @@ impl InMemoryVectorStore {
         info!("Creating a new InMemoryVectorStore");
         Ok(Self {
             embeddings: RwLock::new(HashMap::new()),
+            tenants: RwLock::new(HashMap::new()),
         })
     }
 }
@@ -35,75 +46,170 @@ impl InMemoryVectorStore {
 impl VectorStore for InMemoryVectorStore {
     #[instrument(skip(self))]
     async fn get_by_id(&self, id: String) -> Result<Embedding, VectorStoreError> {
+        get_by_id(&*self.embeddings.read().await, &id)
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Embedding>, VectorStoreError> {
         let embeddings = self.embeddings.read().await;
-        let res = embeddings
-            .get(&id)
-            .ok_or(VectorStoreError::EmbeddingNotFound)
-            .cloned();
-        if res.is_ok() {
-            debug!("Found embedding for document");
-        } else {
-            error!("Failed to find embedding for document");
-        };
-        res
+        Ok(ids
+            .iter()
+            .filter_map(|id| embeddings.get(id).cloned())
+            .collect())
     }
 
     async fn store(&self, embedding: Embedding) -> Result<(), VectorStoreError> {
-        let mut embeddings = self.embeddings.write().await;
-        if embedding.raw_data.is_empty() {
-            let res = embeddings
-                .remove(&embedding.id)
-                .ok_or(VectorStoreError::EmbeddingNotFound);
-            if let Err(e) = res.as_ref() {
-                error!(
-                    "Failed to remove document :({}) from InMemoryVectorStore: {e:?}",
+        store(&mut *self.embeddings.write().await, embedding)
+    }
+
+    async fn top_n(&self, query: &[f64], n: usize) -> Result<Vec<Embedding>, VectorStoreError> {
+        top_n(&*self.embeddings.read().await, query, n)
+    }
+
+    async fn top_n_scored(
+        &self,
+        query: &[f64],
+        n: usize,
+    ) -> Result<Vec<(Embedding, f64)>, VectorStoreError> {
+        top_n_scored(&*self.embeddings.read().await, query, n)
+    }
+
+    async fn list_ids(&self) -> Result<Vec<String>, VectorStoreError> {
+        Ok(self.embeddings.read().await.keys().cloned().collect())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_by_id_in(&self, tenant: &str, id: String) -> Result<Embedding, VectorStoreError> {
+        let tenants = self.tenants.read().await;
+        let embeddings = tenants
+            .get(tenant)
+            .ok_or(VectorStoreError::EmbeddingNotFound)?;
+        get_by_id(embeddings, &id)
+    }
+
+    async fn store_in(&self, tenant: &str, embedding: Embedding) -> Result<(), VectorStoreError> {
+        let mut tenants = self.tenants.write().await;
+        let embeddings = tenants.entry(tenant.to_string()).or_default();
+        let res = store(embeddings, embedding);
+        if embeddings.is_empty() {
+            tenants.remove(tenant);
+        }
+        res
+    }
+
+    async fn top_n_in(
+        &self,
+        tenant: &str,
+        query: &[f64],
+        n: usize,
+    ) -> Result<Vec<Embedding>, VectorStoreError> {
+        let tenants = self.tenants.read().await;
+        tenants
+            .get(tenant)
+            .map_or(Ok(vec![]), |embeddings| top_n(embeddings, query, n))
+    }
+
+    async fn list_ids_in(&self, tenant: &str) -> Result<Vec<String>, VectorStoreError> {
+        let tenants = self.tenants.read().await;
+        Ok(tenants
+            .get(tenant)
+            .map(|embeddings| embeddings.keys().cloned().collect())
+            .unwrap_or_default())
+    }
+}
+
+fn get_by_id(embeddings: &Embeddings, id: &str) -> Result<Embedding, VectorStoreError> {
+    let res = embeddings
+        .get(id)
+        .ok_or(VectorStoreError::EmbeddingNotFound)
+        .cloned();
+    if res.is_ok() {
+        debug!("Found embedding for document");
+    } else {
+        error!("Failed to find embedding for document");
+    };
+    res
+}
+
+fn store(embeddings: &mut Embeddings, embedding: Embedding) -> Result<(), VectorStoreError> {
+    if embedding.raw_data.is_empty() {
+        let res = embeddings
+            .remove(&embedding.id)
+            .ok_or(VectorStoreError::EmbeddingNotFound);
+        if let Err(e) = res.as_ref() {
+            error!(
+                "Failed to remove document :({}) from InMemoryVectorStore: {e:?}",
+                embedding.id.clone()
+            );
+        } else {
+            info!(
+                "Removed document :({}) from InMemoryVectorStore",
+                embedding.id.clone()
+            );
+        }
+        res?;
+    } else {
+        if tracing::enabled!(tracing::Level::INFO) {
+            if embeddings.contains_key(&embedding.id.clone()) {
+                info!(
+                    "Updated document :({}) in the InMemoryVectorStore",
                     embedding.id.clone()
                 );
             } else {
                 info!(
-                    "Removed document :({}) from InMemoryVectorStore",
+                    "Inserted document :({}) to the InMemoryVectorStore",
                     embedding.id.clone()
                 );
             }
-            res?;
-        } else {
-            if tracing::enabled!(tracing::Level::INFO) {
-                if embeddings.contains_key(&embedding.id.clone()) {
-                    info!(
-                        "Updated document :({}) in the InMemoryVectorStore",
-                        embedding.id.clone()
-                    );
-                } else {
-                    info!(
-                        "Inserted document :({}) to the InMemoryVectorStore",
-                        embedding.id.clone()
-                    );
-                }
-            }
-            embeddings.insert(embedding.id.clone(), embedding);
         }
-        Ok(())
+        embeddings.insert(embedding.id.clone(), embedding);
     }
+    Ok(())
+}
 
-    async fn top_n(&self, query: &[f64], n: usize) -> Result<Vec<Embedding>, VectorStoreError> {
-        let embeddings = self.embeddings.read().await;
-        let mut results = embeddings
-            .clone()
-            .into_values()
-            .map(|embedding| {
-                let score = cosine_similarity(query, &embedding.embedded_data);
-                (score, embedding)
-            })
-            .collect::<Vec<_>>();
-        results.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
-        results.truncate(n);
-        Ok(results.iter().map(|(_, em)| em.clone()).collect())
-    }
+fn top_n(
+    embeddings: &Embeddings,
+    query: &[f64],
+    n: usize,
+) -> Result<Vec<Embedding>, VectorStoreError> {
+    Ok(top_n_scored(embeddings, query, n)?
+        .into_iter()
+        .map(|(embedding, _)| embedding)
+        .collect())
+}
+
+fn top_n_scored(
+    embeddings: &Embeddings,
+    query: &[f64],
+    n: usize,
+) -> Result<Vec<(Embedding, f64)>, VectorStoreError> {
+    let mut results = embeddings
+        .values()
+        .map(|embedding| {
+            let score = cosine_similarity(&embedding.embedded_data, query).map_err(
+                |MetricError::LengthMismatch(expected, got)| {
+                    error!(
+                        "Query dimension {got} doesn't match the dimension {expected} of embedding :({})",
+                        embedding.id
+                    );
+                    VectorStoreError::DimensionMismatch { expected, got }
+                },
+            )?;
+            Ok((score, embedding))
+        })
+        .collect::<Result<Vec<_>, VectorStoreError>>()?;
+    results.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    results.truncate(n);
+    Ok(results
+        .into_iter()
+        .map(|(score, em)| (em.clone(), score))
+        .collect())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vector_store::ScopedVectorStore;
+    use std::sync::Arc;
 
     #[tokio::test]
     async fn test_get_by_id() {
@@ -114,6 +220,7 @@ mod tests {
         };
         let store = InMemoryVectorStore {
             embeddings: RwLock::new(HashMap::from([("id".to_string(), embedding.clone())])),
+            tenants: RwLock::new(HashMap::new()),
         };
 
         // test getting existing embedding
@@ -131,6 +238,7 @@ mod tests {
     async fn test_store() {
         let store = InMemoryVectorStore {
             embeddings: RwLock::new(HashMap::new()),
+            tenants: RwLock::new(HashMap::new()),
         };
 
         let embedding = Embedding {
@@ -177,6 +285,26 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_get_by_ids_omits_missing_ids() {
+        let store = Arc::new(InMemoryVectorStore::new(None).await.unwrap());
+        let scoped = ScopedVectorStore::new(store.clone(), "alice");
+        let embedding = |id: &str| Embedding {
+            id: id.to_string(),
+            raw_data: format!("document {id}"),
+            embedded_data: vec![1.0, 0.0],
+        };
+        for id in ["a", "b", "c"] {
+            store.store(embedding(id)).await.unwrap();
+            scoped.store(embedding(id)).await.unwrap();
+        }
+        let ids = ["c", "missing", "a"].map(String::from);
+
+        let expected = vec![embedding("c"), embedding("a")];
+        assert_eq!(store.get_by_ids(&ids).await.unwrap(), expected);
+        assert_eq!(scoped.get_by_ids(&ids).await.unwrap(), expected);
+    }
+
     #[tokio::test]
     async fn test_top_n() {
         let (embedding1, embedding2, embedding3) = (
@@ -203,6 +331,7 @@ mod tests {
                 ("id2".to_string(), embedding2.clone()),
                 ("id3".to_string(), embedding3.clone()),
             ])),
+            tenants: RwLock::new(HashMap::new()),
         };
 
         let query = vec![1.0, 2.0, 3.0];
@@ -214,4 +343,85 @@ mod tests {
         assert_eq!(top_n[0], embedding1);
         assert_eq!(top_n[1], embedding2);
     }
+
+    #[tokio::test]
+    async fn test_top_n_dimension_mismatch() {
+        let store = InMemoryVectorStore {
+            embeddings: RwLock::new(HashMap::new()),
+            tenants: RwLock::new(HashMap::new()),
+        };
+        store
+            .store(Embedding {
+                id: "id".to_string(),
+                raw_data: "hello world".to_string(),
+                embedded_data: vec![1.0, 2.0, 3.0],
+            })
+            .await
+            .unwrap();
+
+        let result = store.top_n(&[1.0, 2.0, 3.0, 4.0], 1).await;
+        assert_eq!(
+            result.unwrap_err(),
+            VectorStoreError::DimensionMismatch {
+                expected: 3,
+                got: 4
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_top_n_text_projects_id_and_raw_data() {
+        let store = InMemoryVectorStore::new(None).await.unwrap();
+        store
+            .store(Embedding {
+                id: "id".to_string(),
+                raw_data: "hello world".to_string(),
+                embedded_data: vec![1.0, 2.0, 3.0],
+            })
+            .await
+            .unwrap();
+
+        let results = store.top_n_text(&[1.0, 2.0, 3.0], 1).await.unwrap();
+        assert_eq!(results, [("id".to_string(), "hello world".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_tenants_are_isolated() {
+        let store = Arc::new(InMemoryVectorStore::new(None).await.unwrap());
+        let (alice, bob) = (
+            ScopedVectorStore::new(Arc::clone(&store) as Arc<dyn VectorStore>, "alice"),
+            ScopedVectorStore::new(Arc::clone(&store) as Arc<dyn VectorStore>, "bob"),
+        );
+        let embedding = |id: &str, raw_data: &str| Embedding {
+            id: id.to_string(),
+            raw_data: raw_data.to_string(),
+            embedded_data: vec![1.0, 0.0],
+        };
+        alice
+            .store(embedding("doc", "alice's notes"))
+            .await
+            .unwrap();
+        bob.store(embedding("doc", "bob's notes")).await.unwrap();
+        bob.store(embedding("other", "more of bob's notes"))
+            .await
+            .unwrap();
+
+        let alice_results = alice.top_n(&[1.0, 0.0], 10).await.unwrap();
+        assert_eq!(alice_results, vec![embedding("doc", "alice's notes")]);
+        assert_eq!(bob.top_n(&[1.0, 0.0], 10).await.unwrap().len(), 2);
+        assert_eq!(
+            alice.get_by_id("doc".to_string()).await.unwrap().raw_data,
+            "alice's notes"
+        );
+        assert_eq!(
+            alice.get_by_id("other".to_string()).await,
+            Err(VectorStoreError::EmbeddingNotFound)
+        );
+        assert_eq!(alice.list_ids().await.unwrap(), vec!["doc".to_string()]);
+
+        assert!(store.top_n(&[1.0, 0.0], 10).await.unwrap().is_empty());
+        alice.store(embedding("doc", "")).await.unwrap();
+        assert!(alice.top_n(&[1.0, 0.0], 10).await.unwrap().is_empty());
+        assert_eq!(bob.top_n(&[1.0, 0.0], 10).await.unwrap().len(), 2);
+    }
 }
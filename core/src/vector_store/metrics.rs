@@ -0,0 +1,93 @@
+//! Similarity and distance metrics over embedding vectors.
+//!
+//! Useful for custom [`super::VectorStore`] implementations and reranking logic.
+//! All functions return a [`MetricError`] if the vectors have different lengths.
+
+use thiserror::Error;
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum MetricError {
+    #[error("Vectors have mismatched lengths: {0} and {1}")]
+    LengthMismatch(usize, usize),
+}
+
+/// Computes the cosine similarity of two vectors
+///
+/// Returns `0.0` if either of the vectors has a magnitude of zero.
+///
+/// # Errors
+/// Returns [`MetricError::LengthMismatch`] if the vectors have different lengths
+pub fn cosine_similarity(a: &[f64], b: &[f64]) -> Result<f64, MetricError> {
+    let dot_product = dot_product(a, b)?;
+    let norm_a = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return Ok(0.0);
+    }
+    Ok(dot_product / (norm_a * norm_b))
+}
+
+/// Computes the dot product of two vectors
+///
+/// # Errors
+/// Returns [`MetricError::LengthMismatch`] if the vectors have different lengths
+pub fn dot_product(a: &[f64], b: &[f64]) -> Result<f64, MetricError> {
+    check_lengths(a, b)?;
+    Ok(a.iter().zip(b).map(|(x, y)| x * y).sum())
+}
+
+/// Computes the euclidean distance between two vectors
+///
+/// # Errors
+/// Returns [`MetricError::LengthMismatch`] if the vectors have different lengths
+pub fn euclidean_distance(a: &[f64], b: &[f64]) -> Result<f64, MetricError> {
+    check_lengths(a, b)?;
+    Ok(a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f64>()
+        .sqrt())
+}
+
+fn check_lengths(a: &[f64], b: &[f64]) -> Result<(), MetricError> {
+    if a.len() == b.len() {
+        Ok(())
+    } else {
+        Err(MetricError::LengthMismatch(a.len(), b.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]).unwrap() - 1.0).abs() < f64::EPSILON);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).unwrap().abs() < f64::EPSILON);
+        assert!((cosine_similarity(&[1.0, 0.0], &[-1.0, 0.0]).unwrap() + 1.0).abs() < f64::EPSILON);
+        assert!(cosine_similarity(&[0.0, 0.0], &[1.0, 2.0]).unwrap().abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_dot_product_and_euclidean_distance() {
+        assert!(
+            (dot_product(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]).unwrap() - 32.0).abs() < f64::EPSILON
+        );
+        assert!((euclidean_distance(&[0.0, 0.0], &[3.0, 4.0]).unwrap() - 5.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_mismatched_lengths() {
+        let (a, b) = ([1.0, 2.0, 3.0], [1.0, 2.0]);
+        assert_eq!(
+            cosine_similarity(&a, &b),
+            Err(MetricError::LengthMismatch(3, 2))
+        );
+        assert_eq!(dot_product(&a, &b), Err(MetricError::LengthMismatch(3, 2)));
+        assert_eq!(
+            euclidean_distance(&b, &a),
+            Err(MetricError::LengthMismatch(2, 3))
+        );
+    }
+}
@@ -1,12 +1,19 @@
 use async_trait::async_trait;
 use thiserror::Error;
 
-use super::embeddings::embedding::Embedding;
+use super::embeddings::{embedding::Embedding, BatchResult};
 
+pub mod buffered;
 pub mod in_memory_vec_store;
+pub mod metrics;
+pub mod scoped;
 
+#[allow(clippy::module_name_repetitions)]
+pub use buffered::BufferedVectorStore;
 #[allow(clippy::module_name_repetitions)]
 pub use in_memory_vec_store::InMemoryVectorStore;
+#[allow(clippy::module_name_repetitions)]
+pub use scoped::ScopedVectorStore;
 
 #[allow(clippy::module_name_repetitions)]
 #[derive(Clone, Debug, PartialEq, Eq, Error)]
@@ -19,6 +26,10 @@ pub enum VectorStoreError {
     EmbeddingNotFound,
     #[error("Provider error: {0}")]
     Provider(String),
+    #[error("Embedding dimension mismatch: expected {expected}, got {got}")]
+    DimensionMismatch { expected: usize, got: usize },
+    #[error("Operation not supported by the store: {0}")]
+    Unsupported(String),
 }
 
 #[async_trait]
@@ -26,6 +37,23 @@ pub trait VectorStore: Send + Sync {
     /// Fetch an Embedding from the vec store with a matching id
     async fn get_by_id(&self, id: String) -> Result<Embedding, VectorStoreError>;
 
+    /// Fetches the embeddings with the ids in `ids`, in their order, omitting the ids that
+    /// aren't in the store
+    ///
+    /// Default implementation fetches the embeddings one by one, stores with bulk fetches
+    /// should override it.
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Embedding>, VectorStoreError> {
+        let mut embeddings = Vec::with_capacity(ids.len());
+        for id in ids {
+            match self.get_by_id(id.clone()).await {
+                Ok(embedding) => embeddings.push(embedding),
+                Err(VectorStoreError::EmbeddingNotFound) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(embeddings)
+    }
+
     /// Stores or updates an embedding in the vector store
     ///
     /// if the `raw_data` field is empty, it removes the embedding from the store.
@@ -33,13 +61,111 @@ pub trait VectorStore: Send + Sync {
     /// if the `raw_data` is not empty, it inserts or updates the embedding in the store
     async fn store(&self, embedding: Embedding) -> Result<(), VectorStoreError>;
 
+    /// Stores every embedding of `embeddings` like [`VectorStore::store`], reporting the
+    /// failures per embedding
+    ///
+    /// Default implementation stores the embeddings one by one, stores with bulk upserts
+    /// should override it.
+    async fn batch_store(&self, embeddings: Vec<Embedding>) -> BatchResult<(), VectorStoreError> {
+        let mut results = Vec::with_capacity(embeddings.len());
+        for embedding in embeddings {
+            results.push(self.store(embedding).await);
+        }
+        results.into()
+    }
+
     /// Fetch top n `Embedding`s ordered by cosine_similarity score
     async fn top_n(&self, query: &[f64], n: usize) -> Result<Vec<Embedding>, VectorStoreError>;
-}
 
-pub(crate) fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
-    let dot_product: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
-    let norm_a = a.iter().map(|x| x * x).sum::<f64>().sqrt();
-    let norm_b = b.iter().map(|x| x * x).sum::<f64>().sqrt();
-    dot_product / (norm_a * norm_b)
+    /// Like [`VectorStore::top_n`], but only returns the id and raw data of every embedding,
+    /// eg. to build a prompt's context
+    ///
+    /// Default implementation projects the embeddings returned by [`VectorStore::top_n`],
+    /// remote stores should override it to leave the vectors out of their responses.
+    async fn top_n_text(
+        &self,
+        query: &[f64],
+        n: usize,
+    ) -> Result<Vec<(String, String)>, VectorStoreError> {
+        Ok(self
+            .top_n(query, n)
+            .await?
+            .into_iter()
+            .map(|embedding| (embedding.id, embedding.raw_data))
+            .collect())
+    }
+
+    /// Like [`VectorStore::top_n`], but returns the cosine similarity score of every
+    /// embedding along with it
+    ///
+    /// Default implementation scores the embeddings returned by [`VectorStore::top_n`],
+    /// stores with native scores should override it.
+    async fn top_n_scored(
+        &self,
+        query: &[f64],
+        n: usize,
+    ) -> Result<Vec<(Embedding, f64)>, VectorStoreError> {
+        self.top_n(query, n)
+            .await?
+            .into_iter()
+            .map(|embedding| {
+                let score = metrics::cosine_similarity(&embedding.embedded_data, query).map_err(
+                    |metrics::MetricError::LengthMismatch(expected, got)| {
+                        VectorStoreError::DimensionMismatch { expected, got }
+                    },
+                )?;
+                Ok((embedding, score))
+            })
+            .collect()
+    }
+
+    /// Lists the ids of all embeddings in the store
+    ///
+    /// Default implementation returns [`VectorStoreError::Unsupported`]
+    /// unless overridden by the store implementation.
+    async fn list_ids(&self) -> Result<Vec<String>, VectorStoreError> {
+        Err(VectorStoreError::Unsupported("list_ids".to_string()))
+    }
+
+    /// Like [`VectorStore::get_by_id`], but only fetches embeddings stored for `tenant`
+    ///
+    /// Default implementation returns [`VectorStoreError::Unsupported`]
+    /// unless overridden by the store implementation.
+    async fn get_by_id_in(&self, tenant: &str, id: String) -> Result<Embedding, VectorStoreError> {
+        let _ = (tenant, id);
+        Err(VectorStoreError::Unsupported("get_by_id_in".to_string()))
+    }
+
+    /// Like [`VectorStore::store`], but stores the embedding for `tenant`, isolated from
+    /// the embeddings of other tenants and the unscoped ones
+    ///
+    /// Default implementation returns [`VectorStoreError::Unsupported`]
+    /// unless overridden by the store implementation.
+    async fn store_in(&self, tenant: &str, embedding: Embedding) -> Result<(), VectorStoreError> {
+        let _ = (tenant, embedding);
+        Err(VectorStoreError::Unsupported("store_in".to_string()))
+    }
+
+    /// Like [`VectorStore::top_n`], but only searches the embeddings stored for `tenant`
+    ///
+    /// Default implementation returns [`VectorStoreError::Unsupported`]
+    /// unless overridden by the store implementation.
+    async fn top_n_in(
+        &self,
+        tenant: &str,
+        query: &[f64],
+        n: usize,
+    ) -> Result<Vec<Embedding>, VectorStoreError> {
+        let _ = (tenant, query, n);
+        Err(VectorStoreError::Unsupported("top_n_in".to_string()))
+    }
+
+    /// Like [`VectorStore::list_ids`], but only lists the embeddings stored for `tenant`
+    ///
+    /// Default implementation returns [`VectorStoreError::Unsupported`]
+    /// unless overridden by the store implementation.
+    async fn list_ids_in(&self, tenant: &str) -> Result<Vec<String>, VectorStoreError> {
+        let _ = tenant;
+        Err(VectorStoreError::Unsupported("list_ids_in".to_string()))
+    }
 }
@@ -0,0 +1,61 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use super::{VectorStore, VectorStoreError};
+use crate::embeddings::embedding::Embedding;
+
+/// A handle to a vector store scoped to a single tenant, eg. a user of a multi-tenant app
+///
+/// Every operation goes through the store's tenant-scoped `*_in` methods, so the embeddings
+/// of different tenants never mix even though they share one store instance.
+/// Being a [`VectorStore`] itself, the handle can back a per-tenant
+/// [`crate::embeddings::Embedder`].
+///
+/// # Example
+/// ```rust,ignore
+/// let store: Arc<dyn VectorStore> = Arc::new(InMemoryVectorStore::new(None).await?);
+/// let alice = ScopedVectorStore::new(Arc::clone(&store), "alice");
+/// alice.store(embedding).await?;
+/// let results = alice.top_n(&query, 5).await?;
+/// ```
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone)]
+pub struct ScopedVectorStore {
+    store: Arc<dyn VectorStore>,
+    tenant: String,
+}
+
+impl ScopedVectorStore {
+    /// Creates a handle scoping `store` to `tenant`
+    pub fn new(store: Arc<dyn VectorStore>, tenant: impl Into<String>) -> Self {
+        Self {
+            store,
+            tenant: tenant.into(),
+        }
+    }
+
+    /// The tenant this handle is scoped to
+    #[must_use]
+    pub fn tenant(&self) -> &str {
+        &self.tenant
+    }
+}
+
+#[async_trait]
+impl VectorStore for ScopedVectorStore {
+    async fn get_by_id(&self, id: String) -> Result<Embedding, VectorStoreError> {
+        self.store.get_by_id_in(&self.tenant, id).await
+    }
+
+    async fn store(&self, embedding: Embedding) -> Result<(), VectorStoreError> {
+        self.store.store_in(&self.tenant, embedding).await
+    }
+
+    async fn top_n(&self, query: &[f64], n: usize) -> Result<Vec<Embedding>, VectorStoreError> {
+        self.store.top_n_in(&self.tenant, query, n).await
+    }
+
+    async fn list_ids(&self) -> Result<Vec<String>, VectorStoreError> {
+        self.store.list_ids_in(&self.tenant).await
+    }
+}
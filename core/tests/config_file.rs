@@ -0,0 +1,47 @@
+use seedframe::prelude::*;
+use serde_json::{json, Value};
+
+/// A store recording the config it was built with
+struct RecordingStore {
+    config: Option<Value>,
+}
+
+impl RecordingStore {
+    #[allow(clippy::unused_async)]
+    async fn new(json_config: Option<&str>) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            config: json_config.map(serde_json::from_str).transpose()?,
+        })
+    }
+}
+
+#[vector_store(
+    store = "RecordingStore",
+    config_file = "tests/fixtures/store_config.json"
+)]
+struct FileConfiguredStore;
+
+#[vector_store(
+    store = "RecordingStore",
+    config_file = "tests/fixtures/store_config.json",
+    config = r#"{"namespace": "docs"}"#
+)]
+struct OverriddenStore;
+
+#[tokio::test]
+async fn config_is_read_from_the_file() {
+    let store = FileConfiguredStore::build().await.unwrap();
+    assert_eq!(
+        store.inner.config,
+        Some(json!({ "namespace": "shared", "dimensions": 3 }))
+    );
+}
+
+#[tokio::test]
+async fn inline_config_overrides_the_file() {
+    let store = OverriddenStore::build().await.unwrap();
+    assert_eq!(
+        store.inner.config,
+        Some(json!({ "namespace": "docs", "dimensions": 3 }))
+    );
+}
@@ -0,0 +1,192 @@
+//! Mock LLM server emulating the `OpenAI` chat completions and embeddings endpoints, for testing
+//! providers against their wire format without real API keys.
+
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{header, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+pub const COMPLETIONS_PATH: &str = "/v1/chat/completions";
+pub const EMBEDDINGS_PATH: &str = "/v1/embeddings";
+
+/// Name of the env var holding the API key the mock server expects
+pub const API_KEY_VAR: &str = "SEEDFRAME_MOCK_LLM_API_KEY";
+const API_KEY: &str = "mock-key";
+
+pub struct MockLlmServer {
+    server: MockServer,
+}
+
+impl MockLlmServer {
+    /// Starts a server answering requests without the mock API key with a 401
+    pub async fn start() -> Self {
+        std::env::set_var(API_KEY_VAR, API_KEY);
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(401)
+                    .set_body_json(error_body("Incorrect API key provided", "invalid_api_key")),
+            )
+            .with_priority(u8::MAX)
+            .mount(&server)
+            .await;
+        Self { server }
+    }
+
+    pub fn completions_url(&self) -> String {
+        format!("{}{COMPLETIONS_PATH}", self.server.uri())
+    }
+
+    pub fn embeddings_url(&self) -> String {
+        format!("{}{EMBEDDINGS_PATH}", self.server.uri())
+    }
+
+    /// Answers the next completion request with `body`, requests are answered in the order
+    /// their responses were queued
+    pub async fn queue_completion(&self, body: Value) {
+        self.queue(
+            COMPLETIONS_PATH,
+            ResponseTemplate::new(200).set_body_json(body),
+        )
+        .await;
+    }
+
+    /// Answers the next completion request with `body` after `delay`
+    pub async fn queue_delayed_completion(&self, body: Value, delay: std::time::Duration) {
+        self.queue(
+            COMPLETIONS_PATH,
+            ResponseTemplate::new(200)
+                .set_body_json(body)
+                .set_delay(delay),
+        )
+        .await;
+    }
+
+    /// Answers the next completion request with a stream of `events`, as server-sent events
+    /// ending with `[DONE]`
+    pub async fn queue_completion_stream(&self, events: &[Value]) {
+        let body: String = events
+            .iter()
+            .map(|event| format!("data: {event}\n\n"))
+            .chain(std::iter::once("data: [DONE]\n\n".to_string()))
+            .collect();
+        self.queue(
+            COMPLETIONS_PATH,
+            ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"),
+        )
+        .await;
+    }
+
+    /// Answers the next completion request with an error `status`
+    pub async fn queue_completion_error(&self, status: u16, body: Value) {
+        self.queue(
+            COMPLETIONS_PATH,
+            ResponseTemplate::new(status).set_body_json(body),
+        )
+        .await;
+    }
+
+    /// Answers the next embedding request with `embedding`
+    pub async fn queue_embedding(&self, embedding: &[f64]) {
+        self.queue(
+            EMBEDDINGS_PATH,
+            ResponseTemplate::new(200).set_body_json(embedding_response(embedding)),
+        )
+        .await;
+    }
+
+    async fn queue(&self, endpoint: &str, response: ResponseTemplate) {
+        Mock::given(method("POST"))
+            .and(path(endpoint))
+            .and(header("authorization", format!("Bearer {API_KEY}")))
+            .respond_with(response)
+            .up_to_n_times(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// JSON bodies of the requests received on `endpoint`, in the order they were received
+    pub async fn request_bodies(&self, endpoint: &str) -> Vec<Value> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|request| request.url.path() == endpoint)
+            .map(|request| request.body_json().unwrap())
+            .collect()
+    }
+}
+
+/// A chat completion answering with `content`
+pub fn text_completion(content: &str) -> Value {
+    completion(json!({ "role": "assistant", "content": content }), "stop")
+}
+
+/// A chat completion calling the tool `name` with `arguments`
+pub fn tool_call_completion(id: &str, name: &str, arguments: &Value) -> Value {
+    completion(
+        json!({
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [{
+                "id": id,
+                "type": "function",
+                "function": { "name": name, "arguments": arguments.to_string() }
+            }]
+        }),
+        "tool_calls",
+    )
+}
+
+/// A streamed chunk of a chat completion adding `content` to the response
+pub fn text_chunk(content: &str) -> Value {
+    json!({
+        "id": "chatcmpl-mock",
+        "object": "chat.completion.chunk",
+        "choices": [{ "index": 0, "delta": { "content": content }, "finish_reason": null }]
+    })
+}
+
+/// The final streamed chunk of a chat completion, reporting the usage of the request
+pub fn usage_chunk(prompt_tokens: u64, completion_tokens: u64) -> Value {
+    json!({
+        "id": "chatcmpl-mock",
+        "object": "chat.completion.chunk",
+        "choices": [],
+        "usage": {
+            "prompt_tokens": prompt_tokens,
+            "completion_tokens": completion_tokens,
+            "total_tokens": prompt_tokens + completion_tokens
+        }
+    })
+}
+
+fn completion(message: Value, finish_reason: &str) -> Value {
+    json!({
+        "id": "chatcmpl-mock",
+        "object": "chat.completion",
+        "created": 1_700_000_000,
+        "model": "gpt-4o-mini",
+        "system_fingerprint": "fp_mock",
+        "choices": [{ "index": 0, "message": message, "finish_reason": finish_reason }],
+        "usage": { "prompt_tokens": 12, "completion_tokens": 5, "total_tokens": 17 }
+    })
+}
+
+fn embedding_response(embedding: &[f64]) -> Value {
+    json!({
+        "object": "list",
+        "data": [{ "object": "embedding", "index": 0, "embedding": embedding }],
+        "model": "text-embedding-3-small",
+        "usage": { "prompt_tokens": 3, "total_tokens": 3 }
+    })
+}
+
+/// An `OpenAI` error body
+pub fn error_body(message: &str, code: &str) -> Value {
+    json!({
+        "error": { "message": message, "type": "invalid_request_error", "param": null, "code": code }
+    })
+}
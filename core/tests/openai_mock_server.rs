@@ -0,0 +1,446 @@
+mod mock_llm;
+
+use mock_llm::{
+    error_body, text_chunk, text_completion, tool_call_completion, usage_chunk, MockLlmServer,
+    API_KEY_VAR, COMPLETIONS_PATH, EMBEDDINGS_PATH,
+};
+use seedframe::completion::{
+    CompletionError, CompletionModel, ExtractionError, FinishReason, Message, TokenUsage,
+};
+use seedframe::embeddings::model::EmbeddingModel;
+use seedframe::error::Error;
+use seedframe::prelude::*;
+use seedframe::providers::{completions::OpenAI, embeddings::OpenAIEmbedding};
+use seedframe::tools::{ExecutionStrategy, ToolSet};
+use serde_json::{json, Value};
+
+/// Greets a user
+/// # Arguments
+/// * `name`: name of the user
+#[tool]
+fn greet_user(name: String) -> String {
+    format!("Hello {name}")
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Weather {
+    city: String,
+    celsius: i32,
+}
+
+/// Reports the weather of a city
+/// # Arguments
+/// * `city`: city to report the weather of
+#[tool]
+fn get_weather(city: String) -> Weather {
+    Weather { city, celsius: 21 }
+}
+
+fn openai(server: &MockLlmServer) -> OpenAI {
+    OpenAI::new(Some(
+        &json!({
+            "api_key": API_KEY_VAR,
+            "api_url": server.completions_url(),
+            "model": "gpt-4o-mini"
+        })
+        .to_string(),
+    ))
+}
+
+#[tokio::test]
+async fn completion_request_and_response_match_the_wire_format() {
+    let server = MockLlmServer::start().await;
+    server.queue_completion(text_completion("Hi there!")).await;
+    let mut client = openai(&server).build_client(
+        "You are a helpful assistant",
+        vec![],
+        ToolSet(vec![], ExecutionStrategy::FailEarly),
+    );
+
+    let output = client
+        .prompt("Hello")
+        .with_context(false)
+        .send_detailed()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        output.message,
+        Message::Assistant {
+            content: "Hi there!".to_string(),
+            tool_calls: None,
+            name: None,
+        }
+    );
+    assert_eq!(output.token_usage.total_tokens, Some(17));
+    assert_eq!(output.finish_reason, Some(FinishReason::Stop));
+    assert_eq!(output.system_fingerprint.as_deref(), Some("fp_mock"));
+
+    let bodies = server.request_bodies(COMPLETIONS_PATH).await;
+    assert_eq!(bodies.len(), 1);
+    let body = &bodies[0];
+    assert_eq!(body["model"], "gpt-4o-mini");
+    assert!(body["temperature"].is_number());
+    assert!(body.get("tools").is_none());
+    assert_eq!(
+        body["messages"],
+        json!([
+            { "role": "system", "content": "You are a helpful assistant" },
+            { "role": "user", "content": "Hello" }
+        ])
+    );
+}
+
+#[tokio::test]
+async fn completions_without_usage_parse_with_empty_usage() {
+    let server = MockLlmServer::start().await;
+    let mut body = text_completion("Hi there!");
+    body.as_object_mut().unwrap().remove("usage");
+    server.queue_completion(body).await;
+    let mut client = openai(&server).build_client(
+        "preamble",
+        vec![],
+        ToolSet(vec![], ExecutionStrategy::FailEarly),
+    );
+
+    let output = client
+        .prompt("Hello")
+        .with_context(false)
+        .send_detailed()
+        .await
+        .unwrap();
+
+    assert_eq!(output.token_usage, TokenUsage::default());
+    assert_eq!(output.finish_reason, Some(FinishReason::Stop));
+}
+
+#[tokio::test]
+async fn streamed_usage_is_requested_and_counted() {
+    let server = MockLlmServer::start().await;
+    server
+        .queue_completion_stream(&[text_chunk("Hi "), text_chunk("there!"), usage_chunk(9, 3)])
+        .await;
+    let mut client = openai(&server).build_client(
+        "preamble",
+        vec![],
+        ToolSet(vec![], ExecutionStrategy::FailEarly),
+    );
+
+    let mut streamed = String::new();
+    client
+        .prompt("Hello")
+        .with_context(false)
+        .stream(|text| streamed.push_str(text))
+        .await
+        .unwrap();
+
+    assert_eq!(streamed, "Hi there!");
+    assert_eq!(client.token_usage().total_tokens, Some(12));
+    let body = &server.request_bodies(COMPLETIONS_PATH).await[0];
+    assert_eq!(body["stream"], json!(true));
+    assert_eq!(body["stream_options"], json!({ "include_usage": true }));
+}
+
+#[tokio::test]
+async fn tool_calls_are_parsed_and_executed() {
+    let server = MockLlmServer::start().await;
+    server
+        .queue_completion(tool_call_completion(
+            "call_1",
+            "greet_user",
+            &json!({ "name": "jack" }),
+        ))
+        .await;
+    let mut client = openai(&server).build_client(
+        "preamble",
+        vec![],
+        ToolSet(
+            vec![Box::new(__SF_TOOL_greet_user__::new())],
+            ExecutionStrategy::FailEarly,
+        ),
+    );
+
+    let response = client
+        .prompt("Greet jack")
+        .with_context(false)
+        .send()
+        .await
+        .unwrap();
+
+    let Message::User {
+        tool_responses: Some(responses),
+        ..
+    } = response
+    else {
+        panic!("expected tool responses, got {response:?}");
+    };
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].id, "call_1");
+    assert_eq!(
+        responses[0].content,
+        Value::String("Hello jack".to_string())
+    );
+
+    let body = &server.request_bodies(COMPLETIONS_PATH).await[0];
+    let tools = body["tools"].as_array().unwrap();
+    assert_eq!(tools.len(), 1);
+    assert_eq!(tools[0]["type"], "function");
+    assert_eq!(tools[0]["function"]["name"], "greet_user");
+    assert_eq!(
+        tools[0]["function"]["parameters"]["properties"]["name"]["type"],
+        "string"
+    );
+}
+
+#[tokio::test]
+async fn tool_results_deserialize_into_the_returned_type() {
+    let server = MockLlmServer::start().await;
+    server
+        .queue_completion(tool_call_completion(
+            "call_1",
+            "get_weather",
+            &json!({ "city": "Addis Ababa" }),
+        ))
+        .await;
+    let mut client = openai(&server).build_client(
+        "preamble",
+        vec![],
+        ToolSet(
+            vec![
+                Box::new(__SF_TOOL_get_weather__::new()),
+                Box::new(__SF_TOOL_greet_user__::new()),
+            ],
+            ExecutionStrategy::FailEarly,
+        ),
+    );
+
+    let results: Vec<Weather> = client
+        .prompt("How warm is it in Addis Ababa?")
+        .with_context(false)
+        .collect_tool_results("get_weather")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        results,
+        vec![Weather {
+            city: "Addis Ababa".to_string(),
+            celsius: 21
+        }]
+    );
+}
+
+#[tokio::test]
+async fn error_responses_surface_as_provider_errors() {
+    let server = MockLlmServer::start().await;
+    server
+        .queue_completion_error(
+            429,
+            error_body("Rate limit reached for requests", "rate_limit_exceeded"),
+        )
+        .await;
+    let mut client = openai(&server).build_client(
+        "preamble",
+        vec![],
+        ToolSet(vec![], ExecutionStrategy::FailEarly),
+    );
+
+    let error = client
+        .prompt("Hello")
+        .with_context(false)
+        .send()
+        .await
+        .unwrap_err();
+
+    let Error::Completion(CompletionError::ProviderError(status, message)) = error else {
+        panic!("expected a provider error, got {error:?}");
+    };
+    assert_eq!(status, 429);
+    assert!(message.contains("rate_limit_exceeded"));
+    assert_eq!(client.export_history().len(), 1);
+}
+
+#[tokio::test]
+async fn embedding_request_and_response_match_the_wire_format() {
+    let server = MockLlmServer::start().await;
+    server.queue_embedding(&[0.25, -0.5, 1.0]).await;
+    let model = OpenAIEmbedding::new(Some(
+        &json!({
+            "api_key": API_KEY_VAR,
+            "api_url": server.embeddings_url(),
+            "model": "text-embedding-3-small"
+        })
+        .to_string(),
+    ));
+
+    let embedding = model.embed("hello world").await.unwrap();
+
+    assert_eq!(embedding, vec![0.25, -0.5, 1.0]);
+    let bodies = server.request_bodies(EMBEDDINGS_PATH).await;
+    assert_eq!(
+        bodies,
+        vec![json!({ "input": "hello world", "model": "text-embedding-3-small" })]
+    );
+}
+
+#[derive(Debug, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+struct Forecast {
+    city: String,
+    celsius: i32,
+}
+
+impl seedframe::completion::Extractor for Forecast {}
+
+#[tokio::test]
+async fn invalid_extractions_are_retried_with_feedback() {
+    let server = MockLlmServer::start().await;
+    server
+        .queue_completion(text_completion(
+            r#"{"city": "Addis Ababa", "celsius": "warm"}"#,
+        ))
+        .await;
+    server
+        .queue_completion(text_completion(r#"{"city": "Addis Ababa", "celsius": 22}"#))
+        .await;
+    let mut client = openai(&server).build_client(
+        "preamble",
+        vec![],
+        ToolSet(vec![], ExecutionStrategy::FailEarly),
+    );
+
+    let forecast: Forecast = client
+        .prompt("What's the forecast for Addis Ababa?")
+        .with_context(false)
+        .max_extract_retries(1)
+        .extract()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        forecast,
+        Forecast {
+            city: "Addis Ababa".to_string(),
+            celsius: 22,
+        }
+    );
+    let bodies = server.request_bodies(COMPLETIONS_PATH).await;
+    assert_eq!(bodies.len(), 2);
+    let messages = bodies[1]["messages"].as_array().unwrap();
+    let [.., invalid, feedback] = messages.as_slice() else {
+        panic!("expected the retry to carry feedback, got {messages:?}");
+    };
+    assert_eq!(invalid["role"], "assistant");
+    assert_eq!(feedback["role"], "user");
+    assert!(feedback["content"].as_str().unwrap().contains("celsius"));
+}
+
+#[tokio::test]
+async fn fenced_extractions_are_parsed() {
+    let server = MockLlmServer::start().await;
+    server
+        .queue_completion(text_completion(
+            "\n```json\n{\"city\": \"Addis Ababa\", \"celsius\": 22}\n```\n",
+        ))
+        .await;
+    let mut client = openai(&server).build_client(
+        "preamble",
+        vec![],
+        ToolSet(vec![], ExecutionStrategy::FailEarly),
+    );
+
+    let forecast: Forecast = client
+        .prompt("What's the forecast for Addis Ababa?")
+        .with_context(false)
+        .extract()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        forecast,
+        Forecast {
+            city: "Addis Ababa".to_string(),
+            celsius: 22,
+        }
+    );
+}
+
+#[tokio::test]
+async fn extractions_use_the_prompt_request_options() {
+    let server = MockLlmServer::start().await;
+    server
+        .queue_completion(text_completion(r#"{"city": "Addis Ababa", "celsius": 22}"#))
+        .await;
+    let mut client = openai(&server).build_client(
+        "preamble",
+        vec![],
+        ToolSet(vec![], ExecutionStrategy::FailEarly),
+    );
+
+    let _: Forecast = client
+        .prompt("What's the forecast for Addis Ababa?")
+        .with_context(false)
+        .top_p(0.3)
+        .seed(7)
+        .extract()
+        .await
+        .unwrap();
+
+    let bodies = server.request_bodies(COMPLETIONS_PATH).await;
+    assert_eq!(bodies[0]["top_p"], json!(0.3));
+    assert_eq!(bodies[0]["seed"], json!(7));
+}
+
+#[tokio::test]
+async fn extractions_time_out() {
+    let server = MockLlmServer::start().await;
+    server
+        .queue_delayed_completion(
+            text_completion(r#"{"city": "Addis Ababa", "celsius": 22}"#),
+            std::time::Duration::from_millis(500),
+        )
+        .await;
+    let mut client = openai(&server).build_client(
+        "preamble",
+        vec![],
+        ToolSet(vec![], ExecutionStrategy::FailEarly),
+    );
+
+    let result = client
+        .prompt("What's the forecast for Addis Ababa?")
+        .with_context(false)
+        .timeout(std::time::Duration::from_millis(50))
+        .extract::<Forecast>()
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(Error::Completion(CompletionError::RequestError(_)))
+    ));
+}
+
+#[tokio::test]
+async fn invalid_extractions_fail_without_retries() {
+    let server = MockLlmServer::start().await;
+    server
+        .queue_completion(text_completion(r#"{"city": "Addis Ababa"}"#))
+        .await;
+    let mut client = openai(&server).build_client(
+        "preamble",
+        vec![],
+        ToolSet(vec![], ExecutionStrategy::FailEarly),
+    );
+
+    let result = client
+        .prompt("What's the forecast for Addis Ababa?")
+        .with_context(false)
+        .extract::<Forecast>()
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(Error::Completion(CompletionError::ExtractorError(
+            ExtractionError::InvalidOutput { .. }
+        )))
+    ));
+    assert_eq!(server.request_bodies(COMPLETIONS_PATH).await.len(), 1);
+}
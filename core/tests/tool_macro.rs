@@ -0,0 +1,173 @@
+use dashmap::DashMap;
+use seedframe::prelude::*;
+use seedframe::providers::completions::OpenAICompatible;
+use seedframe::tools::{
+    ExecutionStrategy, Tool, ToolCall, ToolImage, ToolOutput, ToolSet, ToolSetError,
+};
+use serde_json::{json, Value};
+
+/// Describes a user
+/// # Arguments
+/// * `name`: name of the user
+#[tool]
+fn describe_user(name: String) -> serde_json::Value {
+    json!({ "name": name, "admin": false })
+}
+
+/// Describes a user as a JSON string
+/// # Arguments
+/// * `name`: name of the user
+#[tool]
+fn describe_user_string(name: String) -> String {
+    json!({ "name": name }).to_string()
+}
+
+#[tokio::test]
+async fn json_value_tool_responses_are_not_double_encoded() {
+    let tools = ToolSet(
+        vec![
+            Box::new(__SF_TOOL_describe_user__::new()),
+            Box::new(__SF_TOOL_describe_user_string__::new()),
+        ],
+        ExecutionStrategy::FailEarly,
+    );
+    let arguments = Value::String(json!({ "name": "jack" }).to_string()).to_string();
+    let states = DashMap::new();
+
+    let response = tools
+        .call("call_1", "describe_user", &arguments, &states)
+        .await
+        .unwrap();
+    assert_eq!(response.content, json!({ "name": "jack", "admin": false }));
+
+    let response = tools
+        .call("call_2", "describe_user_string", &arguments, &states)
+        .await
+        .unwrap();
+    assert_eq!(
+        response.content,
+        Value::String(r#"{"name":"jack"}"#.to_string())
+    );
+}
+
+/// Greets a user
+/// # Arguments
+/// * `name`: name of the user
+#[tool]
+fn greet_user(name: String) -> String {
+    format!("Hello {name}")
+}
+
+/// Internal helper, not what the model should see
+/// # Arguments
+/// * `query`: text to search for
+#[tool(
+    description = "Searches the knowledge base.\n\nPrefer specific queries, eg. `refund policy`."
+)]
+fn search(query: String) -> String {
+    query
+}
+
+#[test]
+fn description_attribute_overrides_the_doc_comment() {
+    let tool = __SF_TOOL_search__::new();
+    assert_eq!(
+        tool.description(),
+        "Searches the knowledge base.\n\nPrefer specific queries, eg. `refund policy`."
+    );
+    assert_eq!(tool.args()[0].description(), "text to search for");
+}
+
+fn greeting_tools() -> ToolSet {
+    ToolSet(
+        vec![Box::new(__SF_TOOL_greet_user__::new())],
+        ExecutionStrategy::BestEffort,
+    )
+}
+
+#[test]
+fn toolsets_merge_and_report_collisions() {
+    let user_tools = ToolSet(
+        vec![Box::new(__SF_TOOL_describe_user__::new())],
+        ExecutionStrategy::FailEarly,
+    );
+
+    let merged = user_tools.merge(greeting_tools()).unwrap();
+    assert!(merged.find_tool("describe_user").is_ok());
+    assert!(merged.find_tool("greet_user").is_ok());
+    assert!(matches!(merged.1, ExecutionStrategy::FailEarly));
+
+    let collision = merged.merge(greeting_tools());
+    assert!(matches!(
+        collision,
+        Err(ToolSetError::DuplicateTool(name)) if name == "greet_user"
+    ));
+}
+
+#[client(
+    provider = "OpenAICompatible",
+    config = r#"{"api_url": "http://localhost/v1/chat/completions", "model": "test"}"#,
+    tools("describe_user"),
+    tools("describe_user_string"),
+    toolsets("greeting_tools")
+)]
+struct ComposedToolsClient;
+
+#[tokio::test]
+async fn client_macro_composes_tool_groups() {
+    let client = ComposedToolsClient::build("preamble").await;
+    let call = |id: &str, name: &str| ToolCall {
+        id: id.to_string(),
+        name: name.to_string(),
+        arguments: Value::String(json!({ "name": "jack" }).to_string()).to_string(),
+    };
+
+    let responses = client
+        .run_tools(Some(&[
+            call("call_1", "describe_user"),
+            call("call_2", "describe_user_string"),
+            call("call_3", "greet_user"),
+        ]))
+        .await
+        .unwrap();
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[2].content, json!("Hello jack"));
+}
+
+/// Plots a chart
+/// # Arguments
+/// * `title`: title of the chart
+#[tool]
+fn plot_chart(title: String) -> ToolOutput {
+    ToolOutput {
+        content: Value::String(format!("plotted {title}")),
+        images: vec![ToolImage::Url {
+            url: "https://example.com/chart.png".to_string(),
+        }],
+    }
+}
+
+#[tokio::test]
+async fn tool_output_images_are_split_off_the_content() {
+    let tools = ToolSet(
+        vec![Box::new(__SF_TOOL_plot_chart__::new())],
+        ExecutionStrategy::FailEarly,
+    );
+    let arguments = Value::String(json!({ "title": "sales" }).to_string()).to_string();
+
+    let response = tools
+        .call("call_1", "plot_chart", &arguments, &DashMap::new())
+        .await
+        .unwrap();
+    assert_eq!(response.content, json!("plotted sales"));
+    assert_eq!(
+        response.images,
+        vec![ToolImage::Url {
+            url: "https://example.com/chart.png".to_string()
+        }]
+    );
+    assert_eq!(
+        response.text_content(),
+        "plotted sales\n[image: https://example.com/chart.png]"
+    );
+}
@@ -1,7 +1,10 @@
 use async_trait::async_trait;
-use seedframe::completion::{Client, CompletionError, CompletionModel, Message, TokenUsage};
+use seedframe::completion::{
+    insert_extra_params, parse_response_body, warn_unknown_config_keys, Client, CompletionError,
+    CompletionModel, CompletionOutput, FinishReason, Message, TokenUsage,
+};
 use seedframe::embeddings::Embedder;
-use seedframe::tools::{ToolCall, ToolResponse, ToolSet};
+use seedframe::tools::{ToolCall, ToolImage, ToolResponse, ToolSet};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
@@ -15,11 +18,16 @@ mod utils;
 type MessageHistory = Vec<Message>;
 
 #[derive(Serialize, Deserialize)]
-#[serde(deny_unknown_fields)]
 struct ModelConfig {
     api_key: Option<String>,
     api_url: Option<String>,
     model: Option<String>,
+    capture_raw_response: Option<bool>,
+    cache_system_prompt: Option<bool>,
+    cache_context: Option<bool>,
+    /// Unknown keys, passed through to the request body, eg. `top_k`
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Implementation of Seedframe's `CompletionModel` trait for [Anthropic](https://anthropic.com).
@@ -35,6 +43,11 @@ struct ModelConfig {
 /// - `model`: String identifier for the model to use
 /// - `api_key_var`: Environment variable name containing the API key
 /// - `api_url`: Custom API endpoint URL
+/// - `capture_raw_response`: Include the raw response body in errors for unparsable responses
+/// - `cache_system_prompt`: Marks the system prompt for [prompt caching](https://docs.anthropic.com/en/docs/build-with-claude/prompt-caching)
+/// - `cache_context`: Marks the block holding the retrieved `<context>` for prompt caching
+///
+/// Any other keys, eg. `top_k`, are added to the body of every request.
 ///
 /// All of the are optional so the config can be left altogeather or parts of it could be specified
 ///
@@ -42,10 +55,8 @@ struct ModelConfig {
 ///
 /// Usage with the `client` macro:
 /// ```rust,ignore
-/// use seedframe_anthropic::AnthropicCompletionModel;
-///
 /// #[client(
-///     provider = "AnthropicCompletionModel",
+///     provider = "seedframe_anthropic::AnthropicCompletionModel",
 ///     config = r#"{
 ///       "model": "claude-3-7-sonnet-20250219",
 ///       "api_key_var": "ENV_VAR",
@@ -58,7 +69,7 @@ struct ModelConfig {
 ///
 /// When used with the `client` macro:
 /// - Invalid config json will result in a compile-time error
-/// - Unknown fields in configuration will be rejected
+/// - Unknown fields in configuration are logged as warnings and sent with every request
 /// - Missing API keys at runtime will result in errors
 pub struct AnthropicCompletionModel {
     api_key: String,
@@ -66,6 +77,11 @@ pub struct AnthropicCompletionModel {
     client: reqwest::Client,
     model: String,
     system: Option<String>,
+    capture_raw_response: bool,
+    idempotency_key: Option<String>,
+    cache_system_prompt: bool,
+    cache_context: bool,
+    extra_params: serde_json::Map<String, serde_json::Value>,
 }
 
 impl AnthropicCompletionModel {
@@ -80,32 +96,153 @@ impl AnthropicCompletionModel {
     /// # Panics
     /// This function will panic if:
     /// - The provided JSON is malformed and cannot be parsed
-    /// - The JSON contains unknown fields
     /// - Required environment variables are not set
     #[must_use]
     pub fn new(config_json: Option<&str>) -> Self {
-        let (api_key_var, api_url, model) = if let Some(json) = config_json {
-            let config: ModelConfig = serde_json::from_str(json).unwrap();
-            (
-                config.api_key.unwrap_or(API_KEY_ENV_VAR.to_string()),
-                config.api_url.unwrap_or(URL.to_string()),
-                config.model.unwrap_or(DEFAULT_MODEL.to_string()),
-            )
-        } else {
-            (
-                API_KEY_ENV_VAR.to_string(),
-                URL.to_string(),
-                DEFAULT_MODEL.to_string(),
-            )
+        let config: ModelConfig = match config_json {
+            Some(json) => serde_json::from_str(json).unwrap(),
+            None => ModelConfig {
+                api_key: None,
+                api_url: None,
+                model: None,
+                capture_raw_response: None,
+                cache_system_prompt: None,
+                cache_context: None,
+                extra: serde_json::Map::new(),
+            },
         };
+        warn_unknown_config_keys(&config.extra);
+        let api_key_var = config.api_key.unwrap_or(API_KEY_ENV_VAR.to_string());
         let api_key = std::env::var(api_key_var).unwrap();
         Self {
             api_key,
-            api_url,
+            api_url: config.api_url.unwrap_or(URL.to_string()),
             client: reqwest::Client::new(),
-            model,
+            model: config.model.unwrap_or(DEFAULT_MODEL.to_string()),
             system: None,
+            capture_raw_response: config.capture_raw_response.unwrap_or(false),
+            idempotency_key: None,
+            cache_system_prompt: config.cache_system_prompt.unwrap_or(false),
+            cache_context: config.cache_context.unwrap_or(false),
+            extra_params: config.extra,
+        }
+    }
+
+    /// Replaces the HTTP client used for requests to Anthropic, eg. to share a connection pool
+    /// with other providers and embedders talking to the same host.
+    /// A new client is created for every instance by default.
+    #[must_use]
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Includes a truncated snippet of the raw response body in the error when a response
+    /// fails to parse, for debugging. Disabled by default, also settable with the
+    /// `capture_raw_response` config field.
+    #[must_use]
+    pub fn with_raw_response_capture(mut self, capture: bool) -> Self {
+        self.capture_raw_response = capture;
+        self
+    }
+
+    /// Marks the system prompt with an ephemeral `cache_control`, so Anthropic caches the
+    /// request prefix up to it. Disabled by default, also settable with the
+    /// `cache_system_prompt` config field.
+    #[must_use]
+    pub fn with_system_prompt_caching(mut self, cache: bool) -> Self {
+        self.cache_system_prompt = cache;
+        self
+    }
+
+    /// Marks the last block holding the retrieved `<context>` with an ephemeral
+    /// `cache_control`. Disabled by default, also settable with the `cache_context`
+    /// config field.
+    #[must_use]
+    pub fn with_context_caching(mut self, cache: bool) -> Self {
+        self.cache_context = cache;
+        self
+    }
+
+    /// Adds `params` to the body of every request, for parameters without a dedicated setting,
+    /// eg. `top_k`. Params never replace the fields set by seedframe, like `model` or
+    /// `messages`. Unknown keys of the json config are added the same way.
+    #[must_use]
+    pub fn with_extra_params(mut self, params: serde_json::Map<String, serde_json::Value>) -> Self {
+        self.extra_params.extend(params);
+        self
+    }
+
+    /// Builds the body of a messages request, moving the preamble to the `system` field.
+    /// Histories without a preamble get the last one sent by the model
+    fn request_body(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> serde_json::Value {
+        let mut messages = history.clone();
+
+        let system = if let Some(Message::Preamble(p)) = messages.first() {
+            let preamble = p.clone();
+            messages.remove(0);
+            Some(preamble)
+        } else {
+            self.system.clone()
+        };
+        messages.push(message);
+        let messages: Vec<_> = messages
+            .into_iter()
+            .map(Into::<AnthropicMessage>::into)
+            .collect();
+
+        let mut request_body = json!({
+            "store": true,
+            "model": self.model,
+            "messages": messages,
+            "temperature": temperature,
+            "max_tokens": max_tokens,
+        });
+
+        if let Some(tools) = tools {
+            let tools_serialized: Vec<serde_json::Value> =
+                tools.0.iter().map(|t| t.anthropic_serializer()).collect();
+            if let Some(obj) = request_body.as_object_mut() {
+                obj.insert(
+                    "tools".to_string(),
+                    serde_json::Value::Array(tools_serialized),
+                );
+            }
+        }
+
+        if let Some(preamble) = system {
+            request_body["system"] = if self.cache_system_prompt {
+                json!([{"type": "text", "text": preamble, "cache_control": ephemeral_cache()}])
+            } else {
+                serde_json::Value::String(preamble)
+            };
+        }
+        if self.cache_context {
+            let context_block = request_body["messages"]
+                .as_array_mut()
+                .into_iter()
+                .flatten()
+                .rev()
+                .filter_map(|message| message["content"].as_array_mut())
+                .flat_map(|blocks| blocks.iter_mut().rev())
+                .find(|block| {
+                    block["text"]
+                        .as_str()
+                        .is_some_and(|text| text.contains("<context>"))
+                });
+            if let Some(block) = context_block {
+                block["cache_control"] = ephemeral_cache();
+            }
         }
+        insert_extra_params(&self.extra_params, &mut request_body);
+        request_body
     }
 }
 
@@ -133,7 +270,51 @@ pub(crate) enum ContentBlock {
         data: String,
     },
     ToolUse(ToolCall),
-    ToolResult(ToolResponse),
+    #[serde(rename = "tool_result")]
+    ToolResult {
+        tool_use_id: String,
+        content: Vec<ToolResultContent>,
+    },
+}
+
+/// Content of a tool result block, text followed by the images of the tool response
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
+#[serde(tag = "type")]
+pub(crate) enum ToolResultContent {
+    #[serde(rename = "text")]
+    Text { text: String },
+    #[serde(rename = "image")]
+    Image { source: ImageSource },
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
+#[serde(tag = "type")]
+pub(crate) enum ImageSource {
+    #[serde(rename = "base64")]
+    Base64 { media_type: String, data: String },
+    #[serde(rename = "url")]
+    Url { url: String },
+}
+
+impl From<ToolResponse> for ContentBlock {
+    fn from(response: ToolResponse) -> Self {
+        let text = match response.content {
+            serde_json::Value::String(s) => s,
+            v => v.to_string(),
+        };
+        let mut content = vec![ToolResultContent::Text { text }];
+        content.extend(response.images.into_iter().map(|image| {
+            let source = match image {
+                ToolImage::Base64 { media_type, data } => ImageSource::Base64 { media_type, data },
+                ToolImage::Url { url } => ImageSource::Url { url },
+            };
+            ToolResultContent::Image { source }
+        }));
+        Self::ToolResult {
+            tool_use_id: response.id,
+            content,
+        }
+    }
 }
 
 impl From<Message> for AnthropicMessage {
@@ -145,6 +326,7 @@ impl From<Message> for AnthropicMessage {
             Message::User {
                 content,
                 tool_responses,
+                ..
             } => {
                 let mut out: Vec<ContentBlock> = Vec::new();
                 if !content.is_empty() {
@@ -152,15 +334,14 @@ impl From<Message> for AnthropicMessage {
                     vals.iter().for_each(|v| out.push(v.clone()));
                 }
                 if let Some(tools) = tool_responses {
-                    tools
-                        .iter()
-                        .for_each(|t| out.push(ContentBlock::ToolResult(t.clone())));
+                    tools.iter().for_each(|t| out.push(t.clone().into()));
                 }
                 Self::user(out)
             }
             Message::Assistant {
                 content,
                 tool_calls,
+                ..
             } => {
                 let mut out = Vec::new();
                 if !content.is_empty() {
@@ -172,8 +353,9 @@ impl From<Message> for AnthropicMessage {
                         .iter()
                         .for_each(|t| out.push(ContentBlock::ToolUse(t.clone())));
                 }
-                Self::user(out)
+                Self::assistant(out)
             }
+            Message::Tool(response) => Self::user(vec![response.into()]),
         }
     }
 }
@@ -196,7 +378,6 @@ impl CompletionModel for AnthropicCompletionModel {
             tools,
         )
     }
-    #[allow(clippy::too_many_lines)]
     async fn send(
         &mut self,
         message: Message,
@@ -205,60 +386,56 @@ impl CompletionModel for AnthropicCompletionModel {
         temperature: f64,
         max_tokens: usize,
     ) -> Result<(Message, TokenUsage), CompletionError> {
-        let mut messages = history.clone();
-        if let Some(Message::Preamble(p)) = messages.first() {
-            self.system = Some(p.clone());
-            messages.remove(0);
-        }
-        messages.push(message);
-        let messages: Vec<_> = messages
-            .into_iter()
-            .map(Into::<AnthropicMessage>::into)
-            .collect();
-
-        let mut request_body = json!({
-            "store": true,
-            "model": self.model,
-            "messages": messages,
-            "temperature": temperature,
-            "max_tokens": max_tokens,
-        });
+        self.send_detailed(message, history, tools, temperature, max_tokens)
+            .await
+            .map(|output| (output.message, output.token_usage))
+    }
 
-        if let Some(tools) = tools {
-            let tools_serialized: Vec<serde_json::Value> =
-                tools.0.iter().map(|t| t.default_serializer()).collect();
-            if let Some(obj) = request_body.as_object_mut() {
-                obj.insert(
-                    "tools".to_string(),
-                    serde_json::Value::Array(tools_serialized),
-                );
-            }
-        }
+    fn build_request_body(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<serde_json::Value, CompletionError> {
+        Ok(self.request_body(message, history, tools, temperature, max_tokens))
+    }
 
-        if let Some(preamble) = &self.system {
-            if let Some(obj) = request_body.as_object_mut() {
-                obj.insert(
-                    "system".to_string(),
-                    serde_json::Value::String(preamble.to_string()),
-                );
-            }
+    #[allow(clippy::too_many_lines)]
+    async fn send_detailed(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionOutput, CompletionError> {
+        let request_body = self.request_body(message, history, tools, temperature, max_tokens);
+        if let Some(Message::Preamble(p)) = history.first() {
+            self.system = Some(p.clone());
         }
 
-        let response = self
+        let mut request = self
             .client
             .post(&self.api_url)
             .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
+            .header("Content-Type", "application/json");
+        if let Some(key) = &self.idempotency_key {
+            request = request.header("Idempotency-Key", key);
+        }
+        let response = request
             .json(&request_body)
             .send()
             .await
             .map_err(|e| CompletionError::RequestError(e.to_string()))?;
 
         if response.status().is_success() {
-            let response_json: serde_json::Value = response
-                .json()
+            let body = response
+                .text()
                 .await
-                .map_err(|e| CompletionError::ParseError(e.to_string()))?;
+                .map_err(|e| CompletionError::RequestError(e.to_string()))?;
+            let response_json = parse_response_body(&body, self.capture_raw_response)?;
 
             let mut content: Vec<String> = vec![];
             let mut tool_calls: Vec<ToolCall> = vec![];
@@ -314,13 +491,16 @@ impl CompletionModel for AnthropicCompletionModel {
             } else {
                 Some(tool_calls)
             };
-            Ok((
-                Message::Assistant {
+            Ok(CompletionOutput {
+                message: Message::Assistant {
                     content,
                     tool_calls,
+                    name: None,
                 },
                 token_usage,
-            ))
+                finish_reason: parse_stop_reason(&response_json),
+                system_fingerprint: None,
+            })
         } else {
             let status = response.status();
             let error_msg = response
@@ -331,13 +511,46 @@ impl CompletionModel for AnthropicCompletionModel {
             Err(CompletionError::ProviderError(status.into(), error_msg))?
         }
     }
+
+    fn set_idempotency_key(&mut self, key: Option<String>) -> bool {
+        self.idempotency_key = key;
+        true
+    }
+
+    fn provider_name(&self) -> &str {
+        "anthropic"
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+}
+
+/// Maps Anthropic's `stop_reason` to a [`FinishReason`]
+/// The `cache_control` marking a block for prompt caching
+fn ephemeral_cache() -> serde_json::Value {
+    json!({"type": "ephemeral"})
+}
+
+fn parse_stop_reason(response_json: &serde_json::Value) -> Option<FinishReason> {
+    response_json["stop_reason"]
+        .as_str()
+        .map(|reason| match reason {
+            "end_turn" | "stop_sequence" => FinishReason::Stop,
+            "max_tokens" => FinishReason::Length,
+            "tool_use" => FinishReason::ToolCalls,
+            "refusal" => FinishReason::ContentFilter,
+            other => FinishReason::Other(other.to_string()),
+        })
 }
 
 #[cfg(test)]
 mod test {
-    use seedframe::completion::Message;
+    use seedframe::completion::{FinishReason, Message};
+    use seedframe::tools::{ToolImage, ToolResponse};
+    use serde_json::json;
 
-    use crate::{AnthropicMessage, ContentBlock};
+    use crate::{parse_stop_reason, AnthropicCompletionModel, AnthropicMessage, ContentBlock};
 
     #[test]
     fn test_proper_message_conversion() {
@@ -345,6 +558,7 @@ mod test {
         let crate_messages = Message::User {
             content: String::from(st),
             tool_responses: None,
+            name: None,
         };
         let converted = Into::<AnthropicMessage>::into(crate_messages);
         assert_eq!(
@@ -369,4 +583,148 @@ mod test {
             ])
         );
     }
+
+    #[test]
+    fn test_prefill_is_sent_as_last_assistant_message() {
+        std::env::set_var("SEEDFRAME_TEST_ANTHROPIC_API_KEY", "key");
+        let model = AnthropicCompletionModel::new(Some(
+            r#"{"api_key": "SEEDFRAME_TEST_ANTHROPIC_API_KEY"}"#,
+        ));
+        let history = vec![
+            Message::Preamble("preamble".to_string()),
+            Message::User {
+                content: "reply in json".to_string(),
+                tool_responses: None,
+                name: None,
+            },
+        ];
+        let prefill = Message::Assistant {
+            content: "{".to_string(),
+            tool_calls: None,
+            name: None,
+        };
+
+        let body = model.request_body(prefill, &history, None, 1.0, 10);
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(
+            messages.last().unwrap(),
+            &json!({"role": "assistant", "content": [{"type": "text", "text": "{"}]})
+        );
+        assert_eq!(messages[0]["role"], json!("user"));
+        assert_eq!(body["system"], json!("preamble"));
+    }
+
+    #[test]
+    fn test_cache_control_marks_system_prompt_and_context() {
+        std::env::set_var("SEEDFRAME_TEST_ANTHROPIC_API_KEY", "key");
+        let model = AnthropicCompletionModel::new(Some(
+            r#"{"api_key": "SEEDFRAME_TEST_ANTHROPIC_API_KEY", "cache_system_prompt": true}"#,
+        ))
+        .with_context_caching(true);
+        let history = vec![Message::Preamble("large preamble".to_string())];
+        let message = Message::User {
+            content: "question\n\n<context>\nfacts\n</context>\n".to_string(),
+            tool_responses: None,
+            name: None,
+        };
+
+        let body = model.request_body(message.clone(), &history, None, 1.0, 10);
+        assert_eq!(
+            body["system"],
+            json!([{
+                "type": "text",
+                "text": "large preamble",
+                "cache_control": {"type": "ephemeral"}
+            }])
+        );
+        let blocks = body["messages"][0]["content"].as_array().unwrap();
+        assert_eq!(
+            blocks.last().unwrap()["cache_control"],
+            json!({"type": "ephemeral"})
+        );
+
+        let body = model
+            .with_system_prompt_caching(false)
+            .with_context_caching(false)
+            .request_body(message, &history, None, 1.0, 10);
+        assert_eq!(body["system"], json!("large preamble"));
+        assert!(!body.to_string().contains("cache_control"));
+    }
+
+    #[test]
+    fn test_tool_result_images_serialize_as_image_blocks() {
+        let response = Message::Tool(ToolResponse {
+            id: "toolu_1".to_string(),
+            name: "plot".to_string(),
+            content: serde_json::Value::from("rendered the chart"),
+            images: vec![
+                ToolImage::Base64 {
+                    media_type: "image/png".to_string(),
+                    data: "iVBORw0KGgo=".to_string(),
+                },
+                ToolImage::Url {
+                    url: "https://example.com/chart.png".to_string(),
+                },
+            ],
+        });
+
+        assert_eq!(
+            serde_json::to_value(AnthropicMessage::from(response)).unwrap(),
+            json!({
+                "role": "user",
+                "content": [{
+                    "type": "tool_result",
+                    "tool_use_id": "toolu_1",
+                    "content": [
+                        {"type": "text", "text": "rendered the chart"},
+                        {
+                            "type": "image",
+                            "source": {
+                                "type": "base64",
+                                "media_type": "image/png",
+                                "data": "iVBORw0KGgo="
+                            }
+                        },
+                        {
+                            "type": "image",
+                            "source": {"type": "url", "url": "https://example.com/chart.png"}
+                        }
+                    ]
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_stop_reason() {
+        for (reason, expected) in [
+            ("end_turn", FinishReason::Stop),
+            ("stop_sequence", FinishReason::Stop),
+            ("max_tokens", FinishReason::Length),
+            ("tool_use", FinishReason::ToolCalls),
+            ("refusal", FinishReason::ContentFilter),
+            ("pause_turn", FinishReason::Other("pause_turn".to_string())),
+        ] {
+            let response = json!({ "stop_reason": reason });
+            assert_eq!(parse_stop_reason(&response), Some(expected));
+        }
+        assert_eq!(parse_stop_reason(&json!({})), None);
+    }
+
+    #[test]
+    fn test_unknown_config_keys_are_sent_in_the_body() {
+        std::env::set_var("SEEDFRAME_TEST_ANTHROPIC_API_KEY", "key");
+        let model = AnthropicCompletionModel::new(Some(
+            r#"{"api_key": "SEEDFRAME_TEST_ANTHROPIC_API_KEY", "top_k": 5, "model": "claude"}"#,
+        ));
+        let message = Message::User {
+            content: "hi".to_string(),
+            tool_responses: None,
+            name: None,
+        };
+
+        let body = model.request_body(message, &vec![], None, 1.0, 10);
+        assert_eq!(body["top_k"], json!(5));
+        assert_eq!(body["model"], json!("claude"));
+    }
 }
@@ -2,13 +2,13 @@ use super::ContentBlock;
 use regex::Regex;
 
 pub(crate) fn parse_content_blocks(input: &str) -> Vec<ContentBlock> {
-    let re = Regex::new(r"(</?sf_(r_)?thinking>)|([^<]+)").unwrap();
+    let re = Regex::new(r"(</?sf_(r_)?thinking>)|([^<]+|<)").unwrap();
     let mut result = Vec::new();
     let mut current_text = String::new();
     let mut current_block = None;
     let thinking_tag = ("<sf_thinking>", "</sf_thinking>");
     let redacted_thinking_tag = ("<sf_r_thinking>", "</sf_r_thinking>");
-    let thinking_signature_tag = "/sf_sig>";
+    let thinking_signature_tag = "</sf_sig>";
 
     for cap in re.captures_iter(input) {
         if let Some(tag) = cap.get(1) {
@@ -89,6 +89,16 @@ pub(crate) fn parse_content_blocks(input: &str) -> Vec<ContentBlock> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_other_tags_are_kept_as_text() {
+        assert_eq!(
+            parse_content_blocks("question <context>facts</context>"),
+            vec![ContentBlock::Text {
+                text: "question <context>facts</context>".to_string(),
+            }]
+        );
+    }
+
     #[test]
     fn test_parse_content_blocks() {
         let input = "hello world <sf_thinking> how are you</sf_sig>uuulala</sf_thinking> blabla<sf_r_thinking>ulalala</sf_r_thinking>";
@@ -0,0 +1,749 @@
+use async_trait::async_trait;
+use aws_config::BehaviorVersion;
+use aws_sdk_bedrockruntime::config::Region;
+use aws_sdk_bedrockruntime::error::DisplayErrorContext;
+use aws_sdk_bedrockruntime::primitives::Blob;
+use seedframe::completion::{
+    insert_extra_params, parse_response_body, warn_unknown_config_keys, Client, CompletionError,
+    CompletionModel, CompletionOutput, FinishReason, Message, TokenUsage,
+};
+use seedframe::embeddings::Embedder;
+use seedframe::tools::{ToolCall, ToolImage, ToolResponse, ToolSet};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::OnceCell;
+
+const ANTHROPIC_VERSION: &str = "bedrock-2023-05-31";
+const DEFAULT_TEMP: f64 = 1.0;
+const DEFAULT_TOKENS: usize = 1023;
+
+type MessageHistory = Vec<Message>;
+
+/// Re-export of [`BedrockCompletionModel`] under a shorter name
+pub type Bedrock = BedrockCompletionModel;
+
+#[derive(Serialize, Deserialize)]
+struct ModelConfig {
+    model_id: String,
+    region: Option<String>,
+    capture_raw_response: Option<bool>,
+    /// Unknown keys, passed through to the request body, eg. `top_k`
+    #[serde(flatten)]
+    extra: serde_json::Map<String, Value>,
+}
+
+/// The model families whose `InvokeModel` payloads are supported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ModelFamily {
+    Anthropic,
+    Titan,
+}
+
+impl ModelFamily {
+    /// Picks the family from a model id, ignoring cross-region inference profile prefixes
+    /// like `us.` in `us.anthropic.claude-3-5-haiku-20241022-v1:0`. For ARNs the family is
+    /// picked from the model id or inference profile after the last `/`.
+    fn from_model_id(model_id: &str) -> Option<Self> {
+        let model_id = model_id.rsplit('/').next()?;
+        let provider = model_id
+            .split('.')
+            .find(|part| !matches!(*part, "us" | "eu" | "apac" | "us-gov" | "global"))?;
+        match provider {
+            "anthropic" => Some(Self::Anthropic),
+            "amazon" if model_id.contains("titan-text") => Some(Self::Titan),
+            _ => None,
+        }
+    }
+}
+
+/// Implementation of Seedframe's `CompletionModel` trait for [Amazon Bedrock](https://aws.amazon.com/bedrock/),
+/// through the `InvokeModel` API.
+///
+/// Supports the Anthropic (Claude) and Amazon Titan Text model families, requests are
+/// signed with credentials from the standard AWS credential chain.
+///
+/// This type is primarily designed to be used through the `#[client]` macro
+/// rather than being instantiated directly.
+///
+/// # Supported Configuration
+///
+/// - `model_id`: The Bedrock model id, eg. `anthropic.claude-3-5-haiku-20241022-v1:0`
+/// - `region`: *optional* AWS region, taken from the environment if left out
+/// - `capture_raw_response`: *optional* Include the raw response body in errors for
+///   unparsable responses
+///
+/// Any other keys, eg. `top_k`, are added to the body of every request.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// #[client(
+///     provider = "seedframe_bedrock::Bedrock",
+///     config = r#"{
+///       "model_id": "anthropic.claude-3-5-haiku-20241022-v1:0",
+///       "region": "us-east-1"
+///     }"#
+/// )]
+/// struct BedrockClient;
+/// ```
+/// # Error Handling
+///
+/// When used with the `client` macro:
+/// - Invalid config json will result in a compile-time error
+/// - Unknown fields in configuration are logged as warnings and sent with every request
+/// - Missing AWS credentials will result in errors at runtime
+pub struct BedrockCompletionModel {
+    model_id: String,
+    family: ModelFamily,
+    region: Option<String>,
+    client: OnceCell<aws_sdk_bedrockruntime::Client>,
+    capture_raw_response: bool,
+    extra_params: serde_json::Map<String, Value>,
+}
+
+impl BedrockCompletionModel {
+    /// Creates a new `BedrockCompletionModel` from its JSON configuration.
+    ///
+    /// The AWS configuration is loaded on the first request.
+    ///
+    /// # Panics
+    /// This function will panic if:
+    /// - The configuration is missing or malformed
+    /// - The model id doesn't belong to a supported model family
+    #[must_use]
+    pub fn new(config_json: Option<&str>) -> Self {
+        let config: ModelConfig =
+            serde_json::from_str(config_json.expect("Bedrock requires a `model_id` config"))
+                .unwrap();
+        warn_unknown_config_keys(&config.extra);
+        let family = ModelFamily::from_model_id(&config.model_id).unwrap_or_else(|| {
+            panic!(
+                "Unsupported Bedrock model family for `{}`, expected an Anthropic or Titan Text model",
+                config.model_id
+            )
+        });
+        Self {
+            model_id: config.model_id,
+            family,
+            region: config.region,
+            client: OnceCell::new(),
+            capture_raw_response: config.capture_raw_response.unwrap_or(false),
+            extra_params: config.extra,
+        }
+    }
+
+    /// Replaces the Bedrock runtime client, eg. to use an already loaded AWS configuration.
+    /// A client is created from the default AWS configuration on first use otherwise.
+    #[must_use]
+    pub fn with_sdk_client(mut self, client: aws_sdk_bedrockruntime::Client) -> Self {
+        self.client = OnceCell::new_with(Some(client));
+        self
+    }
+
+    /// Includes a truncated snippet of the raw response body in the error when a response
+    /// fails to parse, for debugging. Disabled by default, also settable with the
+    /// `capture_raw_response` config field.
+    #[must_use]
+    pub fn with_raw_response_capture(mut self, capture: bool) -> Self {
+        self.capture_raw_response = capture;
+        self
+    }
+
+    /// Adds `params` to the body of every request, for parameters without a dedicated setting,
+    /// eg. `top_k`. Params never replace the fields set by seedframe, like `messages`. Unknown
+    /// keys of the json config are added the same way.
+    #[must_use]
+    pub fn with_extra_params(mut self, params: serde_json::Map<String, Value>) -> Self {
+        self.extra_params.extend(params);
+        self
+    }
+
+    async fn sdk_client(&self) -> &aws_sdk_bedrockruntime::Client {
+        self.client
+            .get_or_init(|| async {
+                let mut loader = aws_config::defaults(BehaviorVersion::latest());
+                if let Some(region) = &self.region {
+                    loader = loader.region(Region::new(region.clone()));
+                }
+                aws_sdk_bedrockruntime::Client::new(&loader.load().await)
+            })
+            .await
+    }
+
+    /// Builds the `InvokeModel` body in the payload format of the model's family
+    fn request_body(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<Value, CompletionError> {
+        let mut messages = history.clone();
+        messages.push(message);
+        let mut body = match self.family {
+            ModelFamily::Anthropic => anthropic_body(messages, tools, temperature, max_tokens),
+            ModelFamily::Titan => {
+                if tools.is_some() {
+                    return Err(CompletionError::RequestError(
+                        "Titan text models don't support tool use".to_string(),
+                    ));
+                }
+                titan_body(&messages, temperature, max_tokens)
+            }
+        };
+        insert_extra_params(&self.extra_params, &mut body);
+        Ok(body)
+    }
+}
+
+/// Builds an Anthropic messages payload, moving the preamble to the `system` field
+fn anthropic_body(
+    messages: MessageHistory,
+    tools: Option<&ToolSet>,
+    temperature: f64,
+    max_tokens: usize,
+) -> Value {
+    let mut system = None;
+    let mut turns: Vec<Value> = Vec::new();
+    for message in messages {
+        let (role, content) = match message {
+            Message::Preamble(preamble) => {
+                system = Some(preamble);
+                continue;
+            }
+            Message::User {
+                content,
+                tool_responses,
+                ..
+            } => {
+                let mut blocks = text_block(content);
+                blocks.extend(
+                    tool_responses
+                        .into_iter()
+                        .flatten()
+                        .map(anthropic_tool_result),
+                );
+                ("user", blocks)
+            }
+            Message::Assistant {
+                content,
+                tool_calls,
+                ..
+            } => {
+                let mut blocks = text_block(content);
+                blocks.extend(tool_calls.into_iter().flatten().map(|call| {
+                    let input = serde_json::from_str::<Value>(&call.arguments)
+                        .unwrap_or_else(|_| json!({}));
+                    json!({"type": "tool_use", "id": call.id, "name": call.name, "input": input})
+                }));
+                ("assistant", blocks)
+            }
+            Message::Tool(response) => {
+                let result = anthropic_tool_result(response);
+                // results of parallel tool calls have to be sent in a single user turn
+                if let Some(turn) = turns.last_mut().filter(|turn| turn["role"] == "user") {
+                    if let Some(blocks) = turn["content"].as_array_mut() {
+                        blocks.push(result);
+                        continue;
+                    }
+                }
+                ("user", vec![result])
+            }
+        };
+        turns.push(json!({"role": role, "content": content}));
+    }
+
+    let mut body = json!({
+        "anthropic_version": ANTHROPIC_VERSION,
+        "messages": turns,
+        "temperature": temperature,
+        "max_tokens": max_tokens,
+    });
+    if let Some(system) = system {
+        body["system"] = Value::String(system);
+    }
+    if let Some(tools) = tools {
+        let tools: Vec<Value> = tools.0.iter().map(|t| t.anthropic_serializer()).collect();
+        body["tools"] = Value::Array(tools);
+    }
+    body
+}
+
+fn text_block(content: String) -> Vec<Value> {
+    if content.is_empty() {
+        vec![]
+    } else {
+        vec![json!({"type": "text", "text": content})]
+    }
+}
+
+fn anthropic_tool_result(response: ToolResponse) -> Value {
+    let text = match response.content {
+        Value::String(s) => s,
+        v => v.to_string(),
+    };
+    let mut content = vec![json!({"type": "text", "text": text})];
+    content.extend(response.images.into_iter().map(|image| {
+        let source = match image {
+            ToolImage::Base64 { media_type, data } => {
+                json!({"type": "base64", "media_type": media_type, "data": data})
+            }
+            ToolImage::Url { url } => json!({"type": "url", "url": url}),
+        };
+        json!({"type": "image", "source": source})
+    }));
+    json!({"type": "tool_result", "tool_use_id": response.id, "content": content})
+}
+
+/// Builds a Titan text payload, flattening the conversation into a single prompt
+fn titan_body(messages: &MessageHistory, temperature: f64, max_tokens: usize) -> Value {
+    let mut prompt = String::new();
+    for message in messages {
+        let line = match message {
+            Message::Preamble(preamble) => preamble.clone(),
+            Message::User { content, .. } => format!("User: {content}"),
+            Message::Assistant { content, .. } => format!("Bot: {content}"),
+            Message::Tool(response) => {
+                format!("Tool {}: {}", response.name, response.text_content())
+            }
+        };
+        prompt.push_str(&line);
+        prompt.push('\n');
+    }
+    prompt.push_str("Bot:");
+    json!({
+        "inputText": prompt,
+        "textGenerationConfig": {
+            "temperature": temperature,
+            "maxTokenCount": max_tokens,
+        },
+    })
+}
+
+fn parse_anthropic_response(response_json: &Value) -> Result<CompletionOutput, CompletionError> {
+    let parse_error = |field: &str| CompletionError::ParseError(format!("Missing `{field}`"));
+    let mut content = String::new();
+    let mut tool_calls = Vec::new();
+    for block in response_json["content"]
+        .as_array()
+        .ok_or_else(|| parse_error("content"))?
+    {
+        match block["type"].as_str() {
+            Some("text") => content.push_str(block["text"].as_str().unwrap_or_default()),
+            Some("tool_use") => tool_calls.push(ToolCall {
+                id: block["id"].as_str().unwrap_or_default().to_string(),
+                name: block["name"].as_str().unwrap_or_default().to_string(),
+                arguments: block["input"].to_string(),
+            }),
+            _ => {}
+        }
+    }
+    let usage = &response_json["usage"];
+    let input_tokens = usage["input_tokens"]
+        .as_u64()
+        .ok_or_else(|| parse_error("usage.input_tokens"))?;
+    let output_tokens = usage["output_tokens"]
+        .as_u64()
+        .ok_or_else(|| parse_error("usage.output_tokens"))?;
+    let finish_reason = response_json["stop_reason"]
+        .as_str()
+        .map(|reason| match reason {
+            "end_turn" | "stop_sequence" => FinishReason::Stop,
+            "max_tokens" => FinishReason::Length,
+            "tool_use" => FinishReason::ToolCalls,
+            "refusal" => FinishReason::ContentFilter,
+            other => FinishReason::Other(other.to_string()),
+        });
+    Ok(CompletionOutput {
+        message: Message::Assistant {
+            content,
+            tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+            name: None,
+        },
+        token_usage: TokenUsage {
+            prompt_tokens: Some(input_tokens),
+            completion_tokens: Some(output_tokens),
+            total_tokens: Some(input_tokens + output_tokens),
+        },
+        finish_reason,
+        system_fingerprint: None,
+    })
+}
+
+fn parse_titan_response(response_json: &Value) -> Result<CompletionOutput, CompletionError> {
+    let parse_error = |field: &str| CompletionError::ParseError(format!("Missing `{field}`"));
+    let result = &response_json["results"][0];
+    let content = result["outputText"]
+        .as_str()
+        .ok_or_else(|| parse_error("results[0].outputText"))?
+        .trim_start()
+        .to_string();
+    let prompt_tokens = response_json["inputTextTokenCount"].as_u64();
+    let completion_tokens = result["tokenCount"].as_u64();
+    let finish_reason = result["completionReason"]
+        .as_str()
+        .map(|reason| match reason {
+            "FINISH" | "STOP_CRITERIA_MET" => FinishReason::Stop,
+            "LENGTH" => FinishReason::Length,
+            "CONTENT_FILTERED" => FinishReason::ContentFilter,
+            other => FinishReason::Other(other.to_string()),
+        });
+    Ok(CompletionOutput {
+        message: Message::Assistant {
+            content,
+            tool_calls: None,
+            name: None,
+        },
+        token_usage: TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens.zip(completion_tokens).map(|(p, c)| p + c),
+        },
+        finish_reason,
+        system_fingerprint: None,
+    })
+}
+
+#[allow(refining_impl_trait)]
+#[async_trait]
+impl CompletionModel for BedrockCompletionModel {
+    fn build_client(
+        self,
+        preamble: impl AsRef<str>,
+        embedder_instances: Vec<Embedder>,
+        tools: ToolSet,
+    ) -> Client<Self> {
+        Client::new(
+            self,
+            preamble,
+            DEFAULT_TEMP,
+            DEFAULT_TOKENS,
+            embedder_instances,
+            tools,
+        )
+    }
+
+    async fn send(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<(Message, TokenUsage), CompletionError> {
+        self.send_detailed(message, history, tools, temperature, max_tokens)
+            .await
+            .map(|output| (output.message, output.token_usage))
+    }
+
+    fn build_request_body(
+        &self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<Value, CompletionError> {
+        self.request_body(message, history, tools, temperature, max_tokens)
+    }
+
+    async fn send_detailed(
+        &mut self,
+        message: Message,
+        history: &MessageHistory,
+        tools: Option<&ToolSet>,
+        temperature: f64,
+        max_tokens: usize,
+    ) -> Result<CompletionOutput, CompletionError> {
+        let request_body = self.request_body(message, history, tools, temperature, max_tokens)?;
+        let output = self
+            .sdk_client()
+            .await
+            .invoke_model()
+            .model_id(&self.model_id)
+            .content_type("application/json")
+            .accept("application/json")
+            .body(Blob::new(request_body.to_string()))
+            .send()
+            .await
+            .map_err(|e| match e.raw_response() {
+                Some(response) => CompletionError::ProviderError(
+                    response.status().as_u16(),
+                    DisplayErrorContext(&e).to_string(),
+                ),
+                None => CompletionError::RequestError(DisplayErrorContext(&e).to_string()),
+            })?;
+
+        let body = std::str::from_utf8(output.body().as_ref())
+            .map_err(|e| CompletionError::ParseError(e.to_string()))?;
+        let response_json = parse_response_body(body, self.capture_raw_response)?;
+        match self.family {
+            ModelFamily::Anthropic => parse_anthropic_response(&response_json),
+            ModelFamily::Titan => parse_titan_response(&response_json),
+        }
+    }
+
+    fn provider_name(&self) -> &str {
+        "bedrock"
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(model_id: &str) -> Bedrock {
+        Bedrock::new(Some(
+            &json!({"model_id": model_id, "region": "us-east-1"}).to_string(),
+        ))
+    }
+
+    #[test]
+    fn test_model_family_from_model_id() {
+        for (model_id, family) in [
+            (
+                "anthropic.claude-3-5-haiku-20241022-v1:0",
+                Some(ModelFamily::Anthropic),
+            ),
+            (
+                "us.anthropic.claude-3-7-sonnet-20250219-v1:0",
+                Some(ModelFamily::Anthropic),
+            ),
+            (
+                "arn:aws:bedrock:us-east-1:123456789012:inference-profile/us.anthropic.claude-3-7-sonnet-20250219-v1:0",
+                Some(ModelFamily::Anthropic),
+            ),
+            (
+                "arn:aws:bedrock:us-east-1::foundation-model/amazon.titan-text-express-v1",
+                Some(ModelFamily::Titan),
+            ),
+            ("amazon.titan-text-express-v1", Some(ModelFamily::Titan)),
+            ("amazon.titan-embed-text-v2:0", None),
+            ("meta.llama3-70b-instruct-v1:0", None),
+        ] {
+            assert_eq!(ModelFamily::from_model_id(model_id), family, "{model_id}");
+        }
+    }
+
+    #[test]
+    fn test_anthropic_payload_mapping() {
+        let history = vec![
+            Message::Preamble("Be brief".to_string()),
+            Message::User {
+                content: "What's the weather?".to_string(),
+                tool_responses: None,
+                name: None,
+            },
+            Message::Assistant {
+                content: String::new(),
+                tool_calls: Some(vec![ToolCall {
+                    id: "call_1".to_string(),
+                    name: "weather".to_string(),
+                    arguments: r#"{"city":"Paris"}"#.to_string(),
+                }]),
+                name: None,
+            },
+        ];
+        let message = Message::Tool(ToolResponse {
+            id: "call_1".to_string(),
+            name: "weather".to_string(),
+            content: json!("sunny"),
+            images: vec![],
+        });
+        let body = model("anthropic.claude-3-5-haiku-20241022-v1:0")
+            .build_request_body(message, &history, None, 0.5, 100)
+            .unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "anthropic_version": "bedrock-2023-05-31",
+                "system": "Be brief",
+                "temperature": 0.5,
+                "max_tokens": 100,
+                "messages": [
+                    {"role": "user", "content": [{"type": "text", "text": "What's the weather?"}]},
+                    {"role": "assistant", "content": [
+                        {"type": "tool_use", "id": "call_1", "name": "weather", "input": {"city": "Paris"}}
+                    ]},
+                    {"role": "user", "content": [
+                        {"type": "tool_result", "tool_use_id": "call_1", "content": [{"type": "text", "text": "sunny"}]}
+                    ]},
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_anthropic_payload_merges_tool_results() {
+        let call = |id: &str| ToolCall {
+            id: id.to_string(),
+            name: "weather".to_string(),
+            arguments: "{}".to_string(),
+        };
+        let result = |id: &str, content: &str| {
+            Message::Tool(ToolResponse {
+                id: id.to_string(),
+                name: "weather".to_string(),
+                content: json!(content),
+                images: vec![],
+            })
+        };
+        let history = vec![
+            Message::User {
+                content: "Weather in Paris and Rome?".to_string(),
+                tool_responses: None,
+                name: None,
+            },
+            Message::Assistant {
+                content: String::new(),
+                tool_calls: Some(vec![call("call_1"), call("call_2")]),
+                name: None,
+            },
+            result("call_1", "sunny"),
+        ];
+        let body = model("anthropic.claude-3-5-haiku-20241022-v1:0")
+            .build_request_body(result("call_2", "rainy"), &history, None, 0.5, 100)
+            .unwrap();
+        assert_eq!(
+            body["messages"],
+            json!([
+                {"role": "user", "content": [{"type": "text", "text": "Weather in Paris and Rome?"}]},
+                {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "call_1", "name": "weather", "input": {}},
+                    {"type": "tool_use", "id": "call_2", "name": "weather", "input": {}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "call_1", "content": [{"type": "text", "text": "sunny"}]},
+                    {"type": "tool_result", "tool_use_id": "call_2", "content": [{"type": "text", "text": "rainy"}]}
+                ]},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_titan_payload_mapping() {
+        let history = vec![
+            Message::Preamble("Be brief".to_string()),
+            Message::User {
+                content: "Hi".to_string(),
+                tool_responses: None,
+                name: None,
+            },
+            Message::Assistant {
+                content: "Hello!".to_string(),
+                tool_calls: None,
+                name: None,
+            },
+        ];
+        let message = Message::User {
+            content: "Bye".to_string(),
+            tool_responses: None,
+            name: None,
+        };
+        let body = model("amazon.titan-text-express-v1")
+            .build_request_body(message, &history, None, 0.5, 100)
+            .unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "inputText": "Be brief\nUser: Hi\nBot: Hello!\nUser: Bye\nBot:",
+                "textGenerationConfig": {"temperature": 0.5, "maxTokenCount": 100},
+            })
+        );
+    }
+
+    #[test]
+    fn test_unknown_config_keys_are_sent_in_the_body() {
+        let model = Bedrock::new(Some(
+            &json!({"model_id": "anthropic.claude-3-5-haiku-20241022-v1:0", "top_k": 5})
+                .to_string(),
+        ));
+        let message = Message::User {
+            content: "Hi".to_string(),
+            tool_responses: None,
+            name: None,
+        };
+        let body = model
+            .build_request_body(message, &vec![], None, 0.5, 100)
+            .unwrap();
+        assert_eq!(body["top_k"], json!(5));
+        assert_eq!(body["max_tokens"], json!(100));
+    }
+
+    #[test]
+    fn test_response_parsing_per_family() {
+        let output = parse_anthropic_response(&json!({
+            "content": [
+                {"type": "text", "text": "Let me check"},
+                {"type": "tool_use", "id": "call_1", "name": "weather", "input": {"city": "Paris"}}
+            ],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 10, "output_tokens": 5},
+        }))
+        .unwrap();
+        assert_eq!(
+            output.message,
+            Message::Assistant {
+                content: "Let me check".to_string(),
+                tool_calls: Some(vec![ToolCall {
+                    id: "call_1".to_string(),
+                    name: "weather".to_string(),
+                    arguments: r#"{"city":"Paris"}"#.to_string(),
+                }]),
+                name: None,
+            }
+        );
+        assert_eq!(output.finish_reason, Some(FinishReason::ToolCalls));
+        assert_eq!(output.token_usage.total_tokens, Some(15));
+
+        let output = parse_titan_response(&json!({
+            "inputTextTokenCount": 7,
+            "results": [{"tokenCount": 3, "outputText": " Goodbye!", "completionReason": "LENGTH"}],
+        }))
+        .unwrap();
+        assert_eq!(
+            output.message,
+            Message::Assistant {
+                content: "Goodbye!".to_string(),
+                tool_calls: None,
+                name: None,
+            }
+        );
+        assert_eq!(output.finish_reason, Some(FinishReason::Length));
+        assert_eq!(output.token_usage.total_tokens, Some(10));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn simple_bedrock_completion_request() {
+        let mut model = model("anthropic.claude-3-5-haiku-20241022-v1:0");
+        let response = model
+            .send(
+                Message::User {
+                    content: r#"Reply with "okay" without the quotes, and NOTHING else."#
+                        .to_string(),
+                    tool_responses: None,
+                    name: None,
+                },
+                &vec![],
+                None,
+                0.0,
+                10,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response.0,
+            Message::Assistant {
+                content: "okay".to_string(),
+                tool_calls: None,
+                name: None,
+            }
+        );
+    }
+}
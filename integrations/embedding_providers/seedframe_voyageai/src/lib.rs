@@ -90,6 +90,15 @@ impl VoyageAIEmbedding {
             model,
         }
     }
+
+    /// Replaces the HTTP client used for requests to VoyageAI, eg. to share a connection pool
+    /// with other providers and embedders talking to the same host.
+    /// A new client is created for every instance by default.
+    #[must_use]
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
 }
 
 #[derive(Deserialize)]
@@ -125,11 +134,15 @@ impl EmbeddingModel for VoyageAIEmbedding {
                 .await
                 .map_err(|e| EmbedderError::ParseError(e.to_string()))?;
 
-            Ok(response
+            let embeddings: Vec<f64> = response
                 .data
                 .into_iter()
                 .flat_map(|d| d.embedding)
-                .collect())
+                .collect();
+            if embeddings.is_empty() && !data.is_empty() {
+                return Err(EmbedderError::ParseError("empty embedding".to_string()));
+            }
+            Ok(embeddings)
         } else {
             let error_message = response
                 .text()
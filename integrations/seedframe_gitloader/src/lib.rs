@@ -0,0 +1,607 @@
+//! A git repository loader implementing the `seedframe::loader::Loader` trait.
+//!
+//! This module provides a `GitLoader` struct that clones or opens a git repository, publishes
+//! a document for every file matching the configured glob patterns, and optionally pulls the
+//! repository at regular intervals, publishing the files that changed.
+
+use async_trait::async_trait;
+use git2::{
+    build::{CheckoutBuilder, RepoBuilder},
+    Delta, ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult,
+};
+use glob::Pattern;
+use seedframe::document::Document;
+use seedframe::loader::Loader;
+use serde::Deserialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+use tokio::sync::Mutex;
+use tracing::{debug, error, info, instrument};
+
+const CHANNEL_CAPACITY: usize = 256;
+const DEFAULT_INCLUDE: &str = "**/*";
+
+/// Configuration structure for the git loader.
+///
+/// This is deserialized from the JSON config provided in the `#[loader]` macro.
+///
+/// # Examples
+///
+/// ```json
+/// {
+///     "url": "https://github.com/Shifta-Robel/SeedFrame",
+///     "path": "/tmp/seedframe",
+///     "branch": "main",
+///     "include": ["**/*.rs", "**/*.md"],
+///     "exclude": ["target/**"],
+///     "interval": 300
+/// }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    url: Option<String>,
+    path: Option<String>,
+    branch: Option<String>,
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+    interval: Option<u64>,
+}
+
+#[derive(Debug, Error)]
+pub enum GitLoaderError {
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Pattern(#[from] glob::PatternError),
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+    #[error("Can't fast-forward branch `{0}` to its remote counterpart")]
+    NotFastForward(String),
+}
+
+/// A git repository loader implementation of the `seedframe::Loader` trait.
+///
+/// The `GitLoader` clones the repository at `url` into `path` (or a temporary directory if no
+/// path is given), or opens the existing repository at `path`, and publishes a document for
+/// every file of the checked out commit matching one of the `include` glob patterns and none
+/// of the `exclude` ones. Patterns are matched against paths relative to the repository root,
+/// binary files are skipped.
+///
+/// With an `interval` (in seconds) set, the branch is fast-forwarded to its `origin`
+/// counterpart at every interval and documents are published for the files that changed,
+/// deleted files are published with empty content. The `branch` defaults to the remote's
+/// default branch when cloning and to the checked out branch otherwise.
+///
+/// Documents are identified by the repository url (or path) joined with the file's relative
+/// path, which is also available in the `path` metadata entry along with the hash of the
+/// commit in the `commit` entry. Every subscriber first receives the documents of all
+/// currently loaded files.
+///
+/// # Usage
+///
+/// Intended for use through the `#[loader]` proc-macro from seedframe:
+/// ```ignore
+/// #[loader(
+///     external = "GitLoader",
+///     config = r#"{
+///         "url": "https://github.com/Shifta-Robel/SeedFrame",
+///         "include": ["**/*.rs"],
+///         "exclude": ["target/**"],
+///         "interval": 300
+///     }"#
+/// )]
+/// struct SomeStruct;
+/// ```
+pub struct GitLoader {
+    sender: Sender<Document>,
+    documents: Arc<Mutex<HashMap<String, Document>>>,
+}
+
+impl GitLoader {
+    /// Creates a new `GitLoader` from a JSON configuration string.
+    ///
+    /// The repository gets cloned or opened in the background, failures are logged.
+    ///
+    /// # Errors
+    /// This function will error if:
+    ///  - no json config is passed, or it specifies neither a `url` nor a `path`
+    ///  - the provided JSON is malformed or contains unknown fields
+    ///  - one of the glob patterns is invalid
+    pub fn new(json_str: Option<&str>) -> Result<Self, GitLoaderError> {
+        let json_str = json_str.ok_or(GitLoaderError::InvalidConfig(
+            "Expected a json config with atleast one of the `url` or `path` fields specified!"
+                .to_string(),
+        ))?;
+        let config: Config = serde_json::from_str(json_str)?;
+        let interval = config.interval.map(Duration::from_secs);
+        let repo = Arc::new(Repo::try_from(config)?);
+
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let documents = Arc::new(Mutex::new(HashMap::new()));
+
+        let task_sender = sender.clone();
+        let task_documents = Arc::clone(&documents);
+        tokio::spawn(async move {
+            let mut last = None;
+            loop {
+                let task_repo = Arc::clone(&repo);
+                match tokio::task::spawn_blocking(move || task_repo.sync(last)).await {
+                    Ok(Ok((head, changed))) => {
+                        last = Some(head);
+                        info!("Loaded {} files from commit {head}", changed.len());
+                        let mut documents = task_documents.lock().await;
+                        for document in changed {
+                            if document.data.is_empty() {
+                                documents.remove(&document.id);
+                            } else {
+                                documents.insert(document.id.clone(), document.clone());
+                            }
+                            let _ = task_sender.send(document);
+                        }
+                    }
+                    Ok(Err(e)) => error!("Syncing the git repository failed: {e}"),
+                    Err(e) => error!("Syncing the git repository panicked: {e}"),
+                }
+
+                let Some(interval) = interval else {
+                    break;
+                };
+                tokio::time::sleep(interval).await;
+            }
+        });
+
+        Ok(Self { sender, documents })
+    }
+}
+
+/// Where to load the repository from and which of its files to publish
+struct Repo {
+    url: Option<String>,
+    path: PathBuf,
+    branch: Option<String>,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    id_prefix: String,
+}
+
+impl TryFrom<Config> for Repo {
+    type Error = GitLoaderError;
+
+    fn try_from(config: Config) -> Result<Self, Self::Error> {
+        let (path, id_prefix) = match (&config.url, config.path) {
+            (Some(url), Some(path)) => (PathBuf::from(path), url.clone()),
+            (Some(url), None) => {
+                let mut hasher = DefaultHasher::new();
+                url.hash(&mut hasher);
+                let path =
+                    std::env::temp_dir().join(format!("seedframe_git_{:x}", hasher.finish()));
+                (path, url.clone())
+            }
+            (None, Some(path)) => (PathBuf::from(&path), path),
+            (None, None) => Err(GitLoaderError::InvalidConfig(
+                "One of the `url` or `path` fields must be specified!".to_string(),
+            ))?,
+        };
+        let patterns = |patterns: Vec<String>| {
+            patterns
+                .iter()
+                .map(|p| Pattern::new(p))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(Self {
+            url: config.url,
+            path,
+            branch: config.branch,
+            include: patterns(
+                config
+                    .include
+                    .unwrap_or_else(|| vec![DEFAULT_INCLUDE.to_string()]),
+            )?,
+            exclude: patterns(config.exclude.unwrap_or_default())?,
+            id_prefix: id_prefix.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+impl Repo {
+    /// Brings the repository up to date, returning the new head commit along with the documents
+    /// for the files changed since `last`, or for all files on the first sync
+    #[instrument(skip(self), fields(path = ?self.path))]
+    fn sync(&self, last: Option<Oid>) -> Result<(Oid, Vec<Document>), GitLoaderError> {
+        let repo = self.open()?;
+        if self.url.is_some() || last.is_some() {
+            pull(&repo)?;
+        }
+        let head = repo.head()?.peel_to_commit()?.id();
+        let documents = match last {
+            None => self.all_files(&repo, head)?,
+            Some(last) if last == head => vec![],
+            Some(last) => self.changed_files(&repo, last, head)?,
+        };
+        Ok((head, documents))
+    }
+
+    /// Opens the repository at `path`, cloning it from `url` if there's none
+    fn open(&self) -> Result<Repository, GitLoaderError> {
+        if let Ok(repo) = Repository::open(&self.path) {
+            if let Some(branch) = &self.branch {
+                checkout_branch(&repo, branch)?;
+            }
+            return Ok(repo);
+        }
+        let url = self.url.as_ref().ok_or_else(|| {
+            GitLoaderError::InvalidConfig(format!("{:?} isn't a git repository", self.path))
+        })?;
+
+        info!("Cloning {url} into {:?}", self.path);
+        let mut builder = RepoBuilder::new();
+        if let Some(branch) = &self.branch {
+            builder.branch(branch);
+        }
+        Ok(builder.clone(url, &self.path)?)
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        self.include.iter().any(|p| p.matches(path))
+            && !self.exclude.iter().any(|p| p.matches(path))
+    }
+
+    fn all_files(&self, repo: &Repository, commit: Oid) -> Result<Vec<Document>, GitLoaderError> {
+        let tree = repo.find_commit(commit)?.tree()?;
+        let mut files = vec![];
+        tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+            if entry.kind() == Some(ObjectType::Blob) {
+                if let Some(name) = entry.name() {
+                    files.push((format!("{root}{name}"), entry.id()));
+                }
+            }
+            TreeWalkResult::Ok
+        })?;
+
+        Ok(files
+            .into_iter()
+            .filter(|(path, _)| self.matches(path))
+            .filter_map(|(path, blob)| self.file_document(repo, &path, blob, commit))
+            .collect())
+    }
+
+    fn changed_files(
+        &self,
+        repo: &Repository,
+        old: Oid,
+        new: Oid,
+    ) -> Result<Vec<Document>, GitLoaderError> {
+        let old_tree = repo.find_commit(old)?.tree()?;
+        let new_tree = repo.find_commit(new)?.tree()?;
+        let diff = repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?;
+
+        let mut documents = vec![];
+        for delta in diff.deltas() {
+            if delta.status() == Delta::Deleted {
+                if let Some(path) = delta.old_file().path().and_then(Path::to_str) {
+                    if self.matches(path) {
+                        debug!("Detected deleted file {path}");
+                        documents.push(self.document(path, String::new(), new));
+                    }
+                }
+            } else if let Some(path) = delta.new_file().path().and_then(Path::to_str) {
+                if self.matches(path) {
+                    debug!("Detected new or changed file {path}");
+                    documents.extend(self.file_document(repo, path, delta.new_file().id(), new));
+                }
+            }
+        }
+        Ok(documents)
+    }
+
+    fn file_document(
+        &self,
+        repo: &Repository,
+        path: &str,
+        blob: Oid,
+        commit: Oid,
+    ) -> Option<Document> {
+        let blob = match repo.find_blob(blob) {
+            Ok(blob) => blob,
+            Err(e) => {
+                error!("Failed to read {path}: {e}");
+                return None;
+            }
+        };
+        if blob.is_binary() {
+            debug!("Skipping binary file {path}");
+            return None;
+        }
+        let data = String::from_utf8_lossy(blob.content()).into_owned();
+        Some(self.document(path, data, commit))
+    }
+
+    fn document(&self, path: &str, data: String, commit: Oid) -> Document {
+        Document::builder()
+            .id(format!("{}/{path}", self.id_prefix))
+            .data(data)
+            .metadata("path", path)
+            .metadata("commit", commit.to_string())
+            .build()
+    }
+}
+
+fn checkout_branch(repo: &Repository, branch: &str) -> Result<(), GitLoaderError> {
+    let refname = format!("refs/heads/{branch}");
+    if repo.head()?.name() != Some(refname.as_str()) {
+        // check out the tree before moving HEAD, so the checkout compares against the old tree
+        let target = repo.revparse_single(&refname)?;
+        repo.checkout_tree(&target, Some(CheckoutBuilder::new().safe()))?;
+        repo.set_head(&refname)?;
+    }
+    Ok(())
+}
+
+/// Fast-forwards the checked out branch to its `origin` counterpart,
+/// repositories without an `origin` remote are left as is
+fn pull(repo: &Repository) -> Result<(), GitLoaderError> {
+    let Ok(mut remote) = repo.find_remote("origin") else {
+        return Ok(());
+    };
+    let head = repo.head()?;
+    let (Some(refname), Some(branch)) = (head.name(), head.shorthand()) else {
+        return Ok(());
+    };
+
+    remote.fetch(&[branch], None, None)?;
+    let fetched = repo.reference_to_annotated_commit(&repo.find_reference("FETCH_HEAD")?)?;
+    let (analysis, _) = repo.merge_analysis(&[&fetched])?;
+    if analysis.is_up_to_date() {
+        return Ok(());
+    }
+    if !analysis.is_fast_forward() {
+        return Err(GitLoaderError::NotFastForward(branch.to_string()));
+    }
+
+    debug!("Fast-forwarding {branch} to {}", fetched.id());
+    repo.checkout_tree(
+        &repo.find_object(fetched.id(), None)?,
+        Some(CheckoutBuilder::new().safe()),
+    )?;
+    repo.find_reference(refname)?
+        .set_target(fetched.id(), "seedframe: fast-forward")?;
+    Ok(())
+}
+
+#[async_trait]
+impl Loader for GitLoader {
+    /// Subscribes to the loader, first receiving the documents for all currently loaded files
+    /// followed by all subsequent changes, so subscribers joining after the first sync don't
+    /// miss the repository's files.
+    async fn subscribe(&self) -> Receiver<Document> {
+        let documents = self.documents.lock().await;
+        let mut upstream = self.sender.subscribe();
+        let (tx, rx) = broadcast::channel(documents.len() + CHANNEL_CAPACITY);
+        for document in documents.values() {
+            let _ = tx.send(document.clone());
+        }
+        drop(documents);
+        tokio::spawn(async move {
+            loop {
+                match upstream.recv().await {
+                    Ok(doc) => {
+                        if tx.send(doc).is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+    use seedframe::embeddings::{model::EmbeddingModel, Embedder, EmbedderError};
+    use seedframe::vector_store::{InMemoryVectorStore, VectorStore};
+    use tokio::time::timeout;
+
+    fn commit_files(repo: &Repository, files: &[(&str, Option<&str>)], message: &str) {
+        let root = repo.workdir().unwrap();
+        let mut index = repo.index().unwrap();
+        for (path, content) in files {
+            if let Some(content) = content {
+                let file = root.join(path);
+                std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+                std::fs::write(file, content).unwrap();
+                index.add_path(Path::new(path)).unwrap();
+            } else {
+                std::fs::remove_file(root.join(path)).unwrap();
+                index.remove_path(Path::new(path)).unwrap();
+            }
+        }
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("seedframe", "seedframe@example.com").unwrap();
+        let parent = repo.head().ok().map(|h| h.peel_to_commit().unwrap());
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parent.iter().collect::<Vec<_>>(),
+        )
+        .unwrap();
+    }
+
+    async fn receive_all(receiver: &mut Receiver<Document>) -> Vec<Document> {
+        let mut documents = vec![];
+        while let Ok(Ok(doc)) = timeout(Duration::from_secs(2), receiver.recv()).await {
+            documents.push(doc);
+        }
+        documents.sort_by(|a, b| a.id.cmp(&b.id));
+        documents
+    }
+
+    #[tokio::test]
+    async fn test_loads_matching_files_with_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_files(
+            &repo,
+            &[
+                ("src/lib.rs", Some("pub fn lib() {}")),
+                ("src/main.rs", Some("fn main() {}")),
+                ("README.md", Some("# readme")),
+                ("target/build.rs", Some("fn build() {}")),
+            ],
+            "initial commit",
+        );
+        let head = repo
+            .head()
+            .unwrap()
+            .peel_to_commit()
+            .unwrap()
+            .id()
+            .to_string();
+
+        let path = dir.path().to_str().unwrap();
+        let config = serde_json::json!({
+            "path": path,
+            "include": ["**/*.rs"],
+            "exclude": ["target/**"]
+        })
+        .to_string();
+        let loader = GitLoader::new(Some(&config)).unwrap();
+        let mut receiver = loader.subscribe().await;
+        let documents = receive_all(&mut receiver).await;
+
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].id, format!("{path}/src/lib.rs"));
+        assert_eq!(documents[0].data, "pub fn lib() {}");
+        assert_eq!(documents[0].metadata["path"], "src/lib.rs");
+        assert_eq!(documents[0].metadata["commit"], head);
+        assert_eq!(documents[1].metadata["path"], "src/main.rs");
+    }
+
+    #[tokio::test]
+    async fn test_interval_pulls_changed_files() {
+        let origin_dir = tempfile::tempdir().unwrap();
+        let origin = Repository::init(origin_dir.path()).unwrap();
+        commit_files(
+            &origin,
+            &[("a.txt", Some("first")), ("b.txt", Some("second"))],
+            "initial commit",
+        );
+        let clone_dir = tempfile::tempdir().unwrap();
+        let clone_path = clone_dir.path().join("clone");
+
+        let config = serde_json::json!({
+            "url": origin_dir.path().to_str().unwrap(),
+            "path": clone_path.to_str().unwrap(),
+            "interval": 1
+        })
+        .to_string();
+        let loader = GitLoader::new(Some(&config)).unwrap();
+        let mut receiver = loader.subscribe().await;
+        let initial = receive_all(&mut receiver).await;
+        assert_eq!(
+            initial.iter().map(|d| d.data.as_str()).collect::<Vec<_>>(),
+            ["first", "second"]
+        );
+
+        commit_files(
+            &origin,
+            &[("a.txt", Some("updated")), ("b.txt", None)],
+            "update a, delete b",
+        );
+        let changed = receive_all(&mut receiver).await;
+        assert_eq!(changed.len(), 2);
+        assert_eq!(changed[0].metadata["path"], "a.txt");
+        assert_eq!(changed[0].data, "updated");
+        assert_eq!(changed[1].metadata["path"], "b.txt");
+        assert!(changed[1].data.is_empty());
+        assert_eq!(
+            std::fs::read_to_string(clone_path.join("a.txt")).unwrap(),
+            "updated"
+        );
+    }
+
+    struct ConstantEmbeddingModel;
+
+    #[async_trait]
+    impl EmbeddingModel for ConstantEmbeddingModel {
+        async fn embed(&self, _data: &str) -> Result<Vec<f64>, EmbedderError> {
+            Ok(vec![1.0, 0.0])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_embedder_stores_files_loaded_before_it_subscribed() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_files(
+            &repo,
+            &[("a.txt", Some("first")), ("b.txt", Some("second"))],
+            "initial commit",
+        );
+
+        let config = serde_json::json!({ "path": dir.path().to_str().unwrap() }).to_string();
+        let loader = GitLoader::new(Some(&config)).unwrap();
+        timeout(Duration::from_secs(2), async {
+            while loader.documents.lock().await.len() < 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the first sync didn't finish");
+
+        let store: Arc<Mutex<Box<dyn VectorStore>>> = Arc::new(Mutex::new(Box::new(
+            InMemoryVectorStore::new(None).await.unwrap(),
+        )));
+        let _embedder = Embedder::init(
+            vec![Arc::new(loader)],
+            Arc::clone(&store),
+            Arc::new(Box::new(ConstantEmbeddingModel)),
+        )
+        .await;
+
+        let ids = timeout(Duration::from_secs(2), async {
+            loop {
+                let mut ids = store.lock().await.list_ids().await.unwrap();
+                if ids.len() == 2 {
+                    ids.sort();
+                    break ids;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the loaded files weren't stored");
+        let path = dir.path().to_str().unwrap();
+        assert_eq!(ids, [format!("{path}/a.txt"), format!("{path}/b.txt")]);
+    }
+
+    #[test]
+    fn test_config_requires_a_source() {
+        assert!(matches!(
+            GitLoader::new(Some(r#"{"include": ["**/*.rs"]}"#)),
+            Err(GitLoaderError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            GitLoader::new(Some(r#"{"path": "/tmp", "include": ["[a-"]}"#)),
+            Err(GitLoaderError::Pattern(_))
+        ));
+    }
+}
@@ -11,10 +11,12 @@ use seedframe::loader::Loader;
 use serde::de::Error;
 use serde::Deserialize;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::broadcast::{self, Receiver, Sender};
 use tokio::sync::Mutex;
 
+const REPLAY_CHANNEL_CAPACITY: usize = 16;
+
 /// Configuration structure for the web scraper.
 ///
 /// This is deserialized from the JSON config provided in the `#[loader]` macro.
@@ -26,7 +28,9 @@ use tokio::sync::Mutex;
 /// {
 ///     "url": "https://example.com",
 ///     "interval": 5,
-///     "selector": "div.content"
+///     "selector": "div.content",
+///     "stable_id": true,
+///     "interval_jitter_secs": 2
 /// }
 /// ```
 #[derive(Debug, Deserialize)]
@@ -35,6 +39,8 @@ struct Config {
     url: String,
     interval: Option<u64>,
     selector: Option<String>,
+    stable_id: Option<bool>,
+    interval_jitter_secs: Option<u64>,
 }
 
 /// A web scraper implementation of the `seedframe::Loader` trait.
@@ -42,7 +48,15 @@ struct Config {
 /// The `WebScraper` fetches HTML content from a specified URL at regular intervals
 /// (or once, if no interval is specified) and publishes the results to subscribers.
 /// It can optionally filter content using CSS selectors.
-/// The unit of intervals is seconds. The interval and selector fields are optional.
+/// The unit of intervals is seconds. The interval, selector and stable_id fields are optional.
+///
+/// With `stable_id` enabled (the default) documents are identified by their URL alone, so
+/// re-scraping a page updates its entry in the vector store instead of adding a new one.
+/// Setting it to `false` appends a timestamp to the id, keeping every scrape as its own document.
+/// The time of the scrape is always available in the `scraped_at` metadata entry.
+///
+/// `interval_jitter_secs` randomizes every sleep between scrapes by up to ± the given seconds,
+/// keeping scrapers that share an interval from hitting the target at the same time.
 ///
 /// # Usage
 ///
@@ -60,6 +74,7 @@ struct Config {
 /// ```
 pub struct WebScraper {
     sender: Arc<Mutex<Sender<Document>>>,
+    last: Arc<Mutex<Option<Document>>>,
 }
 
 impl WebScraper {
@@ -83,16 +98,23 @@ impl WebScraper {
         let url = config.url;
         let interval = config.interval.map(Duration::from_secs);
         let selector = config.selector;
+        let stable_id = config.stable_id.unwrap_or(true);
+        let jitter = config.interval_jitter_secs.map(Duration::from_secs);
+
+        let last = Arc::new(Mutex::new(None));
 
         let task_sender = Arc::clone(&sender);
+        let task_last = Arc::clone(&last);
         tokio::spawn(async move {
             let run_once = interval.is_none();
             let selector = selector.and_then(|s| Selector::parse(&s).ok());
+            let mut rng = XorShift::from_time();
 
             loop {
-                match Self::fetch_and_parse(&url, selector.as_ref()).await {
+                match Self::fetch_and_parse(&url, selector.as_ref(), stable_id).await {
                     Ok(document) => {
                         let sender = task_sender.lock().await;
+                        *task_last.lock().await = Some(document.clone());
                         let _ = sender.send(document);
                     }
                     Err(e) => eprintln!("Scraping failed: {e}"),
@@ -103,6 +125,7 @@ impl WebScraper {
                 }
 
                 if let Some(dur) = interval {
+                    let dur = jitter.map_or(dur, |jitter| jittered(dur, jitter, &mut rng));
                     tokio::time::sleep(dur).await;
                 } else {
                     break;
@@ -110,13 +133,14 @@ impl WebScraper {
             }
         });
 
-        Ok(Self { sender })
+        Ok(Self { sender, last })
     }
 
     /// Fetches and parses website content
     async fn fetch_and_parse(
         url: &str,
         selector: Option<&Selector>,
+        stable_id: bool,
     ) -> Result<Document, reqwest::Error> {
         let html = reqwest::get(url).await?.text().await?;
         let data = match selector {
@@ -128,18 +152,80 @@ impl WebScraper {
             None => html,
         };
 
-        Ok(Document {
-            id: format!("{}-{}", url, Utc::now().timestamp_millis()),
-            data,
-        })
+        let timestamp = Utc::now().timestamp_millis();
+        let id = if stable_id {
+            url.to_string()
+        } else {
+            format!("{url}-{timestamp}")
+        };
+
+        Ok(Document::builder()
+            .id(id)
+            .data(data)
+            .metadata("url", url)
+            .metadata("scraped_at", timestamp.to_string())
+            .build())
     }
 }
 
+/// Minimal xorshift PRNG, good enough for spreading out scrape times
+struct XorShift(u64);
+
+impl XorShift {
+    #[allow(clippy::cast_possible_truncation)]
+    fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        // the state must never be zero
+        Self(nanos | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Randomizes `interval` by up to ± `jitter`, with millisecond granularity
+#[allow(clippy::cast_possible_truncation)]
+fn jittered(interval: Duration, jitter: Duration, rng: &mut XorShift) -> Duration {
+    let jitter_ms = jitter.as_millis() as u64;
+    let offset = Duration::from_millis(rng.next() % (2 * jitter_ms + 1));
+    (interval + offset).saturating_sub(jitter)
+}
+
 #[async_trait]
 impl Loader for WebScraper {
     async fn subscribe(&self) -> Receiver<Document> {
         self.sender.lock().await.subscribe()
     }
+
+    /// Subscribes to the scraper, first receiving the most recently scraped document (if any)
+    /// followed by all subsequent scrapes.
+    async fn subscribe_replay(&self) -> Receiver<Document> {
+        let mut upstream = self.sender.lock().await.subscribe();
+        let (tx, rx) = broadcast::channel(REPLAY_CHANNEL_CAPACITY);
+        if let Some(doc) = self.last.lock().await.clone() {
+            let _ = tx.send(doc);
+        }
+        tokio::spawn(async move {
+            loop {
+                match upstream.recv().await {
+                    Ok(doc) => {
+                        if tx.send(doc).is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        rx
+    }
 }
 
 #[cfg(test)]
@@ -161,6 +247,22 @@ mod tests {
         assert_eq!(config.url, "https://example.com");
         assert_eq!(config.interval, Some(60));
         assert_eq!(config.selector, Some("div.content".to_string()));
+        assert_eq!(config.interval_jitter_secs, None);
+    }
+
+    #[test]
+    fn test_jittered_interval_stays_within_bounds() {
+        let (interval, jitter) = (Duration::from_secs(5), Duration::from_secs(2));
+        let mut rng = XorShift::from_time();
+        let sleeps: Vec<_> = (0..20)
+            .map(|_| jittered(interval, jitter, &mut rng))
+            .collect();
+
+        assert!(sleeps
+            .iter()
+            .all(|d| *d >= interval - jitter && *d <= interval + jitter));
+        assert!(sleeps.iter().any(|d| *d != sleeps[0]));
+        assert!(jittered(Duration::from_secs(1), jitter, &mut rng) <= Duration::from_secs(3));
     }
 
     #[tokio::test]
@@ -175,7 +277,7 @@ mod tests {
 
         let selector = Selector::parse("div.content").unwrap();
         let selector = Some(&selector);
-        let result = WebScraper::fetch_and_parse(&url, selector).await;
+        let result = WebScraper::fetch_and_parse(&url, selector, true).await;
 
         mock_server.assert();
         assert!(result.is_ok());
@@ -197,7 +299,8 @@ mod tests {
             r#"{{
                 "url": "{}",
                 "interval": 1,
-                "selector": null
+                "selector": null,
+                "stable_id": false
             }}"#,
             url
         );
@@ -222,6 +325,44 @@ mod tests {
         assert_eq!(second.data, "Just Content");
     }
 
+    #[tokio::test]
+    async fn test_stable_id_reused_across_scrapes() {
+        let mut mock_server = mockito::Server::new_async().await;
+        let url = mock_server.url();
+        let _ = mock_server
+            .mock("GET", "/")
+            .with_body("Test Content")
+            .expect_at_least(2)
+            .create();
+
+        let first = WebScraper::fetch_and_parse(&url, None, true).await.unwrap();
+        let second = WebScraper::fetch_and_parse(&url, None, true).await.unwrap();
+
+        assert_eq!(first.id, url);
+        assert_eq!(first.id, second.id);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_replay_receives_last_document() {
+        let mut mock_server = mockito::Server::new_async().await;
+        let url = mock_server.url();
+        let _ = mock_server
+            .mock("GET", "/")
+            .with_body("Test Content")
+            .create();
+
+        let scraper = WebScraper::new(Some(&format!(r#"{{"url": "{url}"}}"#))).unwrap();
+        let mut receiver = scraper.subscribe().await;
+        let first = receiver.recv().await.unwrap();
+
+        let mut late_receiver = scraper.subscribe_replay().await;
+        let replayed = time::timeout(Duration::from_secs(1), late_receiver.recv())
+            .await
+            .expect("Didn't receive replayed document")
+            .unwrap();
+        assert_eq!(first, replayed);
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_one_time_scraping() {
@@ -239,7 +380,7 @@ mod tests {
         }
 
         assert_eq!(received.len(), 1);
-        assert!(&received.first().unwrap().id.starts_with(&format!("{url}")));
+        assert!(&received.first().unwrap().id.starts_with(&url));
     }
 
     #[tokio::test]
@@ -50,9 +50,13 @@ struct Config {
 /// )]
 /// struct SomeStruct;
 /// ```
+///
+/// The tenant-scoped methods, used by [`seedframe::vector_store::ScopedVectorStore`], store
+/// each tenant's embeddings in a namespace named after the tenant.
 pub struct PineconeVectorStore {
     index: Mutex<Index>,
     namespace: Namespace,
+    dimension: Option<usize>,
 }
 
 const PINECONE_API_VERSION: &str = "2025-01";
@@ -91,24 +95,92 @@ impl PineconeVectorStore {
             source_tag: json_config.source_tag,
         };
         let client = config.client().expect("Failed to create pinecone instance");
-        let index = Mutex::new(
-            client
-                .index(&json_config.index_host)
-                .await
-                .map_err(into_vec_store_error)?,
-        );
+        let mut index = client
+            .index(&json_config.index_host)
+            .await
+            .map_err(into_vec_store_error)?;
+        let dimension = index
+            .describe_index_stats(None)
+            .await
+            .ok()
+            .map(|stats| stats.dimension as usize);
         let name = json_config.namespace.unwrap_or_default();
         let namespace = Namespace { name };
-        Ok(Self { index, namespace })
+        Ok(Self {
+            index: Mutex::new(index),
+            namespace,
+            dimension,
+        })
     }
 }
 
 #[async_trait]
 impl VectorStore for PineconeVectorStore {
     async fn get_by_id(&self, id: String) -> Result<Embedding, VectorStoreError> {
+        self.get_by_id_from(&self.namespace, &id).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Embedding>, VectorStoreError> {
+        self.get_by_ids_from(&self.namespace, ids).await
+    }
+
+    async fn store(&self, embedding: Embedding) -> Result<(), VectorStoreError> {
+        self.store_to(&self.namespace, embedding).await
+    }
+
+    async fn top_n(&self, query: &[f64], n: usize) -> Result<Vec<Embedding>, VectorStoreError> {
+        self.top_n_from(&self.namespace, query, n, true).await
+    }
+
+    async fn top_n_text(
+        &self,
+        query: &[f64],
+        n: usize,
+    ) -> Result<Vec<(String, String)>, VectorStoreError> {
+        Ok(self
+            .top_n_from(&self.namespace, query, n, false)
+            .await?
+            .into_iter()
+            .map(|embedding| (embedding.id, embedding.raw_data))
+            .collect())
+    }
+
+    async fn list_ids(&self) -> Result<Vec<String>, VectorStoreError> {
+        self.list_ids_from(&self.namespace).await
+    }
+
+    async fn get_by_id_in(&self, tenant: &str, id: String) -> Result<Embedding, VectorStoreError> {
+        self.get_by_id_from(&tenant_namespace(tenant), &id).await
+    }
+
+    async fn store_in(&self, tenant: &str, embedding: Embedding) -> Result<(), VectorStoreError> {
+        self.store_to(&tenant_namespace(tenant), embedding).await
+    }
+
+    async fn top_n_in(
+        &self,
+        tenant: &str,
+        query: &[f64],
+        n: usize,
+    ) -> Result<Vec<Embedding>, VectorStoreError> {
+        self.top_n_from(&tenant_namespace(tenant), query, n, true)
+            .await
+    }
+
+    async fn list_ids_in(&self, tenant: &str) -> Result<Vec<String>, VectorStoreError> {
+        self.list_ids_from(&tenant_namespace(tenant)).await
+    }
+}
+
+impl PineconeVectorStore {
+    async fn get_by_id_from(
+        &self,
+        namespace: &Namespace,
+        id: &str,
+    ) -> Result<Embedding, VectorStoreError> {
         let mut index_guard = self.index.lock().await;
         let resp = index_guard
-            .query_by_id(&id, 1, &self.namespace, None, Some(true), Some(true))
+            .query_by_id(id, 1, namespace, None, Some(true), Some(true))
             .await
             .map_err(into_vec_store_error)?;
         Ok(Embeddings::try_from(resp)?
@@ -117,38 +189,113 @@ impl VectorStore for PineconeVectorStore {
             .ok_or(VectorStoreError::EmbeddingNotFound)?
             .clone())
     }
-    async fn store(&self, embedding: Embedding) -> Result<(), VectorStoreError> {
+
+    /// Fetches every id in a single request, the ids missing from the response are omitted
+    async fn get_by_ids_from(
+        &self,
+        namespace: &Namespace,
+        ids: &[String],
+    ) -> Result<Vec<Embedding>, VectorStoreError> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let id_refs: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let mut index_guard = self.index.lock().await;
+        let resp = index_guard
+            .fetch(&id_refs, namespace)
+            .await
+            .map_err(into_vec_store_error)?;
+        ids.iter()
+            .filter_map(|id| resp.vectors.get(id))
+            .map(|vector| {
+                let metadata = vector.metadata.clone().ok_or(VectorStoreError::Provider(
+                    "Fetch response without raw data".to_string(),
+                ))?;
+                Ok(Embedding {
+                    id: vector.id.clone(),
+                    embedded_data: vector.values.iter().map(|&v| f64::from(v)).collect(),
+                    raw_data: raw_data_from(&metadata),
+                })
+            })
+            .collect()
+    }
+
+    async fn store_to(
+        &self,
+        namespace: &Namespace,
+        embedding: Embedding,
+    ) -> Result<(), VectorStoreError> {
         let mut index_guard = self.index.lock().await;
         if embedding.raw_data.is_empty() {
             () = index_guard
-                .delete_by_id(&[&embedding.id], &self.namespace)
+                .delete_by_id(&[&embedding.id], namespace)
                 .await
                 .map_err(into_vec_store_error)?;
         } else {
             _ = index_guard
-                .upsert(&[vector_from_embedding(embedding)], &self.namespace)
+                .upsert(&[vector_from_embedding(embedding)], namespace)
                 .await
                 .map_err(into_vec_store_error)?;
         }
         Ok(())
     }
+
     #[allow(clippy::cast_possible_truncation)]
-    async fn top_n(&self, query: &[f64], n: usize) -> Result<Vec<Embedding>, VectorStoreError> {
+    async fn top_n_from(
+        &self,
+        namespace: &Namespace,
+        query: &[f64],
+        n: usize,
+        include_values: bool,
+    ) -> Result<Vec<Embedding>, VectorStoreError> {
+        if let Some(expected) = self.dimension.filter(|&d| d != query.len()) {
+            return Err(VectorStoreError::DimensionMismatch {
+                expected,
+                got: query.len(),
+            });
+        }
         let mut index_guard = self.index.lock().await;
         let resp = index_guard
             .query_by_value(
                 query.iter().map(|&v| v as f32).collect::<Vec<f32>>(),
                 None,
                 n as u32,
-                &self.namespace,
+                namespace,
                 None,
-                Some(true),
+                Some(include_values),
                 Some(true),
             )
             .await
             .map_err(into_vec_store_error)?;
         Ok(Embeddings::try_from(resp)?.0)
     }
+
+    async fn list_ids_from(&self, namespace: &Namespace) -> Result<Vec<String>, VectorStoreError> {
+        let mut index_guard = self.index.lock().await;
+        let mut ids = vec![];
+        let mut pagination_token: Option<String> = None;
+        loop {
+            let resp = index_guard
+                .list(namespace, None, None, pagination_token.as_deref())
+                .await
+                .map_err(into_vec_store_error)?;
+            ids.extend(resp.vectors.into_iter().map(|v| v.id));
+            match resp.pagination {
+                Some(pagination) if !pagination.next.is_empty() => {
+                    pagination_token = Some(pagination.next);
+                }
+                _ => break,
+            }
+        }
+        Ok(ids)
+    }
+}
+
+/// Tenants are mapped to Pinecone namespaces of the same name
+fn tenant_namespace(tenant: &str) -> Namespace {
+    Namespace {
+        name: tenant.to_string(),
+    }
 }
 
 fn value_from_str(value: String) -> Value {
@@ -178,25 +325,30 @@ impl TryFrom<QueryResponse> for Embeddings {
     fn try_from(value: QueryResponse) -> Result<Self, Self::Error> {
         let mut embeddings: Vec<Embedding> = vec![];
         for m in value.matches {
-            let mut raw_data = String::new();
             let metadata = m.metadata.ok_or(VectorStoreError::Provider(
                 "Query response without raw data".to_string(),
             ))?;
-            metadata
-                .fields
-                .iter()
-                .for_each(|(k, v)| raw_data.push_str(&format!("\"{k}\":\"{v:?}\"")));
             let embedded_data = m.values.iter().map(|&v| f64::from(v)).collect();
             embeddings.push(Embedding {
                 id: m.id,
                 embedded_data,
-                raw_data,
+                raw_data: raw_data_from(&metadata),
             });
         }
         Ok(Embeddings(embeddings))
     }
 }
 
+/// Rebuilds the raw data of an embedding from the metadata of its vector
+fn raw_data_from(metadata: &Metadata) -> String {
+    let mut raw_data = String::new();
+    metadata
+        .fields
+        .iter()
+        .for_each(|(k, v)| raw_data.push_str(&format!("\"{k}\":\"{v:?}\"")));
+    raw_data
+}
+
 #[allow(clippy::needless_pass_by_value)]
 fn into_vec_store_error(e: PineconeError) -> VectorStoreError {
     VectorStoreError::Provider(e.to_string())
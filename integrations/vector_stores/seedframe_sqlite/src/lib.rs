@@ -0,0 +1,357 @@
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, error, info, instrument};
+
+use seedframe::embeddings::embedding::Embedding;
+use seedframe::vector_store::{metrics::cosine_similarity, VectorStore, VectorStoreError};
+
+/// Configuration structure for the SQLite vector store.
+///
+/// This is deserialized from the JSON config provided in the `#[vector_store]` macro.
+///
+/// # Examples
+///
+/// ```json
+/// {
+///     "db_path": "/tmp/seedframe.db",
+///     "dimensions": 1536
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    db_path: String,
+    dimensions: usize,
+}
+
+/// A vector store persisting embeddings to a [SQLite](https://sqlite.org) database file.
+///
+/// Embeddings survive restarts without running a database server, making it a good fit
+/// for small to medium local RAG applications. Similarity search is a brute-force cosine
+/// similarity scan over the stored embeddings. Queries run on tokio's blocking thread pool,
+/// so they don't stall the async runtime.
+///
+/// # Usage
+///
+/// Intended for use through the `#[vector_store]` proc-macro from seedframe:
+/// ```ignore
+/// #[vector_store(
+///     store = "SqliteVectorStore",
+///     config = r#"{
+///       "db_path": "/tmp/seedframe.db",
+///       "dimensions": 1536
+///     }"#
+/// )]
+/// struct SomeStruct;
+/// ```
+pub struct SqliteVectorStore {
+    connection: Arc<Mutex<Connection>>,
+    dimensions: usize,
+}
+
+impl SqliteVectorStore {
+    /// Creates a new `SqliteVectorStore` from a JSON configuration string,
+    /// creating the database file and table if they don't exist yet
+    ///
+    /// # Errors
+    /// This function will error if:
+    ///  - no json config is passed
+    ///  - the provided JSON is malformed or contains unknown fields
+    ///  - the database can't be opened or initialized
+    #[allow(clippy::unused_async)]
+    pub async fn new(config_json: Option<&str>) -> Result<Self, VectorStoreError> {
+        let config_json = config_json.ok_or(VectorStoreError::FailedToCreateStore(
+            "A config json with the required `db_path` and `dimensions` expected!".to_string(),
+        ))?;
+        let config: Config = serde_json::from_str(config_json)
+            .map_err(|e| VectorStoreError::FailedToCreateStore(e.to_string()))?;
+
+        let connection = Connection::open(&config.db_path)
+            .map_err(|e| VectorStoreError::FailedToCreateStore(e.to_string()))?;
+        connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS embeddings (
+                    id TEXT PRIMARY KEY,
+                    raw_data TEXT NOT NULL,
+                    embedding BLOB NOT NULL
+                )",
+                [],
+            )
+            .map_err(|e| VectorStoreError::FailedToCreateStore(e.to_string()))?;
+        info!("Opened SQLite vector store at {}", config.db_path);
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            dimensions: config.dimensions,
+        })
+    }
+
+    /// Runs `f` with the locked connection on a blocking thread
+    async fn with_connection<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Connection) -> Result<T, VectorStoreError> + Send + 'static,
+    ) -> Result<T, VectorStoreError> {
+        let connection = Arc::clone(&self.connection);
+        tokio::task::spawn_blocking(move || {
+            let connection = connection.lock().map_err(|_| {
+                error!("SQLite connection mutex poisoned");
+                VectorStoreError::Provider("SQLite connection mutex poisoned".to_string())
+            })?;
+            f(&connection)
+        })
+        .await
+        .map_err(|e| VectorStoreError::Provider(e.to_string()))?
+    }
+
+    fn check_dimensions(&self, got: usize) -> Result<(), VectorStoreError> {
+        if got == self.dimensions {
+            Ok(())
+        } else {
+            error!(
+                "Embedding dimension {got} doesn't match the store's dimension {}",
+                self.dimensions
+            );
+            Err(VectorStoreError::DimensionMismatch {
+                expected: self.dimensions,
+                got,
+            })
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn into_vec_store_error(e: rusqlite::Error) -> VectorStoreError {
+    VectorStoreError::Provider(e.to_string())
+}
+
+fn encode(embedding: &[f64]) -> Vec<u8> {
+    embedding.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn decode(bytes: &[u8]) -> Vec<f64> {
+    bytes
+        .chunks_exact(8)
+        .map(|c| f64::from_le_bytes(c.try_into().expect("chunks are 8 bytes long")))
+        .collect()
+}
+
+#[async_trait]
+impl VectorStore for SqliteVectorStore {
+    #[instrument(skip(self))]
+    async fn get_by_id(&self, id: String) -> Result<Embedding, VectorStoreError> {
+        self.with_connection(move |connection| {
+            connection
+                .query_row(
+                    "SELECT raw_data, embedding FROM embeddings WHERE id = ?1",
+                    params![id],
+                    |row| {
+                        Ok(Embedding {
+                            id: id.clone(),
+                            raw_data: row.get(0)?,
+                            embedded_data: decode(&row.get::<_, Vec<u8>>(1)?),
+                        })
+                    },
+                )
+                .optional()
+                .map_err(into_vec_store_error)?
+                .ok_or(VectorStoreError::EmbeddingNotFound)
+        })
+        .await
+    }
+
+    #[instrument(skip(self, embedding), fields(id = embedding.id))]
+    async fn store(&self, embedding: Embedding) -> Result<(), VectorStoreError> {
+        if embedding.raw_data.is_empty() {
+            return self
+                .with_connection(move |connection| {
+                    let deleted = connection
+                        .execute(
+                            "DELETE FROM embeddings WHERE id = ?1",
+                            params![embedding.id],
+                        )
+                        .map_err(into_vec_store_error)?;
+                    if deleted == 0 {
+                        return Err(VectorStoreError::EmbeddingNotFound);
+                    }
+                    info!(
+                        "Removed document :({}) from the SqliteVectorStore",
+                        embedding.id
+                    );
+                    Ok(())
+                })
+                .await;
+        }
+
+        self.check_dimensions(embedding.embedded_data.len())?;
+        self.with_connection(move |connection| {
+            connection
+                .execute(
+                    "INSERT INTO embeddings (id, raw_data, embedding) VALUES (?1, ?2, ?3)
+                     ON CONFLICT(id) DO UPDATE SET raw_data = excluded.raw_data, embedding = excluded.embedding",
+                    params![
+                        embedding.id,
+                        embedding.raw_data,
+                        encode(&embedding.embedded_data)
+                    ],
+                )
+                .map_err(|e| VectorStoreError::FailedUpsert(e.to_string()))?;
+            debug!(
+                "Upserted document :({}) to the SqliteVectorStore",
+                embedding.id
+            );
+            Ok(())
+        })
+        .await
+    }
+
+    #[instrument(skip(self, query))]
+    async fn top_n(&self, query: &[f64], n: usize) -> Result<Vec<Embedding>, VectorStoreError> {
+        self.check_dimensions(query.len())?;
+        let embeddings = self
+            .with_connection(|connection| {
+                let mut statement = connection
+                    .prepare("SELECT id, raw_data, embedding FROM embeddings")
+                    .map_err(into_vec_store_error)?;
+                let embeddings = statement
+                    .query_map([], |row| {
+                        Ok(Embedding {
+                            id: row.get(0)?,
+                            raw_data: row.get(1)?,
+                            embedded_data: decode(&row.get::<_, Vec<u8>>(2)?),
+                        })
+                    })
+                    .map_err(into_vec_store_error)?
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(into_vec_store_error);
+                embeddings
+            })
+            .await?;
+
+        let mut results = embeddings
+            .into_iter()
+            .map(|embedding| {
+                let score = cosine_similarity(&embedding.embedded_data, query).map_err(|_| {
+                    VectorStoreError::DimensionMismatch {
+                        expected: embedding.embedded_data.len(),
+                        got: query.len(),
+                    }
+                })?;
+                Ok((score, embedding))
+            })
+            .collect::<Result<Vec<_>, VectorStoreError>>()?;
+        results.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        results.truncate(n);
+        Ok(results.into_iter().map(|(_, em)| em).collect())
+    }
+
+    #[instrument(skip(self))]
+    async fn list_ids(&self) -> Result<Vec<String>, VectorStoreError> {
+        self.with_connection(|connection| {
+            let mut statement = connection
+                .prepare("SELECT id FROM embeddings")
+                .map_err(into_vec_store_error)?;
+            let ids = statement
+                .query_map([], |row| row.get(0))
+                .map_err(into_vec_store_error)?
+                .collect::<Result<Vec<String>, _>>()
+                .map_err(into_vec_store_error);
+            ids
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn store_at(path: &std::path::Path) -> SqliteVectorStore {
+        let config = format!(
+            r#"{{"db_path": "{}", "dimensions": 3}}"#,
+            path.to_str().unwrap()
+        );
+        SqliteVectorStore::new(Some(&config)).await.unwrap()
+    }
+
+    fn embedding(id: &str, raw_data: &str, embedded_data: Vec<f64>) -> Embedding {
+        Embedding {
+            id: id.to_string(),
+            raw_data: raw_data.to_string(),
+            embedded_data,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store_and_get_by_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.db");
+        let first = embedding("id1", "hello world", vec![1.0, 2.0, 3.0]);
+        {
+            let store = store_at(&path).await;
+            store.store(first.clone()).await.unwrap();
+            assert_eq!(store.get_by_id("id1".to_string()).await.unwrap(), first);
+        }
+
+        let store = store_at(&path).await;
+        assert_eq!(store.get_by_id("id1".to_string()).await.unwrap(), first);
+
+        let updated = embedding("id1", "shalom world", vec![3.0, 2.0, 1.0]);
+        store.store(updated.clone()).await.unwrap();
+        assert_eq!(store.get_by_id("id1".to_string()).await.unwrap(), updated);
+
+        store.store(embedding("id1", "", vec![])).await.unwrap();
+        assert_eq!(
+            store.get_by_id("id1".to_string()).await,
+            Err(VectorStoreError::EmbeddingNotFound)
+        );
+        assert_eq!(
+            store.store(embedding("id1", "", vec![])).await,
+            Err(VectorStoreError::EmbeddingNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_top_n() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_at(&dir.path().join("store.db")).await;
+        let (e1, e2, e3) = (
+            embedding("id1", "hello world", vec![1.0, 2.0, 3.0]),
+            embedding("id2", "shalom world", vec![4.0, 5.0, 6.0]),
+            embedding("id3", "hola world", vec![-1.0, -2.0, -3.0]),
+        );
+        for e in [&e1, &e2, &e3] {
+            store.store(e.clone()).await.unwrap();
+        }
+
+        let results = store.top_n(&[1.0, 2.0, 3.0], 2).await.unwrap();
+        assert_eq!(results, vec![e1, e2]);
+        assert_eq!(
+            store.top_n(&[1.0, 2.0, 3.0, 4.0], 2).await,
+            Err(VectorStoreError::DimensionMismatch {
+                expected: 3,
+                got: 4
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_at(&dir.path().join("store.db")).await;
+        assert!(store.list_ids().await.unwrap().is_empty());
+        store
+            .store(embedding("id1", "hello world", vec![1.0, 2.0, 3.0]))
+            .await
+            .unwrap();
+        store
+            .store(embedding("id2", "shalom world", vec![4.0, 5.0, 6.0]))
+            .await
+            .unwrap();
+
+        let mut ids = store.list_ids().await.unwrap();
+        ids.sort();
+        assert_eq!(ids, vec!["id1".to_string(), "id2".to_string()]);
+    }
+}
@@ -1,17 +1,23 @@
-use darling::{ast::NestedMeta, FromMeta};
-use proc_macro2::TokenStream;
+use darling::{ast::NestedMeta, util::SpannedValue, FromMeta};
+use proc_macro2::{Span, TokenStream};
 use quote::quote;
 use std::fmt::Display;
 use syn::{parse::Parser, ItemStruct, Meta};
 use thiserror::Error;
 
+use crate::config_file::resolve_config;
+
 #[derive(Debug, FromMeta, Clone)]
 struct ClientConfig {
-    provider: syn::Type,
+    provider: SpannedValue<syn::Type>,
     #[darling(default)]
     config: Option<JsonStr>,
     #[darling(default)]
-    tools: Option<ToolNames>,
+    config_file: Option<String>,
+    #[darling(multiple)]
+    tools: Vec<ToolNames>,
+    #[darling(default)]
+    toolsets: Option<ToolNames>,
     #[darling(default)]
     execution_mode: Option<String>,
 }
@@ -24,8 +30,35 @@ pub(crate) enum ClientMacroError {
     UnrecognizedAttribute(String),
     #[error("Unknown execution mode : '{0}'. valid options are fail_early or best_effort")]
     UnknownExecutionMode(String),
+    #[error("Invalid toolset function path '{0}'")]
+    InvalidToolsetPath(String),
+    #[error(
+        "Unknown provider '{0}', did you mean '{1}'? built-in providers are: {builtins}",
+        builtins = BUILTIN_PROVIDERS.join(", ")
+    )]
+    UnknownProvider(String, &'static str, Span),
+    #[error(
+        "Unknown provider '{0}', expected one of {builtins} (or a path to an external provider)",
+        builtins = BUILTIN_PROVIDERS.join(", ")
+    )]
+    NotAProvider(String, Span),
 }
 
+impl ClientMacroError {
+    /// The span to point the error at, the macro invocation is used for errors without one
+    pub(crate) fn span(&self) -> Option<Span> {
+        match self {
+            Self::UnknownProvider(_, _, span) | Self::NotAProvider(_, span) => Some(*span),
+            _ => None,
+        }
+    }
+}
+
+/// Completion models exported from `seedframe::providers::completions` the macro can build,
+/// ie. the ones with a `new(Option<&str>)` constructor. Wrappers like `Caching`, `Fallback`
+/// and `Routing` take other models and have to be built by hand.
+const BUILTIN_PROVIDERS: [&str; 4] = ["OpenAI", "OpenAICompatible", "DeepSeek", "Xai"];
+
 #[derive(Debug, Clone)]
 struct JsonStr(serde_json::Value);
 impl FromMeta for JsonStr {
@@ -66,6 +99,9 @@ impl FromMeta for ToolNames {
 enum ExecutionModeType {
     FailEarly,
     BestEffort,
+    ReportFailures,
+    Sequential,
+    Concurrent,
 }
 
 impl Display for ExecutionModeType {
@@ -76,6 +112,9 @@ impl Display for ExecutionModeType {
             match self {
                 Self::FailEarly => "seedframe::tools::ExecutionStrategy::FailEarly",
                 Self::BestEffort => "seedframe::tools::ExecutionStrategy::BestEffort",
+                Self::ReportFailures => "seedframe::tools::ExecutionStrategy::ReportFailures",
+                Self::Sequential => "seedframe::tools::ExecutionStrategy::Sequential",
+                Self::Concurrent => "seedframe::tools::ExecutionStrategy::Concurrent",
             }
         )
     }
@@ -85,7 +124,10 @@ impl ExecutionModeType {
     fn from_str(provider: &str) -> Result<Self, ClientMacroError> {
         match provider {
             "best_effort" => Ok(Self::BestEffort),
+            "report_failures" => Ok(Self::ReportFailures),
             "fail_early" => Ok(Self::FailEarly),
+            "sequential" => Ok(Self::Sequential),
+            "concurrent" => Ok(Self::Concurrent),
             unknown => Err(ClientMacroError::UnknownExecutionMode(unknown.to_string())),
         }
     }
@@ -125,20 +167,39 @@ pub(crate) fn client_impl(
     }
     .to_string();
     let tool_execution_mode = syn::Type::from_string(&tool_execution_mode)?;
-    let tool_names: Vec<proc_macro2::Ident> =
-        parse_tools(&config.clone().tools.map(|v| v.0).unwrap_or_default());
-    let tool_set = quote! {
+    let tool_names: Vec<proc_macro2::Ident> = parse_tools(
+        &config
+            .tools
+            .iter()
+            .flat_map(|group| group.0.clone())
+            .collect::<Vec<_>>(),
+    );
+    let toolset_fns = parse_toolsets(&config.toolsets.clone().map(|v| v.0).unwrap_or_default())?;
+    let mut tool_set = quote! {
     seedframe::tools::ToolSet(vec![#(Box::new(#tool_names::new())),*], #tool_execution_mode) };
+    if !toolset_fns.is_empty() {
+        tool_set = quote! {
+            [#(#toolset_fns()),*].into_iter()
+                .try_fold(#tool_set, seedframe::tools::ToolSet::merge)
+                .unwrap_or_else(|e| panic!("Failed to compose the client's tools: {e}"))
+        };
+    }
 
-    let t = config.provider.clone();
+    check_provider(&config.provider)?;
+    let t = config.provider.as_ref().clone();
 
-    let model_init = if let Some(json_str) = config.config {
-        let json_str = serde_json::to_string(&json_str.0).unwrap();
+    let resolved = resolve_config(
+        config.config.as_ref().map(|json| &json.0),
+        config.config_file.as_deref(),
+    )?;
+    let model_init = if let Some(json) = &resolved.json {
+        let json_str = serde_json::to_string(json).unwrap();
         quote! {let model = #t::new(Some(#json_str));}
     } else {
         quote! {let model = #t::new(None);}
     };
 
+    let tracking = resolved.tracking;
     Ok(quote! {
         #struct_vis struct #struct_ident;
 
@@ -154,9 +215,60 @@ pub(crate) fn client_impl(
             }
         }
 
+        #tracking
     })
 }
 
+/// Rejects bare provider names other than the built-in providers, suggesting the built-in
+/// provider a name looks like a misspelling of.
+///
+/// External providers have to be given as paths, eg. `seedframe_bedrock::Bedrock`.
+fn check_provider(provider: &SpannedValue<syn::Type>) -> Result<(), ClientMacroError> {
+    let syn::Type::Path(type_path) = provider.as_ref() else {
+        return Ok(());
+    };
+    let Some(ident) = type_path.path.get_ident() else {
+        return Ok(());
+    };
+    let name = ident.to_string();
+    if BUILTIN_PROVIDERS.contains(&name.as_str()) {
+        return Ok(());
+    }
+    match BUILTIN_PROVIDERS
+        .iter()
+        .find(|builtin| is_typo_of(&name, builtin))
+    {
+        Some(builtin) => Err(ClientMacroError::UnknownProvider(
+            name,
+            builtin,
+            provider.span(),
+        )),
+        None => Err(ClientMacroError::NotAProvider(name, provider.span())),
+    }
+}
+
+/// Whether `name` differs from `builtin` only by case or by at most two edits
+fn is_typo_of(name: &str, builtin: &str) -> bool {
+    let (name, builtin) = (name.to_lowercase(), builtin.to_lowercase());
+    let (a, b): (Vec<char>, Vec<char>) = (name.chars().collect(), builtin.chars().collect());
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev + usize::from(ca != cb);
+            prev = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(prev + 1);
+        }
+    }
+    row[b.len()]
+        <= if b.len() > 4 {
+            2
+        } else {
+            usize::from(b.len() > 2)
+        }
+}
+
 fn parse_embedders(input: &ItemStruct) -> Result<TokenStream, ClientMacroError> {
     let embedder_types = {
         let mut embedders = Vec::new();
@@ -191,6 +303,16 @@ fn parse_embedders(input: &ItemStruct) -> Result<TokenStream, ClientMacroError>
     Ok(embedder_instances)
 }
 
+fn parse_toolsets(toolsets: &[String]) -> Result<Vec<syn::Path>, ClientMacroError> {
+    toolsets
+        .iter()
+        .map(|path| {
+            syn::parse_str::<syn::Path>(path)
+                .map_err(|_| ClientMacroError::InvalidToolsetPath(path.clone()))
+        })
+        .collect()
+}
+
 fn parse_tools(tools: &[String]) -> Vec<proc_macro2::Ident> {
     tools
         .iter()
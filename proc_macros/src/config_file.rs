@@ -0,0 +1,48 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::path::PathBuf;
+
+/// The JSON config of a declaration, combining its `config_file` and inline `config`
+pub(crate) struct ResolvedConfig {
+    pub(crate) json: Option<serde_json::Value>,
+    /// Tokens making the expansion depend on the config file, so editing it triggers a rebuild
+    pub(crate) tracking: TokenStream,
+}
+
+/// Reads `file`, relative to the manifest dir of the crate being compiled, and merges `inline`
+/// over it, the keys set inline take precedence over the ones set in the file
+pub(crate) fn resolve_config(
+    inline: Option<&serde_json::Value>,
+    file: Option<&str>,
+) -> darling::Result<ResolvedConfig> {
+    let Some(file) = file else {
+        return Ok(ResolvedConfig {
+            json: inline.cloned(),
+            tracking: TokenStream::new(),
+        });
+    };
+    let path = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map_or_else(|| PathBuf::from(file), |dir| PathBuf::from(dir).join(file));
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|e| darling::Error::custom(format!("Failed to read config file `{file}`: {e}")))?;
+    let mut json: serde_json::Value = serde_json::from_str(&contents).map_err(|e| {
+        darling::Error::custom(format!("Invalid JSON in config file `{file}`: {e}"))
+    })?;
+
+    if let Some(inline) = inline {
+        let (Some(base), Some(overrides)) = (json.as_object_mut(), inline.as_object()) else {
+            return Err(darling::Error::custom(format!(
+                "Both the config file `{file}` and `config` must be JSON objects to be merged"
+            )));
+        };
+        for (key, value) in overrides {
+            base.insert(key.clone(), value.clone());
+        }
+    }
+
+    let path = path.to_string_lossy().into_owned();
+    Ok(ResolvedConfig {
+        json: Some(json),
+        tracking: quote! { const _: &str = include_str!(#path); },
+    })
+}
@@ -4,11 +4,20 @@ use quote::ToTokens;
 use quote::{format_ident, quote};
 use thiserror::Error;
 
+use crate::config_file::resolve_config;
+
 #[derive(Debug, FromMeta, Clone)]
 struct EmbedderConfig {
-    provider: syn::Type,
+    #[darling(default)]
+    provider: Option<syn::Type>,
     #[darling(default)]
     config: Option<JsonStr>,
+    #[darling(default)]
+    config_file: Option<String>,
+    #[darling(default)]
+    workers: Option<usize>,
+    #[darling(default)]
+    dedup: Option<bool>,
 }
 
 #[derive(Debug, Clone)]
@@ -30,6 +39,10 @@ pub(crate) enum EmbedderMacroError {
     MissingVectorStore,
     #[error("Unrecognized attribute {0}")]
     UnrecognizedAttribute(String),
+    #[error("Expected a boolean for the `normalize` config field")]
+    InvalidNormalize,
+    #[error("An embedder config requires a provider")]
+    ConfigWithoutProvider,
 }
 
 pub(crate) fn embedder_impl(
@@ -56,21 +69,36 @@ pub(crate) fn embedder_impl(
         }
     };
     let (struct_ident, struct_vis) = (&input.ident, &input.vis);
-    let builder_impl = generate_builder(&input, &config)?;
+    let resolved = resolve_config(
+        config.config.as_ref().map(|json| &json.0),
+        config.config_file.as_deref(),
+    )?;
+    let embedder_init = generate_init(&input, &config, resolved.json)?;
+    let tracking = resolved.tracking;
     Ok(quote! {
         #struct_vis struct #struct_ident{
             inner: seedframe::embeddings::Embedder,
         }
 
         impl #struct_ident {
-            #builder_impl
+            /// Builds the embedder on the first call, later calls share the same embedder
+            #struct_vis async fn build() -> Self {
+                static INSTANCE: ::tokio::sync::OnceCell<seedframe::embeddings::Embedder> =
+                    ::tokio::sync::OnceCell::const_new();
+                Self {
+                    inner: INSTANCE.get_or_init(|| async { #embedder_init }).await.clone(),
+                }
+            }
         }
+
+        #tracking
     })
 }
 
-fn generate_builder(
+fn generate_init(
     input: &syn::ItemStruct,
     config: &EmbedderConfig,
+    mut json_config: Option<serde_json::Value>,
 ) -> Result<proc_macro2::TokenStream, EmbedderMacroError> {
     let vector_store_type = {
         let mut vector_store = None;
@@ -134,25 +162,52 @@ fn generate_builder(
         });
     }
 
-    let t = config.provider.clone();
-    let embedding_model_init = if let Some(json_str) = &config.config {
-        let json_str = serde_json::to_string(&json_str.0).unwrap();
-        quote! { ::std::sync::Arc::new(::std::boxed::Box::new(#t::new(Some(#json_str)))) }
+    let Some(t) = config.provider.clone() else {
+        if json_config.is_some() {
+            return Err(EmbedderMacroError::ConfigWithoutProvider);
+        }
+        return Ok(quote! {
+            seedframe::embeddings::Embedder::init_without_model(
+                vec![#loader_instances],
+                #vector_store_instanciated,
+            ).await
+        });
+    };
+    let normalize = match json_config
+        .as_mut()
+        .and_then(serde_json::Value::as_object_mut)
+        .and_then(|object| object.remove("normalize"))
+    {
+        None => false,
+        Some(serde_json::Value::Bool(normalize)) => normalize,
+        Some(_) => return Err(EmbedderMacroError::InvalidNormalize),
+    };
+    let model = if let Some(json) = &json_config {
+        let json_str = serde_json::to_string(json).unwrap();
+        quote! { #t::new(Some(#json_str)) }
     } else {
-        quote! { ::std::sync::Arc::new(::std::boxed::Box::new(#t::new(None))) }
+        quote! { #t::new(None) }
+    };
+    let workers = config.workers.unwrap_or(1);
+    let dedup = config.dedup.unwrap_or(false);
+    let embedding_model_init = if normalize {
+        quote! {
+            ::std::sync::Arc::new(::std::boxed::Box::new(
+                ::seedframe::embeddings::model::NormalizedEmbeddingModel::new(::std::boxed::Box::new(#model))
+            ))
+        }
+    } else {
+        quote! { ::std::sync::Arc::new(::std::boxed::Box::new(#model)) }
     };
-
-    let vis = input.clone().vis;
 
     Ok(quote! {
-        #vis async fn build() -> Self {
-            Self { inner:
-                seedframe::embeddings::Embedder::init(
-                    vec![#loader_instances],
-                    #vector_store_instanciated,
-                    #embedding_model_init,
-                ).await
-            }
-        }
+        seedframe::embeddings::Embedder::init_with_options(
+            vec![#loader_instances],
+            #vector_store_instanciated,
+            #embedding_model_init,
+            seedframe::embeddings::IngestOptions::default()
+                .workers(#workers)
+                .deduplicate(#dedup),
+        ).await
     })
 }
@@ -1,8 +1,9 @@
 use proc_macro::TokenStream;
-use proc_macro_error::{abort_call_site, proc_macro_error};
+use proc_macro_error::{abort, abort_call_site, proc_macro_error};
 use syn::{parse_macro_input, DeriveInput};
 
 mod client;
+mod config_file;
 mod embedder;
 mod loader;
 mod tool;
@@ -12,9 +13,15 @@ mod vector_store;
 /// Loaders are responsible for loading resources from various sources and sending them to embedders.
 ///
 /// # Usage with built-in Loaders
-/// When using a built-in loader (like `FileOnceLoader` or `FileUpdatingLoader`), specify:
+/// When using a built-in loader (like `FileOnceLoader`, `FileUpdatingLoader`, `FilePollingLoader`
+/// or `LogTailLoader`), specify:
 /// - `kind`: The name of the built-in loader type
-/// - `path`: A glob pattern for files to load (required for file-based loaders)
+/// - `path`: A glob pattern for files to load (required for file-based loaders), the path of
+///   the file to tail for `LogTailLoader`
+/// - `interval`: Seconds between polls (optional, only supported by `FilePollingLoader` and
+///   `LogTailLoader`)
+/// - `batch_lines`: Maximum number of lines per document (optional, only supported by
+///   `LogTailLoader`)
 ///
 /// ```rust,ignore
 /// #[loader(
@@ -22,12 +29,27 @@ mod vector_store;
 ///   path = "/path/to/files/**/*.txt"
 /// )]
 /// pub struct MyLoader;
+///
+/// #[loader(
+///   kind = "FilePollingLoader",
+///   path = "/mnt/share/**/*.txt",
+///   interval = 30
+/// )]
+/// pub struct MyPollingLoader;
+///
+/// #[loader(
+///   kind = "LogTailLoader",
+///   path = "/var/log/app.log",
+///   batch_lines = 10
+/// )]
+/// pub struct MyLogLoader;
 /// ```
 ///
 /// # Usage with external Loaders
 /// When using a custom loader implementation, specify:
 /// - `external`: The type of your custom loader
 /// - `config`: Optional JSON configuration for the loader
+/// - `config_file`: Optional path to a JSON configuration file, see [`client`]
 ///
 /// ```rust,ignore
 /// #[loader(
@@ -52,6 +74,7 @@ pub fn loader(args: TokenStream, input: TokenStream) -> TokenStream {
 /// Specify:
 /// - `store`: The type of vector store (built-in or external)
 /// - `config`: JSON configuration for the vector store
+/// - `config_file`: Path to a JSON configuration file, see [`client`]
 ///
 /// ```rust,ignore
 /// #[vector_store(
@@ -74,8 +97,15 @@ pub fn vector_store(args: TokenStream, input: TokenStream) -> TokenStream {
 ///
 /// # Usage
 /// Specify:
-/// - `provider`: The type of embedder provider (built-in or external)
-/// - `config`: JSON configuration for the embedder
+/// - `provider`: The type of embedder provider (built-in or external), leaving it out
+///   declares an embedder without a model, that only stores documents with pre-computed
+///   embeddings
+/// - `config`: JSON configuration for the embedder, setting `"normalize": true` in it
+///   L2-normalizes every embedding before it's stored or used as a query
+/// - `config_file`: Path to a JSON configuration file, see [`client`]
+/// - `workers`: How many documents of each loader are embedded concurrently, defaults to 1
+/// - `dedup`: Whether to skip loaded documents with the same content as a document stored
+///   under another id, defaults to `false`
 ///
 /// ```rust,ignore
 /// #[embedder(
@@ -98,19 +128,30 @@ pub fn embedder(args: TokenStream, input: TokenStream) -> TokenStream {
 ///
 /// # Usage
 /// Required attributes:
-/// - `provider`: The type of completion provider
+/// - `provider`: The type of completion provider, one of the built-in providers (`OpenAI`,
+///    `OpenAICompatible`, `DeepSeek` or `Xai`) or a path to an external one, eg.
+///    `seedframe_anthropic::AnthropicCompletionModel`
 ///
 /// Optional attributes:
-/// - `tools`: List of tool functions to attach to the client
-/// - `execution_mode`: Tool execution mode ("best_effort" or "fail_early")
+/// - `tools`: List of tool functions to attach to the client, can be repeated to attach
+///    several groups of tools
+/// - `toolsets`: List of paths to functions returning a `ToolSet`, merged with the `tools`.
+///    Building the client panics if two tools share a name
+/// - `execution_mode`: Tool execution mode ("best_effort", "report_failures", "fail_early",
+///    "sequential" or "concurrent"), tool responses are always ordered like the calls they answer
 /// - `config`: JSON configuration for the provider, might be an error not to specify depending on
 ///    the provider
+/// - `config_file`: Path to a JSON configuration file, relative to the crate's `Cargo.toml`,
+///    read at compile time. Keys also set in `config` are overridden by it, so a file can hold
+///    the config shared by several declarations
 ///
 /// ```rust,ignore
 /// #[client(
 ///     provider = "OpenAI",
 ///     config = "{\"model\": \"gpt-4\"}",
 ///     tools("capitalize", "greet"),
+///     tools("translate"),
+///     toolsets("math::tools"),
 ///     execution_mode = "best_effort"
 /// )]
 /// struct MyClient;
@@ -120,7 +161,10 @@ pub fn embedder(args: TokenStream, input: TokenStream) -> TokenStream {
 pub fn client(args: TokenStream, input: TokenStream) -> TokenStream {
     let tk_stream = client::client_impl(args.into(), input.into());
     if let Err(e) = tk_stream {
-        abort_call_site!(e.to_string())
+        match e.span() {
+            Some(span) => abort!(span, e.to_string()),
+            None => abort_call_site!(e.to_string()),
+        }
     }
     tk_stream.unwrap().into()
 }
@@ -129,6 +173,9 @@ pub fn client(args: TokenStream, input: TokenStream) -> TokenStream {
 ///
 /// The macro parses function and argument descriptions from doc comments.
 /// Documentation is required for both the function and all arguments (except State parameters).
+/// Arguments are documented in a `# Arguments` section with one line per argument, the name can
+/// be wrapped in backticks and separated from its description by a `:` or a `-`,
+/// eg. ``* `input`: description``, `* input: description` or `* input - description`.
 ///
 /// # Usage
 /// ```rust,ignore
@@ -140,6 +187,18 @@ pub fn client(args: TokenStream, input: TokenStream) -> TokenStream {
 ///     input.to_uppercase()
 /// }
 /// ```
+///
+/// # Attributes
+/// - `rename`: Name the tool is exposed to the model as, defaults to the function name
+/// - `description`: Description of the tool sent to the model, overriding the one of the doc
+///   comment, eg. for multi-paragraph or markdown descriptions. The arguments still need to be
+///   documented in the doc comment
+///
+/// # Return values
+/// The value returned by the function becomes the content of the tool response.
+/// Functions declared to return `serde_json::Value` have their value used as is, any other
+/// return type gets converted with `serde_json::to_value`, so a `String` holding JSON is sent
+/// as a JSON string rather than as the object it describes.
 #[proc_macro_error]
 #[proc_macro_attribute]
 pub fn tool(args: TokenStream, input: TokenStream) -> TokenStream {
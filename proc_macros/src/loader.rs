@@ -4,6 +4,8 @@ use quote::{format_ident, quote};
 use std::fmt::Display;
 use thiserror::Error;
 
+use crate::config_file::resolve_config;
+
 #[derive(Debug, FromMeta, Clone)]
 struct LoaderConfig {
     #[darling(default)]
@@ -11,9 +13,15 @@ struct LoaderConfig {
     #[darling(default)]
     path: Option<String>,
     #[darling(default)]
+    interval: Option<u64>,
+    #[darling(default)]
+    batch_lines: Option<usize>,
+    #[darling(default)]
     external: Option<syn::Type>,
     #[darling(default)]
     config: Option<JsonStr>,
+    #[darling(default)]
+    config_file: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -29,7 +37,7 @@ impl FromMeta for JsonStr {
 
 #[derive(Debug, Error)]
 pub(crate) enum LoaderMacroError {
-    #[error("Unknown Loader kind: '{0}'. valid options are FileOnceLoader,FileUpdatingLoader")]
+    #[error("Unknown Loader kind: '{0}'. valid options are FileOnceLoader,FileUpdatingLoader,FilePollingLoader,LogTailLoader")]
     UnknownLoader(String),
     #[error(transparent)]
     ParseError(#[from] darling::Error),
@@ -44,6 +52,8 @@ pub(crate) enum LoaderMacroError {
 enum BuiltinLoaderType {
     FileOnceLoader,
     FileUpdatingLoader,
+    FilePollingLoader,
+    LogTailLoader,
 }
 
 impl Display for BuiltinLoaderType {
@@ -56,6 +66,10 @@ impl Display for BuiltinLoaderType {
                     "seedframe::loader::builtins::file_loaders::file_once_loader::FileOnceLoader",
                 Self::FileUpdatingLoader =>
                     "seedframe::loader::builtins::file_loaders::file_updating_loader::FileUpdatingLoader",
+                Self::FilePollingLoader =>
+                    "seedframe::loader::builtins::file_loaders::file_polling_loader::FilePollingLoader",
+                Self::LogTailLoader =>
+                    "seedframe::loader::builtins::file_loaders::log_tail_loader::LogTailLoader",
             }
         )
     }
@@ -66,18 +80,25 @@ impl BuiltinLoaderType {
         match kind {
             "FileOnceLoader" => Ok(Self::FileOnceLoader),
             "FileUpdatingLoader" => Ok(Self::FileUpdatingLoader),
+            "FilePollingLoader" => Ok(Self::FilePollingLoader),
+            "LogTailLoader" => Ok(Self::LogTailLoader),
             unknown => Err(LoaderMacroError::UnknownLoader(unknown.to_string())),
         }
     }
     fn required_args(&self) -> &'static [&'static str] {
         match self {
-            Self::FileOnceLoader | Self::FileUpdatingLoader => &["path"],
+            Self::FileOnceLoader
+            | Self::FileUpdatingLoader
+            | Self::FilePollingLoader
+            | Self::LogTailLoader => &["path"],
         }
     }
 
     fn supported_args(&self) -> &'static [&'static str] {
         match self {
             Self::FileOnceLoader | Self::FileUpdatingLoader => &["path"],
+            Self::FilePollingLoader => &["path", "interval"],
+            Self::LogTailLoader => &["path", "interval", "batch_lines"],
         }
     }
 }
@@ -119,7 +140,12 @@ pub(crate) fn loader_impl(
     };
 
     let loader_type = get_type(&config)?;
-    let builder_impl = generate_builder(&config, &loader_type, struct_vis);
+    let resolved = resolve_config(
+        config.config.as_ref().map(|json| &json.0),
+        config.config_file.as_deref(),
+    )?;
+    let builder_impl = generate_builder(&config, resolved.json.as_ref(), &loader_type, struct_vis);
+    let tracking = resolved.tracking;
 
     let kind = match loader_type {
         LoaderType::BuiltIn(t) | LoaderType::External(t) => t,
@@ -139,9 +165,15 @@ pub(crate) fn loader_impl(
             async fn subscribe(&self) -> ::tokio::sync::broadcast::Receiver<::seedframe::document::Document> {
                 self.inner.subscribe().await
             }
+
+            async fn subscribe_replay(&self) -> ::tokio::sync::broadcast::Receiver<::seedframe::document::Document> {
+                self.inner.subscribe_replay().await
+            }
         }
 
         #static_loader_instance
+
+        #tracking
     })
 }
 
@@ -167,13 +199,13 @@ fn validate_config(config: &LoaderConfig) -> Result<(), LoaderMacroError> {
         let required = loader_type.required_args();
         let supported = loader_type.supported_args();
 
-        let check_arg = |name: &str, value: &Option<String>| {
-            if value.is_none() && required.contains(&name) {
+        let check_arg = |name: &str, is_set: bool| {
+            if !is_set && required.contains(&name) {
                 Err(LoaderMacroError::MissingArgument(
                     name.to_string(),
                     loader_type.to_string(),
                 ))
-            } else if value.is_some() && !supported.contains(&name) {
+            } else if is_set && !supported.contains(&name) {
                 Err(LoaderMacroError::UnsupportedArgument(
                     name.to_string(),
                     loader_type.to_string(),
@@ -182,12 +214,25 @@ fn validate_config(config: &LoaderConfig) -> Result<(), LoaderMacroError> {
                 Ok(())
             }
         };
-        check_arg("path", &config.path)?;
+        check_arg("path", config.path.is_some())?;
+        check_arg("interval", config.interval.is_some())?;
+        check_arg("batch_lines", config.batch_lines.is_some())?;
+        check_arg("config_file", config.config_file.is_some())?;
     } else if config.path.is_some() {
         Err(LoaderMacroError::UnsupportedArgument(
             "path".to_string(),
             "external".to_string(),
         ))?;
+    } else if config.interval.is_some() {
+        Err(LoaderMacroError::UnsupportedArgument(
+            "interval".to_string(),
+            "external".to_string(),
+        ))?;
+    } else if config.batch_lines.is_some() {
+        Err(LoaderMacroError::UnsupportedArgument(
+            "batch_lines".to_string(),
+            "external".to_string(),
+        ))?;
     };
     Ok(())
 }
@@ -204,21 +249,28 @@ fn get_type(config: &LoaderConfig) -> Result<LoaderType, LoaderMacroError> {
 
 fn generate_builder(
     config: &LoaderConfig,
+    json_config: Option<&serde_json::Value>,
     loader_type: &LoaderType,
     vis: &syn::Visibility,
 ) -> proc_macro2::TokenStream {
     match loader_type {
         LoaderType::BuiltIn(t) => {
             let path = config.path.as_ref().unwrap().to_string();
+            let interval = config.interval.map(|secs| {
+                quote! { .interval(::std::time::Duration::from_secs(#secs)) }
+            });
+            let batch_lines = config
+                .batch_lines
+                .map(|lines| quote! { .batch_lines(#lines) });
             quote! {
                 #vis fn build() -> Self {
-                    Self { inner: (#t::new(vec![#path.to_string()]).unwrap().build().unwrap()) }
+                    Self { inner: (#t::new(vec![#path.to_string()]).unwrap()#interval #batch_lines.build().unwrap()) }
                 }
             }
         }
         LoaderType::External(t) => {
-            if let Some(json_str) = &config.config {
-                let json_str = serde_json::to_string(&json_str.0).unwrap();
+            if let Some(json) = json_config {
+                let json_str = serde_json::to_string(json).unwrap();
                 quote! {
                     #vis fn build() -> Self {
                         Self { inner: (#t::new(Some(#json_str)).unwrap()) }
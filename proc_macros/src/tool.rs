@@ -8,11 +8,16 @@ use thiserror::Error;
 struct ToolConfig {
     #[darling(default)]
     rename: Option<String>,
+    #[darling(default)]
+    description: Option<String>,
 }
 
 #[derive(Debug, Error)]
 pub(crate) enum ToolMacroError {
-    #[error("fn argument '{0}' needs to be documented")]
+    #[error(
+        "fn argument '{0}' needs to be documented, expected a line like `* {0}: description` \
+         (or `* {0} - description`) under a `# Arguments` heading in the doc comment"
+    )]
     UndocumentedArg(String),
     #[error("Description for tool '{0}' not given")]
     DescriptionForFnNotFound(String),
@@ -96,9 +101,17 @@ pub(crate) fn tool_impl(
         quote! { #fn_ident(#params, #(#states),*) }
     };
 
+    // `serde_json::Value`s are returned as is, anything else gets converted with `to_value`
+    let fn_result = if returns_json_value(&input.sig) {
+        quote! { #fn_call }
+    } else {
+        quote! { serde_json::to_value(#fn_call)? }
+    };
+
     let tool_name = config.rename.unwrap_or(fn_ident.to_string());
-    let description = doc_extracted
-        .0
+    let description = config
+        .description
+        .or(doc_extracted.0)
         .ok_or(ToolMacroError::DescriptionForFnNotFound(tool_name.clone()))?;
     let tool_struct_name = format_ident!("__SF_TOOL_{}__", tool_name);
     let m = Punct::new('#', Spacing::Joint);
@@ -146,7 +159,7 @@ pub(crate) fn tool_impl(
                 let mut args = args.as_str();
                 args = &args[1..args.len() - 1];
                 let params: Params = serde_json::from_str(args)?;
-                Ok(serde_json::to_value(#fn_call)?)
+                Ok(#fn_result)
             }
         }
     })
@@ -181,6 +194,26 @@ fn get_tool_arg_token_streams(
     (tool_args, params_struct, params)
 }
 
+/// Whether the function's declared return type is `serde_json::Value`
+fn returns_json_value(sig: &syn::Signature) -> bool {
+    let syn::ReturnType::Type(_, ty) = &sig.output else {
+        return false;
+    };
+    let Type::Path(type_path) = &**ty else {
+        return false;
+    };
+    let segments: Vec<String> = type_path
+        .path
+        .segments
+        .iter()
+        .map(|s| s.ident.to_string())
+        .collect();
+    matches!(
+        segments.iter().map(String::as_str).collect::<Vec<_>>()[..],
+        ["Value"] | ["serde_json", "Value"]
+    )
+}
+
 fn validate_fn_type_bounds(input: &syn::ItemFn) -> Result<(), darling::Error> {
     for arg in &input.sig.inputs {
         if let syn::FnArg::Typed(pat_type) = arg {
@@ -314,17 +347,102 @@ pub fn parse_doc_comment(doc: &str) -> (Option<String>, Vec<(String, String)>) {
         .iter()
         .find(|(h, _)| h.as_deref() == Some("Arguments"))
     {
-        for line in args_lines {
-            if let Some(stripped) = line.strip_prefix('*') {
-                let content = stripped.trim();
-                if let Some((name_part, desc)) = content.split_once(':') {
-                    let name = name_part.trim().trim_matches('`').to_string();
-                    let description = desc.trim().to_string();
-                    arguments.push((name, description));
-                }
-            }
-        }
+        arguments.extend(args_lines.iter().filter_map(|line| parse_arg_line(line)));
     }
 
     (description, arguments)
 }
+
+/// Parses an argument line of the `# Arguments` section into the argument's name and
+/// description. The name can be wrapped in backticks and separated from the description by
+/// a `:` or a `-`, the line can start with a `*` or `-` bullet, eg. `* name: description`,
+/// ``* `name` - description`` or `- name: description`
+fn parse_arg_line(line: &str) -> Option<(String, String)> {
+    let content = line
+        .strip_prefix('*')
+        .or_else(|| line.strip_prefix('-'))?
+        .trim();
+    let (name, rest) = if let Some(quoted) = content.strip_prefix('`') {
+        quoted.split_once('`')?
+    } else {
+        let end = content
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(content.len());
+        content.split_at(end)
+    };
+    let description = rest
+        .trim_start()
+        .strip_prefix(':')
+        .or_else(|| rest.trim_start().strip_prefix('-'))?;
+    if name.is_empty() {
+        return None;
+    }
+    Some((name.to_string(), description.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(doc: &str) -> Vec<(String, String)> {
+        parse_doc_comment(doc).1
+    }
+
+    fn arg(name: &str, description: &str) -> (String, String) {
+        (name.to_string(), description.to_string())
+    }
+
+    #[test]
+    fn parses_description_and_backticked_args() {
+        let (description, arguments) = parse_doc_comment(
+            "Greets a user\n# Arguments\n* `name`: name of the user\n* `times`: how often: at most 3",
+        );
+        assert_eq!(description.as_deref(), Some("Greets a user"));
+        assert_eq!(
+            arguments,
+            [
+                arg("name", "name of the user"),
+                arg("times", "how often: at most 3")
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_args_without_backticks() {
+        assert_eq!(
+            args("# Arguments\n* name: name of the user"),
+            [arg("name", "name of the user")]
+        );
+        assert_eq!(
+            args("# Arguments\n* name - name of the user"),
+            [arg("name", "name of the user")]
+        );
+    }
+
+    #[test]
+    fn parses_dash_separator_with_backticks() {
+        assert_eq!(
+            args("# Arguments\n* `user_name` - the user's name: first - last"),
+            [arg("user_name", "the user's name: first - last")]
+        );
+    }
+
+    #[test]
+    fn parses_dash_bullets() {
+        assert_eq!(
+            args("# Arguments\n- `name`: name of the user\n- lang - language code"),
+            [
+                arg("name", "name of the user"),
+                arg("lang", "language code")
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_malformed_lines_and_other_sections() {
+        assert_eq!(
+            args("* name: outside the section\n# Arguments\nname: no bullet\n* name without separator\n# Returns\n* value: ignored"),
+            []
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use darling::{ast::NestedMeta, FromMeta};
 use proc_macro2::TokenStream;
 use quote::quote;
+
+use crate::config_file::resolve_config;
 use thiserror::Error;
 
 #[derive(Debug, FromMeta, Clone)]
@@ -9,6 +11,8 @@ struct VectorStoreConfig {
     store: Option<syn::Type>,
     #[darling(default)]
     config: Option<JsonStr>,
+    #[darling(default)]
+    config_file: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -53,7 +57,12 @@ pub(crate) fn vector_store_impl(
 
     let (struct_ident, struct_vis) = (&input.ident, &input.vis);
     let kind = config.store.clone().unwrap();
-    let builder_impl = generate_builder(&config, &kind, struct_vis);
+    let resolved = resolve_config(
+        config.config.as_ref().map(|json| &json.0),
+        config.config_file.as_deref(),
+    )?;
+    let builder_impl = generate_builder(resolved.json.as_ref(), &kind, struct_vis);
+    let tracking = resolved.tracking;
 
     Ok(quote! {
         #struct_vis struct #struct_ident{
@@ -63,16 +72,18 @@ pub(crate) fn vector_store_impl(
         impl #struct_ident {
             #builder_impl
         }
+
+        #tracking
     })
 }
 
 fn generate_builder(
-    config: &VectorStoreConfig,
+    config: Option<&serde_json::Value>,
     kind: &syn::Type,
     vis: &syn::Visibility,
 ) -> proc_macro2::TokenStream {
-    let init_store = if let Some(config) = &config.config {
-        let config = serde_json::to_string(&config.0).unwrap();
+    let init_store = if let Some(config) = config {
+        let config = serde_json::to_string(config).unwrap();
         quote! { #kind::new(Some(#config)).await.unwrap() }
     } else {
         quote! { #kind::new(None).await.unwrap() }
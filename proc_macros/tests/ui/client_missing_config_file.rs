@@ -0,0 +1,6 @@
+use seedframe_macros::client;
+
+#[client(provider = "OpenAI", config_file = "missing_config.json")]
+struct Client;
+
+fn main() {}
@@ -0,0 +1,6 @@
+use seedframe_macros::client;
+
+#[client(provider = "OpenAi")]
+struct MisspelledClient;
+
+fn main() {}
@@ -0,0 +1,6 @@
+use seedframe_macros::client;
+
+#[client(provider = "MyModel")]
+struct UnknownClient;
+
+fn main() {}